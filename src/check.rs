@@ -0,0 +1,1496 @@
+use crate::{
+    clock_widget, color, config, logging, overflow, placeholders,
+    ranges::Ranges,
+    structures::{Align, Position},
+    theme, timer_widget, transform, triggers,
+};
+use json::JsonValue;
+use log::Level;
+use std::{process, str::FromStr};
+
+/// Matches quoted integers in the JSON Schema, which are accepted as well.
+const INTEGER_PATTERN: &str = r"^\s*[+-]?\d+\s*$";
+
+/// Matches quoted numbers in the JSON Schema.
+const NUMBER_PATTERN: &str = r"^\s*[+-]?(\d+\.?\d*|\.\d+)([eE][+-]?\d+)?\s*$";
+
+/// The type a config key is expected to have.
+#[derive(Clone, Copy)]
+pub enum KeyType {
+    String,
+    Integer,
+    Number,
+    Bool,
+    Array,
+    Object,
+    /// Any of the listed types.
+    OneOf(&'static [KeyType]),
+}
+
+impl KeyType {
+    /// Checks if `value` is of this type.
+    fn matches(self, value: &JsonValue) -> bool {
+        match self {
+            // The getters coerce numbers and bools to strings, and numeric strings to numbers.
+            KeyType::String => value.is_string() || value.is_number() || value.is_boolean(),
+            KeyType::Integer => config::as_i32(value).is_some(),
+            KeyType::Number => config::as_f64(value).is_some(),
+            // `try_get_bool` also accepts quoted booleans.
+            KeyType::Bool => value.is_boolean() || matches!(value.as_str(), Some("true" | "false")),
+            KeyType::Array => value.is_array(),
+            KeyType::Object => value.is_object(),
+            KeyType::OneOf(types) => types.iter().any(|key_type| key_type.matches(value)),
+        }
+    }
+
+    /// The JSON Schema of the type.
+    fn schema(self) -> JsonValue {
+        match self {
+            KeyType::String => json::object! { type: ["string", "number", "boolean"] },
+            KeyType::Integer => json::object! {
+                anyOf: [{ type: "integer" }, { type: "string", pattern: INTEGER_PATTERN }]
+            },
+            KeyType::Number => json::object! {
+                anyOf: [{ type: "number" }, { type: "string", pattern: NUMBER_PATTERN }]
+            },
+            KeyType::Bool => json::object! {
+                anyOf: [{ type: "boolean" }, { enum: ["true", "false"] }]
+            },
+            KeyType::Array => json::object! { type: "array" },
+            KeyType::Object => json::object! { type: "object" },
+            KeyType::OneOf(types) => json::object! {
+                anyOf: types.iter().map(|key_type| key_type.schema()).collect::<Vec<_>>()
+            },
+        }
+    }
+
+    /// Human-readable name of the type.
+    fn name(self) -> String {
+        match self {
+            KeyType::String => String::from("a string"),
+            KeyType::Integer => String::from("an integer"),
+            KeyType::Number => String::from("a number"),
+            KeyType::Bool => String::from("a bool"),
+            KeyType::Array => String::from("an array"),
+            KeyType::Object => String::from("an object"),
+            KeyType::OneOf(types) => types
+                .iter()
+                .map(|key_type| key_type.name())
+                .collect::<Vec<_>>()
+                .join(" or "),
+        }
+    }
+}
+
+/// An update-rate in milliseconds, or `config::UPDATE_ONCE`.
+const UPDATE_RATE: KeyType = KeyType::OneOf(&[KeyType::Integer, KeyType::String]);
+
+/// Keys supported in the `hybrid` section, and whether they are required.
+pub const HYBRID_KEYS: &[(&str, KeyType, bool)] = &[
+    ("update_rate", UPDATE_RATE, false),
+    ("update_rate_on_battery", UPDATE_RATE, false),
+    ("r", KeyType::Number, true),
+    ("g", KeyType::Number, true),
+    ("b", KeyType::Number, true),
+    ("a", KeyType::Number, true),
+    ("expand_left", KeyType::Bool, false),
+    ("expand_right", KeyType::Bool, false),
+    ("position", KeyType::String, false),
+    ("allow_keyboard", KeyType::Bool, false),
+    (
+        "monitor",
+        KeyType::OneOf(&[KeyType::String, KeyType::Array, KeyType::Integer]),
+        false,
+    ),
+    ("stylesheet", KeyType::String, false),
+    ("gtk_theme", KeyType::String, false),
+    ("icon_theme", KeyType::String, false),
+    ("cava_sed", KeyType::String, false),
+    ("cava_bars", KeyType::Integer, false),
+    ("cava_framerate", KeyType::Integer, false),
+    ("fps", KeyType::Integer, false),
+    ("command_timeout", KeyType::Integer, false),
+    ("margin_top", KeyType::Integer, false),
+    ("margin_bottom", KeyType::Integer, false),
+    ("margin_left", KeyType::Integer, false),
+    ("margin_right", KeyType::Integer, false),
+    ("exclusive", KeyType::Bool, false),
+    ("thousands_separator", KeyType::String, false),
+    ("default_format", KeyType::String, false),
+    ("variables_ignore_case", KeyType::Bool, false),
+    ("bare_variables", KeyType::Bool, false),
+    ("error_widget", KeyType::String, false),
+    (
+        "on_startup",
+        KeyType::OneOf(&[KeyType::String, KeyType::Array]),
+        false,
+    ),
+    (
+        "on_reload",
+        KeyType::OneOf(&[KeyType::String, KeyType::Array]),
+        false,
+    ),
+    ("background", KeyType::String, false),
+    ("opacity", KeyType::Number, false),
+    ("spacing", KeyType::Integer, false),
+    (
+        "scale",
+        KeyType::OneOf(&[KeyType::Bool, KeyType::Number]),
+        false,
+    ),
+    (
+        "width",
+        KeyType::OneOf(&[KeyType::Integer, KeyType::String]),
+        false,
+    ),
+    (
+        "height",
+        KeyType::OneOf(&[KeyType::Integer, KeyType::String]),
+        false,
+    ),
+];
+
+/// Keys supported by every widget type.
+pub const COMMON_WIDGET_KEYS: &[(&str, KeyType)] = &[
+    ("on_click", KeyType::String),
+    ("on_middle_click", KeyType::String),
+    ("on_right_click", KeyType::String),
+    ("on_scroll_up", KeyType::String),
+    ("on_scroll_down", KeyType::String),
+    ("tooltip_command", KeyType::String),
+    ("command_timeout", KeyType::Integer),
+    ("retries", KeyType::Integer),
+    ("retry_delay", KeyType::Integer),
+    ("class", KeyType::OneOf(&[KeyType::String, KeyType::Array])),
+    ("visible_command", KeyType::String),
+    ("update_rate", UPDATE_RATE),
+    ("update_rate_on_battery", UPDATE_RATE),
+    ("update_on", KeyType::Array),
+    ("cache_ttl", KeyType::Integer),
+    ("align", KeyType::String),
+    ("order", KeyType::Integer),
+    ("font", KeyType::String),
+    ("font_size", KeyType::Number),
+    ("max_length", KeyType::Integer),
+    ("max_width", KeyType::Integer),
+    ("min_width", KeyType::Integer),
+    ("width_chars", KeyType::Integer),
+    ("text_align", KeyType::String),
+    ("scroll", KeyType::Bool),
+    ("scroll_speed", KeyType::Number),
+    ("margin_top", KeyType::Integer),
+    ("margin_bottom", KeyType::Integer),
+    ("margin_left", KeyType::Integer),
+    ("margin_right", KeyType::Integer),
+    ("expand", KeyType::Bool),
+    ("fill", KeyType::Bool),
+    ("color", KeyType::String),
+    ("background", KeyType::String),
+    ("when_env", KeyType::String),
+    ("when_command", KeyType::String),
+    ("enabled", KeyType::Bool),
+    ("export_variables", KeyType::Bool),
+    ("id", KeyType::String),
+    ("template", KeyType::String),
+];
+
+/// Keys supported by each widget type.
+pub const WIDGET_KEYS: &[(&str, &[(&str, KeyType)])] = &[
+    (
+        "label",
+        &[
+            ("text", KeyType::String),
+            ("command", KeyType::String),
+            ("tooltip", KeyType::String),
+            ("listen", KeyType::Bool),
+            ("update_rate", UPDATE_RATE),
+            ("cache_ttl", KeyType::Integer),
+            ("command_timeout", KeyType::Integer),
+            ("show_errors", KeyType::Bool),
+            ("fallback", KeyType::String),
+            ("trim", KeyType::Bool),
+            ("markup", KeyType::Bool),
+            ("transform", KeyType::Array),
+            ("source", KeyType::String),
+            ("transition_ms", KeyType::Integer),
+        ],
+    ),
+    (
+        "script",
+        &[
+            ("text", KeyType::String),
+            ("command", KeyType::String),
+            ("tooltip", KeyType::String),
+            ("transform", KeyType::Array),
+        ],
+    ),
+    (
+        "button",
+        &[
+            ("text", KeyType::String),
+            ("command", KeyType::String),
+            ("tooltip", KeyType::String),
+        ],
+    ),
+    (
+        "spacing",
+        &[
+            ("spacing_start", KeyType::Integer),
+            ("spacing_end", KeyType::Integer),
+        ],
+    ),
+    ("box", &[("width", KeyType::Integer)]),
+    (
+        "group",
+        &[("widgets", KeyType::Array), ("spacing", KeyType::Integer)],
+    ),
+    (
+        "canvas",
+        &[
+            ("draw", KeyType::String),
+            ("command", KeyType::String),
+            ("width", KeyType::Integer),
+            ("height", KeyType::Integer),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+        ],
+    ),
+    ("separator", &[]),
+    ("cava", &[]),
+    (
+        "battery",
+        &[
+            ("format", KeyType::String),
+            ("ranges", KeyType::Array),
+            ("device", KeyType::String),
+            ("fallback", KeyType::String),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+        ],
+    ),
+    (
+        "brightness",
+        &[
+            ("format", KeyType::String),
+            ("ranges", KeyType::Array),
+            ("device", KeyType::String),
+            ("step", KeyType::Integer),
+            ("set_command", KeyType::String),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+        ],
+    ),
+    (
+        "clock",
+        &[
+            ("format", KeyType::String),
+            ("timezone", KeyType::String),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+        ],
+    ),
+    ("cmd", &[]),
+    (
+        "cpu",
+        &[
+            ("format", KeyType::String),
+            ("ranges", KeyType::Array),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+        ],
+    ),
+    (
+        "image",
+        &[
+            ("path", KeyType::String),
+            ("icon", KeyType::String),
+            ("command", KeyType::String),
+            ("size", KeyType::Integer),
+            ("hide_missing", KeyType::Bool),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+        ],
+    ),
+    (
+        "keyboard",
+        &[
+            ("format", KeyType::String),
+            ("names", KeyType::Object),
+            ("tooltip", KeyType::String),
+        ],
+    ),
+    (
+        "media",
+        &[
+            ("format", KeyType::String),
+            ("player", KeyType::String),
+            ("tooltip", KeyType::String),
+        ],
+    ),
+    (
+        "volume",
+        &[
+            ("format", KeyType::String),
+            ("ranges", KeyType::Array),
+            ("tooltip", KeyType::String),
+        ],
+    ),
+    (
+        "temperature",
+        &[
+            ("format", KeyType::String),
+            ("ranges", KeyType::Array),
+            ("sensor", KeyType::String),
+            ("warning_threshold", KeyType::Integer),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+        ],
+    ),
+    (
+        "graph",
+        &[
+            ("command", KeyType::String),
+            ("source", KeyType::String),
+            ("samples", KeyType::Integer),
+            ("style", KeyType::String),
+            ("min", KeyType::Number),
+            ("max", KeyType::Number),
+            ("width", KeyType::Integer),
+            ("height", KeyType::Integer),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+            ("transition_ms", KeyType::Integer),
+        ],
+    ),
+    (
+        "disk",
+        &[
+            ("format", KeyType::String),
+            ("ranges", KeyType::Array),
+            ("path", KeyType::String),
+            ("base", KeyType::Integer),
+            ("fallback", KeyType::String),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+        ],
+    ),
+    (
+        "network",
+        &[
+            ("format", KeyType::String),
+            ("interface", KeyType::String),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+        ],
+    ),
+    (
+        "progress",
+        &[
+            ("value", KeyType::Number),
+            ("command", KeyType::String),
+            ("min", KeyType::Number),
+            ("max", KeyType::Number),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+            ("transition_ms", KeyType::Integer),
+        ],
+    ),
+    ("tray", &[("icon_size", KeyType::Integer)]),
+    ("workspaces", &[]),
+    (
+        "memory",
+        &[
+            ("format", KeyType::String),
+            ("ranges", KeyType::Array),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+        ],
+    ),
+    (
+        "timer",
+        &[
+            ("target", KeyType::String),
+            ("format", KeyType::String),
+            ("elapsed_format", KeyType::String),
+            ("on_complete", KeyType::String),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+        ],
+    ),
+    (
+        "uptime",
+        &[
+            ("format", KeyType::String),
+            ("tooltip", KeyType::String),
+            ("update_rate", UPDATE_RATE),
+        ],
+    ),
+    (
+        "window",
+        &[
+            ("format", KeyType::String),
+            ("fallback", KeyType::String),
+            ("tooltip", KeyType::String),
+        ],
+    ),
+];
+
+/// Keys each widget type can't do without, it needs at least one of them to be a non-empty
+/// string. Widgets missing all of them are skipped when the bar is built, see `missing_keys`,
+/// except for script and image widgets which Hybrid exits on, see `config::require`.
+pub const REQUIRED_WIDGET_KEYS: &[(&str, &[&str])] = &[
+    ("script", &["command"]),
+    ("image", &["path", "icon", "command"]),
+    ("timer", &["target"]),
+    ("canvas", &["draw", "command"]),
+    ("graph", &["command", "source"]),
+];
+
+/// Describes what a widget of `widget_type` is missing from its `block`, if anything.
+pub fn missing_keys(widget_type: &str, block: &JsonValue) -> Option<String> {
+    let (_, required) = REQUIRED_WIDGET_KEYS
+        .iter()
+        .find(|(name, _)| *name == widget_type)?;
+    let is_set = |name: &&str| block[*name].as_str().is_some_and(|value| !value.is_empty());
+    if required.iter().any(is_set) {
+        return None;
+    }
+
+    let required: Vec<String> = required.iter().map(|name| format!("`{name}`")).collect();
+    Some(match required.as_slice() {
+        [name] => format!("{widget_type} widgets need a {name}"),
+        [names @ .., last] => format!(
+            "{widget_type} widgets need a {} or {last}",
+            names.join(", ")
+        ),
+        [] => unreachable!("Every widget type requires at least one key"),
+    })
+}
+
+/// Validates the config without launching the bar, then exits.
+/// Exits with `0` if the config is valid, otherwise all problems are printed and it exits with `1`.
+pub fn run() -> ! {
+    let config = match config::read_config() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("[{}] {err}", logging::level(Level::Error));
+            process::exit(1)
+        }
+    };
+
+    let problems = validate(&config);
+    if problems.is_empty() {
+        if config::is_stdin() {
+            println!("[HYBRID] The config from stdin is valid");
+        } else {
+            println!("[HYBRID] '{}' is valid", config::get_config_path());
+        }
+
+        process::exit(0)
+    }
+
+    for problem in &problems {
+        eprintln!("[{}] {problem}", logging::level(Level::Error));
+    }
+
+    eprintln!("[HYBRID] Found {} problem(s)", problems.len());
+    process::exit(1)
+}
+
+/// Prints a JSON Schema of the config to stdout, then exits.
+/// It's generated from the same keys as `validate`, so the two can't drift apart.
+pub fn dump_schema() -> ! {
+    println!("{}", json::stringify_pretty(get_schema(), 2));
+    process::exit(0)
+}
+
+/// Builds a JSON Schema (draft 2020-12) describing the `hybrid` section, `variables` and all
+/// widget types.
+fn get_schema() -> JsonValue {
+    let mut hybrid = json::object! {
+        type: "object",
+        properties: {},
+        required: [],
+        additionalProperties: false,
+    };
+
+    for (key, key_type, required) in HYBRID_KEYS {
+        hybrid["properties"][*key] = key_type.schema();
+        if *required {
+            let _ = hybrid["required"].push(*key);
+        }
+    }
+
+    let mut schema = json::object! {
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        title: "HybridBar config",
+        type: "object",
+        properties: {
+            "$schema": { type: "string" },
+            hybrid: hybrid,
+            variables: {
+                type: "object",
+                additionalProperties: {
+                    anyOf: [
+                        { type: ["string", "number", "boolean"] },
+                        {
+                            type: "object",
+                            properties: {
+                                command: { type: "string" },
+                                value: { type: ["string", "number", "boolean"] },
+                                raw: { type: "boolean" },
+                            },
+                            additionalProperties: false,
+                        },
+                    ],
+                },
+            },
+            sources: {
+                type: "object",
+                additionalProperties: {
+                    type: "object",
+                    properties: {
+                        command: { type: "string" },
+                        update_rate: UPDATE_RATE.schema(),
+                        command_timeout: KeyType::Integer.schema(),
+                    },
+                    required: ["command"],
+                    additionalProperties: false,
+                },
+            },
+            computed: {
+                type: "object",
+                additionalProperties: {
+                    anyOf: [
+                        { type: ["string", "number", "boolean"] },
+                        {
+                            type: "object",
+                            properties: {
+                                value: { type: ["string", "number", "boolean"] },
+                                from: { type: "string" },
+                                transform: { type: "array", items: { type: "object" } },
+                            },
+                            additionalProperties: false,
+                        },
+                    ],
+                },
+            },
+            templates: { type: "object", additionalProperties: { type: "object" } },
+            include: { type: "array", items: { type: "string" } },
+        },
+        patternProperties: {},
+        required: ["hybrid"],
+    };
+
+    // Same rules as `validate_widget` for naming widgets.
+    for (widget_type, keys) in WIDGET_KEYS {
+        let mut widget = json::object! {
+            type: "object",
+            properties: {},
+            additionalProperties: false,
+        };
+
+        for (key, key_type) in keys.iter().chain(COMMON_WIDGET_KEYS) {
+            widget["properties"][*key] = key_type.schema();
+        }
+
+        let pattern = format!("^(left|centered|center|right)-{widget_type}_.+$");
+        schema["patternProperties"][pattern.as_str()] = widget;
+    }
+
+    schema
+}
+
+/// 0.3.5: The smallest value of sizes and margins, which are clamped to it and
+///   `config::MAX_PIXELS`. Negative margins are only allowed on the bar.
+const PIXEL_MINIMUMS: &[(&str, i32)] = &[
+    ("margin_top", 0),
+    ("margin_bottom", 0),
+    ("margin_left", 0),
+    ("margin_right", 0),
+    ("spacing", 0),
+    ("spacing_start", 0),
+    ("spacing_end", 0),
+    ("min_width", 0),
+    ("max_width", 0),
+    ("size", 1),
+    ("icon_size", 1),
+];
+
+/// Reports the sizes and margins of `block` at `root` which are out of range, see
+/// `PIXEL_MINIMUMS`. Widgets also have their `width` and `height` checked against `min_size`,
+/// the bar sizes itself as it can be a percentage.
+fn validate_pixel_ranges(
+    root: &str,
+    block: &JsonValue,
+    min_size: Option<i32>,
+    problems: &mut Vec<String>,
+) {
+    let is_bar = min_size.is_none();
+    let sizes = min_size.map(|min| [("width", min), ("height", min)]);
+    let keys = PIXEL_MINIMUMS
+        .iter()
+        .chain(sizes.iter().flatten())
+        .map(|(key, min)| match *key {
+            margin if is_bar && margin.starts_with("margin_") => {
+                (margin, -config::MAX_NEGATIVE_BAR_MARGIN)
+            }
+            key => (key, *min),
+        });
+    for (key, min) in keys {
+        let Some(value) = config::as_i32(&block[key]) else {
+            continue;
+        };
+
+        if !(min..=config::MAX_PIXELS).contains(&value) {
+            problems.push(format!(
+                "{root}:{key} {value} is out of range, it's clamped to {min}-{}",
+                config::MAX_PIXELS
+            ));
+        }
+    }
+}
+
+/// Validates `config`, returning a human-readable list of all problems found.
+pub fn validate(config: &JsonValue) -> Vec<String> {
+    let mut problems = Vec::new();
+    let hybrid = &config["hybrid"];
+    if !hybrid.is_object() {
+        problems.push(String::from("Missing the `hybrid` section"));
+    }
+
+    // 0.3.5: A hex `background` replaces the separate color components.
+    let background = hybrid["background"].as_str();
+    let has_background = background.and_then(color::parse_hex).is_some();
+    if let Some(background) = background.filter(|_| !has_background) {
+        problems.push(format!(
+            "hybrid:background '{background}' is invalid, expected a hex color like `#1e1e2ecc`"
+        ));
+    }
+
+    validate_pixel_ranges("hybrid", hybrid, None, &mut problems);
+
+    if let Some(opacity) = config::as_f64(&hybrid["opacity"]) {
+        if !(0.0..=1.0).contains(&opacity) {
+            problems.push(format!(
+                "hybrid:opacity {opacity} is out of range, it's clamped to 0.0-1.0"
+            ));
+        }
+    }
+
+    for (key, key_type, required) in HYBRID_KEYS {
+        let value = &hybrid[*key];
+        if value.is_null() {
+            let is_color = matches!(*key, "r" | "g" | "b" | "a");
+            if *required && hybrid.is_object() && !(is_color && has_background) {
+                problems.push(format!("hybrid:{key} is required, but it's missing"));
+            }
+
+            continue;
+        }
+
+        if !key_type.matches(value) {
+            problems.push(format!("hybrid:{key} should be {}", key_type.name()));
+        }
+    }
+
+    for (key, _) in hybrid.entries() {
+        if !HYBRID_KEYS.iter().any(|(name, _, _)| *name == key) {
+            let known_keys = HYBRID_KEYS.iter().map(|(name, _, _)| *name);
+            problems.push(format!(
+                "hybrid: Unknown key '{key}'{}",
+                did_you_mean(key, known_keys)
+            ));
+        }
+    }
+
+    validate_update_rate(
+        "hybrid",
+        "update_rate",
+        &hybrid["update_rate"],
+        &mut problems,
+    );
+    validate_update_rate(
+        "hybrid",
+        "update_rate_on_battery",
+        &hybrid["update_rate_on_battery"],
+        &mut problems,
+    );
+    if let Some(position) = hybrid["position"].as_str() {
+        if !position.is_empty() && Position::from_str(position).is_err() {
+            problems.push(format!(
+                "hybrid:position '{position}' is invalid, expected top, bottom, left or right"
+            ));
+        }
+    }
+
+    if let Some(default_format) = hybrid["default_format"].as_str() {
+        if !default_format.is_empty()
+            && !default_format.contains(config::DEFAULT_FORMAT_PLACEHOLDER)
+        {
+            problems.push(format!(
+                "hybrid:default_format '{default_format}' has no `{}`, so widgets using it won't \
+                 show their value",
+                config::DEFAULT_FORMAT_PLACEHOLDER
+            ));
+        }
+    }
+
+    for key in ["gtk_theme", "icon_theme"] {
+        let is_installed = match key {
+            "gtk_theme" => theme::is_gtk_theme_installed,
+            _ => theme::is_icon_theme_installed,
+        };
+
+        if let Some(name) = hybrid[key].as_str().filter(|name| !is_installed(name)) {
+            problems.push(format!(
+                "hybrid:{key} '{name}' isn't installed, so the system theme is used instead"
+            ));
+        }
+    }
+
+    let variables = &config["variables"];
+    if !variables.is_null() && !variables.is_object() {
+        problems.push(String::from(
+            "`variables` must be an object, so its variables are ignored",
+        ));
+    }
+
+    for (name, value) in variables.entries().filter(|(_, value)| value.is_object()) {
+        let has_value = !value["value"].is_null() && !value["value"].is_object();
+        if value["command"].is_string() == has_value {
+            problems.push(format!(
+                "variables:{name} should be a string, or an object with either a `command` or a \
+                 `value`"
+            ));
+        }
+
+        if !value["raw"].is_null() && config::as_bool(&value["raw"]).is_none() {
+            problems.push(format!("variables:{name}:raw should be a bool"));
+        }
+    }
+
+    // Valid includes are merged in and removed while reading the config.
+    if !config["include"].is_null() {
+        problems.push(String::from(
+            "`include` should be an array of paths, so it's ignored",
+        ));
+    }
+
+    validate_computed(config, &mut problems);
+    validate_variable_usages(config, &mut problems);
+    validate_placeholder_shadowing(config, &mut problems);
+    validate_sources(config, &mut problems);
+    validate_templates(config, &mut problems);
+
+    // Widget names paired with the key of the first widget using them.
+    let mut names: Vec<(String, &str)> = Vec::new();
+    for (key, block) in config.entries() {
+        // Same rules as `ui::create_components` for what's considered a widget.
+        if !key.contains('-') || !key.contains('_') {
+            continue;
+        }
+
+        validate_widget(key, block, &mut problems);
+        // Same as `ui::get_widget_name`, but for `config` rather than the cached config.
+        let name = block["id"]
+            .as_str()
+            .or_else(|| key.split_once('_').map(|(_, name)| name))
+            .unwrap_or_default();
+        if name.is_empty() {
+            continue;
+        }
+
+        match names.iter().find(|(other, _)| other == name) {
+            Some((_, other_key)) => problems.push(format!(
+                "{key}: The name '{name}' is already used by '{other_key}', set an `id` to tell \
+                 them apart"
+            )),
+            None => names.push((name.to_string(), key)),
+        }
+    }
+
+    validate_groups(config, &mut problems);
+    problems
+}
+
+/// Source keys, along with their types.
+const SOURCE_KEYS: &[(&str, KeyType)] = &[
+    ("command", KeyType::String),
+    ("update_rate", UPDATE_RATE),
+    ("command_timeout", KeyType::Integer),
+];
+
+/// 0.3.5: Reports sources without a command, with unknown keys, and labels showing a source that
+///   doesn't exist.
+fn validate_sources(config: &JsonValue, problems: &mut Vec<String>) {
+    let sources = &config["sources"];
+    if !sources.is_null() && !sources.is_object() {
+        problems.push(String::from(
+            "`sources` must be an object, so its sources are ignored",
+        ));
+    }
+
+    for (name, source) in sources.entries() {
+        if !source["command"].is_string() {
+            problems.push(format!("sources:{name} needs a `command` printing JSON"));
+        }
+
+        for (key, value) in source.entries() {
+            match SOURCE_KEYS.iter().find(|(other, _)| *other == key) {
+                Some((_, key_type)) if !key_type.matches(value) => problems.push(format!(
+                    "sources:{name}:{key} should be {}",
+                    key_type.name()
+                )),
+                Some(_) => {}
+                None => problems.push(format!(
+                    "sources:{name}: Unknown key '{key}'{}",
+                    did_you_mean(key, SOURCE_KEYS.iter().map(|(key, _)| *key))
+                )),
+            }
+        }
+
+        validate_update_rate(
+            &format!("sources:{name}"),
+            "update_rate",
+            &source["update_rate"],
+            problems,
+        );
+    }
+
+    // Graphs may sample other widgets as well, so only labels are known to need a source.
+    for (key, block) in config.entries().filter(|(key, _)| key.contains("-label_")) {
+        let Some(path) = block["source"].as_str() else {
+            continue;
+        };
+
+        let source = path.split_once('.').map(|(source, _)| source);
+        if !source.is_some_and(|source| sources[source].is_object()) {
+            problems.push(format!(
+                "{key}:source '{path}' isn't a field of a source, expected one like `weather.temp`"
+            ));
+        }
+    }
+}
+
+/// 0.3.5: Reports templates which aren't objects, and blocks whose template couldn't be applied.
+///   `config::apply_templates` removes the `template` of every block it applies one to.
+fn validate_templates(config: &JsonValue, problems: &mut Vec<String>) {
+    let templates = &config["templates"];
+    if !templates.is_null() && !templates.is_object() {
+        problems.push(String::from(
+            "`templates` must be an object, so its templates are ignored",
+        ));
+    }
+
+    for (name, _) in templates.entries().filter(|(_, keys)| !keys.is_object()) {
+        problems.push(format!(
+            "templates:{name} should be an object of widget keys"
+        ));
+    }
+
+    for (key, block) in config.entries().filter(|(key, _)| *key != "templates") {
+        if let Some(name) = block["template"].as_str() {
+            problems.push(format!(
+                "{key}:template '{name}' doesn't exist or ends up using itself, so it's ignored"
+            ));
+        }
+    }
+}
+
+/// Reports groups without widgets, widgets which don't exist, widgets in more than one group and
+/// groups which end up containing themselves.
+fn validate_groups(config: &JsonValue, problems: &mut Vec<String>) {
+    let is_widget = |key: &str| key.contains('-') && key.contains('_') && config.has_key(key);
+    let groups: Vec<(&str, Vec<&str>)> = config
+        .entries()
+        .filter(|(key, _)| {
+            let identifier = key.split('_').next().unwrap_or_default();
+            is_widget(key) && identifier.split_once('-').map(|(_, kind)| kind) == Some("group")
+        })
+        .map(|(key, block)| {
+            let widgets = block["widgets"].members().filter_map(JsonValue::as_str);
+            (key, widgets.collect())
+        })
+        .collect();
+
+    // Widget keys paired with the first group containing them.
+    let mut grouped: Vec<(&str, &str)> = Vec::new();
+    for (key, widgets) in &groups {
+        let block = &config[*key];
+        if block["widgets"].members().next().is_none() {
+            problems.push(format!(
+                "{key}: Groups need a `widgets` array with the keys of the widgets inside them"
+            ));
+        }
+
+        if block["widgets"].members().any(|widget| !widget.is_string()) {
+            problems.push(format!(
+                "{key}:widgets should only contain the keys of widgets, as strings"
+            ));
+        }
+
+        for widget in widgets {
+            if !is_widget(widget) {
+                problems.push(format!(
+                    "{key}:widgets has '{widget}', which isn't a widget"
+                ));
+                continue;
+            }
+
+            match grouped.iter().find(|(other, _)| other == widget) {
+                Some((_, other_group)) => problems.push(format!(
+                    "{key}:widgets has '{widget}', which is already in '{other_group}'"
+                )),
+                None => grouped.push((widget, key)),
+            }
+        }
+    }
+
+    for (key, _) in &groups {
+        // Walks the groups inside this one, looking for itself.
+        let mut pending = vec![*key];
+        let mut seen: Vec<&str> = Vec::new();
+        while let Some(group) = pending.pop() {
+            let Some((_, widgets)) = groups.iter().find(|(other, _)| *other == group) else {
+                continue;
+            };
+
+            if widgets.contains(key) {
+                problems.push(format!(
+                    "{key}: The group ends up containing itself, through '{group}'"
+                ));
+                break;
+            }
+
+            for widget in widgets {
+                if !seen.contains(widget) {
+                    seen.push(widget);
+                    pending.push(widget);
+                }
+            }
+        }
+    }
+}
+
+/// Reports an update-rate at `key` -> `update_rate_key` that's a string, but neither a number, a
+/// number with a unit nor `config::UPDATE_ONCE`.
+fn validate_update_rate(
+    key: &str,
+    update_rate_key: &str,
+    update_rate: &JsonValue,
+    problems: &mut Vec<String>,
+) {
+    if let Some(string) = update_rate.as_str() {
+        if let Err(err) = config::parse_update_rate(update_rate) {
+            problems.push(format!(
+                "{key}:{update_rate_key} '{string}' is invalid, {err}"
+            ));
+        }
+    }
+}
+
+/// Validates a single widget block.
+fn validate_widget(key: &str, block: &JsonValue, problems: &mut Vec<String>) {
+    let (identifier, name) = key.split_once('_').unwrap_or((key, ""));
+    let Some((alignment, widget_type)) = identifier.split_once('-') else {
+        problems.push(format!(
+            "{key}: Widget should be named as [alignment]-[widget_type]_[name]"
+        ));
+        return;
+    };
+
+    if Align::from_str(&alignment.to_uppercase()).is_err() {
+        problems.push(format!(
+            "{key}: Invalid alignment '{alignment}', expected left, centered or right"
+        ));
+    }
+
+    if let Some(align) = block["align"].as_str() {
+        if Align::from_str(&align.to_uppercase()).is_err() {
+            problems.push(format!(
+                "{key}:align '{align}' is invalid, expected left, center or right"
+            ));
+        }
+    }
+
+    if let Some(text_align) = block["text_align"].as_str() {
+        if overflow::parse_text_align(text_align).is_none() {
+            problems.push(format!(
+                "{key}:text_align '{text_align}' is invalid, expected left, center or right"
+            ));
+        }
+    }
+
+    // Boxes take up no space by default.
+    let min_size = if widget_type == "box" { 0 } else { 1 };
+    validate_pixel_ranges(key, block, Some(min_size), problems);
+
+    if name.is_empty() {
+        problems.push(format!("{key}: The widget name is empty"));
+    }
+
+    for update_rate_key in ["update_rate", "update_rate_on_battery"] {
+        validate_update_rate(key, update_rate_key, &block[update_rate_key], problems);
+    }
+
+    if block["update_on"].is_array() {
+        if let Err(err) = triggers::parse_list(&block["update_on"]) {
+            problems.push(format!("{key}:update_on is invalid, {err}"));
+        }
+    }
+
+    let Some((_, known_keys)) = WIDGET_KEYS.iter().find(|(name, _)| *name == widget_type) else {
+        problems.push(format!(
+            "{key}: There are no widgets identified as '{widget_type}'{}",
+            did_you_mean(widget_type, WIDGET_KEYS.iter().map(|(name, _)| *name))
+        ));
+        return;
+    };
+
+    if !block.is_object() {
+        problems.push(format!("{key}: The widget should be an object"));
+        return;
+    }
+
+    if let Some(missing) = missing_keys(widget_type, block) {
+        problems.push(format!("{key}: {missing}"));
+    }
+
+    if let Some(target) = block["target"].as_str().filter(|_| widget_type == "timer") {
+        if timer_widget::parse_target(target, chrono::Local::now()).is_none() {
+            problems.push(format!(
+                "{key}:target '{target}' is invalid, expected a time like `15:30` or an offset \
+                 like `+25m`"
+            ));
+        }
+    }
+
+    if let Some(style) = block["style"].as_str().filter(|_| widget_type == "graph") {
+        if !matches!(style, "line" | "bars") {
+            problems.push(format!(
+                "{key}:style '{style}' is invalid, expected `line` or `bars`"
+            ));
+        }
+    }
+
+    if block["transform"].is_array() {
+        if let Err(err) = transform::parse(&block["transform"]) {
+            problems.push(format!("{key}:transform is invalid, {err}"));
+        }
+    }
+
+    if block["ranges"].is_array() {
+        if let Err(err) = Ranges::parse(&block["ranges"]) {
+            problems.push(format!("{key}:ranges is invalid, {err}"));
+        }
+    }
+
+    if let Some(format) = block["format"].as_str().filter(|_| widget_type == "clock") {
+        if !clock_widget::is_valid_format(format) {
+            problems.push(format!(
+                "{key}:format '{format}' isn't a valid strftime format, like `%H:%M`"
+            ));
+        }
+    }
+
+    for (child_key, value) in block.entries() {
+        let known_key = known_keys
+            .iter()
+            .chain(COMMON_WIDGET_KEYS)
+            .find(|(name, _)| *name == child_key);
+        match known_key {
+            Some((_, key_type)) if !key_type.matches(value) => {
+                problems.push(format!("{key}:{child_key} should be {}", key_type.name()))
+            }
+            Some(_) => {}
+            None => {
+                let known_keys = known_keys
+                    .iter()
+                    .chain(COMMON_WIDGET_KEYS)
+                    .map(|(name, _)| *name);
+                problems.push(format!(
+                    "{key}: Unknown key '{child_key}' for '{widget_type}' widgets{}",
+                    did_you_mean(child_key, known_keys)
+                ))
+            }
+        }
+    }
+}
+
+/// Keys of the objects in the `computed` section, along with their types.
+const COMPUTED_KEYS: &[(&str, KeyType)] = &[
+    (
+        "value",
+        KeyType::OneOf(&[KeyType::String, KeyType::Number, KeyType::Bool]),
+    ),
+    ("from", KeyType::String),
+    ("transform", KeyType::Array),
+];
+
+/// 0.3.5: Reports entries of `computed` which can't be resolved, see `config::compute`.
+fn validate_computed(config: &JsonValue, problems: &mut Vec<String>) {
+    let computed = &config["computed"];
+    if !computed.is_null() && !computed.is_object() {
+        problems.push(String::from(
+            "`computed` must be an object, so its entries are ignored",
+        ));
+    }
+
+    for (name, entry) in computed.entries() {
+        if config["variables"].has_key(name) {
+            problems.push(format!(
+                "computed:{name} is named like a variable, so it's ignored"
+            ));
+        }
+
+        if entry.is_null() || entry.is_array() {
+            problems.push(format!(
+                "computed:{name} should be a string, or an object with a `value` or a `from`"
+            ));
+            continue;
+        }
+
+        for (key, value) in entry.entries() {
+            match COMPUTED_KEYS.iter().find(|(other, _)| *other == key) {
+                Some((_, key_type)) if !key_type.matches(value) => problems.push(format!(
+                    "computed:{name}:{key} should be {}",
+                    key_type.name()
+                )),
+                Some(_) => {}
+                None => problems.push(format!(
+                    "computed:{name}: Unknown key '{key}'{}",
+                    did_you_mean(key, COMPUTED_KEYS.iter().map(|(key, _)| *key))
+                )),
+            }
+        }
+
+        if entry.is_object() && entry["value"].is_null() == entry["from"].is_null() {
+            problems.push(format!(
+                "computed:{name} needs exactly one of `value` and `from`"
+            ));
+        }
+
+        if let Some(path) = entry["from"].as_str() {
+            let value = config::get_at_path(config, path);
+            if value.is_none_or(|value| value.is_object() || value.is_array()) {
+                problems.push(format!(
+                    "computed:{name}:from '{path}' isn't a value of the config"
+                ));
+            }
+        }
+
+        if let Err(err) = transform::parse(&entry["transform"]) {
+            problems.push(format!("computed:{name}:transform is invalid, {err}"));
+        }
+    }
+}
+
+/// 0.3.5: Reports variables named like a placeholder of a widget in the config, as its formats
+///   show the placeholder rather than the variable, see `config::try_get_format`.
+fn validate_placeholder_shadowing(config: &JsonValue, problems: &mut Vec<String>) {
+    let ignore_case = config::as_bool(&config["hybrid"]["variables_ignore_case"]) == Some(true);
+    let mut widget_types: Vec<(&str, &str)> = Vec::new();
+    for (key, _) in config.entries() {
+        let Some(widget_type) = config::get_widget_type(key) else {
+            continue;
+        };
+
+        if !widget_types.iter().any(|(other, _)| *other == widget_type) {
+            widget_types.push((widget_type, key));
+        }
+    }
+
+    let names = config["variables"]
+        .entries()
+        .map(|(name, _)| ("variables", name))
+        .chain(
+            config["computed"]
+                .entries()
+                .map(|(name, _)| ("computed", name)),
+        );
+    for (section, name) in names {
+        for (widget_type, key) in &widget_types {
+            let shadows = placeholders::get_names(widget_type)
+                .iter()
+                .any(|placeholder| {
+                    *placeholder == name || (ignore_case && placeholder.eq_ignore_ascii_case(name))
+                });
+            if shadows {
+                problems.push(format!(
+                    "{section}:{name} is named like the `{{{name}}}` placeholder of {widget_type} \
+                     widgets like '{key}', which their formats show instead. Rename it to use it \
+                     there"
+                ));
+            }
+        }
+    }
+}
+
+/// Keys filled in with placeholders rather than variables, so `{percent}` isn't mistaken for one.
+fn has_placeholders(key: &str) -> bool {
+    key.ends_with("format") || key == "set_command"
+}
+
+/// Collects the strings variables are expanded in from `value` and everything nested inside of it.
+fn collect_strings<'a>(key: &str, value: &'a JsonValue, strings: &mut Vec<&'a str>) {
+    if has_placeholders(key) {
+        return;
+    }
+
+    if let Some(string) = value.as_str() {
+        strings.push(string);
+    }
+
+    for (key, value) in value.entries() {
+        collect_strings(key, value, strings);
+    }
+
+    for value in value.members() {
+        collect_strings("", value, strings);
+    }
+}
+
+/// Finds the names written as `{name}` in `string`, skipping `${VAR}`, escaped braces and
+/// placeholders of other widgets like `{name.value}`.
+fn find_variable_tokens(string: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut rest = string;
+    while let Some(start) = rest.find('{') {
+        let escaped = rest[start + 1..].starts_with('{');
+        let after_dollar = rest[..start].ends_with('$');
+        let name = &rest[start + 1..];
+        let length = name
+            .find(|char: char| !char.is_alphanumeric() && char != '_')
+            .unwrap_or(name.len());
+        if !escaped && !after_dollar && length > 0 && name[length..].starts_with('}') {
+            tokens.push(&name[..length]);
+        }
+
+        // Skips both braces of an escape, so `{{{x}` still finds `{x}`.
+        rest = &rest[start + if escaped { 2 } else { 1 }..];
+    }
+
+    tokens
+}
+
+/// 0.3.5: Reports variables which nothing uses anymore, and `{name}` tokens without a variable.
+fn validate_variable_usages(config: &JsonValue, problems: &mut Vec<String>) {
+    let hybrid = &config["hybrid"];
+    let variables = &config["variables"];
+    let mut strings = Vec::new();
+    for (key, block) in config
+        .entries()
+        .filter(|(key, _)| *key != "hybrid" && *key != "variables")
+    {
+        collect_strings(key, block, &mut strings);
+    }
+
+    // Raw values are never expanded, so they can't use other variables.
+    for (name, value) in variables.entries() {
+        if config::as_bool(&value["raw"]) != Some(true) {
+            collect_strings(name, value, &mut strings);
+        }
+    }
+
+    let ignore_case = config::as_bool(&hybrid["variables_ignore_case"]).unwrap_or_default();
+    let same_name = |a: &str, b: &str| match ignore_case {
+        true => a.eq_ignore_ascii_case(b),
+        false => a == b,
+    };
+
+    // Names ending in `*` cover every name starting with the rest of it.
+    let defines = |name: &str, token: &str| match name.strip_suffix('*') {
+        Some(prefix) => token
+            .get(..prefix.len())
+            .is_some_and(|start| same_name(start, prefix)),
+        None => same_name(name, token),
+    };
+
+    // Configs written before variables needed braces use their bare names, which can't be told
+    // apart from plain text, so only the names are looked for.
+    if config::as_bool(&hybrid["bare_variables"]).unwrap_or_default() {
+        for (name, _) in variables.entries() {
+            let name = name.strip_suffix('*').unwrap_or(name);
+            if !name.is_empty() && !strings.iter().any(|string| string.contains(name)) {
+                problems.push(format!("variables:{name} isn't used anywhere"));
+            }
+        }
+
+        return;
+    }
+
+    let tokens: Vec<&str> = strings
+        .iter()
+        .flat_map(|string| find_variable_tokens(string))
+        .collect();
+    // Formats expand variables too, except for the placeholders of their widget. Anything else is
+    // kept as-is if there's no variable for it, so they only count as usages.
+    let mut format_tokens = Vec::new();
+    for (key, block) in config.entries() {
+        let names = placeholders::get_names(config::get_widget_type(key).unwrap_or_default());
+        for (_, format) in block.entries().filter(|(key, _)| has_placeholders(key)) {
+            let format = format.as_str().unwrap_or_default();
+            format_tokens.extend(
+                find_variable_tokens(format)
+                    .into_iter()
+                    .filter(|token| !names.contains(token)),
+            );
+        }
+    }
+
+    for (name, _) in variables.entries() {
+        if tokens
+            .iter()
+            .chain(&format_tokens)
+            .any(|token| defines(name, token))
+        {
+            continue;
+        }
+
+        let bare = name.strip_suffix('*').unwrap_or(name);
+        if !bare.is_empty() && strings.iter().any(|string| string.contains(bare)) {
+            problems.push(format!(
+                "variables:{name} is only used without braces, write it as `{{{bare}}}` or set \
+                 `hybrid` -> `bare_variables`"
+            ));
+        } else {
+            problems.push(format!("variables:{name} isn't used anywhere"));
+        }
+    }
+
+    // 0.3.5: Entries of `computed` are variables as well.
+    let all_variables = || variables.entries().chain(config["computed"].entries());
+    let mut undefined: Vec<&str> = Vec::new();
+    for token in tokens {
+        if !undefined.contains(&token) && !all_variables().any(|(name, _)| defines(name, token)) {
+            undefined.push(token);
+        }
+    }
+
+    for token in undefined {
+        let names = all_variables().map(|(name, _)| name);
+        problems.push(format!(
+            "`{{{token}}}` looks like a variable, but there's no variable named '{token}'{}",
+            did_you_mean(token, names)
+        ));
+    }
+}
+
+/// Suggests the known key closest to the misspelled `key`, formatted as `, did you mean 'x'?`.
+/// Empty if none of them are close enough to be a typo.
+fn did_you_mean<'a>(key: &str, known_keys: impl Iterator<Item = &'a str>) -> String {
+    // Allow one typo for every few characters, so short keys don't match everything.
+    let max_distance = (key.chars().count() / 3).max(1);
+    known_keys
+        .map(|known_key| (edit_distance(key, known_key), known_key))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known_key)| format!(", did you mean '{known_key}'?"))
+        .unwrap_or_default()
+}
+
+/// The Levenshtein distance between `a` and `b`, as in how many characters have to be inserted,
+/// removed or replaced to turn one into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // Only the previous row of the matrix is needed to compute the next one.
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gets the problems `validate_variable_usages` finds in `config`.
+    fn variable_problems(config: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        validate_variable_usages(&json::parse(config).unwrap(), &mut problems);
+        problems
+    }
+
+    #[test]
+    fn variable_tokens() {
+        assert_eq!(find_variable_tokens("{a} and {b_2}"), ["a", "b_2"]);
+        // Environment variables and escaped braces aren't variables.
+        assert!(find_variable_tokens("${HOME} {{x}} {} {a b}").is_empty());
+        assert_eq!(find_variable_tokens("{{{x}"), ["x"]);
+        assert_eq!(find_variable_tokens("{unclosed {c}"), ["c"]);
+    }
+
+    #[test]
+    fn variable_usages() {
+        assert!(variable_problems(
+            r#"{
+                "variables": { "used": "a", "icon_*": "b", "raw": { "value": "{nope}", "raw": true } },
+                "computed": { "light": { "value": "{used}" } },
+                "left-label_a": { "text": "{used} {icon_battery} {light} {raw} ${HOME} {{x}}" }
+            }"#
+        )
+        .is_empty());
+
+        assert_eq!(
+            variable_problems(
+                r#"{
+                    "variables": { "unused": "a", "name": "b" },
+                    "left-label_a": { "text": "{nam} name" }
+                }"#
+            ),
+            [
+                String::from("variables:unused isn't used anywhere"),
+                String::from(
+                    "variables:name is only used without braces, write it as `{name}` or set \
+                     `hybrid` -> `bare_variables`"
+                ),
+                String::from(
+                    "`{nam}` looks like a variable, but there's no variable named 'nam', did \
+                     you mean 'name'?"
+                ),
+            ]
+        );
+
+        // Names are compared without their case if the config says so.
+        let ignore_case = r#"{
+            "hybrid": { "variables_ignore_case": true },
+            "variables": { "Accent": "a" },
+            "left-label_a": { "text": "{accent}" }
+        }"#;
+        assert!(variable_problems(ignore_case).is_empty());
+        assert_eq!(
+            variable_problems(&ignore_case.replace("true", "false")).len(),
+            2
+        );
+
+        // Placeholders of the widget in formats aren't variables, other tokens there are usages.
+        assert!(variable_problems(
+            r#"{
+                "variables": { "unit": "%" },
+                "left-cpu_a": { "format": "{usage}{unit} {missing}" }
+            }"#
+        )
+        .is_empty());
+
+        // Bare variables can't be told apart from text, so only the names are looked for.
+        assert_eq!(
+            variable_problems(
+                r#"{
+                    "hybrid": { "bare_variables": true },
+                    "variables": { "used": "a", "unused": "b" },
+                    "left-label_a": { "text": "used {undefined}" }
+                }"#
+            ),
+            [String::from("variables:unused isn't used anywhere")]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::{env, process};
+
+lazy_static! {
+    /// The parsed command-line arguments.
+    pub static ref ARGS: Args = Args::parse(env::args().skip(1));
+}
+
+/// Command-line arguments passed to Hybrid.
+#[derive(Default)]
+pub struct Args {
+    /// Full path to the config file, takes precedence over `HYBRID_CONFIG`.
+    pub config: Option<String>,
+    /// Name of the profile, selecting `config.<name>.json` inside the config path.
+    pub profile: Option<String>,
+    /// Validate the config and exit, without launching the bar.
+    pub check: bool,
+    /// Print the widgets in the i3bar protocol to stdout, instead of launching the bar.
+    pub i3bar: bool,
+    /// Print a JSON Schema of the config and exit.
+    pub dump_schema: bool,
+    /// Log debug output, regardless of `HYBRID_LOG`.
+    pub verbose: bool,
+    /// Don't color the logs, even when stderr is a terminal.
+    pub no_color: bool,
+    /// Values set through `--set <json-pointer>=<value>`, in order.
+    pub overrides: Vec<(String, String)>,
+}
+
+impl Args {
+    /// Parses the arguments, exiting with an error message if any of them are invalid.
+    fn parse(mut args: impl Iterator<Item = String>) -> Self {
+        let mut result = Self::default();
+        while let Some(arg) = args.next() {
+            // Support both `--key value` and `--key=value`.
+            let (key, inline_value) = match arg.split_once('=') {
+                Some((key, value)) if key.starts_with("--") => (key, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+
+            match key {
+                "-c" | "--config" => {
+                    result.config =
+                        Some(inline_value.or_else(|| args.next()).unwrap_or_else(|| {
+                            exit_with_error(&format!("Missing a path after '{key}'"))
+                        }))
+                }
+                "-p" | "--profile" => {
+                    let profile = inline_value.or_else(|| args.next()).unwrap_or_else(|| {
+                        exit_with_error(&format!("Missing a profile name after '{key}'"))
+                    });
+
+                    // It becomes part of a file name.
+                    if profile.is_empty() || profile.contains('/') {
+                        exit_with_error(&format!("Invalid profile name '{profile}'"))
+                    }
+
+                    result.profile = Some(profile)
+                }
+                "--set" => {
+                    let set = inline_value.or_else(|| args.next()).unwrap_or_else(|| {
+                        exit_with_error(&format!("Missing an override after '{key}'"))
+                    });
+
+                    match set.split_once('=') {
+                        Some((pointer, value)) if pointer.starts_with('/') => result
+                            .overrides
+                            .push((pointer.to_string(), value.to_string())),
+                        _ => exit_with_error(&format!(
+                            "Invalid override '{set}', expected `/json/pointer=value`"
+                        )),
+                    }
+                }
+                "--check" => result.check = true,
+                "--i3bar" => result.i3bar = true,
+                "--dump-schema" => result.dump_schema = true,
+                "-v" | "--verbose" => result.verbose = true,
+                "--no-color" => result.no_color = true,
+                _ => exit_with_error(&format!("Unknown argument '{arg}'")),
+            }
+        }
+
+        result
+    }
+}
+
+/// Logs `message` as an error and exits.
+pub fn exit_with_error(message: &str) -> ! {
+    log::error!("{message}!");
+    process::exit(1)
+}
@@ -1,54 +1,1118 @@
-use crate::{environment, math};
-use heapless::Vec;
+use crate::{
+    check, cli,
+    command::{self, CommandRunner},
+    environment, hooks, jsonc, locks, math, placeholders,
+    schema::HybridConfig,
+    structures::Position,
+    toml_config, transform, transition,
+};
+use aho_corasick::{AhoCorasick, MatchKind};
 use json::JsonValue;
-use std::{fs, i32, sync::RwLock};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::HashSet,
+    env,
+    fmt::Display,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, RwLock, RwLockReadGuard},
+    time::{Duration, SystemTime},
+};
+
+/// How many times `with_variables` may expand nested variables before giving up.
+const MAX_VARIABLE_DEPTH: usize = 16;
+
+/// The config written on first run, when there's no config yet.
+const DEFAULT_CONFIG: &str = r#"{
+    "hybrid": {
+        "update_rate": 100,
+        "r": 10,
+        "g": 10,
+        "b": 10,
+        "a": 1.0
+    },
+    "left-label_username": {
+        "text": "user: ",
+        "command": "whoami",
+        "tooltip": "This is your username from whoami"
+    }
+}
+"#;
 
 lazy_static! {
     /// Caches the config.
     // "Why not Mutex" : https://onesignal.com/blog/thread-safety-rust/#:~:text=They%20have%20one%20important%20difference,exclusive%20access%20for%20write%20locks.
-    // TODO: Make read_config_cached() - if possible.
     pub static ref CONFIG: RwLock<JsonValue> = RwLock::new(JsonValue::Null);
+
+    /// Caches the custom variables, rebuilt every time the config is cached.
+    static ref VARIABLES: RwLock<CustomVariables> = RwLock::new(CustomVariables::default());
+
+    /// Caches `hybrid` -> `thousands_separator`, as it's used on every update of grouped numbers.
+    static ref THOUSANDS_SEPARATOR: RwLock<Option<String>> = RwLock::new(None);
+
+    /// Caches `get_frame_interval`, as it's read whenever an animation starts.
+    static ref FRAME_INTERVAL: RwLock<Option<Duration>> = RwLock::new(None);
+
+    /// The typed version of `CONFIG`, rebuilt every time the config is cached.
+    static ref PARSED: RwLock<Arc<HybridConfig>> = RwLock::new(Arc::default());
+
+    /// When the config file was last modified, as of the last `cache()`.
+    static ref MODIFIED: RwLock<Option<SystemTime>> = RwLock::new(None);
+
+    /// The out of range update-rates that have been warned about, as `root:update_rate`.
+    static ref CLAMPED_UPDATE_RATES: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+    /// The config read from stdin, as it can only be read once.
+    static ref STDIN_CONFIG: Mutex<Option<String>> = Mutex::new(None);
+}
+
+/// The custom variables from the `variables` section of the config.
+#[derive(Default)]
+struct CustomVariables {
+    /// Matches all variable names, `None` if there aren't any variables.
+    matcher: Option<AhoCorasick>,
+    /// The variable names, indexed the same as `values`.
+    names: Vec<String>,
+    /// The variable values, indexed by the matched pattern.
+    values: Vec<String>,
+    /// Whether the variable ends in `*`, indexed the same as `values`.
+    wildcards: Vec<bool>,
+    /// Whether the value is used as-is, without expanding what it contains, indexed the same as
+    /// `values`.
+    raw: Vec<bool>,
+    /// Whether names are written as `{name}`, rather than matched anywhere as bare substrings.
+    delimited: bool,
+}
+
+/// Ending a variable name with this makes it match any name starting with the rest, like `PATH_*`
+/// matching `PATH_HOME` and `PATH_TMP`.
+const VARIABLE_WILDCARD: char = '*';
+
+/// Replaced with what the wildcard matched in the value of a wildcard variable.
+const VARIABLE_WILDCARD_PLACEHOLDER: &str = "{*}";
+
+impl CustomVariables {
+    /// Builds the matcher from the variables, which are a name, a value and whether the value is
+    /// raw. ASCII case in names is ignored if `ignore_case` is set.
+    /// Names are only replaced when written as `{name}`, unless `bare` brings back the matching of
+    /// bare names from before `0.3.5`.
+    fn new(variables: Vec<(String, String, bool)>, ignore_case: bool, bare: bool) -> Self {
+        let ((names, values), raw): ((Vec<_>, Vec<_>), Vec<_>) = variables
+            .into_iter()
+            // Empty names would match everywhere, and so would a lone wildcard.
+            .filter(|variable| {
+                !variable.0.is_empty() && variable.0 != VARIABLE_WILDCARD.to_string()
+            })
+            .map(|(name, value, raw)| ((name, value), raw))
+            .unzip();
+        if names.is_empty() {
+            return Self::default();
+        }
+
+        let wildcards: Vec<bool> = names
+            .iter()
+            .map(|name| name.ends_with(VARIABLE_WILDCARD))
+            .collect();
+        let patterns = names.iter().zip(&wildcards).map(|(name, &wildcard)| {
+            let name = name.strip_suffix(VARIABLE_WILDCARD).unwrap_or(name);
+            match (bare, wildcard) {
+                (true, _) => name.to_owned(),
+                // The rest of a wildcard name is matched in `replace`, up to the closing brace.
+                (false, true) => format!("{{{name}"),
+                (false, false) => format!("{{{name}}}"),
+            }
+        });
+
+        // 0.3.5: Match all variables in a single pass, preferring the longest one so that `foo`
+        //   doesn't get replaced inside of `foobar`.
+        let matcher = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .ascii_case_insensitive(ignore_case)
+            .build(patterns)
+            .expect("Failed building the variables matcher!");
+        Self {
+            matcher: Some(matcher),
+            names,
+            values,
+            wildcards,
+            raw,
+            delimited: !bare,
+        }
+    }
+
+    /// Replaces every variable in `input` once. Wildcard variables also take the letters, digits and
+    /// underscores following their prefix, which are put at `{*}` in their value. Written as
+    /// `{name}`, these have to be followed by the closing brace.
+    /// The values of raw variables are moved to `raw_values`, leaving a stand-in behind.
+    fn replace(&self, matcher: &AhoCorasick, input: &str, raw_values: &mut Vec<String>) -> String {
+        let mut result = String::with_capacity(input.len());
+        let mut last = 0;
+        for found in matcher.find_iter(input) {
+            // Part of the name a wildcard variable has already taken.
+            if found.start() < last {
+                continue;
+            }
+
+            // Like `${HOME}`, which is left to `environment::expand_vars`.
+            if self.delimited && input[..found.start()].ends_with('$') {
+                continue;
+            }
+
+            let index = found.pattern().as_usize();
+            let mut end = found.end();
+            let value = if self.wildcards[index] {
+                let rest = &input[end..];
+                let length = rest
+                    .find(|char: char| !char.is_alphanumeric() && char != '_')
+                    .unwrap_or(rest.len());
+                end += length;
+                if self.delimited {
+                    // Like `{PATH_` in `{PATH_a b}`, which isn't a variable.
+                    if !rest[length..].starts_with('}') {
+                        continue;
+                    }
+
+                    end += 1;
+                }
+
+                self.values[index].replace(VARIABLE_WILDCARD_PLACEHOLDER, &rest[..length])
+            } else {
+                self.values[index].clone()
+            };
+
+            result.push_str(&input[last..found.start()]);
+            // Raw values stand in as `placeholders::RAW_STAND_IN` while the rest is expanded.
+            if self.raw[index] {
+                result.push_str(&placeholders::stand_in(
+                    placeholders::RAW_STAND_IN,
+                    raw_values.len(),
+                ));
+                raw_values.push(value);
+            } else {
+                result.push_str(&value);
+            }
+            last = end;
+        }
+
+        result.push_str(&input[last..]);
+        result
+    }
+}
+
+/// Errors that can occur while loading the config.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file couldn't be read.
+    Io { path: String, error: io::Error },
+    /// The config file was read, but isn't valid JSON.
+    Parse { path: String, error: json::Error },
+    /// The config file was read, but isn't valid TOML.
+    TomlParse {
+        path: String,
+        error: toml::de::Error,
+    },
+    /// The config file ends up including itself.
+    IncludeCycle { path: String },
+    /// The config file has an `include` entry which isn't a string.
+    InvalidInclude { path: String, include: String },
+    /// A `--set` override points at a value which can't be set.
+    InvalidOverride { pointer: String, reason: String },
+    /// A `{ "file": ... }` value points at a file which can't be read.
+    ValueFile {
+        key: String,
+        path: String,
+        error: io::Error,
+    },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io { path, error } => {
+                write!(f, "Failed reading config file from '{path}': {error}")
+            }
+            // Formatted like compiler errors, so editors and terminals can jump to the location.
+            ConfigError::Parse {
+                path,
+                error: json::Error::UnexpectedCharacter { ch, line, column },
+            } => write!(f, "{path}:{line}:{column}: unexpected '{ch}'"),
+            ConfigError::Parse {
+                path,
+                error: json::Error::UnexpectedEndOfJson,
+            } => write!(
+                f,
+                "{path}: unexpected end of file, is a closing bracket missing"
+            ),
+            ConfigError::Parse { path, error } => {
+                write!(f, "Failed parsing config from '{path}': {error}")
+            }
+            // The TOML error already includes the line and column, along with a snippet.
+            ConfigError::TomlParse { path, error } => write!(f, "{path}: {error}"),
+            ConfigError::IncludeCycle { path } => {
+                write!(f, "The config file '{path}' ends up including itself")
+            }
+            ConfigError::InvalidInclude { path, include } => {
+                write!(
+                    f,
+                    "Invalid include '{include}' in '{path}', expected a path"
+                )
+            }
+            ConfigError::InvalidOverride { pointer, reason } => {
+                write!(f, "Invalid --set '{pointer}', {reason}")
+            }
+            ConfigError::ValueFile { key, path, error } => {
+                write!(
+                    f,
+                    "Failed reading the value of '{key}' from '{path}': {error}"
+                )
+            }
+        }
+    }
 }
 
+/// The name of the directory inside of the config home, unless `HYBRID_CONFIG_DIR_NAME` is set.
+const DEFAULT_DIR_NAME: &str = "HybridBar";
+
 /// Gets the root home path to Hybrid.
+/// Resolved from `$XDG_CONFIG_HOME`, then `$HOME/.config` and lastly `/home/{user}/.config`.
+/// 0.3.5: The `HybridBar` directory inside of it can be renamed with `HYBRID_CONFIG_DIR_NAME`.
+/// 0.3.5: `HYBRID_CONFIG_ROOT` replaces the whole path, so tests and sandboxed runs never touch
+///   the real home directory. Nothing else is resolved then, not even the username.
 pub fn get_path() -> String {
-    format!(
-        "/home/{}/.config/HybridBar/",
-        execute!(&heapless::String::<6>::from("whoami"))
-    )
+    let root = environment::try_get_var("HYBRID_CONFIG_ROOT", "");
+    if !root.is_empty() {
+        return format!("{}/", root.trim_end_matches('/'));
+    }
+
+    let config_home = env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|path| !path.is_empty())
+        .or_else(|| {
+            env::var("HOME")
+                .ok()
+                .filter(|path| !path.is_empty())
+                .map(|home| format!("{home}/.config"))
+        })
+        .unwrap_or_else(|| format!("/home/{}/.config", environment::get_username()));
+
+    format!("{}/{}/", config_home.trim_end_matches('/'), get_dir_name())
+}
+
+/// Gets the name of the directory inside of the config home from `HYBRID_CONFIG_DIR_NAME`,
+/// defaulting to `HybridBar`. It has to be a single directory, not a path.
+fn get_dir_name() -> String {
+    let dir_name = environment::try_get_var("HYBRID_CONFIG_DIR_NAME", "");
+    if dir_name.is_empty() {
+        return String::from(DEFAULT_DIR_NAME);
+    }
+
+    if dir_name.contains('/') || dir_name == "." || dir_name == ".." {
+        log::warn!(
+            "HYBRID_CONFIG_DIR_NAME '{dir_name}' should be a directory name rather than a path, using '{DEFAULT_DIR_NAME}'"
+        );
+        return String::from(DEFAULT_DIR_NAME);
+    }
+
+    dir_name
 }
 
+/// The lowest allowed update-rate, besides `0`.
+pub const MIN_UPDATE_RATE: u64 = 5;
+
+/// The highest allowed update-rate, a day.
+/// 0.3.5: Raised from 10 seconds, now that rates can be written in minutes and hours.
+const MAX_UPDATE_RATE: u64 = 86_400_000;
+
+/// An `update_rate` which only updates once, whenever the config is loaded. Same as `-1` and `0`.
+pub const UPDATE_ONCE: &str = "once";
+
+/// The units an `update_rate` can be written in, paired with how many milliseconds they are.
+/// `ms` comes first, so `500ms` isn't read as `500m` followed by an `s`.
+const UPDATE_RATE_UNITS: &[(&str, f64)] = &[
+    ("ms", 1.0),
+    ("s", 1000.0),
+    ("m", 60_000.0),
+    ("h", 3_600_000.0),
+];
+
+/// Parses an update-rate into milliseconds, either a number of milliseconds or a string with a
+/// unit like `"500ms"`, `"1.5s"`, `"5m"` or `"1h"`. `UPDATE_ONCE` is read as `-1`.
+pub fn parse_update_rate(value: &JsonValue) -> Result<i32, String> {
+    if let Some(update_rate) = as_i32(value) {
+        return Ok(update_rate);
+    }
+
+    let Some(update_rate) = value.as_str().map(str::trim) else {
+        return Err(String::from(
+            "expected milliseconds, or a string like \"1s\"",
+        ));
+    };
+
+    if update_rate == UPDATE_ONCE {
+        return Ok(-1);
+    }
+
+    let units = || UPDATE_RATE_UNITS.iter().map(|(unit, _)| *unit);
+    let Some((number, factor)) = UPDATE_RATE_UNITS
+        .iter()
+        .find_map(|(unit, factor)| Some((update_rate.strip_suffix(unit)?.trim_end(), factor)))
+    else {
+        let unit =
+            update_rate.trim_start_matches(|char: char| char.is_ascii_digit() || char == '.');
+        return Err(format!(
+            "unknown unit '{unit}', expected one of {} or \"{UPDATE_ONCE}\"",
+            units().collect::<Vec<_>>().join(", ")
+        ));
+    };
+
+    match number.parse::<f64>() {
+        Ok(number) if number.is_finite() && number >= 0.0 => {
+            Ok((number * factor).round().min(i32::MAX as f64) as i32)
+        }
+        _ => Err(format!(
+            "expected a number before the unit, found '{number}'"
+        )),
+    }
+}
+
+/// The update-rate used when `hybrid` -> `update_rate` isn't specified.
+const DEFAULT_UPDATE_RATE: u64 = 100;
+
 /// Returns the set update-rate.
+/// 0.3.5: `0` means labels aren't updated on a timer, only on external events.
 pub fn get_update_rate() -> u64 {
-    let mut update_rate = 100;
-    if let Some(c_update_rate) = try_get("hybrid", "update_rate", false, false) {
-        update_rate = math::clamp_i32(c_update_rate.1, 5, 10_000)
+    get_update_rate_for("hybrid")
+}
+
+/// Returns the update-rate of `root`, parsed and clamped like every other one, or
+/// `DEFAULT_UPDATE_RATE` if it isn't set.
+pub fn get_update_rate_for(root: &str) -> u64 {
+    try_get_update_rate(root, "update_rate").unwrap_or(DEFAULT_UPDATE_RATE)
+}
+
+/// Returns the update-rate of the widget at `widget_root`.
+/// Falls back to the global `hybrid` -> `update_rate` if the widget doesn't specify its own.
+pub fn get_widget_update_rate(widget_root: &str) -> u64 {
+    try_get_update_rate(widget_root, "update_rate").unwrap_or_else(get_update_rate)
+}
+
+/// Returns the update-rate of the widget at `widget_root` while running on battery, `None` if it
+/// keeps its regular one.
+/// 0.3.5: Falls back to `hybrid` -> `update_rate_on_battery` only for widgets following the
+///   global `update_rate`, so a widget with its own `update_rate` is never sped up by it.
+pub fn get_widget_update_rate_on_battery(widget_root: &str) -> Option<u64> {
+    try_get_update_rate(widget_root, "update_rate_on_battery").or_else(|| {
+        with_config(|config| config[widget_root]["update_rate"].is_null())
+            .then(|| try_get_update_rate("hybrid", "update_rate_on_battery"))
+            .flatten()
+    })
+}
+
+/// Gets the clamped update-rate at `root` -> `key`, like `update_rate`, if it's set.
+/// 0.3.5: `UPDATE_ONCE` and `-1` are the same as `0`, for widgets whose output never changes
+///   during a session, like the kernel version. They're only updated again on a reload.
+fn try_get_update_rate(root: &str, key: &str) -> Option<u64> {
+    let update_rate = with_config(|config| config[root][key].clone());
+    as_update_rate(&format!("{root}:{key}"), &update_rate)
+}
+
+/// Parses and clamps `update_rate` like `try_get_update_rate`, for update-rates that aren't
+/// directly inside of a block. `path` names it in warnings, like `sources:weather:update_rate`.
+pub fn as_update_rate(path: &str, update_rate: &JsonValue) -> Option<u64> {
+    if update_rate.is_null() {
+        return None;
+    }
+
+    match parse_update_rate(update_rate) {
+        Ok(-1) => Some(0),
+        Ok(milliseconds) => Some(clamp_update_rate(path, milliseconds)),
+        Err(err) => {
+            log::warn!("{path} '{update_rate}' is invalid, {err}. Ignoring it");
+            None
+        }
+    }
+}
+
+/// Clamps a nonzero update-rate at `path`, like `hybrid:update_rate`, to
+/// `MIN_UPDATE_RATE..MAX_UPDATE_RATE`. `0` is kept as-is.
+/// 0.3.5: Clamping is logged, once per path and value as this is called on every update.
+fn clamp_update_rate(path: &str, update_rate: i32) -> u64 {
+    if update_rate == 0 {
+        return 0;
+    }
+
+    let clamped = math::clamp_i32(update_rate, MIN_UPDATE_RATE as i32, MAX_UPDATE_RATE as i32);
+    if clamped != update_rate
+        && locks::lock(&CLAMPED_UPDATE_RATES).insert(format!("{path}:{update_rate}"))
+    {
+        log::warn!(
+            "{path} {update_rate} is outside of {MIN_UPDATE_RATE}..{MAX_UPDATE_RATE} \
+             milliseconds, using {clamped} instead. Use 0 to turn the timer off"
+        );
     }
 
-    update_rate
+    clamped
         .try_into()
-        .expect("[ERROR] Cannot convert update_rate into u64!\n")
+        .expect("Cannot convert update_rate into u64!")
+}
+
+/// How many frames per second animations are drawn at when `fps` isn't specified.
+const DEFAULT_FPS: i32 = 60;
+
+/// The largest size or margin in pixels, beyond any monitor, so typos are caught.
+pub const MAX_PIXELS: i32 = 10000;
+
+/// How far margins of the bar may pull it past the edge of the monitor.
+pub const MAX_NEGATIVE_BAR_MARGIN: i32 = 64;
+
+/// Gets the `i32` at `root` -> `key` within `min..=max`, if it's set.
+/// 0.3.5: Values outside of the range are clamped with a warning, rather than silently rendering
+///   widgets or the bar invisible. Used for sizes and margins, which GTK would reject or which
+///   could move the bar off-screen.
+pub fn try_get_clamped(root: &str, key: &str, min: i32, max: i32) -> Option<i32> {
+    let (_, value) = try_get(root, key, false, false)?;
+    let clamped = math::clamp_i32(value, min, max);
+    if clamped != value {
+        log::warn!(
+            "{root}:{key} {value} is out of range, expected {min} to {max}. Using {clamped}"
+        );
+    }
+
+    Some(clamped)
+}
+
+/// Same as `try_get_clamped`, but `default` if it isn't set.
+pub fn get_clamped(root: &str, key: &str, default: i32, min: i32, max: i32) -> i32 {
+    try_get_clamped(root, key, min, max).unwrap_or(default)
+}
+
+/// The highest allowed `fps`.
+const MAX_FPS: i32 = 240;
+
+/// Returns how long to wait between the frames of animations, from `hybrid` -> `fps`.
+/// 0.3.5: This is only the redraw rate of animated widgets, like moving progress bars, Cava and
+///   scrolling labels. How often data is read and commands are run is up to `update_rate`.
+pub fn get_frame_interval() -> Duration {
+    locks::read(&FRAME_INTERVAL).unwrap_or_else(read_frame_interval)
+}
+
+/// Reads `get_frame_interval` from the cached config.
+fn read_frame_interval() -> Duration {
+    let fps = try_get("hybrid", "fps", false, false).map_or(DEFAULT_FPS, |fps| fps.1);
+    Duration::from_secs_f64(1.0 / math::clamp_i32(fps, 1, MAX_FPS) as f64)
+}
+
+/// 0.3.5: Returns how long the widget at `key` takes to move to a new value, from `transition_ms`.
+///   `default` if it isn't set, `0` shows new values right away.
+pub fn get_transition(key: &str, default: Duration) -> Duration {
+    try_get_clamped(key, "transition_ms", 0, transition::MAX_TRANSITION_MS)
+        .map_or(default, |ms| Duration::from_millis(ms as u64))
+}
+
+/// Returns the position of the bar from `hybrid` -> `position`, defaults to `Position::TOP`.
+/// Unknown positions fall back to `Position::TOP` with a warning.
+pub fn get_position() -> Position {
+    let Some(c_position) = try_get("hybrid", "position", true, false) else {
+        return Position::TOP;
+    };
+
+    // Prior to 0.3.5 an empty position meant top, keep it that way.
+    if c_position.0.is_empty() {
+        return Position::TOP;
+    }
+
+    Position::from_str(&c_position.0).unwrap_or_else(|_| {
+        log::warn!(
+            "Invalid position '{}', expected top, bottom, left or right. Defaulting to top",
+            c_position.0
+        );
+        Position::TOP
+    })
+}
+
+/// Returns what numbers are grouped by in `{name:grouped}` placeholders, from
+/// `hybrid` -> `thousands_separator`. Defaults to the separator of your locale.
+/// Read once per `cache` rather than on every call.
+pub fn get_thousands_separator() -> String {
+    match &*locks::read(&THOUSANDS_SEPARATOR) {
+        Some(separator) => separator.clone(),
+        None => read_thousands_separator(),
+    }
+}
+
+/// Reads `get_thousands_separator` from the cached config.
+fn read_thousands_separator() -> String {
+    try_get("hybrid", "thousands_separator", true, false).map_or_else(
+        || String::from(environment::get_locale_thousands_separator()),
+        |separator| separator.0,
+    )
+}
+
+/// Returns how often the command of the widget at `widget_root` is retried when it fails, from
+/// `retries` and `retry_delay` in milliseconds.
+pub fn get_command_retry(widget_root: &str) -> command::Retry {
+    command::Retry {
+        retries: get_or_default(widget_root, "retries", false, false)
+            .1
+            .max(0) as u32,
+        delay: Duration::from_millis(
+            get_or_default(widget_root, "retry_delay", false, false)
+                .1
+                .max(0) as u64,
+        ),
+    }
+}
+
+/// How many milliseconds commands may run for when `command_timeout` isn't specified.
+const DEFAULT_COMMAND_TIMEOUT: u64 = 5000;
+
+/// Returns for how many milliseconds the command of the widget at `widget_root` may run before
+/// it's killed.
+/// Falls back to `hybrid` -> `command_timeout`, then to `DEFAULT_COMMAND_TIMEOUT`. `0` means there's no timeout.
+pub fn get_command_timeout(widget_root: &str) -> u64 {
+    try_get(widget_root, "command_timeout", false, false)
+        .or_else(|| try_get("hybrid", "command_timeout", false, false))
+        .map_or(DEFAULT_COMMAND_TIMEOUT, |c_command_timeout| {
+            c_command_timeout.1.max(0) as u64
+        })
 }
 
 /// Caches the config so we don't have to re-parse it every time.
 /// Works as a fix for issue #13
-pub fn cache() {
-    *CONFIG.write().unwrap() = read_config_raw();
+/// If the config can't be read or parsed, the previously cached one is kept.
+pub fn cache() -> Result<(), ConfigError> {
+    // Stat before reading, so a write during the read is picked up by the next check.
+    let modified = get_modified();
+    let mut config = read_config()?;
+    apply_conditions(&mut config, &command::BashRunner);
+    // 0.3.5: Typos would otherwise be silently ignored, `--check` reports the same problems.
+    for problem in check::validate(&config) {
+        log::warn!("{problem}");
+    }
+
+    // 0.3.5: Build the variables once here rather than on every lookup.
+    let ignore_case = as_bool(&config["hybrid"]["variables_ignore_case"]).unwrap_or_default();
+    let bare = as_bool(&config["hybrid"]["bare_variables"]).unwrap_or_default();
+    let variables = with_computed_variables(
+        &config,
+        get_custom_variables(&config, &command::BashRunner),
+        ignore_case,
+        bare,
+    );
+    *locks::write(&VARIABLES) = CustomVariables::new(variables, ignore_case, bare);
+    *locks::write(&PARSED) = Arc::new(parse_typed(&config));
+    *locks::write(&CONFIG) = config;
+    *locks::write(&THOUSANDS_SEPARATOR) = Some(read_thousands_separator());
+    *locks::write(&FRAME_INTERVAL) = Some(read_frame_interval());
+    *locks::write(&MODIFIED) = modified;
+    // Cached command output may be stale with the new config.
+    command::clear_cache();
+    Ok(())
+}
+
+/// Same as `cache`, but also runs the `on_reload` hook.
+/// NOTE: Bursts of file changes are already coalesced by `watcher::watch_config`.
+pub fn reload() -> Result<(), ConfigError> {
+    cache()?;
+    // 0.3.5: Reloads happen on the main loop, which the hook mustn't block.
+    hooks::run_in_background("on_reload");
+    Ok(())
+}
+
+/// Same as `reload`, but only re-parses the config if the file has been modified since it was
+/// last cached. Returns whether the config was re-cached.
+/// If the file can't be stat'ed, it's always re-cached so we never get stuck on stale data.
+/// NOTE: Only the main config file is checked, not the files it includes.
+pub fn cache_if_changed() -> Result<bool, ConfigError> {
+    if let (Some(modified), Some(cached)) = (get_modified(), *locks::read(&MODIFIED)) {
+        if modified <= cached {
+            return Ok(false);
+        }
+    }
+
+    reload()?;
+    Ok(true)
+}
+
+/// Returns when the config file was last modified, `None` if it can't be stat'ed.
+fn get_modified() -> Option<SystemTime> {
+    if is_stdin() {
+        return None;
+    }
+
+    fs::metadata(get_config_path())
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Returns the typed config, as of the last `cache()`. Callers holding on to it keep the config
+/// they got, the next `cache()` doesn't change it.
+/// Prefer this over `try_get` in new code, as the fields already have the right types. Values are
+/// as written in the config, so variables aren't expanded in them.
+pub fn parsed_config() -> Arc<HybridConfig> {
+    locks::read(&PARSED).clone()
+}
+
+/// Deserializes `config` into a `HybridConfig`, one section at a time.
+/// If a key has the wrong type, a warning is logged and only its section falls back to the
+/// defaults, so the rest of the config is still read and `try_get` keeps on working.
+fn parse_typed(config: &JsonValue) -> HybridConfig {
+    let mut parsed = HybridConfig::default();
+    for (key, value) in config.entries() {
+        match key {
+            "hybrid" => parsed.hybrid = parse_section(key, value),
+            "variables" => parsed.variables = parse_section(key, value),
+            "computed" => parsed.computed = parse_section(key, value),
+            "sources" => parsed.sources = parse_section(key, value),
+            "templates" => parsed.templates = parse_section(key, value),
+            // Other top-level keys, like `include`, aren't widgets.
+            _ if value.is_object() => {
+                let widget = parse_section(key, value);
+                parsed.widgets.insert(key.to_string(), widget);
+            }
+            _ => {}
+        }
+    }
+
+    parsed
+}
+
+/// Deserializes the section at `key`, falling back to its defaults if a key has the wrong type.
+fn parse_section<T: DeserializeOwned + Default>(key: &str, value: &JsonValue) -> T {
+    serde_json::from_str(&value.dump()).unwrap_or_else(|err| {
+        log::warn!("{key}: Failed reading it into typed fields, using the defaults: {err}");
+        T::default()
+    })
+}
+
+/// Where system-wide configs are looked for, after the user's own config path.
+const SYSTEM_CONFIG_PATH: &str = "/etc/HybridBar/";
+
+/// Returns the directories searched for the config, in order.
+/// Defaults to `get_path()` followed by `/etc/HybridBar/`, but can be overridden with a
+/// colon-separated list in `HYBRID_CONFIG_DIRS`.
+pub fn get_config_dirs() -> Vec<String> {
+    let dirs = environment::try_get_var("HYBRID_CONFIG_DIRS", "");
+    if dirs.is_empty() {
+        return vec![get_path(), String::from(SYSTEM_CONFIG_PATH)];
+    }
+
+    dirs.split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| format!("{}/", dir.trim_end_matches('/')))
+        .collect()
+}
+
+/// Passed as `--config` to read the config from stdin rather than from a file.
+pub const STDIN_PATH: &str = "-";
+
+/// Whether the config is read from stdin, through `--config -`.
+pub fn is_stdin() -> bool {
+    cli::ARGS.config.as_deref() == Some(STDIN_PATH)
+}
+
+/// Reads the config from stdin the first time, then returns the same content on every reload.
+fn read_stdin() -> io::Result<String> {
+    let mut config = locks::lock(&STDIN_CONFIG);
+    if let Some(config) = &*config {
+        return Ok(config.clone());
+    }
+
+    let mut content = String::new();
+    io::stdin().read_to_string(&mut content)?;
+    *config = Some(content.clone());
+    Ok(content)
+}
+
+/// Resolves the config file in use, as an absolute path.
+/// Precedence: `--config` > `--profile` > `HYBRID_CONFIG` > `config.json`.
+/// 0.3.5: With `--config -` it's `-`, meaning stdin.
+/// 0.3.5: The first of `get_config_dirs()` which has the config file is used. If none do, the
+///   path in the first directory is returned, which is where the default config is created.
+///   Symlinks are kept as they are, so the path is the one that was configured.
+pub fn resolve_config_path() -> PathBuf {
+    if is_stdin() {
+        return PathBuf::from(STDIN_PATH);
+    }
+
+    let path = match &cli::ARGS.config {
+        Some(config_path) => PathBuf::from(config_path),
+        None => {
+            let candidates = get_config_candidates();
+            candidates
+                .iter()
+                .find(|candidate| candidate.exists())
+                .or_else(|| candidates.first())
+                .cloned()
+                .unwrap_or_else(|| PathBuf::from(get_config_file_name()))
+        }
+    };
+
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+/// The name of the config file, from `--profile` or `HYBRID_CONFIG`.
+fn get_config_file_name() -> String {
+    match &cli::ARGS.profile {
+        Some(profile) => format!("config.{profile}.json"),
+        None => environment::try_get_var("HYBRID_CONFIG", "config.json"),
+    }
+}
+
+/// The config file in each of `get_config_dirs()`, whether it exists or not.
+fn get_config_candidates() -> Vec<PathBuf> {
+    let file_name = get_config_file_name();
+    get_config_dirs()
+        .into_iter()
+        .map(|dir| PathBuf::from(format!("{dir}{file_name}")))
+        .collect()
+}
+
+/// Same as `resolve_config_path`, as a `String`.
+pub fn get_config_path() -> String {
+    resolve_config_path().to_string_lossy().to_string()
+}
+
+/// Writes `DEFAULT_CONFIG` to the config path if there's no config file there yet.
+/// A config that exists but can't be accessed, for example due to permissions, is left alone.
+pub fn create_default_if_missing() -> Result<(), ConfigError> {
+    if is_stdin() {
+        return Ok(());
+    }
+
+    let path = get_config_path();
+    match fs::metadata(&path) {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+        _ => return Ok(()),
+    }
+
+    // The default config is JSON, the built-in one is used instead.
+    if toml_config::is_toml(&path) {
+        return Ok(());
+    }
+
+    let io_error = |error| ConfigError::Io {
+        path: path.to_string(),
+        error,
+    };
+
+    if let Some(parent) = Path::new(&path).parent() {
+        fs::create_dir_all(parent).map_err(io_error)?;
+    }
+
+    fs::write(&path, DEFAULT_CONFIG).map_err(io_error)?;
+    log::info!("No config was found, created a default one at '{path}'");
+    Ok(())
+}
+
+/// Reads and parses the config, including all the files it includes.
+/// If there's no config file at all, the built-in `DEFAULT_CONFIG` is used.
+/// 0.3.5: The `--set` overrides are applied on top, see `apply_overrides`.
+pub fn read_config() -> Result<JsonValue, ConfigError> {
+    let mut config = read_config_files()?;
+    apply_overlay(
+        &mut config,
+        &environment::try_get_var("HYBRID_CONFIG_OVERLAY", ""),
+    );
+    apply_overrides(&mut config, &cli::ARGS.overrides)?;
+    apply_templates(&mut config);
+    Ok(config)
+}
+
+/// Same as `read_config`, without the `--set` overrides.
+fn read_config_files() -> Result<JsonValue, ConfigError> {
+    let path = get_config_path();
+    if cli::ARGS.config.is_none() && !Path::new(&path).exists() {
+        log::info!("No config file at '{path}', loading the built-in config");
+        return Ok(json::parse(DEFAULT_CONFIG).expect("The built-in config is invalid!"));
+    }
+
+    if is_stdin() {
+        log::info!("Loading config from stdin");
+    } else {
+        log::info!("Loading config from '{path}'");
+    }
+
+    if cli::ARGS.config.is_some() {
+        return read_config_file(&path, &mut Vec::new());
+    }
+
+    // 0.3.5: The configs further down `get_config_dirs()`, like the system-wide one, are used as
+    //   the base which the ones before them override.
+    let mut config = JsonValue::new_object();
+    for candidate in get_config_candidates().iter().rev() {
+        if !candidate.exists() {
+            continue;
+        }
+
+        let candidate = candidate.to_string_lossy();
+        if candidate != path {
+            log::info!("Using '{candidate}' as a base for '{path}'");
+        }
+
+        merge(&mut config, read_config_file(&candidate, &mut Vec::new())?);
+    }
+
+    Ok(config)
+}
+
+/// Merges `overlay`, a JSON object from `HYBRID_CONFIG_OVERLAY`, over `config` like an include.
+/// 0.3.5: An invalid overlay is logged and ignored, so a typo in it doesn't keep the bar from
+///   starting.
+fn apply_overlay(config: &mut JsonValue, overlay: &str) {
+    if overlay.trim().is_empty() {
+        return;
+    }
+
+    match json::parse(overlay) {
+        Ok(overlay) if overlay.is_object() => {
+            log::debug!("Merging HYBRID_CONFIG_OVERLAY over the config");
+            merge(config, overlay);
+        }
+        Ok(_) => log::error!("HYBRID_CONFIG_OVERLAY should be a JSON object, ignoring it"),
+        Err(err) => log::error!("HYBRID_CONFIG_OVERLAY isn't valid JSON, ignoring it: {err}"),
+    }
+}
+
+/// Sets the values at the JSON pointers of `overrides`, like `/hybrid/update_rate`, as passed
+/// through `--set`. `~1` stands for a `/` inside of a key and `~0` for a `~`.
+/// Values are parsed as JSON, so `250` is a number and `true` a boolean, and anything that isn't
+/// valid JSON is kept as a string. The parent of each value has to exist already.
+fn apply_overrides(
+    config: &mut JsonValue,
+    overrides: &[(String, String)],
+) -> Result<(), ConfigError> {
+    for (pointer, value) in overrides {
+        let invalid = |reason: String| ConfigError::InvalidOverride {
+            pointer: pointer.to_string(),
+            reason,
+        };
+
+        let mut keys: Vec<String> = pointer
+            .split('/')
+            .skip(1)
+            .map(|key| key.replace("~1", "/").replace("~0", "~"))
+            .collect();
+        let Some(last) = keys.pop() else {
+            return Err(invalid(String::from("it has to start with `/`")));
+        };
+
+        let mut parent = &mut *config;
+        for key in &keys {
+            parent = match parent {
+                JsonValue::Object(object) => object.get_mut(key),
+                JsonValue::Array(array) => key
+                    .parse()
+                    .ok()
+                    .and_then(|index: usize| array.get_mut(index)),
+                _ => None,
+            }
+            .ok_or_else(|| invalid(format!("'{key}' doesn't exist")))?;
+        }
+
+        let value = json::parse(value).unwrap_or_else(|_| JsonValue::from(value.as_str()));
+        log::debug!("Setting '{pointer}' to {value}");
+        match parent {
+            JsonValue::Object(object) => object.insert(&last, value),
+            JsonValue::Array(array) => match last.parse::<usize>() {
+                Ok(index) if index < array.len() => array[index] = value,
+                _ => return Err(invalid(format!("'{last}' isn't an index of the array"))),
+            },
+            _ => {
+                return Err(invalid(String::from(
+                    "its parent isn't an object or an array",
+                )))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads and parses the config file at `path`, then merges in the files listed in its `include`
+/// array. Include paths are relative to `get_path()`, unless they are absolute.
+/// Later includes override earlier ones, and the file itself overrides all of its includes.
+/// `loaded` holds the files currently being included, to catch include cycles.
+/// 0.3.5: `STDIN_PATH` reads it from stdin instead, parsed as JSON.
+fn read_config_file(path: &str, loaded: &mut Vec<PathBuf>) -> Result<JsonValue, ConfigError> {
+    let content = if path == STDIN_PATH {
+        read_stdin()
+    } else {
+        fs::read_to_string(path)
+    };
+
+    let content = match content {
+        Ok(content) => content,
+        Err(error) => {
+            return Err(ConfigError::Io {
+                path: path.to_string(),
+                error,
+            })
+        }
+    };
+
+    // 0.3.5: TOML configs are converted into the same tree as JSON ones.
+    let mut config = if toml_config::is_toml(path) {
+        toml_config::parse(&content).map_err(|error| ConfigError::TomlParse {
+            path: path.to_string(),
+            error,
+        })?
+    } else {
+        // 0.3.5: Comments and trailing commas are allowed.
+        json::parse(&jsonc::to_json(&content)).map_err(|error| ConfigError::Parse {
+            path: path.to_string(),
+            error,
+        })?
+    };
+
+    // 0.3.5: Relative to the file they're in, which for the regular config is `get_path()`.
+    let dir = match path {
+        STDIN_PATH => PathBuf::from(get_path()),
+        path => Path::new(path)
+            .parent()
+            .map_or_else(|| PathBuf::from(get_path()), Path::to_path_buf),
+    };
+    read_value_files(&mut config, &dir, "")?;
+
+    // 0.3.5: Left in place when it isn't an array, so `check::validate` can report it.
+    if !config["include"].is_array() {
+        return Ok(config);
+    }
+
+    let includes = config.remove("include");
+
+    let absolute_path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    if loaded.contains(&absolute_path) {
+        return Err(ConfigError::IncludeCycle {
+            path: path.to_string(),
+        });
+    }
+
+    loaded.push(absolute_path);
+    let mut result = JsonValue::new_object();
+    for include in includes.members() {
+        let include = include
+            .as_str()
+            .ok_or_else(|| ConfigError::InvalidInclude {
+                path: path.to_string(),
+                include: include.to_string(),
+            })?;
+
+        let include_path = if include.starts_with('/') {
+            include.to_string()
+        } else {
+            format!("{}{include}", get_path())
+        };
+
+        merge(&mut result, read_config_file(&include_path, loaded)?);
+    }
+
+    loaded.pop();
+    merge(&mut result, config);
+    Ok(result)
+}
+
+/// Replaces every `{ "file": "path" }` in `value` with the trimmed content of that file, so
+/// secrets like API tokens can be kept out of the config. Relative paths are resolved against
+/// `dir`. `key` is the dotted path to `value`, like `variables.0.value`, for naming it in errors.
+fn read_value_files(value: &mut JsonValue, dir: &Path, key: &str) -> Result<(), ConfigError> {
+    let child_key = |child: &str| match key {
+        "" => child.to_string(),
+        key => format!("{key}.{child}"),
+    };
+
+    match value {
+        JsonValue::Object(object) if object.len() == 1 && object.get("file").is_some() => {
+            let Some(file) = object.get("file").and_then(JsonValue::as_str) else {
+                return Ok(());
+            };
+
+            let path = dir.join(file);
+            let content = fs::read_to_string(&path).map_err(|error| ConfigError::ValueFile {
+                key: key.to_string(),
+                path: path.to_string_lossy().to_string(),
+                error,
+            })?;
+
+            *value = JsonValue::from(content.trim());
+        }
+        JsonValue::Object(object) => {
+            for (child, value) in object.iter_mut() {
+                read_value_files(value, dir, &child_key(child))?;
+            }
+        }
+        JsonValue::Array(array) => {
+            for (index, value) in array.iter_mut().enumerate() {
+                read_value_files(value, dir, &child_key(&index.to_string()))?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Put first in an array to append the rest of it to the array it overrides, as in
+/// `["...", "extra"]`.
+const APPEND_MARKER: &str = "...";
+
+/// Deep-merges `overlay` into `base`, which is how includes and the configs in
+/// `get_config_dirs()` are combined:
+/// - Objects are merged key by key, recursively.
+/// - A `null` in an object of `overlay` removes that key from `base`.
+/// - Arrays replace the array in `base`, unless their first element is `APPEND_MARKER`. Then the
+///   rest of them is appended to it instead.
+/// - Anything else in `overlay` replaces the value in `base`.
+fn merge(base: &mut JsonValue, overlay: JsonValue) {
+    match overlay {
+        JsonValue::Object(overlay) if base.is_object() => {
+            for (key, value) in overlay.iter() {
+                if value.is_null() {
+                    base.remove(key);
+                } else {
+                    merge(&mut base[key], value.clone());
+                }
+            }
+        }
+        JsonValue::Array(mut overlay)
+            if overlay.first().and_then(JsonValue::as_str) == Some(APPEND_MARKER) =>
+        {
+            overlay.remove(0);
+            match base {
+                JsonValue::Array(base) => base.append(&mut overlay),
+                base => *base = JsonValue::Array(overlay),
+            }
+        }
+        overlay => *base = overlay,
+    }
 }
 
 /// Parses and returns the config.
-fn read_config_raw() -> JsonValue {
-    let mut conf_path = get_path();
-    conf_path.push_str(&environment::try_get_var("HYBRID_CONFIG", "config.json"));
-    json::parse(
-        &fs::read_to_string(&conf_path)
-            .unwrap_or_else(|_| panic!("[ERROR] Failed reading config file from '{conf_path}'!\n")),
-    )
-    .unwrap_or_else(|_| panic!("[ERROR] Failed parsing config from '{conf_path}'!\n"))
+/// Same as `read_config`, but panics on failure.
+// Kept for backwards compatibility, use `read_config` instead.
+#[allow(dead_code)]
+pub fn read_config_raw() -> JsonValue {
+    read_config().unwrap_or_else(|err| panic!("{err}!"))
+}
+
+/// Returns the cached config, so several keys can be read while only locking it once.
+/// If another thread panicked while holding the lock, the config is still handed out rather than
+/// panicking here too, as it's only ever replaced as a whole.
+/// NOTE: Don't call other config functions while holding on to it, a reload waiting for the lock
+/// in between would deadlock them.
+pub fn read_config_cached() -> RwLockReadGuard<'static, JsonValue> {
+    locks::read(&CONFIG)
+}
+
+/// Calls `f` with the cached config.
+pub fn with_config<R>(f: impl FnOnce(&JsonValue) -> R) -> R {
+    f(&read_config_cached())
 }
 
 /// Tries to fetch a value from the config. Supported types are `String` and `i32`.
-/// Panics if `is_string` is `true` and the `as_i32` function fails.
+/// 0.3.5: Numeric strings are accepted as `i32`, and numbers and bools as `String`.
+/// Panics if `is_string` is `false` and the value isn't an `i32`.
 /// If the specified root/key wasn't found, a `None` value is returned.
 pub fn try_get(
     root: &str,
@@ -56,30 +1120,33 @@ pub fn try_get(
     is_string: bool,
     with_custom_variables: bool,
 ) -> Option<(String, i32)> {
-    let config = &CONFIG.read().unwrap()[root];
-    let default_string = String::default();
-    if config.has_key(key) {
+    let value = with_config(|config| {
+        let config = &config[root];
+        if !config.has_key(key) {
+            // The key wasn't found, so just return None.
+            return None;
+        }
+
         let grabbed_value = &config[key];
 
         // If the desired value isn't a string, try and get it as a 32-bit integer.
         if !is_string {
             return Some((
-                default_string,
-                grabbed_value
-                    .as_i32()
-                    .unwrap_or_else(|| panic!("[ERROR] Failed parsing {root}:{key} as i32!\n")),
+                String::default(),
+                as_i32(grabbed_value)
+                    .unwrap_or_else(|| panic!("Failed parsing {root}:{key} as i32!")),
             ));
         }
 
-        // Convert it to a string-value.
-        if with_custom_variables {
-            Some((with_variables(grabbed_value.to_string()), 0))
-        } else {
-            Some((grabbed_value.to_string(), 0))
-        }
+        // Convert it to a string-value, numbers and bools are stringified.
+        Some((grabbed_value.to_string(), 0))
+    })?;
+
+    // Expanded after the config lock has been released.
+    if is_string && with_custom_variables {
+        Some((with_variables(value.0), 0))
     } else {
-        // The key wasn't found, so just return None.
-        None
+        Some(value)
     }
 }
 
@@ -96,29 +1163,1074 @@ pub fn get_or_default(
     try_get(root, key, is_string, with_custom_variables).unwrap_or_else(|| (String::default(), 0))
 }
 
-/// Gets all the custom variables.
-fn get_custom_variables() -> Vec<(String, String), 64> {
-    let cfg = &CONFIG.read().unwrap()["variables"];
-    // 0.3.0: Only allow for 64 variables.
-    let mut vector: Vec<(String, String), 64> = Vec::new();
-    for entry in cfg.entries() {
-        vector
-            .push((entry.0.to_string(), entry.1.to_string()))
-            .expect("[ERROR] You cannot have more than `64` variables!\n");
+/// Same as `try_get`, but returns the first of `keys` that's present, for fallbacks and renamed
+/// keys. Custom variables are applied to strings, and empty strings fall through to the next key.
+/// If none of the keys were found, a `None` value is returned.
+pub fn try_get_first(root: &str, keys: &[&str], is_string: bool) -> Option<(String, i32)> {
+    keys.iter().find_map(|key| {
+        try_get(root, key, is_string, is_string).filter(|value| !is_string || !value.0.is_empty())
+    })
+}
+
+/// Describes how to add the required `root:key` to the config, see `require`.
+fn describe_required(root: &str, key: &str, is_string: bool) -> String {
+    let example = if is_string { "\"...\"" } else { "0" };
+    format!("{root}:{key} is required, add it to the config like `\"{root}\": {{ \"{key}\": {example} }}`")
+}
+
+/// Same as `try_get`, but for keys that are required. Custom variables are applied to strings.
+/// If the specified root/key wasn't found, an error naming it is printed and Hybrid exits.
+// 0.3.5: Makes required keys explicit, rather than each caller deciding on its own default.
+pub fn require(root: &str, key: &str, is_string: bool) -> (String, i32) {
+    try_get(root, key, is_string, is_string)
+        .unwrap_or_else(|| cli::exit_with_error(&describe_required(root, key, is_string)))
+}
+
+/// Tries to fetch a format string, with variables applied but `{{` and `}}` kept escaped so that
+/// they survive `placeholders::fill`.
+/// 0.3.5: The placeholders of the widget type are left for `placeholders::fill`, even if there's a
+///   variable with the same name, see `placeholders::WIDGET_PLACEHOLDERS`.
+/// If the specified root/key wasn't found, a `None` value is returned.
+pub fn try_get_format(root: &str, key: &str) -> Option<String> {
+    let placeholders = placeholders::get_names(get_widget_type(root).unwrap_or_default());
+    try_get(root, key, true, false).map(|format| with_format_variables(format.0, placeholders))
+}
+
+/// Gets the widget type from a widget key, like `memory` for `right-memory_ram`.
+pub fn get_widget_type(key: &str) -> Option<&str> {
+    let (identifier, _) = key.split_once('_')?;
+    identifier
+        .split_once('-')
+        .map(|(_, widget_type)| widget_type)
+}
+
+/// The spot in `hybrid` -> `default_format` where the format of the widget goes.
+pub const DEFAULT_FORMAT_PLACEHOLDER: &str = "{}";
+
+/// Gets the `format` of the widget at `root`. Without one, `default` is used wrapped in
+/// `hybrid` -> `default_format`, so that for example `" {}"` puts an icon in front of every widget.
+pub fn get_format(root: &str, default: &str) -> String {
+    try_get_format(root, "format").unwrap_or_else(|| with_default_format(default))
+}
+
+/// Puts `default` at the `{}` in `hybrid` -> `default_format`, or returns it as-is if there's no
+/// default format or it's empty.
+pub fn with_default_format(default: &str) -> String {
+    match try_get_format("hybrid", "default_format") {
+        Some(default_format) if !default_format.is_empty() => {
+            default_format.replacen(DEFAULT_FORMAT_PLACEHOLDER, default, 1)
+        }
+        _ => String::from(default),
+    }
+}
+
+/// Gets the value at the dotted `path` of `config`, like `hybrid.margins.top` or `widgets.0.text`.
+/// Numeric segments index into arrays. `None` if any segment wasn't found or is the wrong type of
+/// node.
+pub fn get_at_path<'a>(config: &'a JsonValue, path: &str) -> Option<&'a JsonValue> {
+    let mut value = config;
+    for segment in path.split('.') {
+        value = match value {
+            JsonValue::Object(object) => object.get(segment)?,
+            JsonValue::Array(array) => array.get(segment.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
     }
 
-    vector
+    Some(value)
+}
+
+/// Tries to fetch a value from the config by a dotted path, for example `hybrid.margins.top`.
+/// Panics if `is_string` is `false` and the value isn't an `i32`.
+/// If any segment wasn't found or is the wrong type of node, a `None` value is returned.
+// Kept for backwards compatibility, use `get_at_path` with `with_config` instead.
+#[allow(dead_code)]
+pub fn try_get_path(path: &str, is_string: bool) -> Option<(String, i32)> {
+    with_config(|config| {
+        let value = get_at_path(config, path)?;
+        if !is_string {
+            let value = as_i32(value).unwrap_or_else(|| panic!("Failed parsing {path} as i32!"));
+            return Some((String::default(), value));
+        }
+
+        Some((value.to_string(), 0))
+    })
+}
+
+/// Tries to fetch an array from the config, with each element converted to a string and custom
+/// variables applied.
+/// If the specified root/key wasn't found or isn't an array, a `None` value is returned.
+pub fn try_get_array(root: &str, key: &str) -> Option<Vec<String>> {
+    let elements: Vec<String> = with_config(|config| {
+        let value = &config[root][key];
+        if !value.is_array() {
+            return None;
+        }
+
+        Some(value.members().map(|member| member.to_string()).collect())
+    })?;
+
+    // Expanded after the config lock has been released.
+    Some(elements.into_iter().map(with_variables).collect())
+}
+
+/// Tries to fetch a `bool` from the config.
+/// Both JSON booleans and the strings `"true"` / `"false"` are accepted.
+/// If the specified root/key wasn't found or isn't a boolean, a `None` value is returned.
+pub fn try_get_bool(root: &str, key: &str) -> Option<bool> {
+    with_config(|config| as_bool(&config[root][key]))
+}
+
+/// Reads `value` as a `bool`, also accepting the strings `"true"` and `"false"`.
+pub fn as_bool(value: &JsonValue) -> Option<bool> {
+    if let Some(bool) = value.as_bool() {
+        return Some(bool);
+    }
+
+    // For people who quote everything.
+    match value.as_str() {
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        _ => None,
+    }
+}
+
+/// Tries to fetch a `f64` from the config.
+/// If the specified root/key wasn't found, isn't a number or is NaN/Infinity, a `None` value is
+/// returned.
+pub fn try_get_f64(root: &str, key: &str) -> Option<f64> {
+    with_config(|config| as_f64(&config[root][key]))
+}
+
+/// Reads `value` as an `i32`, also accepting numeric strings like `"100"` for people who quote
+/// everything.
+pub fn as_i32(value: &JsonValue) -> Option<i32> {
+    value
+        .as_i32()
+        .or_else(|| value.as_str()?.trim().parse().ok())
+}
+
+/// Reads `value` as a finite `f64`, also accepting numeric strings like `"0.5"`.
+pub fn as_f64(value: &JsonValue) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_str()?.trim().parse().ok())
+        .filter(|value: &f64| value.is_finite())
+}
+
+/// Same as `try_get_bool`, but returns `false` if the value is `None`.
+pub fn get_or_default_bool(root: &str, key: &str) -> bool {
+    try_get_bool(root, key).unwrap_or_default()
+}
+
+/// Same as `try_get_f64`, but returns `0.0` if the value is `None`.
+pub fn get_or_default_f64(root: &str, key: &str) -> f64 {
+    try_get_f64(root, key).unwrap_or_default()
+}
+
+/// Returns for how long commands run while loading `config` may take. The config isn't cached
+/// yet at that point, so `get_command_timeout` can't be used.
+fn get_load_timeout(config: &JsonValue) -> Duration {
+    let timeout = as_i32(&config["hybrid"]["command_timeout"])
+        .map_or(DEFAULT_COMMAND_TIMEOUT, |timeout| timeout.max(0) as u64);
+    Duration::from_millis(timeout)
+}
+
+/// Gets the keys of the template named `name` from `templates`, along with those of the templates
+/// it's based on through its own `template`. `None` if one of them doesn't exist, or if they end
+/// up using themselves.
+fn resolve_template(
+    templates: &JsonValue,
+    name: &str,
+    used: &mut Vec<String>,
+) -> Option<JsonValue> {
+    let template = &templates[name];
+    if !template.is_object() || used.iter().any(|used| used == name) {
+        return None;
+    }
+
+    used.push(name.to_string());
+    let mut keys = match template["template"].as_str() {
+        Some(base) => resolve_template(templates, base, used)?,
+        None => JsonValue::new_object(),
+    };
+
+    merge(&mut keys, template.clone());
+    keys.remove("template");
+    Some(keys)
+}
+
+/// Replaces the `template` of every top-level block with the keys of that template from
+/// `templates`, which the block's own keys then override like `merge` does. Blocks whose template
+/// can't be resolved keep their `template` key, so `check::validate` can report it.
+/// 0.3.5: Templates can be based on other templates through a `template` of their own.
+fn apply_templates(config: &mut JsonValue) {
+    let templates = config["templates"].clone();
+    let keys: Vec<String> = config
+        .entries()
+        .filter(|(key, block)| *key != "templates" && block["template"].is_string())
+        .map(|(key, _)| key.to_string())
+        .collect();
+    for key in keys {
+        let name = config[&key]["template"].to_string();
+        let Some(mut block) = resolve_template(&templates, &name, &mut Vec::new()) else {
+            log::warn!("{key}: The template '{name}' doesn't exist, or ends up using itself");
+            continue;
+        };
+
+        let mut own_keys = config[&key].take();
+        own_keys.remove("template");
+        merge(&mut block, own_keys);
+        config[&key] = block;
+    }
+}
+
+/// Drops the top-level blocks of `config` which are disabled through `enabled`, or whose
+/// `when_env` or `when_command` condition is false, then removes these keys from the remaining
+/// blocks.
+/// 0.3.5: Evaluated once whenever the config is cached, unlike `visible_command`. Reloads then
+///   add and remove the widgets like any other change.
+pub fn apply_conditions(config: &mut JsonValue, runner: &impl CommandRunner) {
+    let timeout = get_load_timeout(config);
+    let keys: Vec<String> = config.entries().map(|(key, _)| key.to_string()).collect();
+    for key in keys {
+        // Dropping `hybrid` would leave the bar without its required keys.
+        if key == "hybrid" || !config[&key].is_object() {
+            continue;
+        }
+
+        // Disabled blocks are dropped without running their conditions.
+        let enabled = config[&key].remove("enabled");
+        if as_bool(&enabled) == Some(false) {
+            log::debug!("Dropping '{key}', as it's disabled");
+            config.remove(&key);
+            continue;
+        }
+
+        let when_env = config[&key].remove("when_env");
+        let when_command = config[&key].remove("when_command");
+        let is_met = when_env.as_str().is_none_or(is_env_condition_met)
+            && when_command
+                .as_str()
+                .is_none_or(|command| runner.execute(command, timeout).success);
+        if !is_met {
+            log::debug!("Dropping '{key}', as its condition is false");
+            config.remove(&key);
+        }
+    }
+}
+
+/// Checks `condition`, either `NAME=value` to compare the environment variable `NAME` with
+/// `value`, or `NAME` to check that it's set and not empty.
+fn is_env_condition_met(condition: &str) -> bool {
+    match condition.split_once('=') {
+        Some((name, value)) => environment::try_get_var(name.trim(), "") == value,
+        None => !environment::try_get_var(condition.trim(), "").is_empty(),
+    }
+}
+
+/// Gets all the custom variables from `config`.
+/// 0.3.5: Variables written as `{ "command": "..." }` get the output of the command as their value.
+///   It's executed every time the config is cached, so on startup and on every reload.
+/// 0.3.5: Objects with `raw` set are used exactly as written, see `restore_raw`.
+fn get_custom_variables(
+    config: &JsonValue,
+    runner: &impl CommandRunner,
+) -> Vec<(String, String, bool)> {
+    let variables = &config["variables"];
+    // 0.3.5: Arrays and scalars are ignored as a whole, the warning for them comes from
+    //   `check::validate` which runs before this.
+    if !variables.is_object() {
+        return Vec::new();
+    }
+
+    let timeout = get_load_timeout(config);
+
+    // 0.3.5: There's no longer a limit of 64 variables. Escaped braces are protected in names and
+    //   values as well, so they behave the same wherever the variable is used.
+    variables
+        .entries()
+        .map(|(name, value)| {
+            let raw = as_bool(&value["raw"]).unwrap_or_default();
+            let value = match value["command"].as_str() {
+                Some(command) => runner.execute(command, timeout).stdout,
+                None if value.is_object() => value["value"].to_string(),
+                None => value.to_string(),
+            };
+
+            // Raw values aren't expanded, so their braces are never escapes either.
+            let value = match raw {
+                true => value,
+                false => placeholders::protect_escapes(&value),
+            };
+            (placeholders::protect_escapes(name), value, raw)
+        })
+        .collect()
+}
+
+/// 0.3.5: Adds the entries of the `computed` section to `variables`, each resolved once from the
+///   variables and the entries before it. Their values are raw, so they're never expanded again.
+///   Entries which can't be resolved are skipped with a warning, `--check` reports them too.
+fn with_computed_variables(
+    config: &JsonValue,
+    mut variables: Vec<(String, String, bool)>,
+    ignore_case: bool,
+    bare: bool,
+) -> Vec<(String, String, bool)> {
+    for (name, entry) in config["computed"].entries() {
+        let name = placeholders::protect_escapes(name);
+        if variables.iter().any(|(other, _, _)| *other == name) {
+            log::warn!("computed:{name} is named like a variable, ignoring it");
+            continue;
+        }
+
+        let resolved = CustomVariables::new(variables.clone(), ignore_case, bare);
+        match compute(config, &resolved, entry) {
+            Ok(value) => variables.push((name, value, true)),
+            Err(err) => log::warn!("computed:{name}: {err}, ignoring it"),
+        }
+    }
+
+    variables
+}
+
+/// Resolves an `entry` of the `computed` section with `variables`. It's either a string, like
+/// `"{home}/notes"`, or an object with either a `value` or the dotted path of a config value as
+/// `from`, and an optional `transform` like `[{ "lighten": 0.2 }]` applied to it.
+fn compute(
+    config: &JsonValue,
+    variables: &CustomVariables,
+    entry: &JsonValue,
+) -> Result<String, String> {
+    let is_value = |value: &JsonValue| !value.is_null() && !value.is_object() && !value.is_array();
+    let value = match entry {
+        JsonValue::Object(_) => match (&entry["value"], entry["from"].as_str()) {
+            (value, None) if is_value(value) => value.to_string(),
+            (JsonValue::Null, Some(path)) => get_at_path(config, path)
+                .filter(|value| is_value(value))
+                .map(JsonValue::to_string)
+                .ok_or_else(|| format!("'{path}' isn't a value of the config"))?,
+            _ => return Err(String::from("expected either a `value` or a `from`")),
+        },
+        value if is_value(value) => value.to_string(),
+        _ => {
+            return Err(String::from(
+                "expected a string, or an object with a `value` or a `from`",
+            ))
+        }
+    };
+
+    let steps =
+        transform::parse(&entry["transform"]).map_err(|err| format!("invalid transform, {err}"))?;
+    transform::apply(&steps, &expand_variables(variables, &value, false))
+}
+
+/// Replaces any variable-matching patterns in the `String` with the variables value, then expands
+/// environment variables.
+/// 0.3.5: `{{` and `}}` are left untouched by both, and only become literal braces in `fill`.
+pub fn with_variables(input: String) -> String {
+    let variables = locks::read(&VARIABLES);
+    expand_variables(&variables, &input, false)
+}
+
+/// Prefix of the environment variables the custom variables are exported as.
+pub const VARIABLE_ENV_PREFIX: &str = "HYBRID_VAR_";
+
+/// Gets the custom variables as environment variables for the commands of the widget at `root`,
+/// like `HYBRID_VAR_base`. Empty unless the widget has `export_variables` set.
+/// Characters which can't be used in shell variable names are replaced with `_`.
+pub fn get_variable_env(root: &str) -> Vec<(String, String)> {
+    if !get_or_default_bool(root, "export_variables") {
+        return Vec::new();
+    }
+
+    let variables = locks::read(&VARIABLES);
+    variables
+        .names
+        .iter()
+        .zip(&variables.values)
+        .zip(&variables.wildcards)
+        .zip(&variables.raw)
+        // Wildcard variables don't have a single name to export them as.
+        .filter(|((_, wildcard), _)| !**wildcard)
+        .map(|(((name, value), _), raw)| {
+            let name: String = name
+                .chars()
+                .map(|char| {
+                    if char.is_ascii_alphanumeric() {
+                        char
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            let value = match raw {
+                true => value.clone(),
+                false => expand_variables(&variables, value, false),
+            };
+            (format!("{VARIABLE_ENV_PREFIX}{name}"), value)
+        })
+        .collect()
+}
+
+/// Same as `with_variables`, but `{{` and `}}` are kept escaped for `placeholders::fill`, and so
+/// are the `placeholders` it's going to fill in.
+fn with_format_variables(input: String, placeholders: &[&str]) -> String {
+    let variables = locks::read(&VARIABLES);
+    expand_format(&variables, &input, placeholders)
+}
+
+/// Expands `variables` in the format `input`, except for its `placeholders`.
+fn expand_format(variables: &CustomVariables, input: &str, placeholders: &[&str]) -> String {
+    let protected = placeholders::protect_placeholders(input, placeholders);
+    let expanded = expand_variables(variables, &protected, true);
+    placeholders::restore_placeholders(&expanded, placeholders)
+}
+
+/// Expands `variables` and environment variables in `input`, leaving escaped braces as they are
+/// written. Only `placeholders::fill` turns them into literal braces, so shell syntax like `}}` in
+/// commands keeps working.
+/// With `for_format` the braces of raw values are escaped too, so `fill` leaves them alone.
+fn expand_variables(variables: &CustomVariables, input: &str, for_format: bool) -> String {
+    let protected = placeholders::protect_escapes(input);
+    let mut raw_values = Vec::new();
+    let expanded = with_custom_variables(variables, protected, &mut raw_values);
+    let expanded = environment::expand_vars(&expanded);
+    let restored = placeholders::restore_escapes(&expanded, true);
+    restore_raw(&restored, &raw_values, for_format)
+}
+
+/// Puts the `raw_values` back in place of their stand-ins, once nothing can expand them anymore.
+/// If `keep_escaped` is `true` their braces are escaped, so a later `fill` leaves them alone too.
+fn restore_raw(input: &str, raw_values: &[String], keep_escaped: bool) -> String {
+    if raw_values.is_empty() {
+        return input.to_string();
+    }
+
+    placeholders::restore_stand_ins(input, placeholders::RAW_STAND_IN, |index| {
+        let value = raw_values.get(index)?;
+        Some(match keep_escaped {
+            true => value.replace('{', "{{").replace('}', "}}"),
+            false => value.clone(),
+        })
+    })
 }
 
 /// Replaces any variable-matching patterns in the `String` with the variables value.
-fn with_variables(input: String) -> String {
+/// Variables may reference other variables, so this keeps on expanding until nothing changes or
+/// `MAX_VARIABLE_DEPTH` is reached.
+fn with_custom_variables(
+    variables: &CustomVariables,
+    input: String,
+    raw_values: &mut Vec<String>,
+) -> String {
+    let Some(matcher) = &variables.matcher else {
+        return input;
+    };
+
     let mut result = input;
-    for variable in get_custom_variables() {
-        // Only replace if `result` actually contains the defined variable.
-        if result.contains(&variable.0) {
-            result = result.replace(&variable.0, &variable.1);
+    for _ in 0..MAX_VARIABLE_DEPTH {
+        let expanded = variables.replace(matcher, &result, raw_values);
+        if expanded == result {
+            return result;
         }
+
+        result = expanded;
     }
 
+    log::warn!("Variables are referencing each other in a cycle, stopped expanding '{result}' after {MAX_VARIABLE_DEPTH} passes");
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variables_respect_escaped_braces() {
+        let variables = CustomVariables::new(
+            vec![
+                (String::from("user"), String::from("me"), false),
+                (String::from("x"), String::from("y"), false),
+            ],
+            false,
+            false,
+        );
+
+        assert_eq!(
+            expand_variables(&variables, "user: {user} {{user}} {x} {{x}} $${{", false),
+            "user: me {{user}} y {{x}} ${{"
+        );
+        // Shell and JSON syntax in commands is passed on as written.
+        assert_eq!(
+            expand_variables(&variables, "jq '{a: {b: .x}}' {x}", false),
+            "jq '{a: {b: .x}}' y"
+        );
+    }
+
+    #[test]
+    fn variables_skip_environment_variables() {
+        let variables = CustomVariables::new(
+            vec![(
+                String::from("HYBRID_TEST_USER"),
+                String::from("hijacked"),
+                false,
+            )],
+            false,
+            false,
+        );
+
+        env::set_var("HYBRID_TEST_USER", "me");
+        assert_eq!(
+            expand_variables(&variables, "{HYBRID_TEST_USER} ${HYBRID_TEST_USER}", false),
+            "hijacked me"
+        );
+    }
+
+    #[test]
+    fn bare_variables() {
+        let variables = CustomVariables::new(
+            vec![
+                (String::from("user"), String::from("me"), false),
+                (String::from("{x}"), String::from("y"), false),
+            ],
+            false,
+            true,
+        );
+
+        assert_eq!(
+            expand_variables(&variables, "user: {{user}} {x} {{x}}", false),
+            "me: {{me}} y {{x}}"
+        );
+    }
+
+    #[test]
+    fn variables_keep_escaped_braces_for_formats() {
+        let variables = CustomVariables::new(
+            vec![(String::from("cool"), String::from("42"), false)],
+            false,
+            false,
+        );
+        assert_eq!(
+            expand_variables(&variables, "{cool} {{percent}} {percent}%", true),
+            "42 {{percent}} {percent}%"
+        );
+    }
+
+    #[test]
+    fn variables_ignore_case() {
+        let home = vec![(String::from("home"), String::from("~"), false)];
+        let variables = CustomVariables::new(home.clone(), true, false);
+        assert_eq!(expand_variables(&variables, "{HOME} {Home}", false), "~ ~");
+
+        let variables = CustomVariables::new(home, false, false);
+        assert_eq!(
+            expand_variables(&variables, "{HOME} {home}", false),
+            "{HOME} ~"
+        );
+    }
+
+    #[test]
+    fn wildcard_variables() {
+        let variables = CustomVariables::new(
+            vec![
+                (String::from("PATH_*"), String::from("/srv/{*}"), false),
+                (String::from("PATH_HOME"), String::from("/home/me"), false),
+            ],
+            false,
+            false,
+        );
+
+        assert_eq!(
+            expand_variables(
+                &variables,
+                "{PATH_logs}/a {PATH_HOME} {PATH_} {PATH_a b}",
+                false
+            ),
+            "/srv/logs/a /home/me /srv/ {PATH_a b}"
+        );
+    }
+
+    /// Builds `CustomVariables` from `(name, value)` pairs, none of them raw.
+    fn variables_of(variables: &[(&str, &str)], bare: bool) -> CustomVariables {
+        let variables = variables
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string(), false))
+            .collect();
+        CustomVariables::new(variables, false, bare)
+    }
+
+    #[test]
+    fn typed_sections_fall_back_on_their_own() {
+        let parsed = parse_typed(
+            &json::parse(
+                r#"{
+                    "hybrid": { "exclusive": "false", "update_rate": "1s" },
+                    "include": ["colors.json"],
+                    "left-label_a": { "text": 42, "markup": "maybe" },
+                    "left-label_b": { "text": "b", "update_rate": "500" }
+                }"#,
+            )
+            .unwrap(),
+        );
+        assert_eq!(parsed.hybrid.exclusive, Some(false));
+        assert_eq!(parsed.hybrid.update_rate, Some(1000));
+        // Only the broken widget loses its values.
+        assert!(parsed.widgets["left-label_a"].text.is_none());
+        assert_eq!(parsed.widgets["left-label_b"].text.as_deref(), Some("b"));
+        assert_eq!(parsed.widgets["left-label_b"].update_rate, Some(500));
+        assert!(!parsed.widgets.contains_key("include"));
+    }
+
+    #[test]
+    fn dotted_paths() {
+        let config = json::parse(
+            r#"{ "hybrid": { "margins": { "top": 4 } }, "widgets": [{ "text": "a" }] }"#,
+        )
+        .unwrap();
+        assert_eq!(get_at_path(&config, "hybrid.margins.top").unwrap(), 4);
+        assert_eq!(get_at_path(&config, "widgets.0.text").unwrap(), "a");
+        assert!(get_at_path(&config, "hybrid.margins").unwrap().is_object());
+        assert!(get_at_path(&config, "hybrid.missing.top").is_none());
+        assert!(get_at_path(&config, "widgets.1.text").is_none());
+        assert!(get_at_path(&config, "widgets.first").is_none());
+        assert!(get_at_path(&config, "hybrid.margins.top.more").is_none());
+    }
+
+    #[test]
+    fn values_by_dotted_paths() {
+        locks::write(&CONFIG)["left-label_dotted_paths"] =
+            json::object! { margins: { top: 4 }, widgets: ["a"] };
+        let path = |path: &str, is_string| {
+            try_get_path(&format!("left-label_dotted_paths.{path}"), is_string)
+        };
+        assert_eq!(path("margins.top", false), Some((String::new(), 4)));
+        assert_eq!(path("margins.top", true), Some((String::from("4"), 0)));
+        assert_eq!(path("widgets.0", true), Some((String::from("a"), 0)));
+        assert_eq!(path("widgets.1", true), None);
+        assert_eq!(path("margins.bottom", false), None);
+        locks::write(&CONFIG).remove("left-label_dotted_paths");
+    }
+
+    #[test]
+    fn computed_variables() {
+        let config = json::parse(
+            r##"{
+                "hybrid": { "background": "#1e1e2e" },
+                "variables": { "accent": "#808080", "dir": "notes" },
+                "computed": {
+                    "accent_light": { "value": "{accent}", "transform": [{ "lighten": 0.5 }] },
+                    "surface": { "from": "hybrid.background", "transform": [{ "lighten": 0.1 }] },
+                    "notes": "${HYBRID_TEST_NOTES_ROOT}/{dir}/{accent_light}",
+                    "braces": "{{literal}}",
+                    "dir": "taken",
+                    "broken": { "from": "hybrid.missing" }
+                },
+                "left-label_a": { "text": "{notes}" }
+            }"##,
+        )
+        .unwrap();
+
+        // Environment variables are expanded too, as `${HOME}` would be.
+        env::set_var("HYBRID_TEST_NOTES_ROOT", "/home");
+        let variables = get_custom_variables(&config, &command::MockRunner::default());
+        let variables = CustomVariables::new(
+            with_computed_variables(&config, variables, false, false),
+            false,
+            false,
+        );
+        assert_eq!(
+            expand_variables(
+                &variables,
+                "{accent_light} {surface} {notes} {braces}",
+                false
+            ),
+            "#c0c0c0 #353543 /home/notes/#c0c0c0 {{literal}}"
+        );
+        // Variables keep their value, and broken entries are left out.
+        assert_eq!(
+            expand_variables(&variables, "{dir} {broken}", false),
+            "notes {broken}"
+        );
+    }
+
+    #[test]
+    fn placeholders_take_precedence_over_variables() {
+        let variables = variables_of(&[("percent", "P"), ("accent", "#f38ba8")], false);
+        let names = placeholders::get_names("memory");
+        let format = expand_format(
+            &variables,
+            "<span color='{accent}'>{percent}%</span> {percent:02} {{percent}}",
+            names,
+        );
+        assert_eq!(
+            format,
+            "<span color='#f38ba8'>{percent}%</span> {percent:02} {{percent}}"
+        );
+        assert_eq!(
+            placeholders::fill(&format, &[("percent", "7")]),
+            "<span color='#f38ba8'>7%</span> 07 {percent}"
+        );
+
+        // Without a placeholder of the same name, like on a label, the variable is used.
+        assert_eq!(expand_format(&variables, "{percent}%", &[]), "P%");
+
+        // Bare variables match anywhere, but still not inside of placeholders.
+        let variables = variables_of(&[("percent", "P")], true);
+        assert_eq!(
+            expand_format(&variables, "{percent} percent", names),
+            "{percent} P"
+        );
+    }
+
+    #[test]
+    fn overlapping_variable_names() {
+        let names = [("cpu", "C"), ("cpu_temp", "T"), ("cp", "P")];
+        let variables = variables_of(&names, false);
+        assert_eq!(
+            expand_variables(&variables, "{cpu_temp} {cpu} {cp} {cpu_}", false),
+            "T C P {cpu_}"
+        );
+
+        // Bare names are matched anywhere, the longest one wins regardless of the order.
+        let variables = variables_of(&names, true);
+        assert_eq!(
+            expand_variables(&variables, "cpu_temp cpu cp", false),
+            "T C P"
+        );
+    }
+
+    #[test]
+    fn variables_referencing_variables() {
+        // Regardless of which one is defined first.
+        for names in [
+            [("path", "{root}/logs"), ("root", "/srv")],
+            [("root", "/srv"), ("path", "{root}/logs")],
+        ] {
+            let variables = variables_of(&names, false);
+            assert_eq!(
+                expand_variables(&variables, "{path} {root}", false),
+                "/srv/logs /srv"
+            );
+        }
+
+        // A value that happens to be a token is expanded, an escaped one from the config isn't.
+        let config =
+            json::parse(r#"{ "variables": { "a": "{b}", "b": "{{c}}", "c": "x" } }"#).unwrap();
+        let variables = get_custom_variables(&config, &command::MockRunner::default());
+        let variables = CustomVariables::new(variables, false, false);
+        assert_eq!(
+            expand_variables(&variables, "{a} {b}", false),
+            "{{c}} {{c}}"
+        );
+
+        // Cycles stop after `MAX_VARIABLE_DEPTH` passes, rather than hanging.
+        let variables = variables_of(&[("a", "{b}"), ("b", "{a}"), ("loop", "x{loop}")], false);
+        assert!(["{a}", "{b}"].contains(&expand_variables(&variables, "{a}", false).as_str()));
+        let looped = expand_variables(&variables, "{loop}", false);
+        assert!(looped.starts_with("xxx") && looped.ends_with("{loop}"));
+    }
+
+    #[test]
+    fn undefined_and_missing_variables() {
+        let variables = variables_of(&[("a", "1")], false);
+        assert_eq!(
+            expand_variables(&variables, "{nope}{a} {} {a", false),
+            "{nope}1 {} {a"
+        );
+
+        // An empty `variables` block leaves everything as it is.
+        let config = json::parse(r#"{ "variables": {} }"#).unwrap();
+        let empty = get_custom_variables(&config, &command::MockRunner::default());
+        assert!(empty.is_empty());
+        let variables = CustomVariables::new(empty, false, false);
+        assert!(variables.matcher.is_none());
+        assert_eq!(
+            expand_variables(&variables, "{a} {{b}}", false),
+            "{a} {{b}}"
+        );
+    }
+
+    #[test]
+    fn variables_with_special_characters() {
+        let value = r"^.*$ [a-z]+ (\d)? a{2,3} | $1 \";
+        let variables = variables_of(&[("pattern", value), ("a.b", "dot"), ("c+", "plus")], false);
+        assert_eq!(expand_variables(&variables, "{pattern}", false), value);
+        // Names are matched literally, not as patterns.
+        assert_eq!(
+            expand_variables(&variables, "{a.b} {axb} {c+} {cc}", false),
+            "dot {axb} plus {cc}"
+        );
+    }
+
+    #[test]
+    fn update_rate_units() {
+        let parse = |value: &str| parse_update_rate(&value.into());
+        assert_eq!(parse("250"), Ok(250));
+        assert_eq!(parse("500ms"), Ok(500));
+        assert_eq!(parse("1.5s"), Ok(1500));
+        assert_eq!(parse("5m"), Ok(300_000));
+        assert_eq!(parse("1 h"), Ok(3_600_000));
+        assert_eq!(parse(UPDATE_ONCE), Ok(-1));
+        assert_eq!(parse_update_rate(&JsonValue::from(100)), Ok(100));
+        assert!(parse("5d").unwrap_err().contains("'d'"));
+        assert!(parse("s").is_err());
+    }
+
+    #[test]
+    fn values_from_files() {
+        let dir = env::temp_dir().join(format!("hybrid-value-files-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("token"), "s3cret\n").unwrap();
+
+        let mut config = json::parse(
+            r#"{ "variables": { "token": { "file": "token" } }, "a": [{ "file": 1 }] }"#,
+        )
+        .unwrap();
+        read_value_files(&mut config, &dir, "").unwrap();
+        assert_eq!(config["variables"]["token"], "s3cret");
+        // Only strings are paths.
+        assert_eq!(config["a"][0]["file"], 1);
+
+        let mut config = json::parse(r#"{ "hybrid": { "x": { "file": "missing" } } }"#).unwrap();
+        let err = read_value_files(&mut config, &dir, "").unwrap_err();
+        assert!(err.to_string().contains("'hybrid.x'"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn includes_which_arent_arrays_are_kept() {
+        let dir = env::temp_dir().join(format!("hybrid-include-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.json");
+        fs::write(&path, r#"{ "include": "other.json", "hybrid": {} }"#).unwrap();
+
+        let config = read_config_file(&path.to_string_lossy(), &mut Vec::new()).unwrap();
+        assert_eq!(config["include"], "other.json");
+        assert!(check::validate(&config)
+            .iter()
+            .any(|problem| problem.starts_with("`include` should be an array")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn first_keys_skip_empty_strings() {
+        locks::write(&CONFIG)["left-image_first_keys"] =
+            json::object! { path: "", icon: "folder", size: 0 };
+        let first =
+            |keys: &[&str], is_string| try_get_first("left-image_first_keys", keys, is_string);
+        assert_eq!(
+            first(&["path", "icon"], true),
+            Some((String::from("folder"), 0))
+        );
+        assert_eq!(first(&["path"], true), None);
+        assert_eq!(first(&["missing", "size"], false), Some((String::new(), 0)));
+        locks::write(&CONFIG).remove("left-image_first_keys");
+    }
+
+    #[test]
+    fn required_keys() {
+        locks::write(&CONFIG)["left-script_required_keys"] =
+            json::object! { command: "date", size: 4 };
+        assert_eq!(
+            require("left-script_required_keys", "command", true),
+            (String::from("date"), 0)
+        );
+        assert_eq!(
+            require("left-script_required_keys", "size", false),
+            (String::new(), 4)
+        );
+        locks::write(&CONFIG).remove("left-script_required_keys");
+
+        // Exits with it otherwise.
+        assert_eq!(
+            describe_required("left-image_a", "path", true),
+            "left-image_a:path is required, add it to the config like \
+             `\"left-image_a\": { \"path\": \"...\" }`"
+        );
+    }
+
+    #[test]
+    fn variables_from_commands() {
+        let config = json::parse(r#"{ "variables": { "host": { "command": "hostname" } } }"#);
+        let runner = command::MockRunner::default().with("hostname", "box", true);
+        assert_eq!(
+            get_custom_variables(&config.unwrap(), &runner),
+            vec![(String::from("host"), String::from("box"), false)]
+        );
+    }
+
+    #[test]
+    fn raw_variables() {
+        let config = json::parse(
+            r#"{ "variables": {
+                "other": "expanded",
+                "raw": { "value": "{other} $HOME {{x}}", "raw": true },
+                "cooked": "{other} $$HOME"
+            } }"#,
+        )
+        .unwrap();
+        let variables = get_custom_variables(&config, &command::MockRunner::default());
+        let variables = CustomVariables::new(variables, false, false);
+        assert_eq!(
+            expand_variables(&variables, "{raw} | {cooked}", false),
+            "{other} $HOME {{x}} | expanded $HOME"
+        );
+        // Still left alone by a later `fill`.
+        assert_eq!(
+            expand_variables(&variables, "{raw} {percent}", true),
+            "{{other}} $HOME {{{{x}}}} {percent}"
+        );
+        // Icons from the private use areas aren't mistaken for raw values.
+        assert_eq!(
+            expand_variables(&variables, "{raw}{raw} \u{F0001}\u{F0000}", false),
+            "{other} $HOME {{x}}{other} $HOME {{x}} \u{F0001}\u{F0000}"
+        );
+    }
+
+    #[test]
+    fn variables_of_the_wrong_type_are_ignored() {
+        for variables in [r#"["a", "b"]"#, r#""oops""#, "5"] {
+            let config = json::parse(&format!(r#"{{ "variables": {variables} }}"#)).unwrap();
+            assert!(get_custom_variables(&config, &command::MockRunner::default()).is_empty());
+        }
+    }
+
+    #[test]
+    fn merge_objects_key_by_key() {
+        let mut base =
+            json::parse(r#"{ "hybrid": { "r": 1, "css": { "a": 1 } }, "x": 1 }"#).unwrap();
+        merge(
+            &mut base,
+            json::parse(r#"{ "hybrid": { "css": { "b": 2 } }, "x": null }"#).unwrap(),
+        );
+
+        assert_eq!(
+            base,
+            json::parse(r#"{ "hybrid": { "r": 1, "css": { "a": 1, "b": 2 } } }"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn merge_overrides_scalars() {
+        let mut base = json::parse(r#"{ "a": 1, "b": "two", "c": { "d": true } }"#).unwrap();
+        merge(&mut base, json::parse(r#"{ "a": "one", "c": 3 }"#).unwrap());
+        assert_eq!(
+            base,
+            json::parse(r#"{ "a": "one", "b": "two", "c": 3 }"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn merge_replaces_arrays() {
+        let mut base = json::parse(r#"{ "a": [1, 2], "b": [1, 2] }"#).unwrap();
+        merge(
+            &mut base,
+            json::parse(r#"{ "a": [3], "b": ["...", 3], "c": ["...", 4] }"#).unwrap(),
+        );
+
+        assert_eq!(
+            base,
+            json::parse(r#"{ "a": [3], "b": [1, 2, 3], "c": [4] }"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn overrides_set_values() {
+        let mut config =
+            json::parse(r#"{ "hybrid": { "update_rate": 100 }, "a/b": [1, 2] }"#).unwrap();
+        let overrides = [
+            (String::from("/hybrid/update_rate"), String::from("250")),
+            (String::from("/hybrid/exclusive"), String::from("false")),
+            (String::from("/a~1b/1"), String::from("%H:%M")),
+        ];
+
+        apply_overrides(&mut config, &overrides).unwrap();
+        assert_eq!(config["hybrid"]["update_rate"], 250);
+        assert_eq!(config["hybrid"]["exclusive"], false);
+        assert_eq!(config["a/b"][1], "%H:%M");
+
+        for pointer in ["/missing/key", "/a~1b/5", "/hybrid/update_rate/x"] {
+            let overrides = [(String::from(pointer), String::from("1"))];
+            assert!(apply_overrides(&mut config, &overrides).is_err());
+        }
+    }
+
+    #[test]
+    fn overlay_is_merged() {
+        let mut config = json::object! {
+            hybrid: { update_rate: 100, layer: "top" },
+            "left-label_a": { text: "a" },
+        };
+
+        apply_overlay(
+            &mut config,
+            r#"{ "hybrid": { "update_rate": 500 }, "left-label_a": null }"#,
+        );
+        assert_eq!(
+            config,
+            json::object! { hybrid: { update_rate: 500, layer: "top" } }
+        );
+
+        // Ignored rather than failing to load.
+        let expected = config.clone();
+        for overlay in ["", "{ \"hybrid\": ", "[1, 2]"] {
+            apply_overlay(&mut config, overlay);
+            assert_eq!(config, expected);
+        }
+    }
+
+    #[test]
+    fn templates_are_overridden() {
+        let mut config = json::parse(
+            r##"{
+                "templates": {
+                    "pill": { "class": "pill", "margin_left": 4, "font": "Mono 10" },
+                    "accent": { "template": "pill", "color": "#f38ba8" },
+                    "a": { "template": "b" },
+                    "b": { "template": "a" }
+                },
+                "right-label_cpu": { "template": "accent", "font": null, "text": "x" },
+                "right-label_loop": { "template": "a" },
+                "right-label_missing": { "template": "nope" }
+            }"##,
+        )
+        .unwrap();
+
+        apply_templates(&mut config);
+        assert_eq!(
+            config["right-label_cpu"],
+            json::parse(
+                r##"{ "class": "pill", "margin_left": 4, "color": "#f38ba8", "text": "x" }"##
+            )
+            .unwrap()
+        );
+        assert_eq!(config["right-label_loop"]["template"], "a");
+        assert_eq!(config["right-label_missing"]["template"], "nope");
+    }
+
+    #[test]
+    fn conditions_drop_blocks() {
+        let mut config = json::parse(
+            r#"{
+                "left-label_kept": { "when_command": "true" },
+                "left-label_dropped": { "when_command": "false" },
+                "left-label_always": {},
+                "left-label_disabled": { "enabled": false, "when_command": "true" },
+                "left-label_enabled": { "enabled": "true" }
+            }"#,
+        )
+        .unwrap();
+
+        let runner = command::MockRunner::default()
+            .with("true", "", true)
+            .with("false", "", false);
+        apply_conditions(&mut config, &runner);
+        assert!(config["left-label_kept"].is_object());
+        assert!(!config["left-label_kept"].has_key("when_command"));
+        assert!(!config.has_key("left-label_dropped"));
+        assert!(config["left-label_always"].is_object());
+        assert!(!config.has_key("left-label_disabled"));
+        assert!(!config["left-label_enabled"].has_key("enabled"));
+    }
+}
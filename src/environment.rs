@@ -1,6 +1,144 @@
-use std::env;
+use std::{env, ffi::CStr, iter::Peekable, mem::MaybeUninit, ptr, str::Chars};
+
+lazy_static! {
+    /// The name of the current user, resolved once.
+    static ref USERNAME: String = resolve_username();
+}
 
 /// Tries to get the value from a specific environment variable.
 pub fn try_get_var(variable: &str, fallback_value: &str) -> String {
     env::var(variable).unwrap_or_else(|_| String::from(fallback_value))
 }
+
+/// Gets the name of the current user, from `$USER` or otherwise the passwd database.
+pub fn get_username() -> &'static str {
+    &USERNAME
+}
+
+/// Resolves the username without spawning `whoami`, which isn't installed everywhere.
+fn resolve_username() -> String {
+    if let Some(user) = env::var("USER").ok().filter(|user| !user.is_empty()) {
+        return user;
+    }
+
+    let mut passwd = MaybeUninit::<libc::passwd>::uninit();
+    let mut buffer = [0 as libc::c_char; 1024];
+    let mut result = ptr::null_mut();
+    // SAFETY: All pointers are valid for the duration of the call, and `pw_name` is only read if
+    // the lookup succeeded, at which point it points into `buffer`.
+    unsafe {
+        libc::getpwuid_r(
+            libc::getuid(),
+            passwd.as_mut_ptr(),
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        );
+
+        if result.is_null() {
+            log::warn!("Couldn't resolve the username");
+            return String::default();
+        }
+
+        CStr::from_ptr((*result).pw_name)
+            .to_string_lossy()
+            .to_string()
+    }
+}
+
+/// Gets the thousands separator of the locale from `LC_ALL`, `LC_NUMERIC` or `LANG`, like `,` for
+/// `en_US.UTF-8` or `.` for `de_DE.UTF-8`. Unknown locales use `,`.
+pub fn get_locale_thousands_separator() -> &'static str {
+    let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .iter()
+        .find_map(|variable| env::var(variable).ok().filter(|locale| !locale.is_empty()))
+        .unwrap_or_default();
+    // Only the language and territory matter, like `de_CH` in `de_CH.UTF-8@euro`.
+    let locale = locale.split(['.', '@']).next().unwrap_or_default();
+    let language = locale.split('_').next().unwrap_or_default();
+    match (language, locale) {
+        (_, "de_CH" | "it_CH" | "fr_CH") => "'",
+        ("de" | "nl" | "it" | "es" | "pt" | "da" | "id" | "tr" | "el", _) => ".",
+        ("fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "uk" | "hu", _) => " ",
+        _ => ",",
+    }
+}
+
+/// Expands `$VAR` and `${VAR}` patterns in `input` with the values of the environment variables.
+/// Undefined variables expand to an empty string like in a shell, and `$$` escapes to a single `$`.
+pub fn expand_vars(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    while let Some(char) = chars.next() {
+        if char != '$' {
+            result.push(char);
+            continue;
+        }
+
+        match chars.peek() {
+            Some('$') => {
+                chars.next();
+                result.push('$');
+            }
+            Some('{') => {
+                chars.next();
+                let mut name = String::new();
+                let mut closed = false;
+                for char in chars.by_ref() {
+                    if char == '}' {
+                        closed = true;
+                        break;
+                    }
+
+                    name.push(char);
+                }
+
+                if closed {
+                    result.push_str(&get_var_or_empty(&name));
+                } else {
+                    // Unterminated, leave it as-is.
+                    result.push_str("${");
+                    result.push_str(&name);
+                }
+            }
+            Some(char) if is_name_start(*char) => {
+                let name = take_name(&mut chars);
+                result.push_str(&get_var_or_empty(&name));
+            }
+            // Not a variable, for example `$1` or a trailing `$`.
+            _ => result.push('$'),
+        }
+    }
+
+    result
+}
+
+/// Checks if `char` can start an environment variable name.
+fn is_name_start(char: char) -> bool {
+    char.is_ascii_alphabetic() || char == '_'
+}
+
+/// Takes all the characters that belong to an environment variable name.
+fn take_name(chars: &mut Peekable<Chars>) -> String {
+    let mut name = String::new();
+    while let Some(char) = chars.peek() {
+        if !char.is_ascii_alphanumeric() && *char != '_' {
+            break;
+        }
+
+        name.push(*char);
+        chars.next();
+    }
+
+    name
+}
+
+/// Gets the value of the environment variable, or an empty string if it isn't defined.
+fn get_var_or_empty(variable: &str) -> String {
+    env::var(variable).unwrap_or_else(|_| {
+        log::debug!(
+            "Environment variable '{variable}' isn't defined, expanding to an empty string"
+        );
+        String::default()
+    })
+}
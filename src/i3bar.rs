@@ -0,0 +1,329 @@
+use crate::{
+    clock_widget, color,
+    command::{self, BashRunner, CommandRunner, RetryRunner},
+    config, label_widget, memory_widget, placeholders, power,
+    ranges::Ranges,
+    resume::SleepDetector,
+    structures::Align,
+    transform::{self, Step},
+    ui, uptime_widget,
+};
+use chrono_tz::Tz;
+use json::JsonValue;
+use std::{
+    io::{self, Write},
+    process, thread,
+    time::{Duration, Instant},
+};
+
+/// The longest the loop sleeps in one go, so it notices a resume from suspend soon after, as the
+/// monotonic clock it sleeps on stands still while the system is asleep.
+const MAX_SLEEP: Duration = Duration::from_secs(1);
+
+/// Widget types which only affect the layout, so there's nothing to show for them. Blocks are
+/// separated by the bar itself.
+const LAYOUT_WIDGETS: &[&str] = &["spacing", "box", "separator", "group"];
+
+/// Where the text of a block comes from.
+enum Source {
+    /// Text that never changes.
+    Static(String),
+    /// The output of `command`, appended to `text`.
+    Command {
+        text: String,
+        command: String,
+        cache_ttl: Duration,
+        timeout: Duration,
+        retry: command::Retry,
+        show_errors: bool,
+        fallback: String,
+        trim: bool,
+        transform: Vec<Step>,
+    },
+    Clock {
+        format: String,
+        timezone: Option<Tz>,
+    },
+    Memory {
+        format: String,
+        ranges: Ranges,
+    },
+    Uptime {
+        format: String,
+    },
+}
+
+/// A widget, shown as a block in the i3bar protocol.
+struct Block {
+    /// The widget type, sent as `name`.
+    widget_type: String,
+    /// The widget name, sent as `instance`.
+    name: String,
+    color: Option<String>,
+    background: Option<String>,
+    /// Whether `full_text` is sent as Pango markup, see `label_widget::uses_markup`.
+    markup: bool,
+    source: Source,
+    update_rate: u64,
+    /// Used instead of `update_rate` while running on battery, if it's set.
+    update_rate_on_battery: Option<u64>,
+    /// The text last read from `source`.
+    full_text: String,
+    /// When to read `source` again, `None` if it isn't updated anymore.
+    next_update: Option<Instant>,
+}
+
+impl Block {
+    /// Reads the text from `source` with commands executed through `runner`, and schedules the
+    /// next update.
+    fn update(&mut self, now: Instant, runner: &impl CommandRunner) {
+        self.full_text = match &self.source {
+            Source::Static(text) => text.clone(),
+            Source::Command {
+                text,
+                command,
+                cache_ttl,
+                timeout,
+                retry,
+                show_errors,
+                fallback,
+                trim,
+                transform,
+            } => {
+                let runner = RetryRunner {
+                    runner,
+                    retry: *retry,
+                };
+                let output = command::execute_cached_with(&runner, command, *cache_ttl, *timeout);
+                let stdout = match trim {
+                    true => &output.stdout,
+                    false => &output.untrimmed_stdout,
+                };
+
+                if *show_errors && !output.success {
+                    format!("{text}{}", output.stderr)
+                } else if label_widget::needs_fallback(stdout, output.success, fallback) {
+                    fallback.clone()
+                } else {
+                    format!(
+                        "{text}{}",
+                        transform::apply_or_raw(&self.name, transform, stdout)
+                    )
+                }
+            }
+            Source::Clock { format, timezone } => clock_widget::format_now(format, *timezone),
+            Source::Memory { format, ranges } => {
+                memory_widget::read(format, ranges).unwrap_or_default()
+            }
+            Source::Uptime { format } => uptime_widget::read(format).unwrap_or_default(),
+        };
+
+        placeholders::publish(&self.name, &self.full_text);
+        let update_rate = power::pick_update_rate(self.update_rate, self.update_rate_on_battery);
+        self.next_update = match (&self.source, update_rate) {
+            (Source::Static(_), _) | (_, 0) => None,
+            (_, update_rate) => Some(now + Duration::from_millis(update_rate)),
+        };
+    }
+
+    /// Converts the block into its i3bar protocol representation.
+    fn to_json(&self) -> JsonValue {
+        let mut block = json::object! {
+            name: self.widget_type.as_str(),
+            instance: self.name.as_str(),
+            full_text: self.full_text.as_str(),
+        };
+
+        if self.markup {
+            block["markup"] = "pango".into();
+        }
+
+        if let Some(color) = &self.color {
+            block["color"] = color.as_str().into();
+        }
+
+        if let Some(background) = &self.background {
+            block["background"] = background.as_str().into();
+        }
+
+        block
+    }
+}
+
+/// Gets the hex color at `key`, normalized to the `#rrggbb` or `#rrggbbaa` i3bar expects.
+/// Invalid colors are left out with a warning.
+fn get_color(key: &str, color_key: &str) -> Option<String> {
+    let (value, _) = config::try_get(key, color_key, true, false)?;
+    match color::parse_hex(&value) {
+        Some(color) => Some(color::to_hex(&color)),
+        None => {
+            log::warn!("{key}: Invalid {color_key} '{value}', expected a hex color");
+            None
+        }
+    }
+}
+
+/// Gets the blocks for all widgets which can be shown without GTK, ordered from left to right.
+fn get_blocks() -> Vec<Block> {
+    let mut blocks = Vec::new();
+    for key in ui::get_widget_keys() {
+        let identifier = key.split(ui::SEPARATOR).next().unwrap_or_default();
+        let Some((widget_alignment, widget_type)) = identifier.split_once(ui::ALIGNMENT) else {
+            continue;
+        };
+
+        let name = ui::get_widget_name(&key);
+        let (text, command, _) = ui::get_base_keys(&key);
+        let markup = match widget_type {
+            "label" => label_widget::uses_markup(&key, &command),
+            "button" => true,
+            _ => false,
+        };
+        let source = match widget_type {
+            "label" if command.is_empty() => Source::Static(text),
+            "label" => {
+                if config::get_or_default_bool(&key, "listen") {
+                    log::warn!(
+                        "{key}: listen isn't supported with --i3bar, running the command on every \
+                         update instead"
+                    );
+                }
+
+                Source::Command {
+                    text,
+                    command,
+                    cache_ttl: Duration::from_secs(
+                        config::get_or_default(&key, "cache_ttl", false, false)
+                            .1
+                            .max(0) as u64,
+                    ),
+                    timeout: Duration::from_millis(config::get_command_timeout(&key)),
+                    retry: config::get_command_retry(&key),
+                    show_errors: config::get_or_default_bool(&key, "show_errors"),
+                    fallback: config::get_or_default(&key, "fallback", true, true).0,
+                    trim: config::try_get_bool(&key, "trim").unwrap_or(true),
+                    transform: transform::get(&key),
+                }
+            }
+            // The command of a button is only run when it's clicked.
+            "button" => Source::Static(text),
+            "clock" => Source::Clock {
+                format: clock_widget::get_format(&key),
+                timezone: clock_widget::get_timezone(&key),
+            },
+            "memory" => Source::Memory {
+                format: config::get_format(&key, memory_widget::DEFAULT_FORMAT),
+                ranges: Ranges::get(&key),
+            },
+            "uptime" => Source::Uptime {
+                format: config::get_format(&key, uptime_widget::DEFAULT_FORMAT),
+            },
+            widget_type if LAYOUT_WIDGETS.contains(&widget_type) => continue,
+            _ => {
+                log::warn!("{key}: {widget_type} widgets aren't supported with --i3bar, skipping");
+                continue;
+            }
+        };
+
+        let alignment = ui::get_alignment(&key, &widget_alignment.to_uppercase());
+        blocks.push((
+            alignment,
+            Block {
+                widget_type: widget_type.to_string(),
+                name,
+                color: get_color(&key, "color"),
+                background: get_color(&key, "background"),
+                markup,
+                source,
+                update_rate: config::get_widget_update_rate(&key),
+                update_rate_on_battery: config::get_widget_update_rate_on_battery(&key),
+                full_text: String::new(),
+                next_update: Some(Instant::now()),
+            },
+        ));
+    }
+
+    // i3bar has no regions, so they're put after each other. The sort is stable, so the `order`
+    // is kept within each region.
+    blocks.sort_by_key(|(alignment, _)| match alignment {
+        Align::LEFT => 0,
+        Align::CENTERED => 1,
+        Align::RIGHT => 2,
+    });
+
+    blocks.into_iter().map(|(_, block)| block).collect()
+}
+
+/// Prints the widgets to stdout in the i3bar protocol instead of drawing the bar, so the config
+/// can be used with bars like swaybar. The status line is printed again whenever a widget changes.
+/// Commands are executed one after another, so a slow command holds up the other widgets.
+pub fn run() -> ! {
+    let mut blocks = get_blocks();
+    let mut stdout = io::stdout().lock();
+
+    // The header, followed by the start of the infinite array of status lines.
+    let header = json::stringify(json::object! { version: 1 });
+    if writeln!(stdout, "{header}\n[").is_err() {
+        process::exit(1)
+    }
+
+    let mut previous_line = String::new();
+    let mut detector = SleepDetector::new();
+    loop {
+        let now = Instant::now();
+        // 0.3.5: Everything is updated right away after a suspend, rather than once it's due.
+        if detector.has_slept() {
+            log::info!("Resumed from suspend, updating every block");
+            command::clear_cache();
+            power::forget();
+            for next_update in blocks
+                .iter_mut()
+                .filter_map(|block| block.next_update.as_mut())
+            {
+                *next_update = now;
+            }
+        }
+
+        for block in &mut blocks {
+            if block
+                .next_update
+                .is_some_and(|next_update| next_update <= now)
+            {
+                block.update(now, &BashRunner);
+            }
+        }
+
+        // Empty blocks would still show up with a separator.
+        let line = json::stringify(JsonValue::Array(
+            blocks
+                .iter()
+                .filter(|block| !block.full_text.is_empty())
+                .map(Block::to_json)
+                .collect(),
+        ));
+
+        if line != previous_line {
+            // The bar has stopped reading, so there's no one left to print to.
+            if writeln!(stdout, "{line},")
+                .and_then(|_| stdout.flush())
+                .is_err()
+            {
+                process::exit(0)
+            }
+
+            previous_line = line;
+        }
+
+        match blocks.iter().filter_map(|block| block.next_update).min() {
+            Some(next_update) => thread::sleep(
+                next_update
+                    .saturating_duration_since(Instant::now())
+                    .min(MAX_SLEEP),
+            ),
+            // Nothing changes anymore, but the bar expects the output to stay open.
+            None => loop {
+                thread::park()
+            },
+        }
+    }
+}
@@ -1,35 +1,60 @@
 use crate::{
     cava::{get_current_bars, HAS_CAVA_CRASHED},
-    config, ui,
+    command::BashRunner,
+    config, pause, power, resume, sources,
+    triggers::{self, Trigger},
+    ui,
     widget::HWidget,
 };
 use glib::Continue;
-use std::time::Duration;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 use tokio::task;
 
+/// Has the label loop been started?
+static HAS_STARTED_LABELS: AtomicBool = AtomicBool::new(false);
+
+/// Has the Cava tick-loop been started?
+static HAS_STARTED_CAVA: AtomicBool = AtomicBool::new(false);
+
 /// Updates dynamic bar content.
+/// Safe to call once per bar, the loops are only started once and pick up widgets from new bars
+/// on their own.
 pub fn update() {
-    update_labels();
+    if !HAS_STARTED_LABELS.swap(true, Ordering::SeqCst) {
+        update_labels();
+    }
+
+    // 0.3.5: Commands shared by several widgets through `sources`.
+    sources::run();
+
     // Only start the tick-loop if there are actually Cava widgets available.
     if ui::CAVA_INSTANCES
         .lock()
-        .expect("[ERROR] Cannot access ui::CAVA_INSTANCES!\n")
+        .expect("Cannot access ui::CAVA_INSTANCES!")
         .is_empty()
+        || HAS_STARTED_CAVA.swap(true, Ordering::SeqCst)
     {
         return;
     }
 
-    // Run `update_cava` closure every 1ms.
-    glib::timeout_add_local(Duration::from_millis(1), update_cava);
+    // 0.3.5: Run `update_cava` once per frame, rather than every 1ms.
+    pause::timeout_add_local(config::get_frame_interval(), update_cava);
 }
 
 /// Updates all Cava widgets.
 fn update_cava() -> Continue {
     let bars = &get_current_bars();
     // Loop through all Cava widget instances and sync the text.
-    for widget in ui::CAVA_INSTANCES
+    for (_, widget) in ui::CAVA_INSTANCES
         .lock()
-        .expect("[ERROR] Cannot access ui::CAVA_INSTANCES!\n")
+        .expect("Cannot access ui::CAVA_INSTANCES!")
         .iter()
     {
         widget.update_label_reg(bars);
@@ -40,27 +65,276 @@ fn update_cava() -> Continue {
 }
 
 /// Updates all labels with a `command` set.
+/// Each label is updated at its own `update_rate`.
+/// Commands are executed on worker threads so that a slow command can't freeze the bar, the
+/// results are then sent back to the main thread which updates the labels.
 /// Only call this once as it's a loop.
 fn update_labels() {
+    // The id of the label in `ui::VEC`, plus the new text. `None` means the label should update
+    // from its own buffer instead.
+    let (sender, receiver) =
+        glib::MainContext::channel::<(usize, Option<String>)>(glib::PRIORITY_DEFAULT);
+    receiver.attach(None, |(id, text)| {
+        // 0.3.5: Labels may have been torn down along with their bar since.
+        if let Some((_, widget)) = ui::VEC
+            .lock()
+            .expect("Cannot access ui::VEC!")
+            .iter()
+            .find(|(other, _)| *other == id)
+        {
+            match text {
+                Some(text) => widget.update_label_reg(&text),
+                None => widget.update_label_internal(),
+            }
+        }
+
+        Continue(true)
+    });
+
     task::spawn(async move {
-        let update_rate = config::get_update_rate();
+        // Never sleep for longer than the global update-rate, in case new labels are added.
+        // With an update-rate of `0` there's no timer, but new labels still have to be picked up.
+        // 0.3.5: At most a second either way, so a resume from suspend is noticed quickly.
+        let update_rate = match config::get_update_rate() {
+            0 => Duration::from_secs(1),
+            update_rate => Duration::from_millis(update_rate).min(Duration::from_secs(1)),
+        };
+        let mut scheduler = Scheduler::default();
+        // Labels whose command is still running, so a hanging command isn't executed again.
+        let running = Arc::new(Mutex::new(HashSet::new()));
+        let mut resumes = resume::count();
+        let mut on_battery = power::on_battery();
         loop {
-            for widget in ui::VEC
-                .lock()
-                .expect("[ERROR] Cannot access ui::VEC!\n")
-                .iter()
+            let now = Instant::now();
+            let mut next_wake = now + update_rate;
+            // 0.3.5: Update every timed label right away after a suspend, rather than showing
+            //   stale values until they're due.
+            if resumes != resume::count() {
+                resumes = resume::count();
+                scheduler.update_all(now);
+            }
+
+            // 0.3.5: Labels that are due while the bars are hidden are updated once one is
+            //   visible again.
+            if pause::is_paused() {
+                tokio::time::sleep_until(next_wake.into()).await;
+                continue;
+            }
+
+            // 0.3.5: Labels with an `update_rate_on_battery` are rescheduled right away when
+            //   switching between battery and AC, as their update-rate changes.
+            let was_on_battery = std::mem::replace(&mut on_battery, power::on_battery());
+            let tick = Tick {
+                now,
+                fired: triggers::take_fired(),
+                power_changed: was_on_battery != on_battery,
+            };
+
+            // Scoped, as the lock can't be held while waiting.
             {
-                // If listen is set, don't execute a one-shot command.
-                if !widget.listen {
-                    let mut text = widget.text.clone();
-                    text.push_str(&execute!(&widget.command));
-                    widget.update_label_reg(&text);
-                } else {
-                    widget.update_label_internal()
+                let widgets = ui::VEC.lock().expect("Cannot access ui::VEC!");
+                for (id, widget) in widgets.iter() {
+                    let id = *id;
+                    if scheduler.is_due(id, &widget.schedule(), &tick) {
+                        // If listen is set, don't execute a one-shot command.
+                        if widget.listen {
+                            // Can only fail if the main loop is gone.
+                            let _ = sender.send((id, None));
+                        } else if running.lock().unwrap().insert(id) {
+                            let sender = sender.clone();
+                            let running = running.clone();
+                            let job = widget.job.clone();
+                            task::spawn_blocking(move || {
+                                let _ = sender.send((id, Some(job.run(&BashRunner))));
+                                running.lock().unwrap().remove(&id);
+                            });
+                        }
+                    }
+
+                    if let Some(next_update) = scheduler.next_update(id) {
+                        next_wake = next_wake.min(next_update);
+                    }
                 }
+
+                scheduler.retain(widgets.len(), |id| {
+                    widgets.iter().any(|(other, _)| *other == id)
+                });
             }
 
-            tokio::time::sleep(Duration::from_millis(update_rate)).await;
+            tokio::select! {
+                _ = tokio::time::sleep_until(next_wake.into()) => {}
+                _ = triggers::wait() => {}
+            }
         }
     });
 }
+
+/// How the label loop schedules a label, borrowed from its `LabelWidget`.
+pub struct Schedule<'a> {
+    pub update_rate: u64,
+    /// Used instead of `update_rate` while running on battery, if it's set.
+    pub update_rate_on_battery: Option<u64>,
+    pub update_on: &'a Option<Vec<Trigger>>,
+    /// Listen labels are synced with their buffer rather than running their command.
+    pub listen: bool,
+}
+
+/// What happened since the previous tick of the label loop.
+struct Tick {
+    now: Instant,
+    /// The triggers that fired, see `triggers::take_fired`.
+    fired: Vec<Trigger>,
+    /// Whether the bar switched between battery and AC.
+    power_changed: bool,
+}
+
+/// When each label should be updated next, by its id in `ui::VEC`. `None` means it's
+/// event-driven and isn't updated on a timer.
+#[derive(Default)]
+struct Scheduler {
+    next_updates: HashMap<usize, Option<Instant>>,
+}
+
+impl Scheduler {
+    /// Makes every timed label due at `now`.
+    fn update_all(&mut self, now: Instant) {
+        for next_update in self.next_updates.values_mut().flatten() {
+            *next_update = now;
+        }
+    }
+
+    /// Checks whether the label with `id` is due on `tick`, in which case its next update is
+    /// scheduled right away.
+    fn is_due(&mut self, id: usize, schedule: &Schedule, tick: &Tick) -> bool {
+        let now = tick.now;
+        // Every label is updated once when it's added, even the event-driven ones.
+        let next_update = self.next_updates.entry(id).or_insert(Some(now));
+        if tick.power_changed && schedule.update_rate_on_battery.is_some() {
+            *next_update = Some(now);
+        }
+
+        // 0.3.5: Labels with an `update_on` are updated right away when one of its triggers
+        //   fires.
+        if schedule
+            .update_on
+            .iter()
+            .flatten()
+            .any(|trigger| tick.fired.contains(trigger))
+        {
+            *next_update = Some(now);
+        }
+
+        if next_update.is_none_or(|next_update| next_update > now) {
+            return false;
+        }
+
+        let update_rate = match triggers::ticks(schedule.update_on) {
+            true => power::pick_update_rate(schedule.update_rate, schedule.update_rate_on_battery),
+            false => 0,
+        };
+        *next_update = match update_rate {
+            // Listen labels are only synced with their buffer on a timer.
+            0 if schedule.listen => Some(now + Duration::from_millis(config::MIN_UPDATE_RATE)),
+            0 => None,
+            update_rate => Some(now + Duration::from_millis(update_rate)),
+        };
+
+        true
+    }
+
+    /// When the label with `id` should be updated next.
+    fn next_update(&self, id: usize) -> Option<Instant> {
+        self.next_updates.get(&id).copied().flatten()
+    }
+
+    /// Forgets the labels that have been torn down, so that only the `count` labels for which
+    /// `is_kept` is true are left.
+    fn retain(&mut self, count: usize, is_kept: impl Fn(usize) -> bool) {
+        // Checking every label on every tick would be wasted, as nothing's torn down most of them.
+        if self.next_updates.len() > count {
+            self.next_updates.retain(|id, _| is_kept(*id));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        allocations, command::MockRunner, label_widget::LabelJob, locks, placeholders,
+        transform::Step,
+    };
+
+    /// How many labels the benchmark config has.
+    const LABELS: usize = 500;
+
+    /// Runs a tick of the label loop over `jobs` like `update_labels` does, except that the due
+    /// commands run through `runner` on the current thread. Then does what the main thread does
+    /// with the output, as far as it doesn't need GTK.
+    fn tick(scheduler: &mut Scheduler, jobs: &[Arc<LabelJob>], runner: &MockRunner, now: Instant) {
+        let tick = Tick {
+            now,
+            fired: triggers::take_fired(),
+            power_changed: false,
+        };
+        let schedule = Schedule {
+            update_rate: 1000,
+            update_rate_on_battery: None,
+            update_on: &None,
+            listen: false,
+        };
+
+        for (index, job) in jobs.iter().enumerate() {
+            if scheduler.is_due(index, &schedule, &tick) {
+                let text = job.run(runner);
+                placeholders::publish(&job.name, &text);
+                // Read by the transition the new content fades in with.
+                config::get_frame_interval();
+            }
+        }
+    }
+
+    #[test]
+    fn label_loop_ticks() {
+        let mut config = locks::write(&config::CONFIG);
+        let jobs: Vec<Arc<LabelJob>> = (0..LABELS)
+            .map(|index| {
+                let key = format!("left-label_tests_tick_{index}");
+                config[key.as_str()] = json::object! { command: "df", update_rate: 1000 };
+                Arc::new(LabelJob {
+                    name: key,
+                    text: String::from("Used: "),
+                    command: String::from("df"),
+                    transform: vec![Step::Round(1), Step::Suffix(String::from(" GiB"))],
+                    ..Default::default()
+                })
+            })
+            .collect();
+        drop(config);
+
+        let runner = MockRunner::default().with("df", "41.26\n", true);
+        let mut scheduler = Scheduler::default();
+        let started = Instant::now();
+        let first = allocations::count(|| tick(&mut scheduler, &jobs, &runner, started));
+        let ticks: Vec<usize> = (1..=3)
+            .map(|second| {
+                let now = started + Duration::from_secs(second);
+                allocations::count(|| tick(&mut scheduler, &jobs, &runner, now))
+            })
+            .collect();
+        assert_eq!(
+            placeholders::get_value("left-label_tests_tick_0").as_deref(),
+            Some("Used: 41.3 GiB")
+        );
+
+        // Every label is due on every tick. Before the output was remembered each of them cost 7
+        // allocations per tick, now it's what `MockRunner` copies plus the text that's sent back.
+        assert!(ticks.iter().all(|tick| *tick <= LABELS * 3), "{ticks:?}");
+        assert!(first > ticks[0], "{first} allocations on the first tick");
+
+        let mut config = locks::write(&config::CONFIG);
+        for job in &jobs {
+            config.remove(&job.name);
+        }
+    }
+}
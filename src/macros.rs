@@ -1,39 +1,70 @@
 #[macro_export]
-/// Logs a [HYBRID] [DEBUG] formatted message to stdout.
-macro_rules! log {
-    ($msg:expr) => {
-        if $crate::environment::try_get_var("HYBRID_LOG", "0") == "1" {
-            println!("[HYBRID] [DEBUG] {}", $msg)
-        }
-    };
-}
-
-#[macro_export]
-/// Executes a bash command and outputs it to `result`.
+/// Executes a bash command and outputs its stdout, normalized through `command::normalize`.
+/// Accepts anything that derefs to `str`, so commands of any length can be passed.
+/// With several commands, they're all run and the output of the last one is returned.
 macro_rules! execute {
     ($($cmd:expr),*) => {
         {
-            let mut result;
+            // Only reassigned when there are several commands.
+            #[allow(unused_mut)]
+            let mut result: String;
             $(
-            if $cmd.is_empty() {
-                // Return a stack-allocated string containing no content.
-                drop(heapless::String::<0>::default());
-            }
-
-            result = String::from_utf8_lossy(
-                &std::process::Command::new("bash")
-                .args(["-c", $cmd])
-                .output()
-                .unwrap()
-                .stdout)
-                .to_string();
-
-            // Remove the last character as its a new line.
-            result.pop();
+            let cmd: &str = &$cmd;
+            result = if cmd.is_empty() {
+                String::default()
+            } else {
+                std::process::Command::new("bash")
+                    .args(["-c", cmd])
+                    .output()
+                    .map(|output| $crate::command::normalize(&String::from_utf8_lossy(&output.stdout), true))
+                    .unwrap_or_default()
+            };
             )*
 
-            // Could use drop(&$result) but then Clippy would whine.
-            format!("{}", result)
+            result
         }
     };
 }
+
+#[macro_export]
+/// Executes `$program` with `$args` and outputs its stdout normalized like `execute!`, without
+/// going through bash.
+/// Unlike `execute!`, every argument is passed to the program as-is, so user-provided text can't
+/// be interpreted as another command.
+/// Example: `execute_args!("notify-send", &["Title", user_text])`
+macro_rules! execute_args {
+    ($program:expr, $args:expr) => {{
+        std::process::Command::new($program)
+            .args($args)
+            .output()
+            .map(|output| {
+                $crate::command::normalize(&String::from_utf8_lossy(&output.stdout), true)
+            })
+            .unwrap_or_default()
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn execute_long_command() {
+        let text = "a".repeat(4096);
+        let command = format!("echo {text}");
+        assert_eq!(execute!(&command), text);
+    }
+
+    #[test]
+    fn execute_empty_command() {
+        assert_eq!(execute!(""), "");
+    }
+
+    #[test]
+    fn execute_keeps_output_without_new_line() {
+        assert_eq!(execute!("printf abc"), "abc");
+    }
+
+    #[test]
+    fn execute_normalizes_output() {
+        assert_eq!(execute!("printf 'a\\r\\nb\\n\\n'"), "a\nb");
+    }
+}
@@ -4,28 +4,145 @@ extern crate lazy_static;
 #[macro_use]
 mod macros;
 
+#[cfg(test)]
+#[path = "utils/allocations.rs"]
+mod allocations;
+#[path = "widgets/battery_widget.rs"]
+mod battery_widget;
 #[path = "widgets/box_widget.rs"]
 mod box_widget;
+#[path = "widgets/brightness_widget.rs"]
+mod brightness_widget;
 #[path = "widgets/button_widget.rs"]
 mod button_widget;
+#[path = "widgets/canvas_widget.rs"]
+mod canvas_widget;
 #[path = "utils/cava.rs"]
 mod cava;
 #[path = "widgets/cava_widget.rs"]
 mod cava_widget;
+mod check;
+mod cli;
+#[path = "widgets/clock_widget.rs"]
+mod clock_widget;
 #[path = "widgets/cmd_widget.rs"]
 mod cmd_widget;
+#[path = "utils/color.rs"]
+mod color;
+#[path = "utils/command.rs"]
+mod command;
+#[path = "utils/compositor.rs"]
+mod compositor;
 mod config;
+#[path = "utils/control.rs"]
+mod control;
+#[path = "widgets/cpu_widget.rs"]
+mod cpu_widget;
+#[path = "widgets/disk_widget.rs"]
+mod disk_widget;
 mod environment;
+#[path = "utils/errors.rs"]
+mod errors;
+#[path = "utils/events.rs"]
+mod events;
+#[path = "widgets/graph_widget.rs"]
+mod graph_widget;
+#[path = "widgets/group_widget.rs"]
+mod group_widget;
+#[path = "utils/hooks.rs"]
+mod hooks;
+#[path = "utils/hyprland_ipc.rs"]
+mod hyprland_ipc;
+mod i3bar;
+#[path = "widgets/image_widget.rs"]
+mod image_widget;
+#[path = "utils/jsonc.rs"]
+mod jsonc;
+#[path = "widgets/keyboard_widget.rs"]
+mod keyboard_widget;
 #[path = "widgets/label_widget.rs"]
 mod label_widget;
+#[path = "utils/locks.rs"]
+mod locks;
+#[path = "utils/logging.rs"]
+mod logging;
 mod r#loop;
 #[path = "utils/math.rs"]
 mod math;
+#[path = "widgets/media_widget.rs"]
+mod media_widget;
+#[path = "widgets/memory_widget.rs"]
+mod memory_widget;
+#[path = "widgets/network_widget.rs"]
+mod network_widget;
+#[path = "utils/overflow.rs"]
+mod overflow;
+#[path = "utils/pause.rs"]
+mod pause;
+#[path = "utils/placeholders.rs"]
+mod placeholders;
+#[path = "utils/power.rs"]
+mod power;
+#[path = "widgets/progress_widget.rs"]
+mod progress_widget;
+#[path = "utils/ranges.rs"]
+mod ranges;
+#[path = "utils/registry.rs"]
+mod registry;
+#[path = "utils/resume.rs"]
+mod resume;
+#[path = "utils/scale.rs"]
+mod scale;
+mod schema;
+#[path = "widgets/script_widget.rs"]
+mod script_widget;
+#[path = "widgets/separator_widget.rs"]
+mod separator_widget;
+#[path = "utils/signals.rs"]
+mod signals;
+#[path = "utils/sources.rs"]
+mod sources;
 #[path = "widgets/spacing_widget.rs"]
 mod spacing_widget;
 mod structures;
+#[path = "utils/stylesheet.rs"]
+mod stylesheet;
+#[path = "utils/sway_ipc.rs"]
+mod sway_ipc;
+#[path = "widgets/temperature_widget.rs"]
+mod temperature_widget;
+#[path = "utils/theme.rs"]
+mod theme;
+#[path = "widgets/timer_widget.rs"]
+mod timer_widget;
+#[path = "utils/toml_config.rs"]
+mod toml_config;
+#[path = "utils/transform.rs"]
+mod transform;
+#[path = "utils/transition.rs"]
+mod transition;
+#[path = "utils/tray.rs"]
+mod tray;
+#[path = "widgets/tray_widget.rs"]
+mod tray_widget;
+#[path = "utils/triggers.rs"]
+mod triggers;
 mod ui;
+#[path = "utils/units.rs"]
+mod units;
+#[path = "widgets/uptime_widget.rs"]
+mod uptime_widget;
+#[path = "utils/visibility.rs"]
+mod visibility;
+#[path = "widgets/volume_widget.rs"]
+mod volume_widget;
+#[path = "utils/watcher.rs"]
+mod watcher;
 mod widget;
+#[path = "widgets/window_widget.rs"]
+mod window_widget;
+#[path = "widgets/workspaces_widget.rs"]
+mod workspaces_widget;
 
 use gtk::gdk::*;
 use gtk::gio::ApplicationFlags;
@@ -34,47 +151,156 @@ use gtk::*;
 use gtk_layer_shell::Edge;
 use json::JsonValue;
 use label_widget::LabelWidget;
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use structures::Position;
 use widget::HWidget;
 
+/// How long shutting down waits for the worker threads, once their commands have been stopped.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
 /// Gets the anchors.
-fn get_anchors() -> [(gtk_layer_shell::Edge, bool); 4] {
-    const ROOT: &str = "hybrid";
-    let mut expand_right = true;
-    let mut expand_left = true;
-    let mut pos = String::from("Top");
+/// The bar is anchored to the edge it's positioned at, `expand_left` and `expand_right` stretch it
+/// along that edge. For vertical bars they stretch it towards the top and bottom respectively.
+/// 0.3.5: If `is_sized` the bar has a `width` (or `height` for vertical bars) along its edge, so
+///   it's only stretched if asked to and centered otherwise.
+fn get_anchors(is_sized: bool) -> [(gtk_layer_shell::Edge, bool); 4] {
+    let hybrid = &config::parsed_config().hybrid;
+    // Check if there's any user-defined values for expand l-r, if there are then sync them.
+    let expand_right = hybrid.expand_right.unwrap_or(!is_sized);
+    let expand_left = hybrid.expand_left.unwrap_or(!is_sized);
+
+    // 0.3.5: Support for left and right positions.
+    match config::get_position() {
+        Position::TOP => [
+            (Edge::Left, expand_left),
+            (Edge::Right, expand_right),
+            (Edge::Top, true),
+            (Edge::Bottom, false),
+        ],
+        Position::BOTTOM => [
+            (Edge::Left, expand_left),
+            (Edge::Right, expand_right),
+            (Edge::Top, false),
+            (Edge::Bottom, true),
+        ],
+        Position::LEFT => [
+            (Edge::Left, true),
+            (Edge::Right, false),
+            (Edge::Top, expand_left),
+            (Edge::Bottom, expand_right),
+        ],
+        Position::RIGHT => [
+            (Edge::Left, false),
+            (Edge::Right, true),
+            (Edge::Top, expand_left),
+            (Edge::Bottom, expand_right),
+        ],
+    }
+}
+
+/// Initializes the status bar.
+fn activate(application: &Application) {
+    // Initialize gdk::Display by default value, which is decided by the compositor.
+    let display =
+        Display::default().expect("Could not get default display, is your compositor doing okay?");
+
+    // 0.3.5: Create a bar on every selected monitor, rather than on a single one.
+    let bars: Rc<RefCell<Vec<(Monitor, ApplicationWindow)>>> = Rc::default();
+    for index in 0..display.n_monitors() {
+        if let Some(monitor) = display.monitor(index) {
+            if is_monitor_selected(&display, &monitor) {
+                let window = create_bar(application, &monitor);
+                bars.borrow_mut().push((monitor, window));
+            }
+        }
+    }
 
-    // Check if there's any user-defined values for expand l-r/pos, if there are then sync them.
-    if let Some(c_expand_right) = config::try_get(ROOT, "expand_right", true, false) {
-        expand_right = c_expand_right.0 == "true";
+    if bars.borrow().is_empty() {
+        log::warn!("No monitors matched hybrid:monitor, waiting for one to be plugged in");
     }
 
-    if let Some(c_expand_left) = config::try_get(ROOT, "expand_left", true, false) {
-        expand_left = c_expand_left.0 == "true";
+    // Keep running while there are no bars, as monitors may be plugged in later on.
+    std::mem::forget(application.hold());
+    // 0.3.5: Quitting ignores the hold, so a `SIGTERM` still shuts the bar down.
+    signals::quit_on_sigterm(application);
+
+    // Spawn and destroy bars as monitors are hot-plugged.
+    let application = application.clone();
+    let added_bars = bars.clone();
+    display.connect_monitor_added(move |display, monitor| {
+        if is_monitor_selected(display, monitor) {
+            log::info!("Monitor added, creating a bar for it");
+            let window = create_bar(&application, monitor);
+            added_bars.borrow_mut().push((monitor.clone(), window));
+        }
+    });
+
+    display.connect_monitor_removed(move |_, monitor| {
+        bars.borrow_mut().retain(|(bar_monitor, window)| {
+            if bar_monitor != monitor {
+                return true;
+            }
+
+            log::info!("Monitor removed, destroying its bar");
+            window.close();
+            false
+        });
+    });
+
+    signals::reload_on_sigusr1();
+    // 0.3.5: Lets other programs read widget values and reload the config.
+    control::listen();
+    // 0.3.5: Values from before a suspend are stale, refresh them right after waking up.
+    resume::refresh_on_resume();
+    log::info!("Ready!");
+}
+
+/// Checks whether a bar should be created on `monitor`, according to `hybrid` -> `monitor`.
+/// Accepts `"all"` (default), a connector name such as `"DP-1"`, an array of connector names or
+/// the index of a single monitor.
+fn is_monitor_selected(display: &Display, monitor: &Monitor) -> bool {
+    let selection = config::with_config(|config| config["hybrid"]["monitor"].clone());
+    if selection.is_null() {
+        return true;
     }
 
-    if let Some(c_pos) = config::try_get(ROOT, "position", true, false) {
-        pos = c_pos.0;
+    // Prior to 0.3.5, only the index of a single monitor was supported.
+    if let Some(index) = selection.as_i32() {
+        return display.monitor(index).as_ref() == Some(monitor);
     }
 
-    if !pos.eq_ignore_ascii_case("Top") && !pos.eq_ignore_ascii_case("Bottom") && !pos.is_empty() {
-        panic!("[ERROR] Invalid position! Values: [ TOP, BOTTOM ] - casing doesn't matter.\n")
+    // GTK3 exposes the connector name as the model on Wayland.
+    let matches = |name: &str| {
+        name.eq_ignore_ascii_case("all") || monitor.model().is_some_and(|model| model == name)
+    };
+
+    if selection.is_array() {
+        return selection.members().any(|name| matches(&name.to_string()));
     }
 
-    // If the position was valid, return the result.
-    [
-        (Edge::Left, expand_left),
-        (Edge::Right, expand_right),
-        (Edge::Top, pos.eq_ignore_ascii_case("Top") || pos.is_empty()),
-        (Edge::Bottom, pos.eq_ignore_ascii_case("Bottom")),
-    ]
+    matches(&selection.to_string())
 }
 
-/// Initializes the status bar.
-fn activate(application: &Application) {
+/// Checks whether there's a display to draw the bar on, through `WAYLAND_DISPLAY` or `DISPLAY`.
+fn has_display() -> bool {
+    ["WAYLAND_DISPLAY", "DISPLAY"]
+        .iter()
+        .any(|variable| !environment::try_get_var(variable, "").is_empty())
+}
+
+/// Creates a bar window on `monitor`, then builds all the widgets on it.
+fn create_bar(application: &Application, monitor: &Monitor) -> ApplicationWindow {
     // Create a normal GTK window however you like
     let window = ApplicationWindow::new(application);
     window.connect_screen_changed(set_visual);
     window.connect_draw(draw);
+    // 0.3.5: Stop updating while the bar isn't visible.
+    pause::track(&window);
 
     // Initialize layer shell before the window has been fully initialized.
     gtk_layer_shell::init_for_window(&window);
@@ -86,82 +312,150 @@ fn activate(application: &Application) {
 
     // Push other windows out of the way
     // Toggling this off may help some if they are in applications that have weird unicode text, which may mess with the bars scaling.
-    gtk_layer_shell::auto_exclusive_zone_enable(&window);
+    // 0.3.5: With `exclusive` set to `false`, the bar overlays windows instead.
+    if config::parsed_config().hybrid.exclusive.unwrap_or(true) {
+        gtk_layer_shell::auto_exclusive_zone_enable(&window);
+    } else {
+        gtk_layer_shell::set_exclusive_zone(&window, 0);
+    }
 
-    for (anchor, state) in get_anchors() {
+    // 0.3.5: Pixel sizes in the config are scaled up on high DPI monitors, on top of GDK's own
+    //   scaling. Set before anything is sized, as the widgets are built with it too.
+    scale::set(scale::for_monitor(monitor));
+
+    // 0.3.5: Allow for sizing the bar in pixels, or relative to the monitor.
+    let geometry = monitor.geometry();
+    let width = get_size("width", geometry.width());
+    let height = get_size("height", geometry.height());
+    window.set_size_request(width.unwrap_or(-1), height.unwrap_or(-1));
+
+    let is_sized = if config::get_position().is_vertical() {
+        height.is_some()
+    } else {
+        width.is_some()
+    };
+
+    for (anchor, state) in get_anchors(is_sized) {
         gtk_layer_shell::set_anchor(&window, anchor, state);
     }
 
+    // 0.3.5: Allow for gaps between the bar and the screen edges, for floating bars.
+    for (edge, key) in [
+        (Edge::Top, "margin_top"),
+        (Edge::Bottom, "margin_bottom"),
+        (Edge::Left, "margin_left"),
+        (Edge::Right, "margin_right"),
+    ] {
+        // Negative margins are allowed for overlap effects, but only so far off-screen.
+        let margin = config::get_clamped(
+            "hybrid",
+            key,
+            0,
+            -config::MAX_NEGATIVE_BAR_MARGIN,
+            config::MAX_PIXELS,
+        );
+        gtk_layer_shell::set_margin(&window, edge, scale::px(margin));
+    }
+
     // Allows for writing in input fields if the value is true.
     // This is false by default since it's stealing focus until you focus a different application,
     // which may trigger some users.
-    if let Some(c_allow_keyboard) = config::try_get("hybrid", "allow_keyboard", true, false) {
-        gtk_layer_shell::set_keyboard_interactivity(&window, c_allow_keyboard.0 == "true");
+    if let Some(c_allow_keyboard) = config::parsed_config().hybrid.allow_keyboard {
+        gtk_layer_shell::set_keyboard_interactivity(&window, c_allow_keyboard);
     }
 
-    // Initialize gdk::Display by default value, which is decided by the compositor.
-    let display = Display::default().expect("[ERROR] Could not get default display, is your compositor doing okay?\n");
-
-    // Loads the monitor variable from config, default is 0.
-    let config_monitor = config::try_get("hybrid", "monitor", false, false)
-        .unwrap_or_else(|| (String::default(), 0));
-
-    // Gets the actual gdk::Monitor from configured number.
-    let monitor = display
-        .monitor(config_monitor.1)
-        .expect("[ERROR] Could not find monitor.\n");
-
     // Sets which monitor should be used for the bar.
-    gtk_layer_shell::set_monitor(&window, &monitor);
+    gtk_layer_shell::set_monitor(&window, monitor);
 
     // For transparency to work.
     window.set_app_paintable(true);
 
     // Build all the widgets.
     ui::build_widgets(&window);
-    log!("Ready!");
+    window
 }
 
-/// Loads the CSS
-pub fn load_css() {
-    let provider = CssProvider::new();
-    // 0.2.8: Allow for defining the name of the stylesheet to look up
-    let mut css_file = String::from("style.css");
-    if let Some(c_css_file) = config::try_get("hybrid", "stylesheet", true, false) {
-        css_file = c_css_file.0
+/// Called upon application startup.
+#[tokio::main]
+async fn main() {
+    logging::init();
+    if cli::ARGS.dump_schema {
+        check::dump_schema()
     }
 
-    let mut css_path = config::get_path();
-    css_path.push_str(&css_file);
+    log::debug!("Caching config...");
+    if let Some(config_path) = &cli::ARGS.config {
+        if !config::is_stdin() && !std::path::Path::new(config_path).exists() {
+            cli::exit_with_error(&format!("The config file '{config_path}' doesn't exist"))
+        }
+    }
 
-    provider
-        .load_from_path(&css_path)
-        .unwrap_or_else(|_| panic!("[ERROR] Failed loading CSS from '{css_file}'!\n"));
+    if cli::ARGS.check {
+        check::run()
+    }
 
-    // Add the provider to the default screen
-    StyleContext::add_provider_for_screen(
-        &Screen::default().expect("[ERROR] Couldn't find any valid displays!\n"),
-        &provider,
-        STYLE_PROVIDER_PRIORITY_APPLICATION,
-    );
-}
+    // Not fatal, as the built-in config is used when there's no config file.
+    if let Err(err) = config::create_default_if_missing() {
+        log::warn!("{err}")
+    }
 
-/// Called upon application startup.
-#[tokio::main]
-async fn main() {
-    log!("Caching config...");
-    config::cache();
-    log!("Building application...");
+    if let Err(err) = config::cache() {
+        cli::exit_with_error(&err.to_string())
+    }
+
+    // 0.3.5: Set up whatever the bar relies on before anything is shown.
+    hooks::run("on_startup");
+
+    // 0.3.5: Print the widgets for other bars instead of drawing our own.
+    if cli::ARGS.i3bar {
+        i3bar::run()
+    }
+
+    // 0.3.5: Fail early with a clear message, rather than somewhere deep inside of GTK.
+    if !has_display() {
+        cli::exit_with_error(
+            "No display found, neither WAYLAND_DISPLAY nor DISPLAY is set. Use --check to \
+             validate the config, or --i3bar to print the widgets without a display",
+        )
+    }
+
+    // The config file is watched off the main thread, so it's reloaded through the main loop.
+    let (reload_sender, reload_receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    reload_receiver.attach(None, |_| {
+        // Events such as permission changes don't touch the contents, so skip re-parsing.
+        let _ = ui::reload("the config file", true);
+        glib::Continue(true)
+    });
+
+    log::debug!("Starting config watcher...");
+    watcher::watch_config(reload_sender);
+    log::debug!("Building application...");
     let application = Application::new(None, ApplicationFlags::default());
-    log!("Loading CSS...");
-    application.connect_startup(|_| load_css());
-    log!("Creating viewport...");
+    log::debug!("Loading CSS...");
+    application.connect_startup(|_| {
+        // 0.3.5: Before the stylesheet, so it's applied on top of the GTK theme right away.
+        theme::apply();
+        stylesheet::load();
+    });
+    log::debug!("Creating viewport...");
     // Activate the layer shell.
     application.connect_activate(|app| {
         activate(app);
     });
 
-    application.run();
+    // Don't let GTK parse the arguments, as it doesn't know about ours.
+    application.run_with_args::<&str>(&[]);
+    // 0.3.5: Only reached once the application has quit, for example from a `SIGTERM`. Running
+    //   commands are stopped so nothing is left orphaned, which lets the worker threads running
+    //   them finish. The async tasks and their children are then dropped along with the runtime.
+    command::terminate_children();
+    let running = command::join_workers(SHUTDOWN_TIMEOUT);
+    if running != 0 {
+        log::warn!("{running} worker thread(s) didn't finish in time, leaving them behind");
+    }
+
+    control::remove_socket();
+    log::info!("Bye!");
 }
 
 /// Applies custom visuals.
@@ -173,34 +467,125 @@ fn set_visual(window: &ApplicationWindow, screen: Option<&Screen>) {
     }
 }
 
-/// Converts the value of a child inside `background` to a `f64`.
-fn get_background_float(cfg: &JsonValue, identifier: &str, from_255: bool) -> f64 {
-    let mut res = cfg["hybrid"][identifier]
-        .as_f64()
-        .unwrap_or_else(|| panic!("[ERROR] Failed converting hybrid:{identifier} to f64!\n"));
+/// The background color of the bar when the config doesn't have a valid one, the same as that of
+/// the default config.
+const DEFAULT_BACKGROUND: (f64, f64, f64, f64) = (10.0 / 255.0, 10.0 / 255.0, 10.0 / 255.0, 1.0);
+
+/// Has the invalid background color been warned about?
+static WARNED_BACKGROUND: AtomicBool = AtomicBool::new(false);
+
+/// Converts the value of a child inside `hybrid` to a `f64`.
+/// `None` if it's missing or isn't a number.
+fn get_background_float(hybrid: &JsonValue, identifier: &str, from_255: bool) -> Option<f64> {
+    let mut res = config::as_f64(&hybrid[identifier])?;
 
     // Only divide by 255 if explicitly told to.
     if from_255 {
         res /= 255.0;
     }
 
-    // Return the result.
-    res
+    // Return the result, anything outside of 0.0-1.0 isn't a valid color component.
+    Some(math::clamp_f64(res, 0.0, 1.0))
+}
+
+/// Gets the size from `hybrid` -> `key`, either in pixels or as a percentage of `monitor_size`
+/// like `"80%"`. `None` if it's unset or malformed, in which case the bar spans the monitor.
+fn get_size(key: &str, monitor_size: i32) -> Option<i32> {
+    let value = config::with_config(|config| config["hybrid"][key].clone());
+    if value.is_null() {
+        return None;
+    }
+
+    let size = match value
+        .as_str()
+        .and_then(|value| value.trim().strip_suffix('%'))
+    {
+        Some(percentage) => percentage
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|percentage| *percentage > 0.0 && *percentage <= 100.0)
+            .map(|percentage| (monitor_size as f64 * percentage / 100.0).round() as i32),
+        // Never larger than the monitor, which would push part of the bar off-screen.
+        None => config::as_i32(&value)
+            .filter(|size| *size > 0)
+            .map(|size| scale::px(size).min(monitor_size)),
+    };
+
+    if size.is_none() {
+        log::warn!(
+            "hybrid:{key} '{value}' is invalid, expected pixels or a percentage like \"80%\". \
+             Spanning the whole monitor instead"
+        );
+    }
+
+    size
+}
+
+/// Gets the background color of the bar as `0.0-1.0` components.
+/// 0.3.5: `hybrid` -> `background` as a hex color like `#1e1e2ecc` takes precedence over `r`, `g`,
+///   `b` and `a`. `opacity` then overrides the alpha of either, clamped to `0.0-1.0`.
+fn get_background(hybrid: &JsonValue) -> (f64, f64, f64, f64) {
+    let (r, g, b, a) = match hybrid["background"].as_str().and_then(color::parse_hex) {
+        Some((r, g, b, a)) => (
+            r as f64 / 255.0,
+            g as f64 / 255.0,
+            b as f64 / 255.0,
+            a as f64 / 255.0,
+        ),
+        None => match (
+            get_background_float(hybrid, "r", true),
+            get_background_float(hybrid, "g", true),
+            get_background_float(hybrid, "b", true),
+            get_background_float(hybrid, "a", false),
+        ) {
+            (Some(r), Some(g), Some(b), Some(a)) => (r, g, b, a),
+            // This runs on every redraw, so it's only warned about once.
+            _ => {
+                if !WARNED_BACKGROUND.swap(true, Ordering::Relaxed) {
+                    log::warn!(
+                        "hybrid:background isn't a valid hex color and hybrid:r, g, b and a \
+                         aren't all set, using the default background color"
+                    );
+                }
+
+                DEFAULT_BACKGROUND
+            }
+        },
+    };
+
+    let a =
+        config::as_f64(&hybrid["opacity"]).map_or(a, |opacity| math::clamp_f64(opacity, 0.0, 1.0));
+    (r, g, b, a)
 }
 
 /// Draws the window using a custom color and opacity.
 fn draw(_: &ApplicationWindow, ctx: &cairo::Context) -> Inhibit {
-    let cfg = config::CONFIG.read().unwrap();
-
-    // Fetch config for the values.
-    let r = get_background_float(&cfg, "r", true);
-    let g = get_background_float(&cfg, "g", true);
-    let b = get_background_float(&cfg, "b", true);
-    let a = get_background_float(&cfg, "a", false);
+    // Fetch config for the values, this runs on every redraw so the config is only locked once.
+    let (r, g, b, a) = get_background(&config::read_config_cached()["hybrid"]);
 
     // Apply
     ctx.set_source_rgba(r, g, b, a);
     ctx.set_operator(cairo::Operator::Screen);
-    ctx.paint().expect("[ERROR] Failed painting!\n");
+    ctx.paint().expect("Failed painting!");
     Inhibit(false)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn background_colors() {
+        let hybrid = json::object! { background: "#ff000080", r: 10, g: 10, b: 10, a: 1.0 };
+        assert_eq!(get_background(&hybrid), (1.0, 0.0, 0.0, 128.0 / 255.0));
+        let hybrid = json::object! { r: 255, g: 0, b: 0, a: 2.0, opacity: 0.5 };
+        assert_eq!(get_background(&hybrid), (1.0, 0.0, 0.0, 0.5));
+
+        // Falls back to the default rather than panicking while drawing.
+        let hybrid = json::object! { background: "not a color" };
+        assert_eq!(get_background(&hybrid), DEFAULT_BACKGROUND);
+        let hybrid = json::object! { background: "#zzzzzz", r: 255 };
+        assert_eq!(get_background(&hybrid), DEFAULT_BACKGROUND);
+    }
+}
@@ -0,0 +1,372 @@
+use crate::config;
+use serde::{Deserialize, Deserializer};
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+/// The whole config, deserialized into typed fields.
+/// Unknown keys are ignored, use `check::validate` for reporting those.
+// 0.3.5: Opt-in alternative to `config::try_get`, which is kept for backwards compatibility.
+//   Not every field is read yet, the ones that are can be found through `config::parsed_config`.
+#[allow(dead_code)]
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct HybridConfig {
+    /// The `hybrid` section.
+    pub hybrid: BarConfig,
+    /// The `variables` section, values aren't expanded.
+    pub variables: HashMap<String, serde_json::Value>,
+    /// The `computed` section, resolved into variables once the config is loaded.
+    pub computed: HashMap<String, serde_json::Value>,
+    /// The `sources` section, commands printing JSON that several widgets show fields of.
+    pub sources: HashMap<String, serde_json::Value>,
+    /// The `templates` section, already applied to the widgets using them.
+    pub templates: HashMap<String, serde_json::Value>,
+    /// Every other top-level block, keyed by its name (for example `left-label_username`).
+    #[serde(flatten)]
+    pub widgets: HashMap<String, WidgetConfig>,
+}
+
+/// The `hybrid` section of the config.
+#[allow(dead_code)]
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct BarConfig {
+    #[serde(deserialize_with = "lenient_update_rate")]
+    pub update_rate: Option<i32>,
+    #[serde(deserialize_with = "lenient_update_rate")]
+    pub update_rate_on_battery: Option<i32>,
+    #[serde(deserialize_with = "lenient_f64")]
+    pub r: f64,
+    #[serde(deserialize_with = "lenient_f64")]
+    pub g: f64,
+    #[serde(deserialize_with = "lenient_f64")]
+    pub b: f64,
+    #[serde(deserialize_with = "lenient_f64")]
+    pub a: f64,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub expand_left: Option<bool>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub expand_right: Option<bool>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub position: Option<String>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub allow_keyboard: Option<bool>,
+    /// `"all"`, a connector name, an array of connector names or a monitor index.
+    pub monitor: Option<serde_json::Value>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub stylesheet: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub gtk_theme: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub icon_theme: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub cava_sed: Option<String>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub cava_bars: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub cava_framerate: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub fps: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub command_timeout: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub margin_top: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub margin_bottom: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub margin_left: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub margin_right: Option<i32>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub exclusive: Option<bool>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub thousands_separator: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub default_format: Option<String>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub variables_ignore_case: Option<bool>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub bare_variables: Option<bool>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub error_widget: Option<String>,
+    /// A command or an array of commands, see `hooks::get_commands`.
+    pub on_startup: Option<serde_json::Value>,
+    /// Same as `on_startup`.
+    pub on_reload: Option<serde_json::Value>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub background: Option<String>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub opacity: Option<f64>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub spacing: Option<i32>,
+    /// Pixels, or a percentage of the monitor like `"80%"`.
+    pub width: Option<serde_json::Value>,
+    pub height: Option<serde_json::Value>,
+    /// `false` to turn scaling off, a fixed factor, or `true` to compute it from the monitor.
+    pub scale: Option<serde_json::Value>,
+}
+
+/// A single widget block. Which keys are used depends on the type of widget.
+#[allow(dead_code)]
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct WidgetConfig {
+    #[serde(deserialize_with = "lenient_string")]
+    pub text: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub command: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub tooltip: Option<String>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub listen: Option<bool>,
+
+    #[serde(deserialize_with = "lenient_update_rate")]
+    pub update_rate: Option<i32>,
+    #[serde(deserialize_with = "lenient_update_rate")]
+    pub update_rate_on_battery: Option<i32>,
+    /// Triggers like `"tick"`, `"focus"` or `"signal:USR2"`, see `triggers::parse`.
+    pub update_on: Option<serde_json::Value>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub cache_ttl: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub command_timeout: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub retries: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub retry_delay: Option<i32>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub show_errors: Option<bool>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub spacing_start: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub spacing_end: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub width: Option<i32>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub format: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub timezone: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub device: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub fallback: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub player: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub sensor: Option<String>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub warning_threshold: Option<i32>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub path: Option<String>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub base: Option<i32>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub interface: Option<String>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub step: Option<i32>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub set_command: Option<String>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub icon_size: Option<i32>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub icon: Option<String>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub size: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub height: Option<i32>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub draw: Option<String>,
+    /// The widget a graph samples, or the field of a source a label or graph shows, like
+    /// `weather.temp`.
+    #[serde(deserialize_with = "lenient_string")]
+    pub source: Option<String>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub samples: Option<i32>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub style: Option<String>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub hide_missing: Option<bool>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub trim: Option<bool>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub markup: Option<bool>,
+    /// Steps like `{ "divide": 1024 }` applied to the command output, see `transform::parse`.
+    pub transform: Option<serde_json::Value>,
+    /// Maps numeric values to `{icon}`, like `[{ "max": 20, "text": "low" }]`, see `Ranges::parse`.
+    pub ranges: Option<serde_json::Value>,
+    /// The keys of the widgets inside a group.
+    pub widgets: Option<serde_json::Value>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub spacing: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub value: Option<f64>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub min: Option<f64>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub max: Option<f64>,
+    /// How many milliseconds labels, graphs and progress bars take to move to new values.
+    #[serde(deserialize_with = "lenient_number")]
+    pub transition_ms: Option<i32>,
+    /// Maps keyboard layout names to what should be shown instead.
+    pub names: HashMap<String, String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub on_click: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub on_middle_click: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub on_right_click: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub on_scroll_up: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub on_scroll_down: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub tooltip_command: Option<String>,
+    /// A string of whitespace-separated classes, or an array of classes.
+    pub class: Option<serde_json::Value>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub visible_command: Option<String>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub export_variables: Option<bool>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub enabled: Option<bool>,
+    /// Overrides the widget name from the widget identifier.
+    #[serde(deserialize_with = "lenient_string")]
+    pub id: Option<String>,
+    /// Unresolved template, it's removed from every widget it was applied to.
+    #[serde(deserialize_with = "lenient_string")]
+    pub template: Option<String>,
+    /// Overrides the alignment from the widget identifier.
+    #[serde(deserialize_with = "lenient_string")]
+    pub align: Option<String>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub order: Option<i32>,
+    /// A Pango font description, like `JetBrains Mono Bold 12`.
+    #[serde(deserialize_with = "lenient_string")]
+    pub font: Option<String>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub font_size: Option<f64>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub max_length: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub max_width: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub min_width: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub width_chars: Option<i32>,
+    /// `left`, `center` or `right`, see `overflow::parse_text_align`.
+    #[serde(deserialize_with = "lenient_string")]
+    pub text_align: Option<String>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub scroll: Option<bool>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub scroll_speed: Option<f64>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub margin_top: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub margin_bottom: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub margin_left: Option<i32>,
+    #[serde(deserialize_with = "lenient_number")]
+    pub margin_right: Option<i32>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub expand: Option<bool>,
+    #[serde(deserialize_with = "lenient_bool")]
+    pub fill: Option<bool>,
+    /// A hex color, like `#ff0000`.
+    #[serde(deserialize_with = "lenient_string")]
+    pub color: Option<String>,
+    #[serde(deserialize_with = "lenient_string")]
+    pub background: Option<String>,
+}
+
+/// Deserializes a bool the same way `config::try_get_bool` reads it, so `"true"` and `"false"`
+/// are accepted too.
+fn lenient_bool<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<bool>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Bool {
+        Bool(bool),
+        String(String),
+    }
+
+    match Option::<Bool>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Bool::Bool(bool)) => Ok(Some(bool)),
+        Some(Bool::String(string)) => match string.as_str() {
+            "true" => Ok(Some(true)),
+            "false" => Ok(Some(false)),
+            _ => Err(serde::de::Error::custom(format!(
+                "expected a bool, found '{string}'"
+            ))),
+        },
+    }
+}
+
+/// Deserializes a number the same way `config::as_i32` and `config::as_f64` read it, so numeric
+/// strings like `"100"` are accepted too.
+fn lenient_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Number<T> {
+        Number(T),
+        String(String),
+    }
+
+    match Option::<Number<T>>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Number::Number(number)) => Ok(Some(number)),
+        Some(Number::String(string)) => string.trim().parse().map(Some).map_err(|err| {
+            serde::de::Error::custom(format!("expected a number, found '{string}': {err}"))
+        }),
+    }
+}
+
+/// Same as `lenient_number`, but strings are read by `config::parse_update_rate`, so they may
+/// have a unit and `config::UPDATE_ONCE` is read as `-1`.
+fn lenient_update_rate<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<i32>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum UpdateRate {
+        Number(i32),
+        String(String),
+    }
+
+    match Option::<UpdateRate>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(UpdateRate::Number(number)) => Ok(Some(number)),
+        Some(UpdateRate::String(string)) => config::parse_update_rate(&string.as_str().into())
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+/// Same as `lenient_number`, for numbers that default to `0.0` when missing.
+fn lenient_f64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    lenient_number(deserializer).map(Option::unwrap_or_default)
+}
+
+/// Deserializes a string the same way `config::try_get` reads it, so numbers and bools are
+/// stringified.
+fn lenient_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Text {
+        String(String),
+        Number(serde_json::Number),
+        Bool(bool),
+    }
+
+    Ok(
+        Option::<Text>::deserialize(deserializer)?.map(|text| match text {
+            Text::String(string) => string,
+            Text::Number(number) => number.to_string(),
+            Text::Bool(bool) => bool.to_string(),
+        }),
+    )
+}
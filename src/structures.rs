@@ -3,9 +3,51 @@ use strum::EnumString;
 /// Widget alignment.
 // Allow for uppercase enum namings here.
 #[allow(clippy::upper_case_acronyms)]
-#[derive(EnumString)]
+#[derive(EnumString, Clone, Copy)]
 pub enum Align {
     LEFT,
+    #[strum(serialize = "CENTERED", serialize = "CENTER")]
     CENTERED,
     RIGHT,
 }
+
+/// Which edge of the screen the bar is placed on.
+// Allow for uppercase enum namings here.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(EnumString, Clone, Copy, PartialEq, Eq, Debug)]
+#[strum(ascii_case_insensitive)]
+pub enum Position {
+    TOP,
+    BOTTOM,
+    LEFT,
+    RIGHT,
+}
+
+impl Position {
+    /// Checks if the bar runs along the left or right edge, meaning widgets are laid out
+    /// vertically.
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Position::LEFT | Position::RIGHT)
+    }
+}
+
+/// A workspace, as reported by the compositor.
+#[derive(Clone, Debug, Default)]
+pub struct Workspace {
+    /// The workspace number, negative for named and special workspaces.
+    pub id: i64,
+    pub name: String,
+    pub focused: bool,
+    pub visible: bool,
+    pub urgent: bool,
+    /// Whether there are any windows on it.
+    pub occupied: bool,
+}
+
+/// The focused window, as reported by the compositor.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Window {
+    pub title: String,
+    /// The Wayland app id, or the window class for XWayland and i3 windows.
+    pub app_id: String,
+}
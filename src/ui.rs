@@ -1,18 +1,86 @@
 use crate::{
-    box_widget::BoxWidget, button_widget::ButtonWidget, cava_widget::CavaWidget,
-    cmd_widget::CmdWidget, r#loop::update, spacing_widget::SpacingWidget, structures::Align, *,
+    battery_widget::{self, BatteryWidget},
+    box_widget::BoxWidget,
+    brightness_widget::{self, BrightnessWidget},
+    button_widget::ButtonWidget,
+    canvas_widget::{self, CanvasWidget},
+    cava_widget::CavaWidget,
+    clock_widget::{self, ClockWidget},
+    cmd_widget::CmdWidget,
+    cpu_widget::{self, CpuWidget},
+    disk_widget::{self, DiskWidget},
+    graph_widget::{self, GraphWidget},
+    group_widget::{self, GroupWidget},
+    image_widget::{self, ImageWidget},
+    keyboard_widget::{self, KeyboardWidget},
+    media_widget::{self, MediaWidget},
+    memory_widget::{self, MemoryWidget},
+    network_widget::{self, NetworkWidget},
+    progress_widget::{self, ProgressWidget},
+    r#loop::update,
+    ranges::Ranges,
+    script_widget::ScriptWidget,
+    separator_widget::SeparatorWidget,
+    spacing_widget::SpacingWidget,
+    structures::Align,
+    temperature_widget::{self, TemperatureWidget},
+    timer_widget::{self, TimerWidget},
+    transition::Transition,
+    tray_widget::{self, TrayWidget},
+    uptime_widget::{self, UptimeWidget},
+    volume_widget::{self, VolumeWidget},
+    window_widget::{self, WindowWidget},
+    workspaces_widget::WorkspacesWidget,
+    *,
 };
 use gtk::traits::*;
 use heapless::Vec;
-use std::{str::FromStr, sync::Mutex};
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 lazy_static! {
-    /// Holds all the dynamic label widgets.
-    pub static ref VEC: Mutex<Vec<LabelWidget, 1024>> = Mutex::new(Vec::new());
+    /// Holds all the dynamic label widgets, by an id that stays the same while they're on a bar.
+    pub static ref VEC: Mutex<Vec<(usize, LabelWidget), 1024>> = Mutex::new(Vec::new());
 
-    /// All active cava label instances.
+    /// All active cava label instances, by an id like the labels.
     // This will be moved to `cava.rs` soon.
-    pub static ref CAVA_INSTANCES: Mutex<Vec<CavaWidget, 8>> = Mutex::new(Vec::new());
+    pub static ref CAVA_INSTANCES: Mutex<Vec<(usize, CavaWidget), 8>> = Mutex::new(Vec::new());
+
+    /// When the config was last reloaded, see `reload`.
+    static ref LAST_RELOAD: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+/// Forced reloads within this long of the previous reload are skipped, as a `SIGUSR1` and the
+/// control socket firing together would otherwise rebuild the bar back-to-back.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Has Cava been started?
+static HAS_STARTED_CAVA: AtomicBool = AtomicBool::new(false);
+
+/// Hands out the ids in `VEC` and `CAVA_INSTANCES`.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Adds `widget` to `widgets` under a new id, which is handed back if `widgets` is full.
+/// 0.3.5: It's removed again once the widget being built is torn down, like when its bar is, so
+///   hot-plugging monitors doesn't fill `widgets` up.
+pub fn register<W: 'static, const N: usize>(
+    widgets: &'static Mutex<Vec<(usize, W), N>>,
+    widget: W,
+) -> Result<(), W> {
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    locks::lock(widgets)
+        .push((id, widget))
+        .map_err(|(_, widget)| widget)?;
+    registry::on_teardown(move || locks::lock(widgets).retain(|(other, _)| *other != id));
+    Ok(())
 }
 
 /// Adds and aligns the specified widget.
@@ -33,10 +101,25 @@ pub fn add_and_align(
 /// Builds all of the widgets.
 pub fn build_widgets(window: &ApplicationWindow) {
     // Create box widgets, which we'll be using to draw the content onto.
-    let root = Box::new(Orientation::Horizontal, 0);
-    let left = Box::new(Orientation::Horizontal, 0);
-    let centered = Box::new(Orientation::Horizontal, 0);
-    let right = Box::new(Orientation::Horizontal, 0);
+    // 0.3.5: Bars on the left or right edge lay their widgets out vertically.
+    let orientation = if config::get_position().is_vertical() {
+        Orientation::Vertical
+    } else {
+        Orientation::Horizontal
+    };
+
+    // 0.3.5: Gap between the widgets within each region, from `hybrid` -> `spacing`.
+    let spacing = scale::px(config::get_clamped(
+        "hybrid",
+        "spacing",
+        0,
+        0,
+        config::MAX_PIXELS,
+    ));
+    let root = Box::new(orientation, 0);
+    let left = Box::new(orientation, spacing);
+    let centered = Box::new(orientation, spacing);
+    let right = Box::new(orientation, spacing);
 
     // 0.2.5: Root expands across the entire bar, previously "left" would do this but it isn't
     //   ideal when customizing, since borders would draw on the entire bar rather than just on the
@@ -58,81 +141,496 @@ pub fn build_widgets(window: &ApplicationWindow) {
     // Add root to the main canvas before finally adding all the widgets and drawing it.
     window.add(&root);
 
+    // 0.3.5: Everything the widgets of the bar have added is torn down along with it, like when
+    //   its monitor is unplugged.
+    let bar = registry::new_bar();
+    window.connect_destroy(move |_| {
+        BARS.with(|bars| bars.borrow_mut().retain(|other| other.id != bar));
+        let torn_down = registry::tear_down(|owner| owner.bar == bar);
+        registry::forget_carried(bar);
+        log::debug!("Bar {bar} was destroyed, ran {torn_down} teardowns of its widgets");
+    });
+
     // Prepare all of the widgets.
-    create_components(&left, &centered, &right);
+    let regions = [left, centered, right];
+    let widgets = get_blueprints()
+        .into_iter()
+        .map(|blueprint| create_built(bar, blueprint, &regions))
+        .collect();
+    BARS.with(|bars| {
+        bars.borrow_mut().push(Bar {
+            id: bar,
+            regions,
+            scale: scale::get(),
+            widgets,
+        })
+    });
+
     // Make every widget visible.
     window.show_all();
     // Update dynamic content.
     update();
 }
 
+/// Refreshes everything that's read from the config on the fly, after it has been reloaded.
+/// 0.3.5: Along with the widgets that have been added, changed or removed.
+pub fn refresh() {
+    theme::apply();
+    stylesheet::reload();
+    rebuild_changed();
+    // Redraw so values read on the fly, like the background color, are refreshed.
+    for window in gtk::Window::list_toplevels() {
+        window.queue_draw();
+    }
+}
+
+/// Reloads the config and refreshes the bar with it, keeping the old config if it can't be loaded.
+/// With `if_changed` it's only reloaded if the file has been modified since it was last cached.
+/// `source` is what asked for the reload, for the logs.
+/// NOTE: Refreshing touches GTK, so this has to be called from the main loop.
+pub fn reload(source: &str, if_changed: bool) -> Result<(), config::ConfigError> {
+    // Changes to the file still go through, the watcher already coalesces those.
+    if !if_changed && is_debounced(*locks::lock(&LAST_RELOAD), Instant::now()) {
+        log::debug!("Skipping the reload from {source}, the config was just reloaded");
+        return Ok(());
+    }
+
+    let result = if if_changed {
+        config::cache_if_changed()
+    } else {
+        config::reload().map(|_| true)
+    };
+
+    match result {
+        Ok(true) => {
+            log::debug!("Config reloaded from {source}");
+            *locks::lock(&LAST_RELOAD) = Some(Instant::now());
+            refresh();
+        }
+        Ok(false) => {}
+        Err(err) => {
+            log::warn!("{err}, keeping the old config");
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks whether a reload at `now` would be too soon after the `last` one.
+fn is_debounced(last: Option<Instant>, now: Instant) -> bool {
+    last.is_some_and(|last| now.saturating_duration_since(last) < RELOAD_DEBOUNCE)
+}
+
 /// Gets the values for `text`, `command` and `tooltip`.
 /// If one is left unspecified, the value is `"", 0`, a.k.a default.
-fn get_base_keys(root: &str) -> (String, String, String) {
+pub fn get_base_keys(root: &str) -> (String, String, String) {
     let text = config::get_or_default(root, "text", true, true).0;
     let command = config::get_or_default(root, "command", true, true).0;
     let tooltip = config::get_or_default(root, "tooltip", true, true).0;
     (text, command, tooltip)
 }
 
-/// Creates all of the widgets.
-fn create_components(left: &Box, centered: &Box, right: &Box) {
-    // Add all of the widgets defined from the config.
-    const ALIGNMENT: char = '-';
-    const SEPARATOR: &str = "_";
-    let mut has_started_cava = false;
-    for (key, _) in config::CONFIG.read().unwrap().entries() {
-        if !key.contains(ALIGNMENT) || !key.contains(SEPARATOR) {
+/// Separates the alignment from the widget type in a widget key, like in `left-label_name`.
+pub const ALIGNMENT: char = '-';
+
+/// Separates the widget type from the widget name in a widget key.
+pub const SEPARATOR: &str = "_";
+
+/// CSS class of the placeholders added in place of widgets with an unknown type.
+const UNKNOWN_CLASS: &str = "unknown";
+
+/// Gets the name of the widget at `key`, which is its `id` if set and otherwise the part of the
+/// key after the widget type, like `ABC` in `left-label_ABC`.
+/// 0.3.5: The name is used for CSS selectors and `{name.value}`, so `id` lets widgets be renamed
+///   without touching the stylesheet.
+pub fn get_widget_name(key: &str) -> String {
+    config::try_get(key, "id", true, false).map_or_else(
+        || {
+            key.split_once(SEPARATOR)
+                .map_or_else(String::default, |(_, name)| name.to_string())
+        },
+        |id| id.0,
+    )
+}
+
+/// Gets the keys of all widgets in the config.
+/// 0.3.5: Sorted by their `order`, which is how they're laid out within each region. The sort is
+///   stable, so the declaration order is kept for widgets with the same order.
+pub fn get_widget_keys() -> std::vec::Vec<String> {
+    // Collect the keys first, as creating the widgets reads the config again.
+    let mut keys: std::vec::Vec<String> = config::with_config(|config| {
+        config
+            .entries()
+            .map(|(key, _)| key.to_string())
+            .filter(|key| key.contains(ALIGNMENT) && key.contains(SEPARATOR))
+            .collect()
+    });
+
+    keys.sort_by_key(|key| config::get_or_default(key, "order", false, false).1);
+    keys
+}
+
+/// Gets the alignment of the widget at `key`, with `widget_alignment` from its identifier in
+/// uppercase.
+/// 0.3.5: The `align` key takes priority over the alignment in the widget identifier.
+pub fn get_alignment(key: &str, widget_alignment: &str) -> Align {
+    match config::try_get(key, "align", true, false) {
+        Some((align, _)) => Align::from_str(&align.to_uppercase())
+            .unwrap_or_else(|_| panic!("{key}: Invalid align '{align}'!")),
+        None => Align::from_str(widget_alignment).expect("Invalid widget alignment!"),
+    }
+}
+
+/// A widget as far as reloads are concerned.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Blueprint {
+    /// What reloads tell widgets apart by, their `id` or their key if they don't have one.
+    pub identity: String,
+    pub key: String,
+    /// What it's built from, see `get_fingerprint`.
+    pub fingerprint: JsonValue,
+}
+
+/// A widget that has been built on a bar.
+struct Built {
+    blueprint: Blueprint,
+    /// What it has added to the regions of the bar, usually the widget itself or its event box.
+    placed: std::vec::Vec<Widget>,
+}
+
+/// A bar along with the widgets built on it, so that reloads only rebuild what has changed.
+struct Bar {
+    id: usize,
+    /// The `left`, `centered` and `right` boxes, which the widgets are added to.
+    regions: [Box; 3],
+    /// The scale the widgets have been built with, see `scale::for_monitor`.
+    scale: f64,
+    /// In the order they're laid out in.
+    widgets: std::vec::Vec<Built>,
+}
+
+thread_local! {
+    /// Every bar that hasn't been destroyed yet.
+    static BARS: RefCell<std::vec::Vec<Bar>> = const { RefCell::new(std::vec::Vec::new()) };
+}
+
+/// What a reload does with a widget.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change {
+    /// Builds the widget at the key.
+    Added(String),
+    /// Rebuilds the widget at the previous key at the current one, which differ if it moved.
+    Changed(String, String),
+    /// Removes the widget at the key.
+    Removed(String),
+}
+
+/// Sections of the config which every widget is built with, see `get_fingerprint`.
+const GLOBAL_SECTIONS: &[&str] = &["variables", "computed", "sources", "templates"];
+
+/// Keys of `hybrid` which widgets fall back to or are laid out by while they're built.
+const GLOBAL_SETTINGS: &[&str] = &[
+    "update_rate",
+    "default_format",
+    "fps",
+    "thousands_separator",
+    "command_timeout",
+    "bare_variables",
+    "variables_ignore_case",
+    "spacing",
+    "position",
+];
+
+/// Gets what the widget at `key` in `config` is built from: its key and its block, followed by
+/// those of the widgets inside of it if it's a group. Along with the global sections and
+/// settings, as they're filled in while it's built.
+pub fn get_fingerprint(config: &JsonValue, key: &str) -> JsonValue {
+    let mut fingerprint = JsonValue::new_array();
+    let mut pending = vec![key.to_string()];
+    while let Some(key) = pending.pop() {
+        // Groups containing each other are only built once, see `GroupWidget`.
+        if fingerprint
+            .members()
+            .any(|widget| widget[0] == key.as_str())
+        {
             continue;
         }
 
-        // Gets the widget identifiers.
-        let identifiers = key.split(SEPARATOR).collect::<Vec<&str, 8>>();
+        if group_widget::is_group(&key) {
+            pending.extend(
+                config[key.as_str()]["widgets"]
+                    .members()
+                    .filter_map(|widget| widget.as_str())
+                    .map(String::from),
+            );
+        }
+
+        let block = config[key.as_str()].clone();
+        // Can only fail if it isn't an array.
+        let _ = fingerprint.push(JsonValue::Array(vec![key.into(), block]));
+    }
 
-        // Identifier example: `left-label_ABC` <= `left-label` is the IDENTIFIER, `ABC` is the NAME.
-        let identifier = identifiers[0];
+    for section in GLOBAL_SECTIONS {
+        let _ = fingerprint.push(config[*section].clone());
+    }
 
-        // Grabs widget alignment and widget type from the identifier separated by '-'.
-        let (widget_alignment, widget_type) = identifier
-            .split_once(ALIGNMENT)
-            .expect("[ERROR] Widget should be named as [alignment]-[widget_type]_[name]\n");
+    for setting in GLOBAL_SETTINGS {
+        let _ = fingerprint.push(config["hybrid"][*setting].clone());
+    }
 
-        // Formats the widget alignment.
-        let f_widget_alignment = widget_alignment.to_uppercase();
+    fingerprint
+}
 
-        // Base keys, all being optional.
-        let base_keys = get_base_keys(key);
-        let text = base_keys.0;
-        let command = base_keys.1;
-        let tooltip = base_keys.2;
-        let alignment = structures::Align::from_str(&f_widget_alignment)
-            .expect("[ERROR] Invalid widget alignment!\n");
+/// Gets the blueprints of the widgets which are added to the regions of a bar, in their order.
+/// 0.3.5: Widgets inside of a group are built by the group instead.
+fn get_blueprints() -> std::vec::Vec<Blueprint> {
+    let grouped = group_widget::get_grouped_keys();
+    let keys: std::vec::Vec<String> = get_widget_keys()
+        .into_iter()
+        .filter(|key| !grouped.contains(key))
+        .collect();
+    config::with_config(|config| {
+        keys.into_iter()
+            .map(|key| Blueprint {
+                identity: config[key.as_str()]["id"]
+                    .as_str()
+                    .unwrap_or(&key)
+                    .to_string(),
+                fingerprint: get_fingerprint(config, &key),
+                key,
+            })
+            .collect()
+    })
+}
 
-        // Gets every element after the widget identifier, then appends '_' in between.
-        let widget_name = identifiers[1..].join(SEPARATOR).to_string();
+/// Compares the `previous` widgets of a bar with the `current` ones. Widgets with the same
+/// identity and fingerprint aren't changed, so they're kept along with their state.
+pub fn diff(previous: &[&Blueprint], current: &[Blueprint]) -> std::vec::Vec<Change> {
+    let mut unmatched: std::vec::Vec<&Blueprint> = previous.to_vec();
+    let mut changes = std::vec::Vec::new();
+    for blueprint in current {
+        let Some(index) = unmatched
+            .iter()
+            .position(|other| other.identity == blueprint.identity)
+        else {
+            changes.push(Change::Added(blueprint.key.clone()));
+            continue;
+        };
 
-        if widget_name.is_empty() {
-            // JSON doesn't play nicely with duplicate keys, will probably end up making a custom
-            // format + parser later on.
-            // Closes issue #14.
-            panic!("[ERROR] Found an empty widget name, this is not currently supported!\n")
+        let other = unmatched.remove(index);
+        if other.fingerprint != blueprint.fingerprint {
+            changes.push(Change::Changed(other.key.clone(), blueprint.key.clone()));
         }
+    }
 
-        log!(format!(
-            "Adding widget '{identifier}' with alignment '{f_widget_alignment}'",
-        ));
+    changes.extend(
+        unmatched
+            .into_iter()
+            .map(|other| Change::Removed(other.key.clone())),
+    );
+    changes
+}
 
-        // Add the widget.
-        add_widget(
-            key,
-            (widget_type, widget_name),
-            (text, command, tooltip),
-            alignment,
-            (left, centered, right),
-            identifier,
-            &mut has_started_cava,
-        )
+/// Builds the widget of `blueprint` on `bar`, remembering what it adds to `regions`.
+fn create_built(bar: usize, blueprint: Blueprint, regions: &[Box; 3]) -> Built {
+    let [left, centered, right] = regions;
+    let previous: std::vec::Vec<Widget> = regions
+        .iter()
+        .flat_map(|region| region.children())
+        .collect();
+    let owner = registry::Owner {
+        bar,
+        key: blueprint.key.clone(),
+    };
+    registry::build(owner, || {
+        create_component(&blueprint.key, left, centered, right)
+    });
+    let placed = regions
+        .iter()
+        .flat_map(|region| region.children())
+        .filter(|widget| !previous.contains(widget))
+        .collect();
+    Built { blueprint, placed }
+}
+
+/// 0.3.5: Rebuilds the widgets which have been added, changed or removed on every bar, in place.
+///   The other widgets are kept as they are, along with their state like the CPU usage history.
+fn rebuild_changed() {
+    let current = get_blueprints();
+    let mut has_built = false;
+    BARS.with(|bars| {
+        for bar in bars.borrow_mut().iter_mut() {
+            has_built |= rebuild_bar(bar, &current);
+        }
+    });
+
+    // Starts the Cava loop, if the first Cava widget has just been added.
+    if has_built {
+        update();
+    }
+}
+
+/// Rebuilds the widgets of `bar` which differ from `current`, then lays them out in its order.
+/// Returns whether anything has been built.
+fn rebuild_bar(bar: &mut Bar, current: &[Blueprint]) -> bool {
+    let previous: std::vec::Vec<&Blueprint> =
+        bar.widgets.iter().map(|built| &built.blueprint).collect();
+    let changes = diff(&previous, current);
+    if changes.is_empty() {
+        return false;
+    }
+
+    log::info!(
+        "Reloading the widgets of bar {}: {}",
+        bar.id,
+        changes
+            .iter()
+            .map(|change| match change {
+                Change::Added(key) => format!("'{key}' was added"),
+                Change::Changed(previous, key) if previous != key => {
+                    format!("'{previous}' was moved to '{key}'")
+                }
+                Change::Changed(_, key) => format!("'{key}' was changed"),
+                Change::Removed(key) => format!("'{key}' was removed"),
+            })
+            .collect::<std::vec::Vec<String>>()
+            .join(", ")
+    );
+
+    // Everything that isn't kept is torn down first, so a changed widget never runs twice.
+    let (kept, torn_down): (std::vec::Vec<Built>, std::vec::Vec<Built>) =
+        std::mem::take(&mut bar.widgets)
+            .into_iter()
+            .partition(|built| {
+                !changes.iter().any(|change| match change {
+                    Change::Changed(key, _) | Change::Removed(key) => *key == built.blueprint.key,
+                    Change::Added(_) => false,
+                })
+            });
+    for built in torn_down {
+        let id = bar.id;
+        registry::tear_down(|owner| owner.bar == id && owner.key == built.blueprint.key);
+        for widget in built.placed {
+            // SAFETY: Nothing uses the widget once it has been torn down. Destroying rather than
+            //   removing it also drops the handlers holding on to it.
+            unsafe { widget.destroy() };
+        }
+    }
+
+    // Widgets are scaled to the monitor of their bar while they're built.
+    let scale = scale::get();
+    scale::set(bar.scale);
+    let mut kept = kept
+        .into_iter()
+        .map(Some)
+        .collect::<std::vec::Vec<Option<Built>>>();
+    bar.widgets = current
+        .iter()
+        .map(|blueprint| {
+            kept.iter_mut()
+                .find(|built| {
+                    built
+                        .as_ref()
+                        .is_some_and(|built| built.blueprint.key == blueprint.key)
+                })
+                .and_then(Option::take)
+                .unwrap_or_else(|| {
+                    let built = create_built(bar.id, blueprint.clone(), &bar.regions);
+                    for widget in &built.placed {
+                        widget.show_all();
+                    }
+
+                    built
+                })
+        })
+        .collect();
+    scale::set(scale);
+    // Whatever hasn't been taken over belonged to widgets that have been removed.
+    registry::forget_carried(bar.id);
+
+    // New widgets are added at the end of their region, so everything is put back in order.
+    for region in &bar.regions {
+        let placed = bar.widgets.iter().flat_map(|built| &built.placed);
+        for (position, widget) in placed
+            .filter(|widget| widget.parent().as_ref() == Some(region.upcast_ref()))
+            .enumerate()
+        {
+            region.reorder_child(widget, position as i32);
+        }
+    }
+
+    true
+}
+
+/// Creates the widget at `key`, aligned within `left`, `centered` and `right`.
+/// 0.3.5: Groups pass their own box as all three, so their widgets end up inside of them.
+pub fn create_component(key: &str, left: &Box, centered: &Box, right: &Box) {
+    // Gets the widget identifiers.
+    let identifiers = key.split(SEPARATOR).collect::<Vec<&str, 8>>();
+
+    // Identifier example: `left-label_ABC` <= `left-label` is the IDENTIFIER, `ABC` is the NAME.
+    let identifier = identifiers[0];
+
+    // Grabs widget alignment and widget type from the identifier separated by '-'.
+    let (widget_alignment, widget_type) = identifier
+        .split_once(ALIGNMENT)
+        .expect("Widget should be named as [alignment]-[widget_type]_[name]");
+
+    // Formats the widget alignment.
+    let f_widget_alignment = widget_alignment.to_uppercase();
+
+    // Base keys, all being optional.
+    let base_keys = get_base_keys(key);
+    let text = base_keys.0;
+    let command = base_keys.1;
+    let tooltip = base_keys.2;
+    let alignment = get_alignment(key, &f_widget_alignment);
+
+    let widget_name = get_widget_name(key);
+
+    if widget_name.is_empty() {
+        // JSON doesn't play nicely with duplicate keys, will probably end up making a custom
+        // format + parser later on.
+        // Closes issue #14.
+        panic!("Found an empty widget name, this is not currently supported!")
+    }
+
+    // 0.3.5: Widgets missing what they need would only show up blank, so they're skipped.
+    //   The problem is shown by the error widget, if there is one.
+    //   Script and image widgets can't be made up for by anything else, so Hybrid exits naming
+    //   the key they're missing instead, see `config::require`.
+    let block = config::with_config(|config| config[key].clone());
+    let missing = check::missing_keys(widget_type, &block)
+        .filter(|_| !matches!(widget_type, "script" | "image"));
+    if let Some(missing) = missing {
+        log::warn!("{key}: {missing}, skipping it");
+        errors::report(&widget_name, &missing);
+        return;
     }
+
+    log::debug!("Adding widget '{identifier}' with alignment '{f_widget_alignment}'",);
+
+    // 0.3.5: Timers of the widget run at `update_rate_on_battery` while on battery.
+    pause::set_battery_update_rate(
+        config::get_widget_update_rate_on_battery(key)
+            .map(|on_battery| (config::get_widget_update_rate(key), on_battery)),
+    );
+    // 0.3.5: Timers of the widget are also called on the triggers of its `update_on`.
+    triggers::set(
+        triggers::get(key).map(|update_on| (config::get_widget_update_rate(key), update_on)),
+    );
+
+    // Add the widget.
+    add_widget(
+        key,
+        (widget_type, widget_name),
+        (text, command, tooltip),
+        alignment,
+        (left, centered, right),
+    );
+    pause::set_battery_update_rate(None);
+    triggers::set(None);
 }
 
 /// Add a new widget of specified identifier.
@@ -144,8 +642,6 @@ fn add_widget(
     text_command_tooltip: (String, String, String),
     alignment: Align,
     left_centered_right: (&Box, &Box, &Box),
-    identifier: &str,
-    has_started_cava: &mut bool,
 ) {
     // Extract name and type.
     let widget_type = widget_pkg.0;
@@ -157,22 +653,63 @@ fn add_widget(
     let tooltip = text_command_tooltip.2;
 
     // Extract left, centered and right.
-    let left = left_centered_right.0;
-    let centered = left_centered_right.1;
-    let right = left_centered_right.2;
+    // 0.3.5: Widgets with event commands are added to an event box instead, which is then aligned
+    //   in place of the widget.
+    let event_box = events::create_event_box(key);
+    let (left, centered, right) = match &event_box {
+        Some((_, inner)) => (inner, inner, inner),
+        None => left_centered_right,
+    };
 
     match widget_type {
         "label" => {
+            let source = config::get_or_default(key, "source", true, false).0;
+            // Fields of a source are command output too.
+            let markup = match source.is_empty() {
+                true => label_widget::uses_markup(key, &command),
+                false => label_widget::uses_markup(key, &source),
+            };
             let label = LabelWidget {
                 tooltip,
-                text,
-                command,
                 label: Label::new(None),
-                listen: config::get_or_default(key, "listen", true, false).0 == "true",
+                listen: config::get_or_default_bool(key, "listen"),
+                update_rate: config::get_widget_update_rate(key),
+                update_rate_on_battery: config::get_widget_update_rate_on_battery(key),
+                update_on: triggers::get(key),
+                markup,
+                transition: Transition::new(config::get_transition(key, Duration::ZERO)),
+                job: Arc::new(label_widget::LabelJob {
+                    name: widget_name.clone(),
+                    text,
+                    command,
+                    cache_ttl: config::get_or_default(key, "cache_ttl", false, false)
+                        .1
+                        .max(0) as u64,
+                    command_timeout: config::get_command_timeout(key),
+                    retry: config::get_command_retry(key),
+                    show_errors: config::get_or_default_bool(key, "show_errors"),
+                    fallback: config::get_or_default(key, "fallback", true, true).0,
+                    trim: config::try_get_bool(key, "trim").unwrap_or(true),
+                    env: config::get_variable_env(key),
+                    transform: transform::get(key),
+                    source,
+                    last_output: Default::default(),
+                }),
             };
 
             label.add(widget_name, alignment, left, centered, right)
         }
+        "script" => {
+            let script = ScriptWidget {
+                tooltip,
+                text,
+                command: config::require(key, "command", true).0,
+                transform: transform::get(key),
+                label: Label::new(None),
+            };
+
+            script.add(widget_name, alignment, left, centered, right)
+        }
         "button" => {
             let button = ButtonWidget {
                 tooltip,
@@ -184,39 +721,672 @@ fn add_widget(
         }
         "spacing" => {
             let spacing = SpacingWidget {
-                spacing_start: config::get_or_default(key, "spacing_start", false, false).1,
-                spacing_end: config::get_or_default(key, "spacing_end", false, false).1,
+                spacing_start: scale::px(config::get_clamped(
+                    key,
+                    "spacing_start",
+                    0,
+                    0,
+                    config::MAX_PIXELS,
+                )),
+                spacing_end: scale::px(config::get_clamped(
+                    key,
+                    "spacing_end",
+                    0,
+                    0,
+                    config::MAX_PIXELS,
+                )),
             };
 
             spacing.add(widget_name, alignment, left, centered, right)
         }
+        "separator" => {
+            let separator = SeparatorWidget {};
+
+            separator.add(widget_name, alignment, left, centered, right)
+        }
+        "group" => {
+            let group = GroupWidget {
+                key: key.to_string(),
+                widgets: group_widget::get_widgets(key),
+                spacing: scale::px(config::get_clamped(
+                    key,
+                    "spacing",
+                    0,
+                    0,
+                    config::MAX_PIXELS,
+                )),
+            };
+
+            group.add(widget_name, alignment, left, centered, right)
+        }
         "box" => {
             let box_widget = BoxWidget {
-                width: config::get_or_default(key, "width", false, false).1,
+                width: scale::px(config::get_clamped(key, "width", 0, 0, config::MAX_PIXELS)),
             };
 
             box_widget.add(widget_name, alignment, left, centered, right)
         }
+        "canvas" => {
+            let size = |key_name: &str| {
+                scale::px(config::get_clamped(
+                    key,
+                    key_name,
+                    canvas_widget::DEFAULT_SIZE,
+                    1,
+                    config::MAX_PIXELS,
+                ))
+            };
+
+            let canvas = CanvasWidget {
+                tooltip,
+                draw: config::get_or_default(key, "draw", true, true).0,
+                command,
+                width: size("width"),
+                height: size("height"),
+                scale: scale::get(),
+                update_rate: config::get_widget_update_rate(key),
+                command_timeout: config::get_command_timeout(key),
+                retry: config::get_command_retry(key),
+                area: DrawingArea::new(),
+            };
+
+            canvas.add(widget_name, alignment, left, centered, right)
+        }
         "cava" => {
             let cava = CavaWidget {
                 label: Label::new(None),
             };
 
-            if !*has_started_cava {
+            // Ensure it only calls update_bars once, even with several bars.
+            if !HAS_STARTED_CAVA.swap(true, Ordering::SeqCst) {
                 cava::update_bars();
-                // Ensure it only calls update_bars once.
-                *has_started_cava = true;
             }
 
             cava.add(widget_name, alignment, left, centered, right)
         }
+        "battery" => {
+            let battery = BatteryWidget {
+                tooltip,
+                format: config::get_format(key, battery_widget::DEFAULT_FORMAT),
+                ranges: Ranges::get(key),
+                device: config::get_or_default(key, "device", true, false).0,
+                fallback: config::get_or_default(key, "fallback", true, true).0,
+                update_rate: config::get_widget_update_rate(key),
+                label: Label::new(None),
+            };
+
+            battery.add(widget_name, alignment, left, centered, right)
+        }
+        "brightness" => {
+            let brightness = BrightnessWidget {
+                tooltip,
+                format: config::get_format(key, brightness_widget::DEFAULT_FORMAT),
+                ranges: Ranges::get(key),
+                device: config::get_or_default(key, "device", true, false).0,
+                step: config::try_get(key, "step", false, false).map_or(5, |step| step.1.max(1)),
+                set_command: config::try_get_format(key, "set_command")
+                    .unwrap_or_else(|| String::from(brightness_widget::DEFAULT_SET_COMMAND)),
+                update_rate: config::get_widget_update_rate(key),
+                label: Label::new(None),
+                event_box: EventBox::new(),
+            };
+
+            brightness.add(widget_name, alignment, left, centered, right)
+        }
+        "clock" => {
+            let clock = ClockWidget {
+                tooltip,
+                format: clock_widget::get_format(key),
+                timezone: clock_widget::get_timezone(key),
+                update_rate: config::get_widget_update_rate(key),
+                label: Label::new(None),
+            };
+
+            clock.add(widget_name, alignment, left, centered, right)
+        }
+        "cpu" => {
+            let cpu = CpuWidget {
+                tooltip,
+                format: config::get_format(key, cpu_widget::DEFAULT_FORMAT),
+                ranges: Ranges::get(key),
+                update_rate: config::get_widget_update_rate(key),
+                label: Label::new(None),
+                previous: Cell::new(None),
+            };
+
+            cpu.add(widget_name, alignment, left, centered, right)
+        }
+        "keyboard" => {
+            let keyboard = KeyboardWidget {
+                tooltip,
+                format: config::get_format(key, keyboard_widget::DEFAULT_FORMAT),
+                names: config::with_config(|config| {
+                    config[key]["names"]
+                        .entries()
+                        .map(|(layout, name)| (layout.to_string(), name.to_string()))
+                        .collect()
+                }),
+                label: Label::new(None),
+            };
+
+            keyboard.add(widget_name, alignment, left, centered, right)
+        }
+        "media" => {
+            let media = MediaWidget {
+                tooltip,
+                format: config::get_format(key, media_widget::DEFAULT_FORMAT),
+                player: config::get_or_default(key, "player", true, false).0,
+                label: Label::new(None),
+            };
+
+            media.add(widget_name, alignment, left, centered, right)
+        }
+        "disk" => {
+            let mut base = config::try_get(key, "base", false, false).map_or(1024, |base| base.1);
+            if base != 1000 && base != 1024 {
+                log::warn!("{key}: Invalid base '{base}', expected 1000 or 1024. Using 1024");
+                base = 1024;
+            }
+
+            let disk = DiskWidget {
+                tooltip,
+                format: config::get_format(key, disk_widget::DEFAULT_FORMAT),
+                ranges: Ranges::get(key),
+                path: config::try_get(key, "path", true, true)
+                    .map_or_else(|| String::from(disk_widget::DEFAULT_PATH), |path| path.0),
+                base: base as u64,
+                fallback: config::get_or_default(key, "fallback", true, true).0,
+                update_rate: config::get_widget_update_rate(key),
+                label: Label::new(None),
+            };
+
+            disk.add(widget_name, alignment, left, centered, right)
+        }
+        "graph" => {
+            let graph = GraphWidget {
+                tooltip,
+                command,
+                source: config::get_or_default(key, "source", true, false).0,
+                samples: config::try_get(key, "samples", false, false)
+                    .map_or(graph_widget::DEFAULT_SAMPLES, |samples| {
+                        samples.1.max(2) as usize
+                    }),
+                bars: config::get_or_default(key, "style", true, false).0 == "bars",
+                min: config::get_or_default_f64(key, "min"),
+                max: config::try_get_f64(key, "max"),
+                width: scale::px(config::get_clamped(
+                    key,
+                    "width",
+                    graph_widget::DEFAULT_WIDTH,
+                    1,
+                    config::MAX_PIXELS,
+                )),
+                // Without a height it fills the height of the bar.
+                height: config::try_get_clamped(key, "height", 1, config::MAX_PIXELS)
+                    .map_or(-1, scale::px),
+                update_rate: config::get_widget_update_rate(key),
+                command_timeout: config::get_command_timeout(key),
+                retry: config::get_command_retry(key),
+                area: DrawingArea::new(),
+                history: RefCell::new(VecDeque::new()),
+                transition: Transition::new(config::get_transition(key, Duration::ZERO)),
+                moving: Cell::new(None),
+            };
+
+            graph.add(widget_name, alignment, left, centered, right)
+        }
+        "image" => {
+            let image = ImageWidget {
+                tooltip,
+                // A file path takes precedence over a themed icon name, both may contain variables.
+                // Without either, the `command` has to print them.
+                source: config::try_get_first(key, &["path", "icon"], true)
+                    .or_else(|| {
+                        command
+                            .is_empty()
+                            .then(|| config::require(key, "path", true))
+                    })
+                    .unwrap_or_default()
+                    .0,
+                command,
+                size: scale::px(config::get_clamped(
+                    key,
+                    "size",
+                    image_widget::DEFAULT_SIZE,
+                    1,
+                    config::MAX_PIXELS,
+                )),
+                hide_missing: config::get_or_default_bool(key, "hide_missing"),
+                update_rate: config::get_widget_update_rate(key),
+                command_timeout: config::get_command_timeout(key),
+                retry: config::get_command_retry(key),
+                image: Image::new(),
+            };
+
+            image.add(widget_name, alignment, left, centered, right)
+        }
+        "memory" => {
+            let memory = MemoryWidget {
+                tooltip,
+                format: config::get_format(key, memory_widget::DEFAULT_FORMAT),
+                ranges: Ranges::get(key),
+                update_rate: config::get_widget_update_rate(key),
+                label: Label::new(None),
+            };
+
+            memory.add(widget_name, alignment, left, centered, right)
+        }
+        "network" => {
+            let network = NetworkWidget {
+                tooltip,
+                format: config::get_format(key, network_widget::DEFAULT_FORMAT),
+                interface: config::try_get(key, "interface", true, true).map_or_else(
+                    || String::from(network_widget::AUTO_INTERFACE),
+                    |interface| interface.0,
+                ),
+                update_rate: config::get_widget_update_rate(key),
+                label: Label::new(None),
+                previous: Cell::new(None),
+            };
+
+            network.add(widget_name, alignment, left, centered, right)
+        }
+        "progress" => {
+            let mut min = config::try_get_f64(key, "min").unwrap_or(progress_widget::DEFAULT_MIN);
+            let mut max = config::try_get_f64(key, "max").unwrap_or(progress_widget::DEFAULT_MAX);
+            if min >= max {
+                log::warn!(
+                    "{key}: 'min' ({min}) has to be lower than 'max' ({max}), using {} and {}",
+                    progress_widget::DEFAULT_MIN,
+                    progress_widget::DEFAULT_MAX
+                );
+                min = progress_widget::DEFAULT_MIN;
+                max = progress_widget::DEFAULT_MAX;
+            }
+
+            let progress = ProgressWidget {
+                tooltip,
+                value: config::get_or_default_f64(key, "value"),
+                command,
+                min,
+                max,
+                update_rate: config::get_widget_update_rate(key),
+                command_timeout: config::get_command_timeout(key),
+                retry: config::get_command_retry(key),
+                progress_bar: ProgressBar::new(),
+                transition: Transition::new(config::get_transition(
+                    key,
+                    progress_widget::DEFAULT_TRANSITION,
+                )),
+            };
+
+            progress.add(widget_name, alignment, left, centered, right)
+        }
+        "uptime" => {
+            let uptime = UptimeWidget {
+                tooltip,
+                format: config::get_format(key, uptime_widget::DEFAULT_FORMAT),
+                update_rate: config::get_widget_update_rate(key),
+                label: Label::new(None),
+            };
+
+            uptime.add(widget_name, alignment, left, centered, right)
+        }
+        "volume" => {
+            let volume = VolumeWidget {
+                tooltip,
+                format: config::get_format(key, volume_widget::DEFAULT_FORMAT),
+                ranges: Ranges::get(key),
+                label: Label::new(None),
+            };
+
+            volume.add(widget_name, alignment, left, centered, right)
+        }
+        "temperature" => {
+            let temperature = TemperatureWidget {
+                tooltip,
+                format: config::get_format(key, temperature_widget::DEFAULT_FORMAT),
+                ranges: Ranges::get(key),
+                sensor: config::get_or_default(key, "sensor", true, false).0,
+                warning_threshold: config::try_get(key, "warning_threshold", false, false)
+                    .map(|threshold| threshold.1),
+                update_rate: config::get_widget_update_rate(key),
+                label: Label::new(None),
+            };
+
+            temperature.add(widget_name, alignment, left, centered, right)
+        }
+        "timer" => {
+            let target = config::try_get(key, "target", true, true)
+                .unwrap_or_default()
+                .0;
+            let Some(target) = timer_widget::parse_target(&target, chrono::Local::now()) else {
+                log::warn!("{key}: Invalid target '{target}', skipping it");
+                return;
+            };
+
+            let format = config::get_format(key, timer_widget::DEFAULT_FORMAT);
+            let timer = TimerWidget {
+                tooltip,
+                elapsed_format: config::try_get_format(key, "elapsed_format")
+                    .unwrap_or_else(|| format.clone()),
+                format,
+                target,
+                on_complete: config::get_or_default(key, "on_complete", true, true).0,
+                command_timeout: config::get_command_timeout(key),
+                update_rate: config::get_widget_update_rate(key),
+                label: Label::new(None),
+                completed: Cell::new(false),
+            };
+
+            timer.add(widget_name, alignment, left, centered, right)
+        }
+        "tray" => {
+            let tray = TrayWidget {
+                icon_size: scale::px(config::get_clamped(
+                    key,
+                    "icon_size",
+                    tray_widget::DEFAULT_ICON_SIZE,
+                    1,
+                    config::MAX_PIXELS,
+                )),
+                container: Box::new(Orientation::Horizontal, 0),
+            };
+
+            tray.add(widget_name, alignment, left, centered, right)
+        }
+        "window" => {
+            let window = WindowWidget {
+                tooltip,
+                format: config::get_format(key, window_widget::DEFAULT_FORMAT),
+                fallback: config::get_or_default(key, "fallback", true, true).0,
+                label: Label::new(None),
+            };
+
+            window.add(widget_name, alignment, left, centered, right)
+        }
+        "workspaces" => {
+            let workspaces = WorkspacesWidget {
+                container: Box::new(Orientation::Horizontal, 0),
+            };
+
+            workspaces.add(widget_name, alignment, left, centered, right)
+        }
         "cmd" => {
             let cmd = CmdWidget {};
 
             cmd.add(widget_name, alignment, left, centered, right)
         }
         _ => {
-            panic!("[ERROR] There are no widgets identified as '{identifier}'!\n")
+            // 0.3.5: A typo in the type, or one from a newer version, shouldn't take the whole bar
+            //   down. A placeholder shows where the widget would have been instead.
+            let problem = format!("There are no widgets identified as '{widget_type}'");
+            log::warn!("{key}: {problem}, adding a placeholder instead");
+            errors::report(&widget_name, &problem);
+
+            let placeholder = gtk::Label::new(Some(&format!("{widget_type}?")));
+            placeholder.set_widget_name(&widget_name);
+            placeholder.set_tooltip_text(Some(&format!("{key}: {problem}")));
+            placeholder.style_context().add_class(UNKNOWN_CLASS);
+            add_and_align(&placeholder, alignment, left, centered, right);
+        }
+    }
+
+    // 0.3.5: Add the CSS classes to the widget that was just added.
+    let target = match alignment {
+        Align::LEFT => left,
+        Align::CENTERED => centered,
+        Align::RIGHT => right,
+    };
+
+    let Some(widget) = target.children().last().cloned() else {
+        return;
+    };
+
+    let style_context = widget.style_context();
+    for class in get_classes(key) {
+        style_context.add_class(&class);
+    }
+
+    // 0.3.5: Quick font tweaks without having to write CSS.
+    let font = config::get_or_default(key, "font", true, false).0;
+    let font_size = config::try_get_f64(key, "font_size").filter(|size| *size > 0.0);
+    if !font.is_empty() || font_size.is_some() {
+        stylesheet::apply_font(&widget, &font, font_size);
+    }
+
+    stylesheet::apply_colors(&widget, key);
+
+    overflow::bind(&widget, key);
+    overflow::reserve_width(&widget, key);
+
+    // 0.3.5: Hide the widget, along with its event box, while `visible_command` fails.
+    let (left, centered, right) = left_centered_right;
+    let outer = match event_box {
+        Some((event_box, _)) => {
+            add_and_align(&event_box, alignment, left, centered, right);
+            event_box.upcast()
         }
+        None => widget,
+    };
+
+    visibility::bind(&outer, key);
+    let region = match alignment {
+        Align::LEFT => left,
+        Align::CENTERED => centered,
+        Align::RIGHT => right,
+    };
+
+    apply_layout(&outer, region, key);
+}
+
+/// Applies the `margin_*`, `expand` and `fill` keys of the widget at `key` to `widget`, which is
+/// inside of `region`.
+fn apply_layout(widget: &Widget, region: &Box, key: &str) {
+    // Only the margins which are set, so the ones the widget has set itself are kept otherwise.
+    // GTK doesn't support negative margins on widgets.
+    let get_margin =
+        |side: &str| config::try_get_clamped(key, side, 0, config::MAX_PIXELS).map(scale::px);
+    if let Some(margin) = get_margin("margin_top") {
+        widget.set_margin_top(margin);
+    }
+
+    if let Some(margin) = get_margin("margin_bottom") {
+        widget.set_margin_bottom(margin);
+    }
+
+    if let Some(margin) = get_margin("margin_left") {
+        widget.set_margin_start(margin);
+    }
+
+    if let Some(margin) = get_margin("margin_right") {
+        widget.set_margin_end(margin);
+    }
+
+    // Only repack when asked to, so widgets keep the GTK defaults otherwise.
+    let expand = config::try_get_bool(key, "expand");
+    let fill = config::try_get_bool(key, "fill");
+    if expand.is_some() || fill.is_some() {
+        region.set_child_packing(
+            widget,
+            expand.unwrap_or_default(),
+            fill.unwrap_or_default(),
+            0,
+            PackType::Start,
+        );
+    }
+}
+
+/// Gets the CSS classes for the widget at `root`, from `class`.
+/// Accepts either an array of classes, or a string of whitespace-separated classes.
+fn get_classes(root: &str) -> std::vec::Vec<String> {
+    if let Some(classes) = config::try_get_array(root, "class") {
+        return classes;
+    }
+
+    config::get_or_default(root, "class", true, true)
+        .0
+        .split_whitespace()
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gets the blueprints of `keys` in `config`, like `get_blueprints` does with the cached config.
+    fn get_test_blueprints(config: &JsonValue, keys: &[&str]) -> std::vec::Vec<Blueprint> {
+        keys.iter()
+            .map(|key| Blueprint {
+                identity: config[*key]["id"].as_str().unwrap_or(key).to_string(),
+                key: key.to_string(),
+                fingerprint: get_fingerprint(config, key),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reloads_only_rebuild_what_changed() {
+        let config = json::parse(
+            r#"{
+                "left-cpu_a": { "format": "{usage}%" },
+                "left-clock_b": { "id": "time" },
+                "right-group_c": { "widgets": ["right-label_d"] },
+                "right-label_d": { "text": "d" },
+                "right-label_e": { "text": "e" }
+            }"#,
+        )
+        .unwrap();
+        let previous = get_test_blueprints(
+            &config,
+            &[
+                "left-cpu_a",
+                "left-clock_b",
+                "right-group_c",
+                "right-label_e",
+            ],
+        );
+        let previous: std::vec::Vec<&Blueprint> = previous.iter().collect();
+        assert!(
+            diff(&previous, &get_test_blueprints(&config, &["left-cpu_a"]))
+                .iter()
+                .all(|change| matches!(change, Change::Removed(_)))
+        );
+
+        let mut config = config;
+        config.remove("left-clock_b");
+        // Still the same widget through its `id`, just somewhere else.
+        config["right-clock_f"] = json::object! { id: "time" };
+        // Widgets inside of a group are built by it, so the group is rebuilt.
+        config["right-label_d"]["text"] = "changed".into();
+        config.remove("right-label_e");
+        config["left-label_g"] = json::object! { text: "g" };
+        let current = get_test_blueprints(
+            &config,
+            &[
+                "left-cpu_a",
+                "left-label_g",
+                "right-clock_f",
+                "right-group_c",
+            ],
+        );
+        assert_eq!(
+            diff(&previous, &current),
+            vec![
+                Change::Added(String::from("left-label_g")),
+                Change::Changed(String::from("left-clock_b"), String::from("right-clock_f")),
+                Change::Changed(String::from("right-group_c"), String::from("right-group_c")),
+                Change::Removed(String::from("right-label_e")),
+            ]
+        );
+
+        // Variables are filled in while building, so every widget is rebuilt with them.
+        let previous: std::vec::Vec<&Blueprint> = current.iter().collect();
+        config["variables"] = json::object! { name: "value" };
+        let current = get_test_blueprints(
+            &config,
+            &[
+                "left-cpu_a",
+                "left-label_g",
+                "right-clock_f",
+                "right-group_c",
+            ],
+        );
+        assert_eq!(diff(&previous, &current).len(), current.len());
+
+        // So are the settings widgets fall back to, unlike those read on the fly.
+        let keys = [
+            "left-cpu_a",
+            "left-label_g",
+            "right-clock_f",
+            "right-group_c",
+        ];
+        let previous: std::vec::Vec<&Blueprint> = current.iter().collect();
+        config["hybrid"] = json::object! { background: "#1e1e2e" };
+        let current = get_test_blueprints(&config, &keys);
+        assert!(diff(&previous, &current).is_empty());
+        config["hybrid"]["update_rate"] = 500.into();
+        let current = get_test_blueprints(&config, &keys);
+        assert_eq!(diff(&previous, &current).len(), current.len());
+        assert!(diff(&previous, &current).iter().all(
+            |change| matches!(change, Change::Changed(previous, current) if previous == current)
+        ));
+
+        let unchanged = current.clone();
+        let previous: std::vec::Vec<&Blueprint> = current.iter().collect();
+        assert!(diff(&previous, &unchanged).is_empty());
+    }
+
+    #[test]
+    fn toggling_enabled_rebuilds_widgets() {
+        // Gets the blueprints of the widgets left once the conditions have been applied.
+        let load = |b: &str, c: &str| {
+            let mut config = json::parse(&format!(
+                r#"{{
+                    "left-group_a": {{ "widgets": ["left-label_b"] }},
+                    "left-label_b": {{ "text": "b"{b} }},
+                    "right-label_c": {{ "text": "c"{c} }}
+                }}"#
+            ))
+            .unwrap();
+            config::apply_conditions(&mut config, &command::MockRunner::default());
+            let keys: std::vec::Vec<&str> = ["left-group_a", "right-label_c"]
+                .into_iter()
+                .filter(|key| config.has_key(key))
+                .collect();
+            get_test_blueprints(&config, &keys)
+        };
+
+        let (enabled, disabled) = (r#", "enabled": true"#, r#", "enabled": false"#);
+        let previous = load(enabled, enabled);
+        let previous: std::vec::Vec<&Blueprint> = previous.iter().collect();
+        // Enabling them explicitly is the same as leaving `enabled` out, so nothing is rebuilt.
+        assert!(diff(&previous, &load("", "")).is_empty());
+
+        // The label inside of the group is gone from the config, so the group is rebuilt without it.
+        assert_eq!(
+            diff(&previous, &load(disabled, disabled)),
+            vec![
+                Change::Changed(String::from("left-group_a"), String::from("left-group_a")),
+                Change::Removed(String::from("right-label_c")),
+            ]
+        );
+
+        let previous = load(enabled, disabled);
+        let previous: std::vec::Vec<&Blueprint> = previous.iter().collect();
+        assert_eq!(
+            diff(&previous, &load(enabled, enabled)),
+            vec![Change::Added(String::from("right-label_c"))]
+        );
+    }
+
+    #[test]
+    fn forced_reloads_are_debounced() {
+        let now = Instant::now();
+        assert!(!is_debounced(None, now));
+        assert!(is_debounced(Some(now), now));
+        assert!(is_debounced(Some(now), now + RELOAD_DEBOUNCE / 2));
+        assert!(!is_debounced(Some(now), now + RELOAD_DEBOUNCE));
     }
 }
@@ -0,0 +1,55 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+thread_local! {
+    /// How many allocations the current thread has made within `count`.
+    static COUNT: Cell<usize> = const { Cell::new(0) };
+    /// Whether the current thread is within `count`.
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Counts allocations on top of the system allocator, so tests can measure what an update costs.
+/// Only allocations made within `count` are counted, everything else is forwarded as-is.
+pub struct CountingAllocator;
+
+/// Adds an allocation to `COUNT`, if the current thread is within `count`.
+fn record() {
+    // Fails while the thread is being torn down, those allocations don't matter.
+    let _ = COUNTING.try_with(|counting| {
+        if counting.get() {
+            COUNT.with(|count| count.set(count.get() + 1));
+        }
+    });
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// SAFETY: Everything is forwarded to the system allocator as-is.
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Counts the allocations `f` makes on the current thread, so other tests running in parallel
+/// don't skew it.
+pub fn count(f: impl FnOnce()) -> usize {
+    COUNT.with(|count| count.set(0));
+    COUNTING.with(|counting| counting.set(true));
+    f();
+    COUNTING.with(|counting| counting.set(false));
+    COUNT.with(Cell::get)
+}
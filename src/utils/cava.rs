@@ -1,8 +1,8 @@
-use crate::{config, math};
+use crate::{command, config, math};
 use std::{fs::File, io::Write, process::Stdio, sync::RwLock};
 use tokio::{
     io::{AsyncBufReadExt, BufReader},
-    process::Command,
+    process::{Child, Command},
     task,
 };
 
@@ -56,8 +56,7 @@ fn get_framerate() -> i32 {
 /// Builds the temporary Cava configuration and then returns the path to it,
 pub fn get_temp_config() -> String {
     let path = String::from("/tmp/cava_tmp_hybrid.conf");
-    let mut file =
-        File::create(&path).expect("[ERROR] Couldn't create the temporary Cava config!\n");
+    let mut file = File::create(&path).expect("Couldn't create the temporary Cava config!");
     // 0.2.7: Support for dynamically configuring the temporary config to an extent.
     let bars = get_bars();
     let framerate = get_framerate();
@@ -77,7 +76,7 @@ ascii_max_range = 7
         )
         .as_bytes(),
     )
-    .expect("[ERROR] Failed writing to the temporary Cava config!\n");
+    .expect("Failed writing to the temporary Cava config!");
     path
 }
 
@@ -95,13 +94,17 @@ pub fn update_bars() {
             .args(["-c", format!("cava -p {path} | sed -u '{sed}'").as_str()])
             .stdout(Stdio::piped())
             .kill_on_drop(true)
+            // 0.3.5: Put it in its own process group, so that cava is stopped with the bar.
+            .process_group(0)
             .spawn()
-            .expect("[ERROR] Cannot start cava script!\n");
+            .expect("Cannot start cava script!");
 
-        let out = child
-            .stdout
-            .take()
-            .expect("[ERROR] Cannot take stdout from child!\n");
+        let pid = child.id();
+        if let Some(pid) = pid {
+            command::register_child(pid);
+        }
+
+        let out = child.stdout.take().expect("Cannot take stdout from child!");
 
         // Drop to free the resources as we don't need to access them anymore.
         drop(sed);
@@ -114,9 +117,8 @@ pub fn update_bars() {
                     match next_line {
                         Ok(t) => t,
                         Err(_) => {
-                            *HAS_CAVA_CRASHED.write().unwrap() = true;
-                            BARS.write().unwrap().clear();
-                            panic!("[WARN] Cava: There are no more lines available. Hybrid will keep on running but Cava will be stopped!\n")
+                            stop(&mut child, pid).await;
+                            panic!("Cava: There are no more lines available. Hybrid will keep on running but Cava will be stopped!")
                         }
                     }
                 };
@@ -124,9 +126,8 @@ pub fn update_bars() {
                 match this {
                     Some(val) => val,
                     None => {
-                        *HAS_CAVA_CRASHED.write().unwrap() = true;
-                        BARS.write().unwrap().clear();
-                        panic!("[WARN] Cava: The string value is None, Hybrid will keep on running but Cava will be stopped!\n")
+                        stop(&mut child, pid).await;
+                        panic!("Cava: The string value is None, Hybrid will keep on running but Cava will be stopped!")
                     }
                 }
             };
@@ -135,3 +136,14 @@ pub fn update_bars() {
         }
     });
 }
+
+/// Marks Cava as crashed once `child` stopped printing bars, then reaps it so it's no longer
+/// terminated on shutdown.
+async fn stop(child: &mut Child, pid: Option<u32>) {
+    *HAS_CAVA_CRASHED.write().unwrap() = true;
+    BARS.write().unwrap().clear();
+    let _ = child.kill().await;
+    if let Some(pid) = pid {
+        command::unregister_child(pid);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::math;
+
+/// Parses a hex color, like `#aabbcc`, into its RGBA components.
+/// Supports the `rgb`, `rrggbb` and `rrggbbaa` forms, with or without the leading `#`.
+/// Colors without an alpha component are fully opaque.
+/// Returns `None` if `input` isn't a valid hex color.
+pub fn parse_hex(input: &str) -> Option<(u8, u8, u8, u8)> {
+    let hex = input.strip_prefix('#').unwrap_or(input);
+    // Also rejects signs like `+`, which `from_str_radix` would otherwise accept.
+    if !hex.chars().all(|char| char.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let component = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+    match hex.len() {
+        3 => {
+            // `#abc` is short for `#aabbcc`.
+            let short = |index: usize| {
+                u8::from_str_radix(&hex[index..index + 1], 16)
+                    .ok()
+                    .map(|value| value * 17)
+            };
+            Some((short(0)?, short(1)?, short(2)?, 255))
+        }
+        6 => Some((component(0)?, component(2)?, component(4)?, 255)),
+        8 => Some((component(0)?, component(2)?, component(4)?, component(6)?)),
+        _ => None,
+    }
+}
+
+/// Formats RGBA components as a CSS `rgba()` color.
+pub fn to_css_rgba(color: &(u8, u8, u8, u8)) -> String {
+    let (r, g, b, a) = color;
+    format!("rgba({r}, {g}, {b}, {:.3})", *a as f64 / 255.0)
+}
+
+/// Formats RGBA components as `#rrggbb`, or `#rrggbbaa` if they aren't fully opaque.
+pub fn to_hex(color: &(u8, u8, u8, u8)) -> String {
+    let (r, g, b, a) = color;
+    match a {
+        255 => format!("#{r:02x}{g:02x}{b:02x}"),
+        _ => format!("#{r:02x}{g:02x}{b:02x}{a:02x}"),
+    }
+}
+
+/// Mixes the RGB components of `color` with those of `target` by `amount`, from `0.0` leaving it
+/// as-is to `1.0` turning it into `target`. The alpha component is kept.
+pub fn mix(color: &(u8, u8, u8, u8), target: u8, amount: f64) -> (u8, u8, u8, u8) {
+    let (r, g, b, a) = *color;
+    let mix = |component: u8| math::lerp(component as f64, target as f64, amount).round() as u8;
+    (mix(r), mix(g), mix(b), a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mix_towards_white_and_black() {
+        let color = (0x80, 0x00, 0xff, 0xcc);
+        assert_eq!(mix(&color, 255, 0.5), (0xc0, 0x80, 0xff, 0xcc));
+        assert_eq!(mix(&color, 0, 0.5), (0x40, 0x00, 0x80, 0xcc));
+        assert_eq!(mix(&color, 0, 2.0), (0, 0, 0, 0xcc));
+    }
+
+    #[test]
+    fn parse_hex_short() {
+        assert_eq!(parse_hex("#abc"), Some((0xaa, 0xbb, 0xcc, 255)));
+    }
+
+    #[test]
+    fn parse_hex_rgb() {
+        assert_eq!(parse_hex("#0a1B2c"), Some((0x0a, 0x1b, 0x2c, 255)));
+    }
+
+    #[test]
+    fn parse_hex_rgba() {
+        assert_eq!(parse_hex("#aabbccdd"), Some((0xaa, 0xbb, 0xcc, 0xdd)));
+    }
+
+    #[test]
+    fn parse_hex_without_hash() {
+        assert_eq!(parse_hex("aabbcc"), Some((0xaa, 0xbb, 0xcc, 255)));
+    }
+
+    #[test]
+    fn parse_hex_invalid_characters() {
+        assert_eq!(parse_hex("#gghhii"), None);
+        assert_eq!(parse_hex("#+abbcc"), None);
+        assert_eq!(parse_hex("#ääb"), None);
+    }
+
+    #[test]
+    fn parse_hex_invalid_length() {
+        assert_eq!(parse_hex(""), None);
+        assert_eq!(parse_hex("#"), None);
+        assert_eq!(parse_hex("#abcd"), None);
+        assert_eq!(parse_hex("#aabbccd"), None);
+    }
+
+    #[test]
+    fn to_css_rgba_formats() {
+        assert_eq!(
+            to_css_rgba(&(170, 187, 204, 255)),
+            "rgba(170, 187, 204, 1.000)"
+        );
+        assert_eq!(to_css_rgba(&(0, 0, 0, 0)), "rgba(0, 0, 0, 0.000)");
+    }
+
+    #[test]
+    fn to_hex_opaque_and_transparent() {
+        assert_eq!(to_hex(&(0xaa, 0xbb, 0xcc, 255)), "#aabbcc");
+        assert_eq!(to_hex(&(0, 0x0f, 0xff, 0x80)), "#000fff80");
+    }
+}
@@ -0,0 +1,480 @@
+use crate::locks;
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    os::unix::process::CommandExt,
+    process::{Command, Stdio},
+    sync::{Mutex, RwLock},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use wait_timeout::ChildExt;
+
+lazy_static! {
+    /// Cached command output, keyed by the command.
+    static ref CACHE: RwLock<HashMap<String, (Instant, CommandOutput)>> = RwLock::new(HashMap::new());
+    /// Process ids of the running children, each leading its own process group.
+    static ref CHILDREN: Mutex<HashSet<u32>> = Mutex::new(HashSet::new());
+    /// The worker threads running commands, see `spawn_worker`.
+    static ref WORKERS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+}
+
+/// Registers the running child `pid`, so it's stopped along with the bar. The child has to lead
+/// its own process group, see `Command::process_group`.
+pub fn register_child(pid: u32) {
+    locks::lock(&CHILDREN).insert(pid);
+}
+
+/// Forgets about `pid` once it has exited.
+pub fn unregister_child(pid: u32) {
+    locks::lock(&CHILDREN).remove(&pid);
+}
+
+/// Sends a `SIGTERM` to the process group of `pid` and forgets about it, unless it has exited
+/// already. For children that are no longer needed while the bar keeps running.
+pub fn terminate_child(pid: u32) {
+    if locks::lock(&CHILDREN).remove(&pid) {
+        log::debug!("Terminating child {pid}");
+        unsafe { libc::kill(-(pid as i32), libc::SIGTERM) };
+    }
+}
+
+/// Sends a `SIGTERM` to the process groups of all running children, so the processes they spawned
+/// are stopped as well.
+pub fn terminate_children() {
+    for pid in locks::lock(&CHILDREN).drain() {
+        log::debug!("Terminating child {pid}");
+        unsafe { libc::kill(-(pid as i32), libc::SIGTERM) };
+    }
+}
+
+/// Runs `work` on a worker thread, which `join_workers` waits for on shutdown. For threads that
+/// run commands, as they end once their children are terminated.
+pub fn spawn_worker(work: impl FnOnce() + Send + 'static) {
+    let mut workers = locks::lock(&WORKERS);
+    // Widgets keep on spawning workers, so don't hold on to the ones that are done.
+    workers.retain(|worker| !worker.is_finished());
+    workers.push(thread::spawn(work));
+}
+
+/// Waits up to `timeout` for the worker threads to finish, call after `terminate_children`.
+/// Returns how many are still running, those are left to end along with the process.
+pub fn join_workers(timeout: Duration) -> usize {
+    let started = Instant::now();
+    let mut workers = std::mem::take(&mut *locks::lock(&WORKERS));
+    while !workers.is_empty() && started.elapsed() < timeout {
+        let (finished, running) = workers.into_iter().partition(|worker| worker.is_finished());
+        workers = running;
+        for worker in finished {
+            let _ = worker.join();
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    let running = workers.len();
+    locks::lock(&WORKERS).extend(workers);
+    running
+}
+
+/// Executes commands, so that tests can hand out canned output rather than spawning processes.
+/// Widgets take the runner their commands go through, the bar itself passes `BashRunner`.
+pub trait CommandRunner {
+    /// Executes `command` with `env` added to its environment, with the same semantics as
+    /// `execute_with_env`.
+    fn execute_with_env(
+        &self,
+        command: &str,
+        timeout: Duration,
+        env: &[(String, String)],
+    ) -> CommandOutput;
+
+    /// Same as `execute_with_env`, without any extra environment variables.
+    fn execute(&self, command: &str, timeout: Duration) -> CommandOutput {
+        self.execute_with_env(command, timeout, &[])
+    }
+}
+
+/// Executes commands through bash, see `execute_with_env`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BashRunner;
+
+impl CommandRunner for BashRunner {
+    fn execute_with_env(
+        &self,
+        command: &str,
+        timeout: Duration,
+        env: &[(String, String)],
+    ) -> CommandOutput {
+        execute_with_env(command, timeout, env)
+    }
+}
+
+/// Executes commands through `runner` with `env` added to their environment.
+pub struct EnvRunner<'a, R: CommandRunner> {
+    pub runner: &'a R,
+    pub env: &'a [(String, String)],
+}
+
+impl<R: CommandRunner> CommandRunner for EnvRunner<'_, R> {
+    fn execute_with_env(
+        &self,
+        command: &str,
+        timeout: Duration,
+        env: &[(String, String)],
+    ) -> CommandOutput {
+        if env.is_empty() {
+            return self.runner.execute_with_env(command, timeout, self.env);
+        }
+
+        self.runner
+            .execute_with_env(command, timeout, &[self.env, env].concat())
+    }
+}
+
+/// How often a failed command is retried, see `RetryRunner`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Retry {
+    /// How many more times the command is executed after it failed, `0` means it isn't retried.
+    pub retries: u32,
+    /// How long to wait between the attempts.
+    pub delay: Duration,
+}
+
+/// Executes commands through `runner`, retrying those that fail as often as `retry` allows.
+/// All attempts share the `timeout` of a single execution, so a retry is only made if there's
+/// still time left for it once the delay has passed.
+pub struct RetryRunner<'a, R: CommandRunner> {
+    pub runner: &'a R,
+    pub retry: Retry,
+}
+
+impl<R: CommandRunner> CommandRunner for RetryRunner<'_, R> {
+    fn execute_with_env(
+        &self,
+        command: &str,
+        timeout: Duration,
+        env: &[(String, String)],
+    ) -> CommandOutput {
+        let started = Instant::now();
+        let mut output = self.runner.execute_with_env(command, timeout, env);
+        for attempt in 1..=self.retry.retries {
+            if output.success {
+                break;
+            }
+
+            // A timeout of zero means there's no timeout, so there's always time left.
+            let remaining = timeout
+                .checked_sub(started.elapsed() + self.retry.delay)
+                .filter(|remaining| !remaining.is_zero());
+            if !timeout.is_zero() && remaining.is_none() {
+                log::debug!("'{command}' failed, but there's no time left to retry it");
+                break;
+            }
+
+            log::debug!(
+                "'{command}' failed, retrying in {}ms ({attempt}/{})",
+                self.retry.delay.as_millis(),
+                self.retry.retries
+            );
+            thread::sleep(self.retry.delay);
+            output =
+                self.runner
+                    .execute_with_env(command, remaining.unwrap_or(Duration::ZERO), env);
+        }
+
+        output
+    }
+}
+
+/// Returns canned output keyed by the command, for tests. Unknown commands fail without output.
+/// The environment of the last command is kept, so tests can check what it would've seen.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockRunner {
+    outputs: HashMap<String, CommandOutput>,
+    pub last_env: std::cell::RefCell<Vec<(String, String)>>,
+}
+
+#[cfg(test)]
+impl MockRunner {
+    /// Makes `command` print `stdout`, and exit with `0` if `success`.
+    pub fn with(self, command: &str, stdout: &str, success: bool) -> Self {
+        self.with_stderr(command, stdout, "", success)
+    }
+
+    /// Same as `with`, but the command also prints `stderr`.
+    pub fn with_stderr(mut self, command: &str, stdout: &str, stderr: &str, success: bool) -> Self {
+        let output = CommandOutput {
+            stdout: normalize(stdout, true),
+            untrimmed_stdout: normalize(stdout, false),
+            stderr: normalize(stderr, true),
+            success,
+        };
+
+        self.outputs.insert(command.to_string(), output);
+        self
+    }
+}
+
+#[cfg(test)]
+impl CommandRunner for MockRunner {
+    fn execute_with_env(
+        &self,
+        command: &str,
+        _: Duration,
+        env: &[(String, String)],
+    ) -> CommandOutput {
+        self.last_env.replace(env.to_vec());
+        self.outputs.get(command).cloned().unwrap_or_default()
+    }
+}
+
+/// Executes `command` through `runner` and returns its output, unless it has already been executed
+/// within `ttl`, in which case the cached output is returned instead.
+/// A `ttl` of zero means the output is never cached.
+pub fn execute_cached_with(
+    runner: &impl CommandRunner,
+    command: &str,
+    ttl: Duration,
+    timeout: Duration,
+) -> CommandOutput {
+    if ttl.is_zero() {
+        return runner.execute(command, timeout);
+    }
+
+    if let Some((executed_at, output)) = locks::read(&CACHE).get(command) {
+        if executed_at.elapsed() < ttl {
+            return output.clone();
+        }
+    }
+
+    let output = runner.execute(command, timeout);
+    locks::write(&CACHE).insert(command.to_string(), (Instant::now(), output.clone()));
+    output
+}
+
+/// Clears all cached command output.
+pub fn clear_cache() {
+    locks::write(&CACHE).clear();
+}
+
+/// Output of an executed command, normalized through `normalize`.
+#[derive(Clone, Default)]
+pub struct CommandOutput {
+    /// What the command wrote to stdout, without trailing new lines.
+    pub stdout: String,
+    /// What the command wrote to stdout, trailing new lines included. For widgets with `trim` set
+    /// to `false`.
+    pub untrimmed_stdout: String,
+    /// What the command wrote to stderr, without trailing new lines.
+    pub stderr: String,
+    /// Whether the command exited with `0`.
+    pub success: bool,
+}
+
+/// Executes a bash command with `env` added to its environment and returns its output.
+/// If the command is still running after `timeout` then it's killed, and an empty output is
+/// returned. A `timeout` of zero means the command may run forever.
+/// Failures are logged along with the exit code and stderr.
+pub fn execute_with_env(
+    command: &str,
+    timeout: Duration,
+    env: &[(String, String)],
+) -> CommandOutput {
+    if command.is_empty() {
+        return CommandOutput::default();
+    }
+
+    let mut child = match Command::new("bash")
+        .args(["-c", command])
+        .envs(env.iter().map(|(name, value)| (name, value)))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Put it in its own process group, so that everything it spawns can be killed with it.
+        .process_group(0)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) => {
+            log::warn!("Failed spawning '{command}': {err}");
+            return CommandOutput::default();
+        }
+    };
+
+    // Read on separate threads, otherwise a command with lots of output could fill up the pipe
+    // and block forever.
+    let stdout = read_to_end(child.stdout.take().expect("Cannot take stdout from child!"));
+    let stderr = read_to_end(child.stderr.take().expect("Cannot take stderr from child!"));
+
+    register_child(child.id());
+    let status = if timeout.is_zero() {
+        child.wait().map(Some)
+    } else {
+        child.wait_timeout(timeout)
+    };
+    unregister_child(child.id());
+
+    let status = match status {
+        Ok(Some(status)) => status,
+        Ok(None) => {
+            log::warn!(
+                "'{command}' timed out after {}ms, killing it",
+                timeout.as_millis()
+            );
+            // Kill the whole process group, then reap the child so it doesn't turn into a zombie.
+            // The reader threads are left alone as they end once the pipes close.
+            unsafe { libc::kill(-(child.id() as i32), libc::SIGKILL) };
+            let _ = child.wait();
+            return CommandOutput::default();
+        }
+        Err(err) => {
+            log::warn!("Failed waiting for '{command}': {err}");
+            let _ = child.kill();
+            let _ = child.wait();
+            return CommandOutput::default();
+        }
+    };
+
+    let stdout = String::from_utf8_lossy(&stdout.join().unwrap_or_default()).to_string();
+    let stderr = String::from_utf8_lossy(&stderr.join().unwrap_or_default()).to_string();
+    let output = CommandOutput {
+        stdout: normalize(&stdout, true),
+        untrimmed_stdout: normalize(&stdout, false),
+        stderr: normalize(&stderr, true),
+        success: status.success(),
+    };
+
+    if !output.success {
+        let code = status
+            .code()
+            .map_or_else(|| String::from("a signal"), |code| code.to_string());
+        log::warn!("'{command}' exited with {code}: {}", output.stderr);
+    }
+
+    output
+}
+
+/// Reads everything from `source` on a separate thread.
+fn read_to_end(mut source: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = source.read_to_end(&mut output);
+        output
+    })
+}
+
+/// Normalizes command output: `\r\n` line endings become `\n`, and if `trim` is set the trailing new
+/// lines are removed so scripts ending in `echo` don't leave blank space behind.
+/// Other whitespace is kept as it may be intentional.
+pub fn normalize(output: &str, trim: bool) -> String {
+    let output = output.replace("\r\n", "\n");
+    if trim {
+        output.trim_end_matches('\n').to_string()
+    } else {
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_trims_trailing_new_lines() {
+        assert_eq!(normalize("42\n", true), "42");
+        assert_eq!(normalize("a\nb\n\n", true), "a\nb");
+        assert_eq!(normalize(" padded \n", true), " padded ");
+    }
+
+    /// Fails until it has been executed `failures` times.
+    struct FlakyRunner {
+        failures: u32,
+        executions: std::cell::Cell<u32>,
+    }
+
+    impl CommandRunner for FlakyRunner {
+        fn execute_with_env(&self, _: &str, _: Duration, _: &[(String, String)]) -> CommandOutput {
+            self.executions.set(self.executions.get() + 1);
+            CommandOutput {
+                success: self.executions.get() > self.failures,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[test]
+    fn failed_commands_are_retried() {
+        let flaky = FlakyRunner {
+            failures: 2,
+            executions: Default::default(),
+        };
+        let mut runner = RetryRunner {
+            runner: &flaky,
+            retry: Retry {
+                retries: 1,
+                delay: Duration::ZERO,
+            },
+        };
+
+        assert!(!runner.execute("flaky", Duration::ZERO).success);
+        assert_eq!(flaky.executions.get(), 2);
+
+        flaky.executions.set(0);
+        runner.retry.retries = 5;
+        assert!(runner.execute("flaky", Duration::ZERO).success);
+        assert_eq!(flaky.executions.get(), 3);
+    }
+
+    #[test]
+    fn retries_respect_the_timeout() {
+        let flaky = FlakyRunner {
+            failures: 1,
+            executions: Default::default(),
+        };
+        let runner = RetryRunner {
+            runner: &flaky,
+            retry: Retry {
+                retries: 3,
+                delay: Duration::from_millis(50),
+            },
+        };
+
+        assert!(!runner.execute("flaky", Duration::from_millis(10)).success);
+        assert_eq!(flaky.executions.get(), 1);
+    }
+
+    #[test]
+    fn env_runners_add_their_variables() {
+        let mock = MockRunner::default();
+        let env = [(String::from("HYBRID_A"), String::from("a"))];
+        let runner = EnvRunner {
+            runner: &mock,
+            env: &env,
+        };
+
+        runner.execute("env", Duration::ZERO);
+        assert_eq!(*mock.last_env.borrow(), env);
+
+        let extra = [(String::from("HYBRID_B"), String::from("b"))];
+        runner.execute_with_env("env", Duration::ZERO, &extra);
+        assert_eq!(*mock.last_env.borrow(), [env, extra].concat());
+    }
+
+    #[test]
+    fn workers_are_joined() {
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        spawn_worker(move || receiver.recv().unwrap_or_default());
+        spawn_worker(|| {});
+        assert_eq!(join_workers(Duration::from_millis(50)), 1);
+
+        drop(sender);
+        assert_eq!(join_workers(Duration::from_secs(5)), 0);
+    }
+
+    #[test]
+    fn normalize_collapses_crlf() {
+        assert_eq!(normalize("a\r\nb\r\n", true), "a\nb");
+        assert_eq!(normalize("a\r\nb\r\n", false), "a\nb\n");
+    }
+}
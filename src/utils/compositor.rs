@@ -0,0 +1,181 @@
+use crate::{
+    hyprland_ipc, locks,
+    structures::{Window, Workspace},
+    sway_ipc,
+};
+use std::{
+    io,
+    os::unix::net::UnixStream,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Gives every tracked stream a unique id, so it can be forgotten again.
+static STREAM_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    /// The event streams currently subscribed to by id, so they can be closed by `reconnect`.
+    static ref STREAMS: Mutex<Vec<(usize, UnixStream)>> = Mutex::new(Vec::new());
+}
+
+/// How long to wait before reconnecting after the IPC connection was lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Keeps track of an event stream while it's alive, so `reconnect` can close it.
+pub struct TrackedStream(usize);
+
+impl Drop for TrackedStream {
+    fn drop(&mut self) {
+        locks::lock(&STREAMS).retain(|(id, _)| *id != self.0);
+    }
+}
+
+/// Keeps track of the event stream `stream` until the returned value is dropped.
+pub fn track(stream: &UnixStream) -> io::Result<TrackedStream> {
+    let id = STREAM_COUNT.fetch_add(1, Ordering::SeqCst);
+    locks::lock(&STREAMS).push((id, stream.try_clone()?));
+    Ok(TrackedStream(id))
+}
+
+/// Closes every event stream, so their watchers reconnect. Connections which died without being
+/// closed, like over a suspend, would otherwise wait for events forever.
+pub fn reconnect() {
+    for (_, stream) in locks::lock(&STREAMS).iter() {
+        // Fails for streams which are already closed, which is fine.
+        let _ = stream.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+/// A compositor which Hybrid can talk to over IPC.
+#[derive(Clone, Copy, Debug)]
+pub enum Compositor {
+    /// Sway, or i3 for everything besides inputs.
+    Sway,
+    Hyprland,
+}
+
+/// What to get notified about.
+#[derive(Clone, Copy)]
+pub enum Events {
+    /// Workspaces and windows changing.
+    Workspaces,
+    /// Inputs changing, like the keyboard layout.
+    Input,
+    /// The focused window changing, or its title.
+    Window,
+}
+
+impl Compositor {
+    /// Detects the running compositor, Hyprland takes priority as it may be nested inside Sway.
+    pub fn detect() -> Option<Self> {
+        if hyprland_ipc::is_available() {
+            Some(Compositor::Hyprland)
+        } else if sway_ipc::get_socket_path().is_some() {
+            Some(Compositor::Sway)
+        } else {
+            None
+        }
+    }
+
+    /// Checks whether `events` can be subscribed to, i3 doesn't report inputs.
+    pub fn supports(self, events: Events) -> bool {
+        match (self, events) {
+            (Compositor::Sway, Events::Input) => sway_ipc::is_sway(),
+            _ => true,
+        }
+    }
+
+    /// Gets all workspaces.
+    pub fn get_workspaces(self) -> io::Result<Vec<Workspace>> {
+        match self {
+            Compositor::Sway => sway_ipc::get_workspaces(),
+            Compositor::Hyprland => hyprland_ipc::get_workspaces(),
+        }
+    }
+
+    /// Switches to `workspace`.
+    pub fn focus_workspace(self, workspace: &Workspace) -> io::Result<()> {
+        match self {
+            Compositor::Sway => sway_ipc::focus_workspace(workspace),
+            Compositor::Hyprland => hyprland_ipc::focus_workspace(workspace),
+        }
+    }
+
+    /// Gets the focused window, `None` if nothing is focused.
+    pub fn get_focused_window(self) -> io::Result<Option<Window>> {
+        match self {
+            Compositor::Sway => sway_ipc::get_focused_window(),
+            Compositor::Hyprland => hyprland_ipc::get_focused_window(),
+        }
+    }
+
+    /// Gets the active keyboard layout, `None` if there's no keyboard.
+    pub fn get_keyboard_layout(self) -> io::Result<Option<String>> {
+        match self {
+            Compositor::Sway => sway_ipc::get_keyboard_layout(),
+            Compositor::Hyprland => hyprland_ipc::get_keyboard_layout(),
+        }
+    }
+
+    /// Subscribes to `events`, calling `on_change` for every event until it returns `false` or
+    /// the connection is lost.
+    fn subscribe(self, events: Events, on_change: impl FnMut() -> bool) -> io::Result<()> {
+        match (self, events) {
+            (Compositor::Sway, Events::Workspaces) => {
+                sway_ipc::subscribe(sway_ipc::WORKSPACE_EVENTS, on_change)
+            }
+            (Compositor::Sway, Events::Input) => {
+                sway_ipc::subscribe(sway_ipc::INPUT_EVENTS, on_change)
+            }
+            (Compositor::Sway, Events::Window) => {
+                sway_ipc::subscribe(sway_ipc::WINDOW_EVENTS, on_change)
+            }
+            (Compositor::Hyprland, Events::Workspaces) => {
+                hyprland_ipc::subscribe(hyprland_ipc::WORKSPACE_EVENTS, on_change)
+            }
+            (Compositor::Hyprland, Events::Input) => {
+                hyprland_ipc::subscribe(hyprland_ipc::INPUT_EVENTS, on_change)
+            }
+            (Compositor::Hyprland, Events::Window) => {
+                hyprland_ipc::subscribe(hyprland_ipc::WINDOW_EVENTS, on_change)
+            }
+        }
+    }
+
+    /// Sends the result of `fetch` to `sender` right away, then again after each of `events`.
+    /// The connection is re-established if it's lost, for example when the compositor restarts.
+    pub fn watch<T: Send + 'static>(
+        self,
+        events: Events,
+        fetch: impl Fn(Compositor) -> io::Result<T> + Send + 'static,
+        sender: glib::Sender<T>,
+    ) {
+        thread::spawn(move || loop {
+            let send = || {
+                match fetch(self) {
+                    // Can only fail if the main loop is gone.
+                    Ok(value) => sender.send(value).is_ok(),
+                    Err(err) => {
+                        log::warn!("Failed reading from {self:?}: {err}");
+                        true
+                    }
+                }
+            };
+
+            if !send() {
+                return;
+            }
+
+            match self.subscribe(events, send) {
+                Ok(_) => return,
+                Err(err) => log::warn!("Lost the connection to {self:?}: {err}, reconnecting"),
+            }
+
+            thread::sleep(RECONNECT_DELAY);
+        });
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{cli, placeholders, ui};
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Requests longer than this are rejected, and the connection is closed.
+const MAX_LINE_LENGTH: usize = 4096;
+
+/// Connections which don't send anything for this long are closed.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a `reload` waits for the main loop before giving up.
+const RELOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether this bar opened the control socket, so it's the one to remove it.
+static IS_LISTENING: AtomicBool = AtomicBool::new(false);
+
+/// Where a reload is sent to the main loop, along with where to send its result.
+type ReloadSender = glib::Sender<mpsc::Sender<Result<(), String>>>;
+
+/// Gets the path of the control socket, `$XDG_RUNTIME_DIR/hybrid-bar.sock`, or
+/// `hybrid-bar.<profile>.sock` with `--profile`, so bars of different profiles don't clash.
+/// `None` without an `XDG_RUNTIME_DIR`, as anything else may be readable by other users.
+pub fn get_socket_path() -> Option<PathBuf> {
+    let runtime_dir = env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .filter(|dir| !dir.is_empty())?;
+    let name = match &cli::ARGS.profile {
+        Some(profile) => format!("hybrid-bar.{profile}.sock"),
+        None => String::from("hybrid-bar.sock"),
+    };
+
+    Some(PathBuf::from(runtime_dir).join(name))
+}
+
+/// Escapes `value` so it fits on a single line, `\` as `\\` and new lines as `\n`.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Handles a single request line, calling `reload` for `reload`. Returns the response line:
+/// - `get <name>` gets the current value of the widget named `name`, `ok <value>`
+/// - `reload` reloads the config like a `SIGUSR1` does, `ok`
+///
+/// Anything else, and widgets without a value, are answered with `error <reason>`.
+pub fn handle(request: &str, reload: impl FnOnce() -> Result<(), String>) -> String {
+    let request = request.trim();
+    let (command, argument) = request.split_once(' ').unwrap_or((request, ""));
+    let result = match (command, argument.trim()) {
+        ("get", "") => Err(String::from("expected a widget name, like `get clock`")),
+        ("get", name) => placeholders::get_value(name)
+            .map(|value| escape(&value))
+            .ok_or_else(|| format!("'{name}' has no value, is it a widget with a name?")),
+        ("reload", "") => reload().map(|_| String::new()),
+        ("reload", _) => Err(String::from("reload doesn't take any arguments")),
+        ("", _) => Err(String::from("empty request")),
+        (command, _) => Err(format!(
+            "unknown command '{command}', expected get or reload"
+        )),
+    };
+
+    match result {
+        Ok(value) if value.is_empty() => String::from("ok"),
+        Ok(value) => format!("ok {value}"),
+        Err(err) => format!("error {}", escape(&err)),
+    }
+}
+
+/// Sends a reload to the main loop, and waits for its result.
+fn request_reload(reloads: &ReloadSender) -> Result<(), String> {
+    let (sender, receiver) = mpsc::channel();
+    reloads
+        .send(sender)
+        .map_err(|_| String::from("the bar is shutting down"))?;
+    receiver
+        .recv_timeout(RELOAD_TIMEOUT)
+        .map_err(|_| String::from("timed out waiting for the reload"))?
+}
+
+/// Answers the requests on `stream`, one per line, until it's closed, idle or misbehaving.
+fn serve(stream: UnixStream, reloads: &ReloadSender) {
+    let _ = stream.set_read_timeout(Some(IDLE_TIMEOUT));
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut line = Vec::new();
+        match (&mut reader)
+            .take(MAX_LINE_LENGTH as u64 + 1)
+            .read_until(b'\n', &mut line)
+        {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+
+        // Whatever follows an overly long line can't be told apart from its rest.
+        if line.len() > MAX_LINE_LENGTH {
+            let _ = writeln!(
+                writer,
+                "error request is longer than {MAX_LINE_LENGTH} bytes"
+            );
+            return;
+        }
+
+        let response = match std::str::from_utf8(&line) {
+            Ok(request) => handle(request, || request_reload(reloads)),
+            Err(_) => String::from("error request isn't valid UTF-8"),
+        };
+
+        if writeln!(writer, "{response}").is_err() {
+            return;
+        }
+    }
+}
+
+/// Opens the control socket at `get_socket_path()`, so other programs can read widget values and
+/// reload the config. A socket left behind by a bar that didn't shut down cleanly is replaced,
+/// one that's still in use is left alone.
+/// The socket is only opened once, later calls would fail to bind it.
+pub fn listen() {
+    let Some(path) = get_socket_path() else {
+        log::info!("XDG_RUNTIME_DIR isn't set, not opening the control socket");
+        return;
+    };
+
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            log::warn!(
+                "Another bar is using the control socket at '{}', not opening it",
+                path.display()
+            );
+            return;
+        }
+
+        let _ = fs::remove_file(&path);
+    }
+
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::warn!(
+                "Failed opening the control socket at '{}': {err}",
+                path.display()
+            );
+            return;
+        }
+    };
+
+    // Reloads touch GTK, so they're forwarded to the main loop.
+    let (reloads, receiver) =
+        glib::MainContext::channel::<mpsc::Sender<Result<(), String>>>(glib::PRIORITY_DEFAULT);
+    receiver.attach(None, |reply| {
+        let result = ui::reload("the control socket", false).map_err(|err| err.to_string());
+
+        // The client may have given up already.
+        let _ = reply.send(result);
+        glib::Continue(true)
+    });
+
+    IS_LISTENING.store(true, Ordering::SeqCst);
+    log::debug!("Listening on '{}'", path.display());
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let reloads = reloads.clone();
+            thread::spawn(move || serve(stream, &reloads));
+        }
+    });
+}
+
+/// Removes the control socket, unless it's used by another bar.
+pub fn remove_socket() {
+    if let Some(path) = get_socket_path().filter(|_| IS_LISTENING.load(Ordering::SeqCst)) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests() {
+        placeholders::publish("control_test", "multi\nline");
+        assert_eq!(handle("get control_test\n", || Ok(())), "ok multi\\nline");
+        assert!(handle("get missing_widget", || Ok(())).starts_with("error "));
+        assert!(handle("get", || Ok(())).starts_with("error "));
+
+        assert_eq!(handle("reload", || Ok(())), "ok");
+        assert_eq!(
+            handle("reload", || Err(String::from("broken"))),
+            "error broken"
+        );
+        assert!(handle("reload now", || Ok(())).starts_with("error "));
+        assert!(handle("shutdown", || Ok(())).contains("'shutdown'"));
+        assert!(handle("  ", || Ok(())).starts_with("error "));
+    }
+}
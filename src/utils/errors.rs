@@ -0,0 +1,90 @@
+use crate::{command::CommandOutput, config, locks, overflow, pause};
+use glib::Continue;
+use gtk::{traits::*, Label};
+use std::{sync::Mutex, time::Duration};
+
+/// How often the error widget checks for new errors.
+const REFRESH_RATE: Duration = Duration::from_millis(250);
+
+lazy_static! {
+    /// The active errors as `(widget name, message)`, the most recent one last.
+    static ref ERRORS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+}
+
+/// Reports `message` as the current error of the widget named `name`, replacing its previous one.
+pub fn report(name: &str, message: &str) {
+    let mut errors = locks::lock(&ERRORS);
+    errors.retain(|(other, _)| other != name);
+    errors.push((name.to_string(), message.to_string()));
+}
+
+/// Clears the error of the widget named `name`, if it has one.
+pub fn clear(name: &str) {
+    locks::lock(&ERRORS).retain(|(other, _)| other != name);
+}
+
+/// Reports an error for the widget named `name` if `output` of `command` failed, and clears it
+/// otherwise.
+pub fn check(name: &str, command: &str, output: &CommandOutput) {
+    if output.success {
+        clear(name);
+        return;
+    }
+
+    // Commands that timed out or couldn't be spawned have no stderr.
+    match output.stderr.lines().find(|line| !line.trim().is_empty()) {
+        Some(line) => report(name, &format!("'{command}' failed: {line}")),
+        None => report(name, &format!("'{command}' failed")),
+    }
+}
+
+/// Gets the most recent error, formatted as `name: message`.
+pub fn latest() -> Option<String> {
+    locks::lock(&ERRORS)
+        .last()
+        .map(|(name, message)| format!("{name}: {message}"))
+}
+
+/// Whether the widget named `name` is the one from `hybrid` -> `error_widget`.
+pub fn is_error_widget(name: &str) -> bool {
+    config::try_get("hybrid", "error_widget", true, false).is_some_and(|widget| widget.0 == name)
+}
+
+/// Shows the most recent error in `label`, which is hidden while there are none.
+pub fn bind(label: &Label) {
+    label.set_no_show_all(true);
+    let label = label.clone();
+    let sync = move || {
+        let error = latest();
+        label.set_visible(error.is_some());
+        let error = error.unwrap_or_default();
+        if overflow::get_content(&label) != error {
+            label.set_text(&error);
+        }
+    };
+
+    sync();
+    pause::timeout_add_local(REFRESH_RATE, move || {
+        sync();
+        Continue(true)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latest_error_wins_until_cleared() {
+        report("tests-a", "first");
+        report("tests-b", "second");
+        assert_eq!(latest().as_deref(), Some("tests-b: second"));
+
+        report("tests-a", "third");
+        assert_eq!(latest().as_deref(), Some("tests-a: third"));
+
+        clear("tests-a");
+        clear("tests-b");
+        assert!(latest().is_none());
+    }
+}
@@ -0,0 +1,188 @@
+use crate::{
+    command::{self, BashRunner, CommandRunner},
+    config, errors, placeholders, registry, ui,
+};
+use glib::Continue;
+use gtk::{
+    gdk::{EventMask, ScrollDirection},
+    prelude::*,
+    EventBox, Orientation,
+};
+use std::{cell::Cell, time::Duration};
+
+/// The click keys, paired with the mouse button they respond to.
+const CLICK_KEYS: [(&str, u32); 3] = [
+    ("on_click", 1),
+    ("on_middle_click", 2),
+    ("on_right_click", 3),
+];
+
+/// How far a smooth scroll (for example on a trackpad) has to go before the scroll command is run,
+/// one notch on a mouse wheel is `1.0`.
+const SCROLL_THRESHOLD: f64 = 1.0;
+
+/// Creates an event box for the widget at `key` if it has any click, scroll or tooltip commands set,
+/// so that widgets which can't receive input themselves (like labels) can still be clicked.
+/// Scrolling text needs one too, as it pauses while hovered.
+/// Returns the event box, plus the box the widget should be added to.
+pub fn create_event_box(key: &str) -> Option<(EventBox, gtk::Box)> {
+    // Variables are applied here, rather than on every click.
+    let clicks: Vec<(u32, String)> = CLICK_KEYS
+        .iter()
+        .filter_map(|(click_key, button)| {
+            config::try_get(key, click_key, true, true).map(|command| (*button, command.0))
+        })
+        .filter(|(_, command)| !command.is_empty())
+        .collect();
+
+    let scroll_up = config::get_or_default(key, "on_scroll_up", true, true).0;
+    let scroll_down = config::get_or_default(key, "on_scroll_down", true, true).0;
+    let tooltip_command = config::get_or_default(key, "tooltip_command", true, true).0;
+    if clicks.is_empty()
+        && scroll_up.is_empty()
+        && scroll_down.is_empty()
+        && tooltip_command.is_empty()
+        && !config::get_or_default_bool(key, "scroll")
+    {
+        return None;
+    }
+
+    let event_box = EventBox::new();
+    let orientation = if config::get_position().is_vertical() {
+        Orientation::Vertical
+    } else {
+        Orientation::Horizontal
+    };
+
+    let inner = gtk::Box::new(orientation, 0);
+    event_box.add(&inner);
+
+    if !scroll_up.is_empty() || !scroll_down.is_empty() {
+        connect_scroll(&event_box, key, scroll_up, scroll_down);
+    }
+
+    if !tooltip_command.is_empty() {
+        connect_tooltip_command(&event_box, &inner, key, tooltip_command);
+    }
+
+    let key = key.to_string();
+    event_box.connect_button_press_event(move |_, event| {
+        let Some((_, command)) = clicks.iter().find(|(button, _)| *button == event.button()) else {
+            return Inhibit(false);
+        };
+
+        log::debug!("Widget '{key}' -> Clicked ({})", event.button());
+        run(command, get_env(&key));
+        Inhibit(true)
+    });
+
+    Some((event_box, inner))
+}
+
+/// Runs `scroll_up` or `scroll_down` when `event_box` is scrolled. Empty commands are ignored.
+fn connect_scroll(event_box: &EventBox, key: &str, scroll_up: String, scroll_down: String) {
+    let key = key.to_string();
+    connect_scroll_steps(event_box, move |steps| {
+        let command = if steps < 0 { &scroll_up } else { &scroll_down };
+        if command.is_empty() {
+            return false;
+        }
+
+        log::debug!("Widget '{key}' -> Scrolled ({steps})");
+        for _ in 0..steps.abs() {
+            run(command, get_env(&key));
+        }
+
+        true
+    });
+}
+
+/// Calls `on_scroll` with how many steps `event_box` was scrolled, negative steps mean up.
+/// `on_scroll` returns whether it handled the scroll, otherwise it's passed on to the parent.
+pub fn connect_scroll_steps(event_box: &EventBox, on_scroll: impl Fn(i32) -> bool + 'static) {
+    event_box.add_events(EventMask::SCROLL_MASK | EventMask::SMOOTH_SCROLL_MASK);
+    // Smooth scrolls arrive as many small deltas, which are added up until they pass
+    // `SCROLL_THRESHOLD` so a single flick doesn't run dozens of commands.
+    let accumulated = Cell::new(0.0);
+    event_box.connect_scroll_event(move |_, event| {
+        let steps = match event.direction() {
+            ScrollDirection::Up => -1,
+            ScrollDirection::Down => 1,
+            ScrollDirection::Smooth => {
+                let mut delta = accumulated.get() + event.delta().1;
+                let steps = (delta / SCROLL_THRESHOLD).trunc();
+                delta -= steps * SCROLL_THRESHOLD;
+                accumulated.set(delta);
+                steps as i32
+            }
+            _ => 0,
+        };
+
+        Inhibit(steps != 0 && on_scroll(steps))
+    });
+}
+
+/// Sets the tooltip of the widget inside `event_box` to the output of `command`, which is run once when it's created
+/// and then every time the pointer enters it.
+fn connect_tooltip_command(event_box: &EventBox, inner: &gtk::Box, key: &str, command: String) {
+    // Commands are run on worker threads, the output is then sent back to the main thread which
+    // updates the tooltip.
+    let (sender, receiver) = glib::MainContext::channel::<String>(glib::PRIORITY_DEFAULT);
+    let inner = inner.clone();
+    registry::attach(receiver, move |tooltip| {
+        // Set on the widget too, as its own (possibly empty) tooltip would be shown otherwise.
+        for child in inner.children() {
+            child.set_tooltip_markup(Some(&tooltip));
+        }
+
+        Continue(true)
+    });
+
+    let timeout = Duration::from_millis(config::get_command_timeout(key));
+    let key = key.to_string();
+    let refresh = move || {
+        let sender = sender.clone();
+        let command = command.clone();
+        let env = get_env(&key);
+        let name = ui::get_widget_name(&key);
+        command::spawn_worker(move || {
+            let output = BashRunner.execute_with_env(&command, timeout, &env);
+            errors::check(&name, &command, &output);
+            // Can only fail if the main loop is gone.
+            let _ = sender.send(output.stdout);
+        });
+    };
+
+    refresh();
+    event_box.add_events(EventMask::ENTER_NOTIFY_MASK);
+    event_box.connect_enter_notify_event(move |_, _| {
+        refresh();
+        Inhibit(false)
+    });
+}
+
+/// Gets the environment for the event commands of the widget at `key`. With `export_variables`
+/// set that's the custom variables, plus the current value of the widget as `HYBRID_VALUE`.
+fn get_env(key: &str) -> Vec<(String, String)> {
+    if !config::get_or_default_bool(key, "export_variables") {
+        return Vec::new();
+    }
+
+    let mut env = config::get_variable_env(key);
+    let value = placeholders::get_value(&ui::get_widget_name(key)).unwrap_or_default();
+    env.push((String::from("HYBRID_VALUE"), value));
+    env
+}
+
+/// Runs `command` with `env` on a separate thread, so the UI isn't blocked while it's running.
+fn run(command: &str, env: Vec<(String, String)>) {
+    let command = command.to_string();
+    if env.is_empty() {
+        command::spawn_worker(move || drop(execute!(&command)));
+    } else {
+        // Commands run on click may take as long as they want, just like with `execute!`.
+        command::spawn_worker(move || {
+            BashRunner.execute_with_env(&command, Duration::ZERO, &env);
+        });
+    }
+}
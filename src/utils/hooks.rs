@@ -0,0 +1,46 @@
+use crate::{
+    command::{self, BashRunner, CommandRunner},
+    config, errors,
+};
+use std::time::Duration;
+
+/// Gets the commands of the hook at `hybrid` -> `hook`, which is either a single command or an
+/// array of them. Variables are expanded in them.
+pub fn get_commands(hook: &str) -> Vec<String> {
+    config::try_get_array("hybrid", hook).unwrap_or_else(|| {
+        config::try_get("hybrid", hook, true, true)
+            .map(|command| vec![command.0])
+            .unwrap_or_default()
+    })
+}
+
+/// Runs the commands of the hook at `hybrid` -> `hook` one after another, so a later one can rely
+/// on an earlier one, each limited by `hybrid` -> `command_timeout`.
+/// Blocks until all of them are done. Failures are logged and shown on the error widget.
+pub fn run(hook: &str) {
+    run_with(hook, &BashRunner);
+}
+
+/// Same as `run`, but executes the commands through `runner`.
+pub fn run_with(hook: &str, runner: &impl CommandRunner) {
+    let commands = get_commands(hook);
+    if commands.is_empty() {
+        return;
+    }
+
+    let name = format!("hybrid:{hook}");
+    let timeout = Duration::from_millis(config::get_command_timeout("hybrid"));
+    errors::clear(&name);
+    for command in commands.iter().filter(|command| !command.is_empty()) {
+        log::debug!("Running {name} '{command}'");
+        let output = runner.execute(command, timeout);
+        if !output.success {
+            errors::check(&name, command, &output);
+        }
+    }
+}
+
+/// Same as `run`, but on a separate thread so the caller isn't blocked.
+pub fn run_in_background(hook: &'static str) {
+    command::spawn_worker(move || run(hook));
+}
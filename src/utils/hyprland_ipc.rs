@@ -0,0 +1,178 @@
+use crate::{
+    compositor,
+    structures::{Window, Workspace},
+};
+use json::JsonValue;
+use std::{
+    env,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+};
+
+/// Events after which the workspaces are fetched again.
+pub const WORKSPACE_EVENTS: &[&str] = &[
+    "workspace",
+    "createworkspace",
+    "destroyworkspace",
+    "moveworkspace",
+    "renameworkspace",
+    "focusedmon",
+    "openwindow",
+    "closewindow",
+    "movewindow",
+];
+
+/// Events after which the focused window is fetched again.
+pub const WINDOW_EVENTS: &[&str] = &["activewindow", "windowtitle", "closewindow", "workspace"];
+
+/// Events after which the keyboard layout is fetched again.
+pub const INPUT_EVENTS: &[&str] = &["activelayout"];
+
+/// Returns the directory holding the sockets of the running Hyprland instance.
+/// Hyprland 0.40+ places them in `$XDG_RUNTIME_DIR/hypr`, older versions in `/tmp/hypr`.
+fn get_socket_dir() -> Option<PathBuf> {
+    let signature = env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()?;
+    if signature.is_empty() {
+        return None;
+    }
+
+    let mut candidates = vec![PathBuf::from("/tmp/hypr").join(&signature)];
+    if let Ok(runtime_dir) = env::var("XDG_RUNTIME_DIR") {
+        candidates.insert(0, PathBuf::from(runtime_dir).join("hypr").join(&signature));
+    }
+
+    candidates
+        .into_iter()
+        .find(|dir| dir.join(".socket2.sock").exists())
+}
+
+/// Checks whether Hyprland is running.
+pub fn is_available() -> bool {
+    get_socket_dir().is_some()
+}
+
+/// Connects to `socket` inside of the socket directory.
+fn connect(socket: &str) -> io::Result<UnixStream> {
+    let dir = get_socket_dir().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Couldn't find the Hyprland sockets, is $HYPRLAND_INSTANCE_SIGNATURE set?",
+        )
+    })?;
+
+    UnixStream::connect(dir.join(socket))
+}
+
+/// Sends `request` over the command socket, the same way `hyprctl` does, and returns the reply.
+fn request(request: &str) -> io::Result<String> {
+    let mut stream = connect(".socket.sock")?;
+    stream.write_all(request.as_bytes())?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(reply)
+}
+
+/// Sends `request` and parses the JSON reply.
+fn request_json(command: &str) -> io::Result<JsonValue> {
+    json::parse(&request(&format!("j/{command}"))?)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Switches to `workspace`, like `hyprctl dispatch workspace N` but without spawning it.
+pub fn focus_workspace(workspace: &Workspace) -> io::Result<()> {
+    let target = if workspace.id > 0 {
+        workspace.id.to_string()
+    } else {
+        format!("name:{}", workspace.name)
+    };
+
+    let reply = request(&format!("dispatch workspace {target}"))?;
+    if reply.trim() != "ok" {
+        return Err(io::Error::other(reply));
+    }
+
+    Ok(())
+}
+
+/// Gets all workspaces, sorted by their number.
+pub fn get_workspaces() -> io::Result<Vec<Workspace>> {
+    let active = request_json("activeworkspace")?["id"].as_i64();
+    let visible: Vec<i64> = request_json("monitors")?
+        .members()
+        .filter_map(|monitor| monitor["activeWorkspace"]["id"].as_i64())
+        .collect();
+
+    let mut workspaces: Vec<Workspace> = request_json("workspaces")?
+        .members()
+        .filter_map(|workspace| {
+            let id = workspace["id"].as_i64()?;
+            // Special workspaces (the scratchpad) have negative ids and aren't switched to.
+            if id < 0 {
+                return None;
+            }
+
+            Some(Workspace {
+                id,
+                name: workspace["name"].to_string(),
+                focused: active == Some(id),
+                visible: visible.contains(&id),
+                urgent: false,
+                occupied: workspace["windows"].as_u32().unwrap_or_default() > 0,
+            })
+        })
+        .collect();
+
+    workspaces.sort_by_key(|workspace| workspace.id);
+    Ok(workspaces)
+}
+
+/// Gets the focused window, `None` if nothing is focused like on an empty workspace.
+pub fn get_focused_window() -> io::Result<Option<Window>> {
+    let window = request_json("activewindow")?;
+    // An empty object when there's no active window.
+    if window["address"].is_null() {
+        return Ok(None);
+    }
+
+    Ok(Some(Window {
+        title: window["title"].as_str().unwrap_or_default().to_string(),
+        app_id: window["class"].as_str().unwrap_or_default().to_string(),
+    }))
+}
+
+/// Gets the active layout of the main keyboard, for example `English (US)`.
+pub fn get_keyboard_layout() -> io::Result<Option<String>> {
+    let devices = request_json("devices")?;
+    let keyboards = || devices["keyboards"].members();
+    // Older versions don't mark the main keyboard, fall back to the first one.
+    Ok(keyboards()
+        .find(|keyboard| keyboard["main"] == true)
+        .or_else(|| keyboards().next())
+        .and_then(|keyboard| keyboard["active_keymap"].as_str())
+        .map(String::from))
+}
+
+/// Listens to the socket2 event stream, calling `on_change` for every event in `events` until it
+/// returns `false` or the connection is lost.
+pub fn subscribe(events: &[&str], mut on_change: impl FnMut() -> bool) -> io::Result<()> {
+    let stream = connect(".socket2.sock")?;
+    let _tracked = compositor::track(&stream)?;
+    let reader = BufReader::new(stream);
+    for line in reader.lines() {
+        // Events are formatted as `EVENT>>DATA`.
+        let line = line?;
+        let Some((event, _)) = line.split_once(">>") else {
+            continue;
+        };
+
+        if events.contains(&event) && !on_change() {
+            return Ok(());
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "The Hyprland event socket was closed",
+    ))
+}
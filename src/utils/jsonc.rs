@@ -0,0 +1,156 @@
+/// Converts JSON with comments (JSONC) into plain JSON, so it can be parsed by the `json` crate.
+/// `//` and `/* */` comments are removed, along with trailing commas before `}` and `]`.
+/// Everything that's removed is replaced by spaces, so the line and column of parse errors still
+/// point at the right place in the original file.
+pub fn to_json(input: &str) -> String {
+    remove_trailing_commas(&remove_comments(input))
+}
+
+/// Replaces all comments outside of strings with spaces, keeping new lines.
+fn remove_comments(input: &str) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    let mut in_string = false;
+    while let Some(char) = chars.next() {
+        if in_string {
+            result.push(char);
+            match char {
+                // Don't let an escaped quote end the string.
+                '\\' => result.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+
+            continue;
+        }
+
+        match (char, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                result.push(char);
+            }
+            ('/', Some('/')) => {
+                result.push(' ');
+                while let Some(char) = chars.next_if(|char| *char != '\n') {
+                    result.push(blank(char));
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                result.push_str("  ");
+                // Unterminated comments run until the end of the file.
+                while let Some(char) = chars.next() {
+                    if char == '*' && chars.peek() == Some(&'/') {
+                        chars.next();
+                        result.push_str("  ");
+                        break;
+                    }
+
+                    result.push(blank(char));
+                }
+            }
+            _ => result.push(char),
+        }
+    }
+
+    result
+}
+
+/// Replaces commas which are directly followed by `}` or `]` with spaces.
+fn remove_trailing_commas(input: &str) -> String {
+    let chars: Vec<char> = input.chars().collect();
+    let mut result = String::with_capacity(input.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, char) in chars.iter().enumerate() {
+        if in_string {
+            match char {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if *char == '"' {
+            in_string = true;
+        } else if *char == ',' {
+            let next = chars[index + 1..].iter().find(|char| !char.is_whitespace());
+            if matches!(next, Some('}' | ']')) {
+                result.push(' ');
+                continue;
+            }
+        }
+
+        result.push(*char);
+    }
+
+    result
+}
+
+/// Replaces `char` with a space, unless it's a new line.
+fn blank(char: char) -> char {
+    if char == '\n' {
+        '\n'
+    } else {
+        ' '
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_are_removed() {
+        let config = json::parse(&to_json(
+            r#"{
+                // The bar itself.
+                "hybrid": { /* inline */ "namespace": "hybrid" },
+                /* Spans
+                   several lines. */
+                "left-label_a": { "text": "a" }
+            }"#,
+        ))
+        .unwrap();
+        assert_eq!(config["hybrid"]["namespace"], "hybrid");
+        assert_eq!(config["left-label_a"]["text"], "a");
+    }
+
+    #[test]
+    fn comments_inside_strings_are_kept() {
+        let config = json::parse(&to_json(
+            r#"{ "url": "https://example.com", "text": "/* not a comment */", "quote": "\"//\"" }"#,
+        ))
+        .unwrap();
+        assert_eq!(config["url"], "https://example.com");
+        assert_eq!(config["text"], "/* not a comment */");
+        assert_eq!(config["quote"], "\"//\"");
+    }
+
+    #[test]
+    fn trailing_commas_are_removed() {
+        let config = json::parse(&to_json(
+            r#"{
+                "left-label_a": {
+                    "transform": [{ "round": 1 }, { "suffix": "," },],
+                    "nested": { "list": [[1, 2,], [3,],], },
+                },
+            }"#,
+        ))
+        .unwrap();
+        assert_eq!(config["left-label_a"]["transform"].len(), 2);
+        assert_eq!(config["left-label_a"]["transform"][1]["suffix"], ",");
+        assert_eq!(config["left-label_a"]["nested"]["list"][0][1], 2);
+        assert_eq!(config["left-label_a"]["nested"]["list"][1].len(), 1);
+        // Only commas directly before a closing bracket are trailing.
+        assert_eq!(to_json(r#"{ "text": ",}" }"#), r#"{ "text": ",}" }"#);
+    }
+
+    #[test]
+    fn errors_point_at_the_original_line() {
+        let error = json::parse(&to_json(
+            "{\n  // A comment.\n  /* Another\n  one. */ \"a\": 1,\n  \"text\" \"b\"\n}",
+        ))
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Unexpected character: \" at (5:10)");
+    }
+}
@@ -0,0 +1,34 @@
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Locks `lock` for reading. If another thread panicked while holding it, the guard is recovered
+/// with a warning rather than panicking here too, so one failing widget can't take down the bar.
+pub fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|err| {
+        warn_poisoned();
+        lock.clear_poison();
+        err.into_inner()
+    })
+}
+
+/// Same as `read`, but locks `lock` for writing.
+pub fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|err| {
+        warn_poisoned();
+        lock.clear_poison();
+        err.into_inner()
+    })
+}
+
+/// Same as `read`, but for a `Mutex`.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err: PoisonError<_>| {
+        warn_poisoned();
+        mutex.clear_poison();
+        err.into_inner()
+    })
+}
+
+/// Logged once per poisoning, as the poison is cleared after recovering.
+fn warn_poisoned() {
+    log::warn!("A thread panicked while holding a lock, recovering its data and carrying on");
+}
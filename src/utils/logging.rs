@@ -0,0 +1,100 @@
+use env_logger::{Builder, Target, WriteStyle};
+use log::{Level, LevelFilter};
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    panic,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The level of HybridBar's own logs when `HYBRID_LOG` isn't set.
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// Whether levels are colored, decided once by `init`.
+static COLORED: AtomicBool = AtomicBool::new(false);
+
+/// Checks whether stderr should be colored: only for terminals, and never with `--no-color` or
+/// a non-empty `NO_COLOR` (https://no-color.org), so files and the journal get plain text.
+fn should_color() -> bool {
+    // Parsed by hand rather than through `cli::ARGS`, same as `--verbose`.
+    let no_color = env::args().skip(1).any(|arg| arg == "--no-color")
+        || env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    !no_color && io::stderr().is_terminal()
+}
+
+/// Formats `level` like `ERROR`, colored if stderr is.
+pub fn level(level: Level) -> String {
+    if !COLORED.load(Ordering::Relaxed) {
+        return level.to_string();
+    }
+
+    let color = match level {
+        Level::Error => 31,
+        Level::Warn => 33,
+        Level::Info => 32,
+        Level::Debug => 36,
+        Level::Trace => 35,
+    };
+
+    format!("\x1b[{color}m{level}\x1b[0m")
+}
+
+/// Initializes the logger, call this before anything is logged.
+/// `HYBRID_LOG` accepts a level (`off`, `error`, `warn`, `info`, `debug` or `trace`) or
+/// `env_logger` filters like `hybrid_bar=debug,zbus=warn`. `1` and `0` are kept from before
+/// `0.3.5`, meaning `debug` and the default level respectively.
+pub fn init() {
+    COLORED.store(should_color(), Ordering::Relaxed);
+    let mut builder = Builder::new();
+    builder
+        .target(Target::Stderr)
+        // 0.3.5: Colors are added by `level`, which knows about `--no-color` and `NO_COLOR`.
+        .write_style(WriteStyle::Never)
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "[HYBRID] [{}] {}",
+                level(record.level()),
+                record.args()
+            )
+        })
+        .filter_level(LevelFilter::Warn);
+
+    // Parsed by hand rather than through `cli::ARGS`, so that argument errors are logged too.
+    let verbose = env::args()
+        .skip(1)
+        .any(|arg| arg == "-v" || arg == "--verbose");
+
+    match env::var("HYBRID_LOG").unwrap_or_default().trim() {
+        _ if verbose => filter_own(&mut builder, LevelFilter::Debug),
+        "" | "0" => filter_own(&mut builder, DEFAULT_LEVEL),
+        "1" => filter_own(&mut builder, LevelFilter::Debug),
+        filters => match filters.parse::<LevelFilter>() {
+            Ok(level) => filter_own(&mut builder, level),
+            Err(_) => {
+                builder.parse_filters(filters);
+            }
+        },
+    }
+
+    builder.init();
+
+    // Log panics too, so they end up in the same place as everything else.
+    panic::set_hook(Box::new(|info| {
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        match info.location() {
+            Some(location) => log::error!("{message} ({location})"),
+            None => log::error!("{message}"),
+        }
+    }));
+}
+
+/// Applies `level` to HybridBar's own logs only, dependencies stay at `warn` as they can be noisy.
+fn filter_own(builder: &mut Builder, level: LevelFilter) {
+    builder.filter_module(env!("CARGO_CRATE_NAME"), level);
+}
@@ -8,3 +8,90 @@ pub fn clamp_i32(input: i32, min: i32, max: i32) -> i32 {
 
     input
 }
+
+/// Ensures the `input` is within the range of `min` and `max`.
+/// NaN isn't within any range, so it becomes `min`.
+pub fn clamp_f64(input: f64, min: f64, max: f64) -> f64 {
+    if input.is_nan() || input < min {
+        return min;
+    } else if input > max {
+        return max;
+    }
+
+    input
+}
+
+/// Linearly interpolates between `a` and `b`, `t` is clamped to `0.0..=1.0`.
+pub fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * clamp_f64(t, 0.0, 1.0)
+}
+
+/// Cubic easing which starts and ends slowly, `t` is clamped to `0.0..=1.0`.
+/// Pass the result to `lerp` to animate between two values.
+pub fn ease_in_out(t: f64) -> f64 {
+    let t = clamp_f64(t, 0.0, 1.0);
+    if t < 0.5 {
+        4.0 * t * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamp_f64_below_min() {
+        assert_eq!(clamp_f64(-0.5, 0.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn clamp_f64_above_max() {
+        assert_eq!(clamp_f64(1.5, 0.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn clamp_f64_in_range() {
+        assert_eq!(clamp_f64(0.25, 0.0, 1.0), 0.25);
+    }
+
+    #[test]
+    fn clamp_f64_nan() {
+        assert_eq!(clamp_f64(f64::NAN, 0.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn lerp_bounds() {
+        assert_eq!(lerp(10.0, 20.0, 0.0), 10.0);
+        assert_eq!(lerp(10.0, 20.0, 1.0), 20.0);
+    }
+
+    #[test]
+    fn lerp_midpoint() {
+        assert_eq!(lerp(10.0, 20.0, 0.5), 15.0);
+        assert_eq!(lerp(20.0, 10.0, 0.25), 17.5);
+    }
+
+    #[test]
+    fn lerp_clamps_t() {
+        assert_eq!(lerp(10.0, 20.0, -1.0), 10.0);
+        assert_eq!(lerp(10.0, 20.0, 2.0), 20.0);
+    }
+
+    #[test]
+    fn ease_in_out_bounds() {
+        assert_eq!(ease_in_out(0.0), 0.0);
+        assert_eq!(ease_in_out(0.5), 0.5);
+        assert_eq!(ease_in_out(1.0), 1.0);
+        assert_eq!(ease_in_out(-1.0), 0.0);
+        assert_eq!(ease_in_out(2.0), 1.0);
+    }
+
+    #[test]
+    fn ease_in_out_is_symmetric_and_slow_at_the_edges() {
+        assert!(ease_in_out(0.1) < 0.1);
+        assert!(ease_in_out(0.9) > 0.9);
+        assert!((ease_in_out(0.25) + ease_in_out(0.75) - 1.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,327 @@
+use crate::{config, math, pause, scale};
+use glib::Continue;
+use gtk::{
+    gdk::{EventMask, NotifyType},
+    pango::{self, EllipsizeMode},
+    prelude::*,
+    Bin, EventBox, Justification, Label, Widget,
+};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Duration,
+};
+
+/// Appended to text which has been cut off.
+const ELLIPSIS: char = '…';
+
+/// Put between the end and the start of scrolling text, so they can be told apart.
+const SCROLL_GAP: &str = "   ";
+
+/// How many characters per second text scrolls by when `scroll_speed` isn't specified.
+const DEFAULT_SCROLL_SPEED: f64 = 4.0;
+
+/// The object data key holding the `Content` of labels which are cut off or scrolling.
+const CONTENT_KEY: &str = "hybrid-content";
+
+/// The content of a label as set by its widget, along with the part of it that's shown.
+struct Content {
+    set: String,
+    shown: String,
+}
+
+/// Gets the content of `label` as it was last set by its widget, including any markup. Unlike
+/// `label.label()` that's before it was cut off or scrolled, so widgets comparing against it
+/// don't set the same content again on every update.
+pub fn get_content(label: &Label) -> String {
+    // SAFETY: `CONTENT_KEY` is only ever set to a `Content`, by `set_content`.
+    unsafe {
+        label
+            .data::<Content>(CONTENT_KEY)
+            .map(|content| content.as_ref().set.clone())
+    }
+    .unwrap_or_else(|| label.label().to_string())
+}
+
+/// Checks whether `label` is showing what was set through `set_content`, so the change it has
+/// been notified of is its own.
+fn is_shown(label: &Label) -> bool {
+    // SAFETY: See `get_content`.
+    unsafe { label.data::<Content>(CONTENT_KEY) }
+        .is_some_and(|content| unsafe { content.as_ref() }.shown == label.label())
+}
+
+/// Remembers that `label` shows `shown` of the content `set` by its widget.
+fn set_content(label: &Label, set: String, shown: String) {
+    // SAFETY: See `get_content`.
+    unsafe { label.set_data(CONTENT_KEY, Content { set, shown }) }
+}
+
+/// The state of scrolling text.
+#[derive(Default)]
+struct Marquee {
+    /// The text as set by the widget.
+    text: RefCell<String>,
+    /// The part of `text` that's currently shown.
+    shown: RefCell<String>,
+    /// How many characters `text` has been scrolled by.
+    offset: Cell<usize>,
+    /// Scrolling is paused while hovered.
+    hovered: Cell<bool>,
+}
+
+/// Keeps the text of `widget` within the `max_length` (in characters) and `max_width` (in pixels)
+/// of the widget at `key`, either by cutting it off or by scrolling it if `scroll` is set.
+/// Applied to whatever text the widget ends up showing, so it's after variables and placeholders
+/// have been filled in.
+pub fn bind(widget: &Widget, key: &str) {
+    let max_length = config::try_get(key, "max_length", false, false)
+        .map(|max_length| max_length.1)
+        .filter(|max_length| *max_length > 0);
+    let max_width = config::try_get_clamped(key, "max_width", 0, config::MAX_PIXELS)
+        .map(scale::px)
+        .filter(|max_width| *max_width > 0);
+    if max_length.is_none() && max_width.is_none() {
+        if config::get_or_default_bool(key, "scroll") {
+            log::warn!("{key}: scroll only works along with max_length or max_width");
+        }
+
+        return;
+    }
+
+    let Some(label) = find_label(widget) else {
+        log::warn!("{key}: max_length and max_width are only supported on widgets showing text");
+        return;
+    };
+
+    if config::get_or_default_bool(key, "scroll") {
+        let speed = config::try_get_f64(key, "scroll_speed").unwrap_or(DEFAULT_SCROLL_SPEED);
+        let get_width: Box<dyn Fn(&Label) -> usize> = match max_length {
+            Some(max_length) => Box::new(move |_| max_length as usize),
+            None => Box::new(move |label| {
+                get_width_chars(label, max_width.unwrap_or_default()) as usize
+            }),
+        };
+
+        bind_scroll(&label, get_width, math::clamp_f64(speed, 0.1, 100.0));
+        return;
+    }
+
+    if let Some(max_length) = max_length {
+        bind_max_length(&label, max_length as usize);
+    }
+
+    if let Some(max_width) = max_width {
+        bind_max_width(&label, max_width);
+    }
+}
+
+/// Gets where text is placed horizontally for a `text_align`, `None` if it isn't `left`, `center`
+/// or `right`.
+pub fn parse_text_align(text_align: &str) -> Option<f32> {
+    match text_align {
+        "left" => Some(0.0),
+        "center" => Some(0.5),
+        "right" => Some(1.0),
+        _ => None,
+    }
+}
+
+/// 0.3.5: Reserves space for `widget` from `min_width` (in pixels) and `width_chars` (in
+/// characters) of the widget at `key`, so its neighbors don't move around as the length of its
+/// text changes. The text is placed within that space by `text_align`, centered by default.
+pub fn reserve_width(widget: &Widget, key: &str) {
+    let min_width = config::try_get_clamped(key, "min_width", 0, config::MAX_PIXELS)
+        .map(scale::px)
+        .filter(|min_width| *min_width > 0);
+    if let Some(min_width) = min_width {
+        // Widgets like images may have requested a size already.
+        let (width, height) = widget.size_request();
+        widget.set_size_request(width.max(min_width), height);
+    }
+
+    let width_chars = config::try_get(key, "width_chars", false, false)
+        .map(|width_chars| width_chars.1)
+        .filter(|width_chars| *width_chars > 0);
+    let text_align = config::try_get(key, "text_align", true, false).map(|text_align| {
+        parse_text_align(&text_align.0).unwrap_or_else(|| {
+            log::warn!(
+                "{key}: Invalid text_align '{}', expected left, center or right",
+                text_align.0
+            );
+            0.5
+        })
+    });
+    if width_chars.is_none() && text_align.is_none() {
+        return;
+    }
+
+    let Some(label) = find_label(widget) else {
+        log::warn!("{key}: width_chars and text_align are only supported on widgets showing text");
+        return;
+    };
+
+    if let Some(width_chars) = width_chars {
+        label.set_width_chars(width_chars);
+    }
+
+    if let Some(text_align) = text_align {
+        label.set_xalign(text_align);
+        label.set_justify(match text_align {
+            0.0 => Justification::Left,
+            1.0 => Justification::Right,
+            _ => Justification::Center,
+        });
+    }
+}
+
+/// Finds the label showing the text of `widget`, which is either the widget itself or inside of
+/// it, like with buttons.
+fn find_label(widget: &Widget) -> Option<Label> {
+    if let Some(label) = widget.downcast_ref::<Label>() {
+        return Some(label.clone());
+    }
+
+    find_label(&widget.downcast_ref::<Bin>()?.child()?)
+}
+
+/// Cuts `text` off after `max_length` characters, including the ellipsis.
+/// Counts characters rather than bytes, so multibyte text isn't cut in half. `None` if it already
+/// fits.
+pub fn truncate(text: &str, max_length: usize) -> Option<String> {
+    if text.chars().count() <= max_length {
+        return None;
+    }
+
+    let mut truncated: String = text.chars().take(max_length.saturating_sub(1)).collect();
+    truncated.push(ELLIPSIS);
+    Some(truncated)
+}
+
+/// Truncates the text of `label` every time it changes.
+fn bind_max_length(label: &Label, max_length: usize) {
+    let truncate_label = move |label: &Label| {
+        // Setting the truncated text notifies again.
+        if is_shown(label) {
+            return;
+        }
+
+        let content = label.label().to_string();
+        match truncate(&label.text(), max_length) {
+            Some(truncated) => {
+                set_content(label, content, truncated.clone());
+                label.set_text(&truncated);
+            }
+            None => set_content(label, content.clone(), content),
+        }
+    };
+
+    truncate_label(label);
+    label.connect_label_notify(truncate_label);
+}
+
+/// Lets Pango ellipsize `label` once it's wider than `max_width`.
+fn bind_max_width(label: &Label, max_width: i32) {
+    // The font may change along with the CSS.
+    let limit_width = move |label: &Label| {
+        label.set_max_width_chars(get_width_chars(label, max_width));
+    };
+
+    label.set_ellipsize(EllipsizeMode::End);
+    limit_width(label);
+    label.connect_style_updated(limit_width);
+}
+
+/// Converts `max_width` in pixels to characters, as GTK limits labels by characters. Uses the
+/// average width of a character in the current font of `label`.
+fn get_width_chars(label: &Label, max_width: i32) -> i32 {
+    let char_width = label
+        .pango_context()
+        .metrics(None, None)
+        .approximate_char_width()
+        / pango::SCALE;
+    (max_width / char_width.max(1)).max(1)
+}
+
+/// Scrolls the text of `label` by `speed` characters per second once it's longer than
+/// `get_width` characters. Starts over when the text changes, and pauses while hovered.
+fn bind_scroll(label: &Label, get_width: impl Fn(&Label) -> usize + 'static, speed: f64) {
+    let marquee = Rc::new(Marquee::default());
+    let render = Rc::new(move |label: &Label, marquee: &Marquee| {
+        let text: Vec<char> = marquee.text.borrow().chars().collect();
+        let width = get_width(label);
+        let shown: String = if text.len() <= width {
+            text.iter().collect()
+        } else {
+            let looped: Vec<char> = text.into_iter().chain(SCROLL_GAP.chars()).collect();
+            let offset = marquee.offset.get() % looped.len();
+            marquee.offset.set(offset);
+            looped.iter().cycle().skip(offset).take(width).collect()
+        };
+
+        // Setting the text notifies again, which is ignored as it's what's already shown.
+        marquee.shown.replace(shown.clone());
+        if label.text() != shown {
+            label.set_text(&shown);
+        }
+    });
+
+    let notify_marquee = marquee.clone();
+    let notify_render = render.clone();
+    label.connect_label_notify(move |label| {
+        let text = label.text();
+        if *notify_marquee.shown.borrow() == text {
+            return;
+        }
+
+        // Widgets may set the same text again on every update, which shouldn't start over.
+        if *notify_marquee.text.borrow() != text {
+            notify_marquee.text.replace(text.to_string());
+            notify_marquee.offset.set(0);
+        }
+
+        let content = label.label().to_string();
+        notify_render(label, &notify_marquee);
+        set_content(label, content, label.label().to_string());
+    });
+
+    let content = label.label().to_string();
+    marquee.text.replace(label.text().to_string());
+    render(label, &marquee);
+    set_content(label, content, label.label().to_string());
+
+    // Labels can't receive events, so hovering is tracked on the event box around the widget.
+    if let Some(event_box) = label
+        .ancestor(EventBox::static_type())
+        .and_then(|event_box| event_box.downcast::<EventBox>().ok())
+    {
+        event_box.add_events(EventMask::ENTER_NOTIFY_MASK | EventMask::LEAVE_NOTIFY_MASK);
+        let enter_marquee = marquee.clone();
+        event_box.connect_enter_notify_event(move |_, _| {
+            enter_marquee.hovered.set(true);
+            Inhibit(false)
+        });
+
+        let leave_marquee = marquee.clone();
+        event_box.connect_leave_notify_event(move |_, event| {
+            // Moving onto a child widget, like a button, doesn't leave the event box.
+            if event.detail() != NotifyType::Inferior {
+                leave_marquee.hovered.set(false);
+            }
+
+            Inhibit(false)
+        });
+    }
+
+    let label = label.clone();
+    // 0.3.5: Never step more often than once per frame.
+    let step = Duration::from_secs_f64(1.0 / speed).max(config::get_frame_interval());
+    pause::timeout_add_local(step, move || {
+        if !marquee.hovered.get() {
+            marquee.offset.set(marquee.offset.get() + 1);
+            render(&label, &marquee);
+        }
+
+        Continue(true)
+    });
+}
@@ -0,0 +1,140 @@
+use crate::{power, registry, triggers};
+use glib::{Continue, SourceId};
+use gtk::{traits::*, ApplicationWindow};
+use std::{
+    cell::{Cell, RefCell},
+    rc::{Rc, Weak},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+/// A timer callback, shared between its glib source and `TIMERS`.
+pub type Timer = RefCell<dyn FnMut() -> Continue>;
+
+thread_local! {
+    /// The callbacks of every timer added through `timeout_add_local`, gone once their source is.
+    static TIMERS: RefCell<Vec<Weak<Timer>>> = RefCell::new(Vec::new());
+
+    /// The regular and the on-battery update-rate of the widget that's currently being built,
+    /// see `set_battery_update_rate`.
+    static BATTERY_UPDATE_RATE: Cell<Option<(Duration, Duration)>> = const { Cell::new(None) };
+}
+
+/// How many bars are currently mapped, so shown on screen.
+static MAPPED_BARS: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether all bars have been unmapped, so updating them would only waste battery.
+/// Bars which haven't been mapped yet don't count, so the first updates aren't held back.
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the update timers are paused, because none of the bars are visible.
+pub fn is_paused() -> bool {
+    PAUSED.load(Ordering::SeqCst)
+}
+
+/// Pauses the update timers while none of the bars are mapped, like when the compositor hides
+/// layer-shell surfaces during a fullscreen game, and resumes them once one is mapped again.
+pub fn track(window: &ApplicationWindow) {
+    window.connect_map(|_| {
+        if MAPPED_BARS.fetch_add(1, Ordering::SeqCst) == 0 && PAUSED.swap(false, Ordering::SeqCst) {
+            log::info!("A bar is visible again, resuming updates");
+        }
+    });
+
+    window.connect_unmap(|_| {
+        if MAPPED_BARS.fetch_sub(1, Ordering::SeqCst) == 1 {
+            log::info!("No bar is visible, pausing updates");
+            PAUSED.store(true, Ordering::SeqCst);
+        }
+    });
+}
+
+/// Sets the `update_rate` and `update_rate_on_battery` of the widget that's about to be built,
+/// `None` once it's built or if it doesn't have the latter.
+/// 0.3.5: Timers it adds at its `update_rate` run at the other one while on battery instead.
+///   Animations and other internal timers keep their own interval.
+pub fn set_battery_update_rate(update_rates: Option<(u64, u64)>) {
+    let update_rates = update_rates.map(|(update_rate, on_battery)| {
+        (
+            Duration::from_millis(update_rate),
+            Duration::from_millis(on_battery),
+        )
+    });
+    BATTERY_UPDATE_RATE.with(|current| current.set(update_rates));
+}
+
+/// Same as `glib::timeout_add_local`, but `func` isn't called while the timers are paused.
+/// Event-driven widgets keep receiving their updates instead, GTK doesn't redraw hidden bars.
+/// 0.3.5: Timers added while building a widget stop once it's torn down, like when its bar is.
+pub fn timeout_add_local<F>(interval: Duration, mut func: F) -> SourceId
+where
+    F: FnMut() -> Continue + 'static,
+{
+    let cancelled = registry::cancelled();
+    let timer_cancelled = cancelled.clone();
+    let timer: Rc<Timer> = Rc::new(RefCell::new(move || match timer_cancelled.get() {
+        true => Continue(false),
+        false => func(),
+    }));
+    TIMERS.with(|timers| {
+        let mut timers = timers.borrow_mut();
+        timers.retain(|timer| timer.strong_count() > 0);
+        timers.push(Rc::downgrade(&timer));
+    });
+
+    // 0.3.5: Widgets with an `update_on` are updated on its triggers, and only on every tick if
+    //   it has `tick`.
+    let ticks = triggers::bind(interval, &timer);
+
+    let on_battery = BATTERY_UPDATE_RATE
+        .with(Cell::get)
+        .filter(|(update_rate, _)| *update_rate == interval)
+        .map(|(_, on_battery)| on_battery);
+    // Ticks at the faster of both rates, then skips the ticks that are too early for the current
+    // one. An on-battery rate of `0` means it isn't updated on a timer while on battery.
+    let tick = match on_battery {
+        Some(on_battery) if !on_battery.is_zero() => interval.min(on_battery),
+        _ => interval,
+    };
+    let mut last_call: Option<Instant> = None;
+    glib::timeout_add_local(tick, move || {
+        // Checked before pausing, so a torn down timer doesn't wait for a bar to be visible again.
+        if cancelled.get() {
+            return Continue(false);
+        }
+
+        if is_paused() || !ticks {
+            return Continue(true);
+        }
+
+        if let Some(on_battery) = on_battery {
+            let current = match power::on_battery() {
+                true => on_battery,
+                false => interval,
+            };
+            // Half a tick of leeway, as timers are never exactly on time.
+            let is_early =
+                last_call.is_some_and(|last_call| last_call.elapsed() + tick / 2 < current);
+            if current.is_zero() || is_early {
+                return Continue(true);
+            }
+
+            last_call = Some(Instant::now());
+        }
+
+        (timer.borrow_mut())()
+    })
+}
+
+/// Calls every timer right away rather than at its next interval, like after resuming from
+/// suspend. The timers keep their own intervals afterwards.
+pub fn run_all() {
+    let timers: Vec<Rc<Timer>> =
+        TIMERS.with(|timers| timers.borrow().iter().filter_map(Weak::upgrade).collect());
+    for timer in timers {
+        // Already running if this was called from inside of a timer.
+        if let Ok(mut func) = timer.try_borrow_mut() {
+            func();
+        }
+    }
+}
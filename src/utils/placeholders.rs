@@ -0,0 +1,495 @@
+use crate::{config, locks, ranges, sources};
+use std::{collections::HashMap, sync::RwLock};
+
+lazy_static! {
+    /// The last value of every widget, keyed by the widget name.
+    static ref VALUES: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
+}
+
+/// Appended to a widget name to use its last value as a placeholder, as in `{cpu.value}`.
+const VALUE_SUFFIX: &str = ".value";
+
+/// Groups numeric values by thousands, as in `{name:grouped}`.
+const GROUPED: &str = "grouped";
+
+/// Stands in for an escaped `{{` while variables are expanded, so nothing can match inside of it.
+/// 0.3.5: Stand-ins are Unicode noncharacters, which are reserved for internal use and never show
+///   up in text. Unlike the private use area, where icon fonts like Nerd Fonts put their glyphs.
+const ESCAPED_OPEN: char = '\u{FDD0}';
+
+/// Stands in for an escaped `}}` while variables are expanded.
+const ESCAPED_CLOSE: char = '\u{FDD1}';
+
+/// Starts the stand-in for the `{name` of a placeholder while variables are expanded, see
+/// `protect_placeholders` and `stand_in`.
+const PLACEHOLDER_STAND_IN: char = '\u{FDD2}';
+
+/// Starts the stand-in for the value of a raw variable while the rest is expanded, see
+/// `stand_in`.
+pub const RAW_STAND_IN: char = '\u{FDD3}';
+
+/// Ends a stand-in started by `PLACEHOLDER_STAND_IN` or `RAW_STAND_IN`.
+const STAND_IN_END: char = '\u{FDD4}';
+
+/// Noncharacters standing in for the digits `0` to `9` of the index in a stand-in, so variables
+/// named like a number can't match inside of it.
+const STAND_IN_DIGITS: u32 = 0xFDE0;
+
+/// The placeholders each widget type fills its formats in with, including `set_command` and
+/// `elapsed_format`.
+pub const WIDGET_PLACEHOLDERS: &[(&str, &[&str])] = &[
+    ("battery", &["capacity", "status", ranges::PLACEHOLDER]),
+    (
+        "brightness",
+        &["percent", "value", "device", ranges::PLACEHOLDER],
+    ),
+    ("cpu", &["usage", ranges::PLACEHOLDER]),
+    (
+        "disk",
+        &["free", "used", "total", "percent", ranges::PLACEHOLDER],
+    ),
+    ("keyboard", &["layout"]),
+    ("media", &["artist", "title", "status"]),
+    ("memory", &["used", "total", "percent", ranges::PLACEHOLDER]),
+    ("network", &["rx", "tx", "interface"]),
+    ("temperature", &["temp", ranges::PLACEHOLDER]),
+    (
+        "timer",
+        &[
+            "days",
+            "hours",
+            "minutes",
+            "seconds",
+            "total_minutes",
+            "total_seconds",
+        ],
+    ),
+    ("uptime", &["days", "hours", "minutes"]),
+    ("volume", &["volume", "muted", ranges::PLACEHOLDER]),
+    ("window", &["title", "app_id"]),
+];
+
+/// Gets the placeholders of `widget_type`, empty for widgets without any.
+pub fn get_names(widget_type: &str) -> &'static [&'static str] {
+    WIDGET_PLACEHOLDERS
+        .iter()
+        .find(|(other, _)| *other == widget_type)
+        .map_or(&[], |(_, names)| names)
+}
+
+/// Builds the stand-in for the item at `index`, which is `kind` followed by the digits of `index`
+/// and `STAND_IN_END`. `kind` tells apart what it stands in for, like `RAW_STAND_IN`.
+pub fn stand_in(kind: char, index: usize) -> String {
+    let mut result = String::from(kind);
+    for digit in index.to_string().chars() {
+        result.extend(
+            digit
+                .to_digit(10)
+                .and_then(|digit| char::from_u32(STAND_IN_DIGITS + digit)),
+        );
+    }
+
+    result.push(STAND_IN_END);
+    result
+}
+
+/// Replaces every stand-in of `kind` in `input` with what `restore` returns for its index. Those
+/// it returns `None` for are left as they are.
+pub fn restore_stand_ins(
+    input: &str,
+    kind: char,
+    restore: impl Fn(usize) -> Option<String>,
+) -> String {
+    let mut result = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find(kind) {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let digits = &rest[kind.len_utf8()..];
+        let length = digits
+            .find(|char: char| !(STAND_IN_DIGITS..STAND_IN_DIGITS + 10).contains(&(char as u32)))
+            .unwrap_or(digits.len());
+        let index = digits[..length]
+            .chars()
+            .try_fold(0usize, |index, digit| {
+                let digit = (digit as u32 - STAND_IN_DIGITS) as usize;
+                index.checked_mul(10)?.checked_add(digit)
+            })
+            .filter(|_| length > 0 && digits[length..].starts_with(STAND_IN_END));
+        match index.and_then(&restore) {
+            Some(value) => {
+                result.push_str(&value);
+                rest = &digits[length + STAND_IN_END.len_utf8()..];
+            }
+            None => {
+                result.push(kind);
+                rest = digits;
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// 0.3.5: Replaces the `{name` of every placeholder in `names`, like `{percent}` or
+///   `{percent:.1}`, with a stand-in so that a variable called `percent` can't expand in its place.
+///   Placeholders take precedence over variables this way. Undo it with `restore_placeholders`.
+pub fn protect_placeholders(input: &str, names: &[&str]) -> String {
+    let mut result = input.to_string();
+    for (index, name) in names.iter().enumerate() {
+        let stand_in = stand_in(PLACEHOLDER_STAND_IN, index);
+        for end in ['}', ':'] {
+            result = result.replace(&format!("{{{name}{end}"), &format!("{stand_in}{end}"));
+        }
+    }
+
+    result
+}
+
+/// Undoes `protect_placeholders`, given the same `names`.
+pub fn restore_placeholders(input: &str, names: &[&str]) -> String {
+    restore_stand_ins(input, PLACEHOLDER_STAND_IN, |index| {
+        names.get(index).map(|name| format!("{{{name}"))
+    })
+}
+
+/// Replaces the escaped braces `{{` and `}}` in `input` with stand-ins, so that substitutions
+/// leave them untouched. Undo it with `restore_escapes`.
+pub fn protect_escapes(input: &str) -> String {
+    input
+        .replace("{{", &ESCAPED_OPEN.to_string())
+        .replace("}}", &ESCAPED_CLOSE.to_string())
+}
+
+/// Undoes `protect_escapes`. If `keep_escaped` is `true` the braces are doubled again, so that a
+/// later `fill` still sees them as escaped, otherwise they become literal `{` and `}`.
+pub fn restore_escapes(input: &str, keep_escaped: bool) -> String {
+    let (open, close) = if keep_escaped {
+        ("{{", "}}")
+    } else {
+        ("{", "}")
+    };
+
+    input
+        .replace(ESCAPED_OPEN, open)
+        .replace(ESCAPED_CLOSE, close)
+}
+
+/// Replaces the `{name}` placeholders in `format` with their values in a single pass, so values
+/// containing placeholders themselves are left alone.
+/// `{{` and `}}` become literal `{` and `}`, and unknown placeholders are kept as-is.
+/// 0.3.5: Numeric values can be grouped by thousands with `{name:grouped}`, like `12,345`, and
+///   rounded to a number of decimals with `{name:.1}`, like `42.9`. Whole numbers can be
+///   zero-padded to a width with `{name:02}`, like `05`. Unknown modifiers are kept as-is.
+///   The last value of another widget can be used as `{widget_name.value}`, see `publish`, and
+///   the fields of a source as `{source.field}`, see `sources::get_field`.
+pub fn fill(format: &str, placeholders: &[(&str, &str)]) -> String {
+    fill_with_separator(format, placeholders, config::get_thousands_separator)
+}
+
+/// Same as `fill`, but `thousands_separator` is only called once a value has to be grouped.
+fn fill_with_separator(
+    format: &str,
+    placeholders: &[(&str, &str)],
+    thousands_separator: impl Fn() -> String,
+) -> String {
+    let mut result = String::with_capacity(format.len());
+    let mut rest = format;
+    while let Some(index) = rest.find(['{', '}']) {
+        result.push_str(&rest[..index]);
+        rest = &rest[index..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            result.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+
+        let placeholder = rest[1..].find('}').and_then(|end| {
+            let (name, modifier) = match rest[1..=end].split_once(':') {
+                Some((name, modifier)) => (name, Some(modifier)),
+                None => (&rest[1..=end], None),
+            };
+
+            let value = match placeholders
+                .iter()
+                .find(|placeholder| placeholder.0 == name)
+            {
+                Some(placeholder) => placeholder.1.to_string(),
+                None => name
+                    .strip_suffix(VALUE_SUFFIX)
+                    .and_then(get_value)
+                    .or_else(|| sources::get_field(name))?,
+            };
+            // Several modifiers are applied in order, as in `{name:.1:grouped}`.
+            let value = modifier
+                .into_iter()
+                .flat_map(|modifier| modifier.split(':'))
+                .try_fold(value, |value, modifier| match modifier {
+                    GROUPED => Some(group_thousands(&value, &thousands_separator())),
+                    modifier if modifier.starts_with('0') => {
+                        Some(pad(&value, modifier.parse().ok()?))
+                    }
+                    modifier => {
+                        let decimals = modifier.strip_prefix('.')?.parse().ok()?;
+                        Some(round(&value, decimals))
+                    }
+                })?;
+
+            Some((value, end + 2))
+        });
+
+        match placeholder {
+            Some((value, length)) => {
+                result.push_str(&value);
+                rest = &rest[length..];
+            }
+            // A lone brace.
+            None => {
+                result.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+/// Stores `value` as the last value of the widget named `name`, so other widgets can show it
+/// through `{name.value}` in their `format`.
+/// 0.3.5: Widgets call this from `update_label_reg` with everything they show, whether or not
+///   anything refers to their value, as formats are only filled in later.
+pub fn publish(name: &str, value: &str) {
+    // Most updates don't change anything, so avoid the write lock for those.
+    if locks::read(&VALUES)
+        .get(name)
+        .is_some_and(|previous| previous == value)
+    {
+        return;
+    }
+
+    locks::write(&VALUES).insert(name.to_string(), value.to_string());
+}
+
+/// Gets the last value published by the widget named `name`.
+pub fn get_value(name: &str) -> Option<String> {
+    locks::read(&VALUES).get(name).cloned()
+}
+
+/// Rounds `value` to `decimals` decimals, for example `42.857143` into `42.9` for `1`.
+/// Values which aren't numbers are returned as-is.
+pub fn round(value: &str, decimals: usize) -> String {
+    match value.trim().parse::<f64>() {
+        Ok(number) if number.is_finite() => {
+            let rounded = format!("{number:.decimals$}");
+            // Small negative numbers shouldn't round to `-0`.
+            match rounded.strip_prefix('-') {
+                Some(unsigned) if unsigned.chars().all(|char| matches!(char, '0' | '.')) => {
+                    unsigned.to_string()
+                }
+                _ => rounded,
+            }
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Pads `value` with leading zeros up to `width` digits, for example `5` into `05` for `2`.
+/// Values which aren't whole numbers are returned as-is.
+pub fn pad(value: &str, width: usize) -> String {
+    if value.is_empty() || !value.chars().all(|char| char.is_ascii_digit()) {
+        return value.to_string();
+    }
+
+    format!("{value:0>width$}")
+}
+
+/// Groups the integer digits of `value` by thousands, for example `-12345.67` into `-12,345.67`.
+/// Values which aren't numbers are returned as-is.
+pub fn group_thousands(value: &str, separator: &str) -> String {
+    let (sign, unsigned) = match value.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", value),
+    };
+
+    let (digits, fraction) = match unsigned.find('.') {
+        Some(index) => unsigned.split_at(index),
+        None => (unsigned, ""),
+    };
+
+    let is_number = !digits.is_empty()
+        && digits.chars().all(|char| char.is_ascii_digit())
+        && fraction.chars().skip(1).all(|char| char.is_ascii_digit());
+    if !is_number {
+        return value.to_string();
+    }
+
+    let mut grouped = String::with_capacity(value.len() + digits.len() / 3 * separator.len());
+    grouped.push_str(sign);
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index) % 3 == 0 {
+            grouped.push_str(separator);
+        }
+
+        grouped.push(digit);
+    }
+
+    grouped.push_str(fraction);
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_placeholders() {
+        assert_eq!(
+            fill("{used}/{total} GiB", &[("used", "4.2"), ("total", "16.0")]),
+            "4.2/16.0 GiB"
+        );
+    }
+
+    #[test]
+    fn fill_mixed_with_escapes() {
+        assert_eq!(
+            fill("cpu {{usage}}: {usage}% {{\"a\": 1}}", &[("usage", "42")]),
+            "cpu {usage}: 42% {\"a\": 1}"
+        );
+    }
+
+    #[test]
+    fn fill_keeps_unknown_and_lone_braces() {
+        assert_eq!(
+            fill("{unknown} { } {usage", &[("usage", "42")]),
+            "{unknown} { } {usage"
+        );
+    }
+
+    #[test]
+    fn fill_does_not_substitute_values() {
+        assert_eq!(
+            fill(
+                "{title} - {artist}",
+                &[("title", "{artist}"), ("artist", "me")]
+            ),
+            "{artist} - me"
+        );
+    }
+
+    #[test]
+    fn protect_and_restore() {
+        let protected = protect_escapes("{{user}} is {user}");
+        assert!(!protected.contains("{{"));
+        assert_eq!(restore_escapes(&protected, false), "{user} is {user}");
+        assert_eq!(restore_escapes(&protected, true), "{{user}} is {user}");
+    }
+
+    #[test]
+    fn stand_ins_leave_icons_alone() {
+        // Nerd Font icons from the private use areas, around where stand-ins used to be.
+        let icons = "\u{E000}\u{E100}\u{F0001}\u{F0002}";
+        let input = format!(
+            "{icons} {} {}",
+            stand_in(RAW_STAND_IN, 0),
+            stand_in(RAW_STAND_IN, 12)
+        );
+        let restored = restore_stand_ins(&input, RAW_STAND_IN, |index| Some(index.to_string()));
+        assert_eq!(restored, format!("{icons} 0 12"));
+
+        // Unknown indices and cut off stand-ins are left as they are.
+        let unknown = format!("{}{RAW_STAND_IN}", stand_in(RAW_STAND_IN, 3));
+        assert_eq!(restore_stand_ins(&unknown, RAW_STAND_IN, |_| None), unknown);
+
+        let protected = protect_placeholders("{percent:.1} {percent} \u{F0001}", &["percent"]);
+        assert!(!protected.contains("{percent"));
+        assert_eq!(
+            restore_placeholders(&protected, &["percent"]),
+            "{percent:.1} {percent} \u{F0001}"
+        );
+    }
+
+    #[test]
+    fn fill_grouped() {
+        assert_eq!(
+            fill_with_separator(
+                "{count:grouped} updates, {count} raw",
+                &[("count", "1234567")],
+                || String::from(",")
+            ),
+            "1,234,567 updates, 1234567 raw"
+        );
+    }
+
+    #[test]
+    fn fill_keeps_unknown_modifiers() {
+        assert_eq!(
+            fill_with_separator("{count:shouty}", &[("count", "42")], || String::from(",")),
+            "{count:shouty}"
+        );
+    }
+
+    #[test]
+    fn fill_published_values() {
+        publish("fill_published_values", "42");
+        assert_eq!(
+            fill_with_separator(
+                "{fill_published_values.value}% {unpublished.value}",
+                &[],
+                || String::from(",")
+            ),
+            "42% {unpublished.value}"
+        );
+    }
+
+    #[test]
+    fn fill_rounded() {
+        assert_eq!(
+            fill_with_separator(
+                "{cpu:.1}% {cpu:.0}% {big:.2:grouped}",
+                &[("cpu", "42.857143"), ("big", "1234567.891")],
+                || String::from(",")
+            ),
+            "42.9% 43% 1,234,567.89"
+        );
+    }
+
+    #[test]
+    fn round_negative_numbers() {
+        assert_eq!(round("-3.14159", 2), "-3.14");
+        assert_eq!(round("-2.6", 0), "-3");
+        assert_eq!(round("-0.04", 1), "0.0");
+        assert_eq!(round("-7", 1), "-7.0");
+    }
+
+    #[test]
+    fn round_not_numbers() {
+        assert_eq!(round("n/a", 1), "n/a");
+        assert_eq!(round("", 1), "");
+        assert_eq!(round("NaN", 1), "NaN");
+        assert_eq!(
+            fill_with_separator("{temp:.1} {temp:.x}", &[("temp", "hot")], || String::from(
+                ","
+            )),
+            "hot {temp:.x}"
+        );
+    }
+
+    #[test]
+    fn group_thousands_numbers() {
+        assert_eq!(group_thousands("0", ","), "0");
+        assert_eq!(group_thousands("999", ","), "999");
+        assert_eq!(group_thousands("1000", ","), "1,000");
+        assert_eq!(group_thousands("12345", "."), "12.345");
+        assert_eq!(group_thousands("-1234567.89", " "), "-1 234 567.89");
+    }
+
+    #[test]
+    fn group_thousands_not_numbers() {
+        assert_eq!(group_thousands("", ","), "");
+        assert_eq!(group_thousands("12ab34", ","), "12ab34");
+        assert_eq!(group_thousands("-", ","), "-");
+        assert_eq!(group_thousands("1.2.3", ","), "1.2.3");
+    }
+}
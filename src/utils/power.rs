@@ -0,0 +1,79 @@
+use crate::locks;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Where the kernel exposes batteries and power adapters.
+pub const POWER_SUPPLY_PATH: &str = "/sys/class/power_supply";
+
+/// How long the power state is reused before sysfs is read again.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    /// The last power state read by `on_battery`, along with when it was read.
+    static ref STATE: Mutex<Option<(Instant, bool)>> = Mutex::new(None);
+}
+
+/// Returns the sysfs directories of all batteries, sorted so `BAT0` comes before `BAT1`.
+pub fn find_batteries() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(POWER_SUPPLY_PATH) else {
+        return Vec::new();
+    };
+
+    let mut batteries: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("BAT"))
+        .map(|entry| entry.path())
+        .collect();
+
+    // `read_dir` isn't ordered.
+    batteries.sort();
+    batteries
+}
+
+/// Whether the battery at `path` reports that it's discharging.
+fn is_discharging(path: &Path) -> bool {
+    fs::read_to_string(path.join("status")).is_ok_and(|status| status.trim() == "Discharging")
+}
+
+/// Whether the system is running on battery, so any battery is discharging. Desktops without a
+/// battery never are. Read again at most every `CHECK_INTERVAL`, as this is called on every
+/// update.
+pub fn on_battery() -> bool {
+    let mut state = locks::lock(&STATE);
+    if let Some((checked, on_battery)) = *state {
+        if checked.elapsed() < CHECK_INTERVAL {
+            return on_battery;
+        }
+    }
+
+    let on_battery = find_batteries()
+        .iter()
+        .any(|battery| is_discharging(battery));
+    if state.is_some_and(|(_, previous)| previous != on_battery) {
+        log::debug!(
+            "Running on {}, switching update-rates",
+            if on_battery { "battery" } else { "AC" }
+        );
+    }
+
+    *state = Some((Instant::now(), on_battery));
+    on_battery
+}
+
+/// Forgets the last power state, so the next `on_battery` reads it again. Used after a resume, as
+/// `CHECK_INTERVAL` doesn't pass while the system is asleep.
+pub fn forget() {
+    *locks::lock(&STATE) = None;
+}
+
+/// Picks `on_battery` over `update_rate` while running on battery, if it's set.
+pub fn pick_update_rate(update_rate: u64, on_battery: Option<u64>) -> u64 {
+    match on_battery {
+        Some(on_battery) if self::on_battery() => on_battery,
+        _ => update_rate,
+    }
+}
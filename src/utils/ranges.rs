@@ -0,0 +1,126 @@
+use crate::config;
+use json::JsonValue;
+
+/// The placeholder the text of the matching range is shown as.
+pub const PLACEHOLDER: &str = "icon";
+
+/// The values from `min` up to `max`, both inclusive, shown as `text`. A missing bound is open.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Range {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub text: String,
+}
+
+/// Maps the numeric value of a widget to a text, usually an icon, through its `ranges`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Ranges(Vec<Range>);
+
+impl Ranges {
+    /// Parses `ranges`, an array like `[{ "max": 20, "text": "low" }, { "text": "high" }]`.
+    /// `null` is no ranges at all. Errors name the range that failed.
+    pub fn parse(ranges: &JsonValue) -> Result<Self, String> {
+        if ranges.is_null() {
+            return Ok(Self::default());
+        }
+
+        if !ranges.is_array() {
+            return Err(String::from("expected an array of ranges"));
+        }
+
+        let mut result = Vec::new();
+        for (index, range) in ranges.members().enumerate() {
+            let invalid = |reason: &str| format!("range {}: {reason}", index + 1);
+            if !range.is_object() {
+                return Err(invalid("expected an object with a `text`"));
+            }
+
+            let bound = |key: &str| match &range[key] {
+                JsonValue::Null => Ok(None),
+                value => config::as_f64(value)
+                    .map(Some)
+                    .ok_or_else(|| invalid(&format!("`{key}` isn't a number"))),
+            };
+
+            let (min, max) = (bound("min")?, bound("max")?);
+            if min.zip(max).is_some_and(|(min, max)| min > max) {
+                return Err(invalid("`min` is above `max`"));
+            }
+
+            let Some(text) = range["text"].as_str() else {
+                return Err(invalid("expected a `text`"));
+            };
+
+            result.push(Range {
+                min,
+                max,
+                text: text.to_string(),
+            });
+        }
+
+        Ok(Self(result))
+    }
+
+    /// Gets the `ranges` of the widget at `key`, no ranges at all if they're invalid.
+    pub fn get(key: &str) -> Self {
+        let ranges = config::with_config(|config| Self::parse(&config[key]["ranges"]));
+        ranges.unwrap_or_else(|err| {
+            log::warn!("{key}: Invalid ranges, {err}");
+            Self::default()
+        })
+    }
+
+    /// Gets the text of the first range `value` is in. A range without bounds matches any value,
+    /// so one put last is the default. Empty if no range matches.
+    pub fn pick(&self, value: f64) -> &str {
+        self.0
+            .iter()
+            .find(|range| {
+                range.min.is_none_or(|min| value >= min) && range.max.is_none_or(|max| value <= max)
+            })
+            .map_or("", |range| &range.text)
+    }
+
+    /// Same as `pick`, for values that may not be numbers. Those match no range.
+    pub fn pick_str(&self, value: &str) -> &str {
+        value
+            .trim()
+            .parse()
+            .ok()
+            .filter(|value: &f64| value.is_finite())
+            .map_or("", |value| self.pick(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        let ranges = Ranges::parse(&json::array![
+            { "max": 20, "text": "low" },
+            { "min": 20, "max": 60, "text": "mid" },
+            { "min": 60, "text": "high" }
+        ])
+        .unwrap();
+        assert_eq!(ranges.pick(0.0), "low");
+        // Both bounds are inclusive, the first match wins.
+        assert_eq!(ranges.pick(20.0), "low");
+        assert_eq!(ranges.pick(20.5), "mid");
+        assert_eq!(ranges.pick(100.0), "high");
+        assert_eq!(ranges.pick_str("n/a"), "");
+
+        let ranges = Ranges::parse(&json::array![
+            { "min": 0, "max": 100, "text": "ok" },
+            { "text": "?" }
+        ])
+        .unwrap();
+        assert_eq!(ranges.pick(-5.0), "?");
+
+        assert!(Ranges::parse(&json::array![{ "min": 5, "max": 1, "text": "x" }]).is_err());
+        assert!(Ranges::parse(&json::array![{ "max": 5 }])
+            .unwrap_err()
+            .contains("range 1"));
+    }
+}
@@ -0,0 +1,218 @@
+use glib::Continue;
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// What a widget was built for: the bar it's on and its key in the config.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Owner {
+    pub bar: usize,
+    pub key: String,
+}
+
+/// A teardown callback, see `on_teardown`.
+type Teardown = Box<dyn FnOnce()>;
+
+/// The state of a torn down widget by its bar and its name, see `carry_over`.
+type Carried = (usize, String, Box<dyn Any>);
+
+/// Hands out the ids of the bars.
+static NEXT_BAR: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The widget that's currently being built, see `build`.
+    static BUILDING: RefCell<Option<Owner>> = const { RefCell::new(None) };
+
+    /// What has to be undone once a widget is gone, by the widget it was added for.
+    static TEARDOWNS: RefCell<Vec<(Owner, Teardown)>> = RefCell::new(Vec::new());
+
+    /// The state of widgets which have been torn down, see `carry_over`.
+    static CARRIED: RefCell<Vec<Carried>> = RefCell::new(Vec::new());
+}
+
+/// Gets the id of a new bar, which its widgets are owned by.
+pub fn new_bar() -> usize {
+    NEXT_BAR.fetch_add(1, Ordering::SeqCst)
+}
+
+/// Builds the widget of `owner` through `build`, so everything it adds is torn down along with it.
+/// Widgets built while another one is, like the widgets of a group, belong to the outer one.
+pub fn build<R>(owner: Owner, build: impl FnOnce() -> R) -> R {
+    let is_outer = BUILDING.with(|building| {
+        let mut building = building.borrow_mut();
+        if building.is_some() {
+            return false;
+        }
+
+        *building = Some(owner);
+        true
+    });
+
+    let result = build();
+    if is_outer {
+        BUILDING.with(|building| building.replace(None));
+    }
+
+    result
+}
+
+/// Calls `teardown` once the widget that's currently being built is torn down. Nothing is torn
+/// down for things added outside of a widget, like the label loop, as they stay for good.
+pub fn on_teardown(teardown: impl FnOnce() + 'static) {
+    let Some(owner) = BUILDING.with(|building| building.borrow().clone()) else {
+        return;
+    };
+
+    TEARDOWNS.with(|teardowns| teardowns.borrow_mut().push((owner, Box::new(teardown))));
+}
+
+/// Gets a flag which is set once the widget that's currently being built is torn down, so its
+/// timers and channels can stop themselves.
+pub fn cancelled() -> Rc<Cell<bool>> {
+    let cancelled = Rc::new(Cell::new(false));
+    let flag = cancelled.clone();
+    on_teardown(move || flag.set(true));
+    cancelled
+}
+
+/// Same as `receiver.attach(None, func)`, except that the receiver is dropped once the widget
+/// that's currently being built is torn down. Its sender then fails, which stops most workers.
+pub fn attach<T: 'static>(
+    receiver: glib::Receiver<T>,
+    mut func: impl FnMut(T) -> Continue + 'static,
+) {
+    let cancelled = cancelled();
+    receiver.attach(None, move |value| match cancelled.get() {
+        true => Continue(false),
+        false => func(value),
+    });
+}
+
+/// Keeps what `get` returns once the widget that's currently being built is torn down, so the
+/// widget named `name` which replaces it on the same bar can pick it up through `take_over`.
+pub fn carry_over<T: 'static>(name: &str, get: impl FnOnce() -> T + 'static) {
+    let Some(owner) = BUILDING.with(|building| building.borrow().clone()) else {
+        return;
+    };
+
+    let name = name.to_string();
+    on_teardown(move || {
+        CARRIED.with(|carried| {
+            let mut carried = carried.borrow_mut();
+            carried.retain(|(bar, other, _)| *bar != owner.bar || *other != name);
+            carried.push((owner.bar, name, Box::new(get())));
+        })
+    });
+}
+
+/// Takes the state that the widget named `name` had before it was rebuilt, see `carry_over`.
+pub fn take_over<T: 'static>(name: &str) -> Option<T> {
+    let owner = BUILDING.with(|building| building.borrow().clone())?;
+    CARRIED.with(|carried| {
+        let mut carried = carried.borrow_mut();
+        let index = carried.iter().position(|(bar, other, state)| {
+            *bar == owner.bar && other == name && state.is::<T>()
+        })?;
+        carried.remove(index).2.downcast().ok().map(|state| *state)
+    })
+}
+
+/// Drops the state that no widget on `bar` has taken over, once it's done rebuilding.
+pub fn forget_carried(bar: usize) {
+    CARRIED.with(|carried| carried.borrow_mut().retain(|(other, _, _)| *other != bar));
+}
+
+/// Tears down every widget whose owner `matches`, newest first. Returns how many teardowns ran.
+/// NOTE: The GTK widgets themselves aren't destroyed, that's up to whoever placed them.
+pub fn tear_down(matches: impl Fn(&Owner) -> bool) -> usize {
+    // Taken out first, as tearing down may add or drop other teardowns.
+    let teardowns: Vec<Teardown> = TEARDOWNS.with(|teardowns| {
+        let mut teardowns = teardowns.borrow_mut();
+        let (matching, kept) = std::mem::take(&mut *teardowns)
+            .into_iter()
+            .partition(|(owner, _)| matches(owner));
+        *teardowns = kept;
+        matching.into_iter().map(|(_, teardown)| teardown).collect()
+    });
+
+    let count = teardowns.len();
+    for teardown in teardowns.into_iter().rev() {
+        teardown();
+    }
+
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widgets_are_torn_down_on_their_own() {
+        let bar = new_bar();
+        let owner = |key: &str| Owner {
+            bar,
+            key: key.to_string(),
+        };
+
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let (outer, inner) = build(owner("left-group_a"), || {
+            let outer = cancelled();
+            let inner_order = order.clone();
+            // Widgets of a group belong to the group.
+            let inner = build(owner("left-label_b"), || {
+                on_teardown(move || inner_order.borrow_mut().push("label"));
+                cancelled()
+            });
+            let outer_order = order.clone();
+            on_teardown(move || outer_order.borrow_mut().push("group"));
+            (outer, inner)
+        });
+
+        let other = build(owner("left-label_c"), cancelled);
+        // Nothing is being built, so it's never torn down.
+        assert!(!cancelled().get());
+
+        assert_eq!(tear_down(|torn| *torn == owner("left-label_b")), 0);
+        assert_eq!(tear_down(|torn| *torn == owner("left-group_a")), 4);
+        assert!(outer.get() && inner.get() && !other.get());
+        assert_eq!(*order.borrow(), ["group", "label"]);
+
+        assert_eq!(tear_down(|torn| torn.bar == bar), 1);
+        assert!(other.get());
+    }
+
+    #[test]
+    fn state_is_carried_over_to_rebuilt_widgets() {
+        let bar = new_bar();
+        let owner = Owner {
+            bar,
+            key: String::from("left-graph_a"),
+        };
+
+        build(owner.clone(), || carry_over("a", || vec![1.0, 2.0]));
+        tear_down(|torn| *torn == owner);
+        // Elsewhere, or through another type, it isn't the same widget.
+        assert_eq!(take_over::<Vec<f64>>("a"), None);
+        let other = Owner {
+            bar: new_bar(),
+            ..owner.clone()
+        };
+        assert_eq!(build(other, || take_over::<Vec<f64>>("a")), None);
+        assert_eq!(build(owner.clone(), || take_over::<String>("a")), None);
+
+        assert_eq!(
+            build(owner.clone(), || take_over::<Vec<f64>>("a")),
+            Some(vec![1.0, 2.0])
+        );
+        assert_eq!(build(owner.clone(), || take_over::<Vec<f64>>("a")), None);
+
+        build(owner.clone(), || carry_over("a", || 1));
+        tear_down(|torn| *torn == owner);
+        forget_carried(bar);
+        assert_eq!(build(owner, || take_over::<i32>("a")), None);
+    }
+}
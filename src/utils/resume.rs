@@ -0,0 +1,172 @@
+use crate::{command, compositor, pause, power};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+use zbus::blocking::{Connection, MessageIterator};
+
+/// Matches logind announcing a suspend, and the resume after it.
+const PREPARE_FOR_SLEEP_RULE: &str = "type='signal',sender='org.freedesktop.login1',\
+                                      interface='org.freedesktop.login1.Manager',\
+                                      member='PrepareForSleep'";
+
+/// How much further the boot clock has to get ahead of the monotonic one to count as a suspend,
+/// so the usual drift between them isn't mistaken for one.
+const MIN_SLEEP: Duration = Duration::from_secs(2);
+
+/// How often `SleepDetector` checks for a suspend without logind.
+const DETECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How many times the system has resumed since the bar started.
+static RESUMES: AtomicUsize = AtomicUsize::new(0);
+
+/// What the logind thread tells the main loop.
+enum Event {
+    Resumed,
+    /// logind can't be reached, so suspends are detected through `SleepDetector` instead.
+    NoLogind,
+}
+
+/// How many times the system has resumed, loops compare it to what they've seen to notice a resume.
+pub fn count() -> usize {
+    RESUMES.load(Ordering::SeqCst)
+}
+
+/// Gets the time since boot including suspends, unlike `Instant` which stands still while the
+/// system sleeps. Neither is affected by the wall clock being changed.
+fn boot_time() -> Option<Duration> {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `time` is a valid timespec for the duration of the call.
+    if unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut time) } != 0 {
+        return None;
+    }
+
+    Some(Duration::new(
+        u64::try_from(time.tv_sec).ok()?,
+        u32::try_from(time.tv_nsec).ok()?,
+    ))
+}
+
+/// Whether the system slept in between, if the boot clock moved `boot` while the monotonic clock
+/// only moved `monotonic`.
+fn has_slept_between(monotonic: Duration, boot: Duration) -> bool {
+    boot.saturating_sub(monotonic) >= MIN_SLEEP
+}
+
+/// Notices suspends by the monotonic clock falling behind the boot clock, for loops which can't
+/// rely on logind, like `--i3bar`.
+pub struct SleepDetector {
+    /// Both clocks as of the last check, `None` without a boot clock.
+    last: Option<(Instant, Duration)>,
+}
+
+impl SleepDetector {
+    pub fn new() -> Self {
+        Self {
+            last: boot_time().map(|boot| (Instant::now(), boot)),
+        }
+    }
+
+    /// Whether the system slept since the last call.
+    pub fn has_slept(&mut self) -> bool {
+        let Some((last_instant, last_boot)) = self.last else {
+            return false;
+        };
+
+        let Some(boot) = boot_time() else {
+            return false;
+        };
+
+        let now = Instant::now();
+        self.last = Some((now, boot));
+        has_slept_between(now - last_instant, boot.saturating_sub(last_boot))
+    }
+}
+
+/// Refreshes everything that may be stale after the system has resumed.
+fn on_resume() {
+    log::info!("Resumed from suspend, refreshing the bar");
+    RESUMES.fetch_add(1, Ordering::SeqCst);
+    // Cached output is from before the suspend, however long ago that was.
+    command::clear_cache();
+    // The power adapter may have been plugged in or out in the meantime.
+    power::forget();
+    // The IPC connections may have silently died, they're reconnected once closed.
+    compositor::reconnect();
+    pause::run_all();
+}
+
+/// Refreshes the bar whenever the system resumes from suspend, as reported by logind over the
+/// system bus. Without logind the clocks are compared every few seconds instead, see
+/// `SleepDetector`.
+/// Only call this once as it's a loop.
+pub fn refresh_on_resume() {
+    // GTK can only be touched from the main loop, so the resume is forwarded to it.
+    let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    thread::spawn(move || {
+        let iterator = Connection::system()
+            .map_err(|err| format!("Cannot connect to the system bus: {err}"))
+            .and_then(|connection| {
+                MessageIterator::for_match_rule(PREPARE_FOR_SLEEP_RULE, &connection, Some(8))
+                    .map_err(|err| format!("Cannot listen to logind: {err}"))
+            });
+        let iterator = match iterator {
+            Ok(iterator) => iterator,
+            Err(err) => {
+                log::debug!("{err}, detecting resumes from the clocks instead");
+                let _ = sender.send(Event::NoLogind);
+                return;
+            }
+        };
+
+        for message in iterator.flatten() {
+            // `true` right before suspending, `false` once resumed.
+            if message.body::<bool>().is_ok_and(|suspending| !suspending)
+                && sender.send(Event::Resumed).is_err()
+            {
+                // The main loop is gone.
+                break;
+            }
+        }
+    });
+
+    receiver.attach(None, |event| {
+        match event {
+            Event::Resumed => on_resume(),
+            Event::NoLogind => {
+                let mut detector = SleepDetector::new();
+                // Not paused along with the bar, as hidden bars have to resync too.
+                glib::timeout_add_local(DETECT_INTERVAL, move || {
+                    if detector.has_slept() {
+                        on_resume();
+                    }
+
+                    glib::Continue(true)
+                });
+            }
+        }
+
+        glib::Continue(true)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sleep_detection() {
+        let seconds = Duration::from_secs;
+        assert!(!has_slept_between(seconds(5), seconds(5)));
+        // The clocks drifting apart a little isn't a suspend.
+        assert!(!has_slept_between(seconds(5), seconds(6)));
+        assert!(has_slept_between(seconds(5), seconds(3600)));
+        // The boot clock never runs slower, but it shouldn't underflow if it did.
+        assert!(!has_slept_between(seconds(5), seconds(1)));
+        assert!(SleepDetector::new().last.is_some());
+    }
+}
@@ -0,0 +1,82 @@
+use crate::config;
+use gtk::gdk::Monitor;
+use std::cell::Cell;
+
+/// The DPI a config written in plain pixels is meant for.
+const BASE_DPI: f64 = 96.0;
+
+/// Monitors reporting a physical width below this many millimeters are assumed to be lying about
+/// it, like projectors and some virtual outputs reporting `0`.
+const MIN_WIDTH_MM: i32 = 50;
+
+thread_local! {
+    /// The scale of the bar that's currently being built, see `set`.
+    static CURRENT: Cell<f64> = const { Cell::new(1.0) };
+}
+
+/// Computes the scale on top of GDK's own for a monitor `width` logical pixels and `width_mm`
+/// millimeters wide, rounded down to halves so regular monitors stay at `1.0`.
+/// GDK already scales logical pixels by its integer scale factor, so `width` is what remains
+/// after that. Never scales down, as GDK errs on the side of too large already.
+pub fn compute(width: i32, width_mm: i32) -> f64 {
+    if width <= 0 || width_mm < MIN_WIDTH_MM {
+        return 1.0;
+    }
+
+    let dpi = f64::from(width) / (f64::from(width_mm) / 25.4);
+    ((dpi / BASE_DPI * 2.0).floor() / 2.0).max(1.0)
+}
+
+/// Gets the scale to apply to pixel sizes in the config for bars on `monitor`, from
+/// `hybrid` -> `scale`. `false` turns it off, a number is used as-is, and otherwise it's
+/// computed from the DPI of the monitor.
+pub fn for_monitor(monitor: &Monitor) -> f64 {
+    let scale = config::with_config(|config| config["hybrid"]["scale"].clone());
+    if let Some(scale) = config::as_f64(&scale) {
+        if scale > 0.0 {
+            return scale;
+        }
+
+        log::warn!("hybrid:scale {scale} has to be above 0, computing it instead");
+    }
+
+    if config::as_bool(&scale) == Some(false) {
+        return 1.0;
+    }
+
+    compute(monitor.geometry().width(), monitor.width_mm())
+}
+
+/// Sets the scale of the bar that's about to be built, pixel sizes are scaled by it through `px`.
+pub fn set(scale: f64) {
+    CURRENT.with(|current| current.set(scale));
+}
+
+/// Gets the scale of the bar that's currently being built.
+pub fn get() -> f64 {
+    CURRENT.with(Cell::get)
+}
+
+/// Scales `pixels` from the config to the bar that's currently being built.
+pub fn px(pixels: i32) -> i32 {
+    (f64::from(pixels) * get()).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_from_dpi() {
+        // A 24" 1080p monitor, and a 27" 1440p one.
+        assert_eq!(compute(1920, 531), 1.0);
+        assert_eq!(compute(2560, 597), 1.0);
+        // A 14" 2880x1800 laptop without GDK scaling, and with a GDK scale factor of 2.
+        assert_eq!(compute(2880, 302), 2.5);
+        assert_eq!(compute(1440, 302), 1.0);
+        // A 27" 4K monitor without GDK scaling.
+        assert_eq!(compute(3840, 597), 1.5);
+        // Unknown physical sizes.
+        assert_eq!(compute(1920, 0), 1.0);
+    }
+}
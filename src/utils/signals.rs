@@ -0,0 +1,65 @@
+use crate::{command, ui};
+use gtk::{gio::prelude::ApplicationExt, Application};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM, SIGUSR1},
+    iterator::Signals,
+};
+use std::{cell::Cell, process, thread};
+
+/// Reloads the config whenever a `SIGUSR1` is received, for example through
+/// `pkill -USR1 hybrid-bar`.
+/// Only call this once as it's a loop.
+pub fn reload_on_sigusr1() {
+    // Signal handlers can't touch GTK, so the signal is forwarded to the main loop.
+    let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    let mut signals = Signals::new([SIGUSR1]).expect("Cannot register the SIGUSR1 handler!");
+
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            if sender.send(()).is_err() {
+                // The main loop is gone.
+                break;
+            }
+        }
+    });
+
+    receiver.attach(None, |_| {
+        let _ = ui::reload("SIGUSR1", false);
+
+        glib::Continue(true)
+    });
+}
+
+/// Quits `application` when a `SIGTERM` or `SIGINT` is received, so the bar shuts down cleanly
+/// rather than leaving its commands and scripts running. A second signal exits right away, in case
+/// shutting down hangs.
+/// Only call this once as it's a loop.
+pub fn quit_on_sigterm(application: &Application) {
+    // Signal handlers can't touch GTK, so the signal is forwarded to the main loop.
+    let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    let mut signals =
+        Signals::new([SIGTERM, SIGINT]).expect("Cannot register the SIGTERM handler!");
+
+    thread::spawn(move || {
+        for signal in signals.forever() {
+            if sender.send(signal).is_err() {
+                // The main loop is gone.
+                break;
+            }
+        }
+    });
+
+    let application = application.clone();
+    let quitting = Cell::new(false);
+    receiver.attach(None, move |signal| {
+        if quitting.replace(true) {
+            log::warn!("Received signal {signal} again, exiting right away");
+            command::terminate_children();
+            process::exit(1)
+        }
+
+        log::info!("Received signal {signal}, shutting down");
+        application.quit();
+        glib::Continue(true)
+    });
+}
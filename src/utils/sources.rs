@@ -0,0 +1,279 @@
+use crate::{
+    command::{BashRunner, CommandRunner},
+    config, errors, locks, pause, resume,
+};
+use json::JsonValue;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+use tokio::task;
+
+/// Has the source loop been started?
+static HAS_STARTED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// The fields of every source by their path, like `weather.temp`.
+    static ref FIELDS: RwLock<HashMap<String, String>> = RwLock::new(HashMap::new());
+}
+
+/// A command from `sources` whose JSON output is shared by several widgets, so it only runs once
+/// per update rather than once per widget.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Source {
+    pub name: String,
+    pub command: String,
+    pub update_rate: u64,
+    pub command_timeout: u64,
+}
+
+/// Gets the sources from the `sources` section, which is an object like
+/// `{ "weather": { "command": "~/weather.sh", "update_rate": "10m" } }`. Variables are expanded in
+/// the commands. Sources without a command are skipped, `--check` reports them.
+pub fn get_sources() -> Vec<Source> {
+    let sources: Vec<(String, String, JsonValue, JsonValue)> = config::with_config(|config| {
+        config["sources"]
+            .entries()
+            .filter_map(|(name, source)| {
+                let command = source["command"].as_str()?;
+                Some((
+                    name.to_string(),
+                    command.to_string(),
+                    source["update_rate"].clone(),
+                    source["command_timeout"].clone(),
+                ))
+            })
+            .collect()
+    });
+
+    // Expanded after the config lock has been released.
+    sources
+        .into_iter()
+        .map(|(name, command, update_rate, command_timeout)| Source {
+            update_rate: config::as_update_rate(
+                &format!("sources:{name}:update_rate"),
+                &update_rate,
+            )
+            .unwrap_or_else(config::get_update_rate),
+            command_timeout: config::as_i32(&command_timeout)
+                .and_then(|timeout| u64::try_from(timeout).ok())
+                .unwrap_or_else(|| config::get_command_timeout("hybrid")),
+            command: config::with_variables(command),
+            name,
+        })
+        .collect()
+}
+
+/// Flattens the JSON `value` into `fields`, every string, number and bool by its path below
+/// `path`. Objects are entered by key and arrays by index, like `weather.days.0.high`.
+pub fn flatten(path: &str, value: &JsonValue, fields: &mut Vec<(String, String)>) {
+    match value {
+        JsonValue::Object(object) => {
+            for (key, value) in object.iter() {
+                flatten(&format!("{path}.{key}"), value, fields);
+            }
+        }
+        JsonValue::Array(array) => {
+            for (index, value) in array.iter().enumerate() {
+                flatten(&format!("{path}.{index}"), value, fields);
+            }
+        }
+        JsonValue::Null => {}
+        value => fields.push((path.to_string(), value.to_string())),
+    }
+}
+
+/// Parses the `output` of the source named `name`, returning its fields.
+pub fn parse(name: &str, output: &str) -> Result<Vec<(String, String)>, String> {
+    let value = json::parse(output).map_err(|err| format!("the output isn't JSON, {err}"))?;
+    if !value.is_object() && !value.is_array() {
+        return Err(String::from("expected an object or an array"));
+    }
+
+    let mut fields = Vec::new();
+    flatten(name, &value, &mut fields);
+    Ok(fields)
+}
+
+/// Gets the field at `path`, like `weather.temp`, from the last output of its source.
+pub fn get_field(path: &str) -> Option<String> {
+    locks::read(&FIELDS).get(path).cloned()
+}
+
+/// Drops the fields of sources which aren't among `sources` anymore, like after a reload removed
+/// or renamed them.
+fn retain_fields(fields: &mut HashMap<String, String>, sources: &[Source]) {
+    fields.retain(|path, _| {
+        sources.iter().any(|source| {
+            path.strip_prefix(source.name.as_str())
+                .is_some_and(|rest| rest.starts_with('.'))
+        })
+    });
+}
+
+/// Runs `source` once and replaces its fields with the new ones. If it fails, or doesn't print
+/// JSON, the previous fields are kept and the problem is shown on the error widget.
+fn update(source: &Source, runner: &impl CommandRunner) {
+    let name = format!("sources:{}", source.name);
+    let output = runner.execute(
+        &source.command,
+        Duration::from_millis(source.command_timeout),
+    );
+    errors::check(&name, &source.command, &output);
+    if !output.success {
+        return;
+    }
+
+    match parse(&source.name, &output.stdout) {
+        Ok(fields) => {
+            let prefix = format!("{}.", source.name);
+            let mut all_fields = locks::write(&FIELDS);
+            all_fields.retain(|path, _| !path.starts_with(&prefix));
+            all_fields.extend(fields);
+        }
+        Err(err) => {
+            log::warn!("{name}: {err}");
+            errors::report(&name, &err);
+        }
+    }
+}
+
+/// Runs every source at its own `update_rate` on worker threads, so a slow command can't block
+/// the widgets showing its fields. Sources are read from the config on every round, so reloads
+/// are picked up.
+/// Safe to call more than once, the loop is only started once.
+pub fn run() {
+    if HAS_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    task::spawn(async move {
+        // When each source should run next. A source whose command or update-rate was changed by a
+        // reload is a new one, so it runs right away.
+        let mut next_updates: HashMap<Source, Option<Instant>> = HashMap::new();
+        // Sources whose command is still running, so a hanging command isn't executed again.
+        let running = Arc::new(Mutex::new(HashSet::new()));
+        let mut resumes = resume::count();
+        loop {
+            let now = Instant::now();
+            let mut next_wake = now + Duration::from_secs(1);
+            // Fields from before a suspend are stale, run everything again.
+            if resumes != resume::count() {
+                resumes = resume::count();
+                next_updates.clear();
+            }
+
+            if pause::is_paused() {
+                tokio::time::sleep_until(next_wake.into()).await;
+                continue;
+            }
+
+            let sources = get_sources();
+            next_updates.retain(|source, _| sources.contains(source));
+            retain_fields(&mut locks::write(&FIELDS), &sources);
+            for source in sources {
+                // Every source runs once when it's added, the update-rate decides the rest.
+                let next_update = next_updates.entry(source.clone()).or_insert(Some(now));
+                if next_update.is_some_and(|next_update| next_update <= now)
+                    && locks::lock(&running).insert(source.name.clone())
+                {
+                    *next_update = match source.update_rate {
+                        0 => None,
+                        update_rate => Some(now + Duration::from_millis(update_rate)),
+                    };
+
+                    let running = running.clone();
+                    task::spawn_blocking(move || {
+                        update(&source, &BashRunner);
+                        locks::lock(&running).remove(&source.name);
+                    });
+                }
+
+                if let Some(next_update) = next_update {
+                    next_wake = next_wake.min(*next_update);
+                }
+            }
+
+            tokio::time::sleep_until(next_wake.into()).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::MockRunner;
+
+    #[test]
+    fn source_fields() {
+        let fields = parse(
+            "weather",
+            r#"{ "temp": 21.5, "sky": "clear", "days": [{ "high": 24 }], "alerts": null }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            fields,
+            vec![
+                (String::from("weather.temp"), String::from("21.5")),
+                (String::from("weather.sky"), String::from("clear")),
+                (String::from("weather.days.0.high"), String::from("24")),
+            ]
+        );
+
+        assert!(parse("weather", "sunny")
+            .unwrap_err()
+            .contains("isn't JSON"));
+        assert!(parse("weather", "42").is_err());
+    }
+
+    #[test]
+    fn failed_sources_keep_their_fields() {
+        let source = Source {
+            name: String::from("tests-battery"),
+            command: String::from("battery.sh"),
+            update_rate: 0,
+            command_timeout: 0,
+        };
+
+        update(
+            &source,
+            &MockRunner::default().with("battery.sh", r#"{ "level": 80 }"#, true),
+        );
+        assert_eq!(get_field("tests-battery.level").as_deref(), Some("80"));
+
+        update(&source, &MockRunner::default());
+        update(
+            &source,
+            &MockRunner::default().with("battery.sh", "oops", true),
+        );
+        assert_eq!(get_field("tests-battery.level").as_deref(), Some("80"));
+    }
+
+    #[test]
+    fn removed_sources_lose_their_fields() {
+        let mut fields: HashMap<String, String> = [
+            "weather.temp",
+            "weather.days.0.high",
+            "weatherman.name",
+            "battery.level",
+        ]
+        .into_iter()
+        .map(|path| (path.to_string(), String::new()))
+        .collect();
+        let weather = Source {
+            name: String::from("weather"),
+            command: String::from("weather.sh"),
+            update_rate: 0,
+            command_timeout: 0,
+        };
+
+        retain_fields(&mut fields, &[weather]);
+        let mut paths: Vec<&String> = fields.keys().collect();
+        paths.sort();
+        assert_eq!(paths, ["weather.days.0.high", "weather.temp"]);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{color, config, scale};
+use glib::translate::IntoGlib;
+use gtk::{
+    gdk::Screen,
+    pango::{self, FontDescription, FontMask, Style},
+    prelude::*,
+    CssProvider, StyleContext, Widget,
+};
+
+thread_local! {
+    /// The provider holding the stylesheet, GTK objects can only be used on the main thread.
+    static PROVIDER: CssProvider = CssProvider::new();
+}
+
+/// Returns the full path to the stylesheet from `hybrid` -> `stylesheet`, relative to the config
+/// path. Defaults to `style.css`.
+fn get_stylesheet_path() -> String {
+    // 0.2.8: Allow for defining the name of the stylesheet to look up
+    let css_file = config::try_get("hybrid", "stylesheet", true, false)
+        .map_or_else(|| String::from("style.css"), |c_css_file| c_css_file.0);
+
+    let mut css_path = config::get_path();
+    css_path.push_str(&css_file);
+    css_path
+}
+
+/// Loads the stylesheet and applies it to the default screen.
+/// Only call this once, use `reload` to load the stylesheet again.
+pub fn load() {
+    PROVIDER.with(|provider| {
+        // 0.3.5: Errors are logged and the rest of the stylesheet is still applied, rather than
+        //   crashing.
+        provider.connect_parsing_error(|_, section, error| {
+            log::warn!("CSS error at line {}: {error}", section.start_line() + 1);
+        });
+
+        // Add the provider to the default screen
+        StyleContext::add_provider_for_screen(
+            &Screen::default().expect("Couldn't find any valid displays!"),
+            provider,
+            gtk::STYLE_PROVIDER_PRIORITY_USER,
+        );
+    });
+
+    reload();
+}
+
+/// Loads the stylesheet again, for example after the config has been reloaded.
+pub fn reload() {
+    let css_path = get_stylesheet_path();
+    PROVIDER.with(|provider| {
+        if let Err(err) = provider.load_from_path(&css_path) {
+            log::warn!("Failed loading CSS from '{css_path}': {err}");
+        }
+    });
+}
+
+/// Applies `font`, a Pango font description like `JetBrains Mono Bold 12`, and `font_size` in
+/// points to `widget` only. `font_size` takes priority over the size in `font`.
+/// It's applied below the priority of the stylesheet, so the stylesheet can still override it.
+pub fn apply_font(widget: &Widget, font: &str, font_size: Option<f64>) {
+    if let Err(err) = apply_css(widget, &get_font_css(font, font_size)) {
+        log::warn!("Failed applying the font '{font}': {err}");
+    }
+}
+
+/// Applies the `color` and `background` of the widget at `key`, hex colors like `#ff0000`, to
+/// `widget` only. Same priority as `apply_font`, so the stylesheet can still override them.
+pub fn apply_colors(widget: &Widget, key: &str) {
+    let mut css = String::new();
+    for (config_key, property) in [("color", "color"), ("background", "background-color")] {
+        let Some((value, _)) = config::try_get(key, config_key, true, false) else {
+            continue;
+        };
+
+        match color::parse_hex(&value) {
+            Some(color) => css.push_str(&format!("{property}: {}; ", color::to_css_rgba(&color))),
+            None => log::warn!("{key}: Invalid {config_key} '{value}', expected a hex color"),
+        }
+    }
+
+    if css.is_empty() {
+        return;
+    }
+
+    if let Err(err) = apply_css(widget, &css) {
+        log::warn!("{key}: Failed applying the colors: {err}");
+    }
+}
+
+/// Applies the CSS `properties` to `widget` only, below the priority of the stylesheet.
+fn apply_css(widget: &Widget, properties: &str) -> Result<(), glib::Error> {
+    let provider = CssProvider::new();
+    provider.load_from_data(format!("* {{ {properties} }}").as_bytes())?;
+    widget
+        .style_context()
+        .add_provider(&provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+    Ok(())
+}
+
+/// Translates the fields that are set in the font description to CSS properties.
+fn get_font_css(font: &str, font_size: Option<f64>) -> String {
+    let mut css = String::new();
+    let description = FontDescription::from_string(font);
+    let fields = description.set_fields();
+    if let Some(family) = description
+        .family()
+        .filter(|_| fields.contains(FontMask::FAMILY))
+    {
+        // Pango separates fallback families with commas, just like CSS does.
+        let families: Vec<String> = family
+            .split(',')
+            .map(|family| format!("\"{}\"", family.trim().replace('"', "\\\"")))
+            .collect();
+        css.push_str(&format!("font-family: {}; ", families.join(", ")));
+    }
+
+    if fields.contains(FontMask::WEIGHT) {
+        css.push_str(&format!(
+            "font-weight: {}; ",
+            description.weight().into_glib()
+        ));
+    }
+
+    if fields.contains(FontMask::STYLE) {
+        let style = match description.style() {
+            Style::Italic => "italic",
+            Style::Oblique => "oblique",
+            _ => "normal",
+        };
+        css.push_str(&format!("font-style: {style}; "));
+    }
+
+    // 0.3.5: Scaled along with the pixel sizes, so text keeps its size relative to the widgets.
+    match font_size {
+        Some(size) => css.push_str(&format!("font-size: {}pt; ", size * scale::get())),
+        None if fields.contains(FontMask::SIZE) => {
+            let size = description.size() as f64 / pango::SCALE as f64 * scale::get();
+            let unit = if description.is_size_absolute() {
+                "px"
+            } else {
+                "pt"
+            };
+            css.push_str(&format!("font-size: {size}{unit}; "));
+        }
+        None => {}
+    }
+
+    css
+}
@@ -0,0 +1,202 @@
+use crate::{
+    compositor,
+    structures::{Window, Workspace},
+};
+use json::JsonValue;
+use std::{
+    env,
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+};
+
+/// Every IPC message starts with this.
+const MAGIC: &[u8; 6] = b"i3-ipc";
+
+/// Runs the payload as a command.
+const RUN_COMMAND: u32 = 0;
+
+/// Gets the list of workspaces.
+const GET_WORKSPACES: u32 = 1;
+
+/// Subscribes to a list of events.
+const SUBSCRIBE: u32 = 2;
+
+/// Gets the layout tree.
+const GET_TREE: u32 = 4;
+
+/// Gets the input devices, Sway only.
+const GET_INPUTS: u32 = 100;
+
+/// Events after which the workspaces are fetched again.
+pub const WORKSPACE_EVENTS: &[&str] = &["workspace", "window"];
+
+/// Events after which the focused window is fetched again. Switching to an empty workspace only
+/// sends a `workspace` event.
+pub const WINDOW_EVENTS: &[&str] = &["window", "workspace"];
+
+/// Events after which the keyboard layout is fetched again, Sway only.
+pub const INPUT_EVENTS: &[&str] = &["input"];
+
+/// Returns the path to the IPC socket, from `$SWAYSOCK` or `$I3SOCK`.
+pub fn get_socket_path() -> Option<String> {
+    ["SWAYSOCK", "I3SOCK"]
+        .into_iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|path| !path.is_empty())
+}
+
+/// Checks whether the compositor is Sway rather than i3, which doesn't have all the same messages.
+pub fn is_sway() -> bool {
+    env::var("SWAYSOCK").is_ok_and(|path| !path.is_empty())
+}
+
+/// Connects to the IPC socket.
+fn connect() -> io::Result<UnixStream> {
+    let path = get_socket_path().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Neither $SWAYSOCK nor $I3SOCK is set",
+        )
+    })?;
+
+    UnixStream::connect(path)
+}
+
+/// Sends a message of `message_type` with `payload`.
+fn send(stream: &mut UnixStream, message_type: u32, payload: &str) -> io::Result<()> {
+    let mut message = Vec::with_capacity(MAGIC.len() + 8 + payload.len());
+    message.extend_from_slice(MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&message_type.to_ne_bytes());
+    message.extend_from_slice(payload.as_bytes());
+    stream.write_all(&message)
+}
+
+/// Receives a message, returning its type and parsed payload.
+fn receive(stream: &mut UnixStream) -> io::Result<(u32, JsonValue)> {
+    let mut header = [0; 14];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid IPC message",
+        ));
+    }
+
+    let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let message_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+    let mut payload = vec![0; length as usize];
+    stream.read_exact(&mut payload)?;
+    let payload = json::parse(&String::from_utf8_lossy(&payload))
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    Ok((message_type, payload))
+}
+
+/// Sends a message and waits for the reply.
+fn request(message_type: u32, payload: &str) -> io::Result<JsonValue> {
+    let mut stream = connect()?;
+    send(&mut stream, message_type, payload)?;
+    Ok(receive(&mut stream)?.1)
+}
+
+/// Runs `command`, for example `workspace 2`.
+pub fn run_command(command: &str) -> io::Result<()> {
+    request(RUN_COMMAND, command).map(|_| ())
+}
+
+/// Switches to `workspace`.
+pub fn focus_workspace(workspace: &Workspace) -> io::Result<()> {
+    run_command(&format!(
+        "workspace \"{}\"",
+        workspace.name.replace('\\', "\\\\").replace('"', "\\\"")
+    ))
+}
+
+/// Collects the names of the workspaces in `node` which have windows on them.
+fn collect_occupied(node: &JsonValue, occupied: &mut Vec<String>) {
+    if node["type"] == "workspace" {
+        if !node["nodes"].is_empty() || !node["floating_nodes"].is_empty() {
+            occupied.push(node["name"].to_string());
+        }
+
+        return;
+    }
+
+    for child in node["nodes"].members() {
+        collect_occupied(child, occupied);
+    }
+}
+
+/// Gets all workspaces.
+pub fn get_workspaces() -> io::Result<Vec<Workspace>> {
+    let mut occupied = Vec::new();
+    collect_occupied(&request(GET_TREE, "")?, &mut occupied);
+    Ok(request(GET_WORKSPACES, "")?
+        .members()
+        .map(|workspace| {
+            let name = workspace["name"].to_string();
+            Workspace {
+                id: workspace["num"].as_i64().unwrap_or(-1),
+                occupied: occupied.contains(&name),
+                name,
+                focused: workspace["focused"].as_bool().unwrap_or_default(),
+                visible: workspace["visible"].as_bool().unwrap_or_default(),
+                urgent: workspace["urgent"].as_bool().unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+/// Finds the focused window in `node`, workspaces and outputs can be focused too.
+fn find_focused(node: &JsonValue) -> Option<&JsonValue> {
+    if node["focused"] == true {
+        return matches!(node["type"].as_str(), Some("con" | "floating_con")).then_some(node);
+    }
+
+    node["nodes"]
+        .members()
+        .chain(node["floating_nodes"].members())
+        .find_map(find_focused)
+}
+
+/// Gets the focused window, `None` if nothing is focused like on an empty workspace.
+pub fn get_focused_window() -> io::Result<Option<Window>> {
+    let tree = request(GET_TREE, "")?;
+    Ok(find_focused(&tree).map(|window| Window {
+        title: window["name"].as_str().unwrap_or_default().to_string(),
+        app_id: window["app_id"]
+            .as_str()
+            .or_else(|| window["window_properties"]["class"].as_str())
+            .unwrap_or_default()
+            .to_string(),
+    }))
+}
+
+/// Gets the active layout of the first keyboard, for example `English (US)`.
+pub fn get_keyboard_layout() -> io::Result<Option<String>> {
+    Ok(request(GET_INPUTS, "")?
+        .members()
+        .filter(|input| input["type"] == "keyboard")
+        .find_map(|input| input["xkb_active_layout_name"].as_str().map(String::from)))
+}
+
+/// Subscribes to `events`, calling `on_change` for every event until it returns `false` or the
+/// connection is lost.
+pub fn subscribe(events: &[&str], mut on_change: impl FnMut() -> bool) -> io::Result<()> {
+    let mut stream = connect()?;
+    let _tracked = compositor::track(&stream)?;
+    let events = JsonValue::from(events.to_vec());
+    send(&mut stream, SUBSCRIBE, &events.dump())?;
+    let (_, reply) = receive(&mut stream)?;
+    if reply["success"] != true {
+        return Err(io::Error::other("Failed subscribing to events"));
+    }
+
+    loop {
+        receive(&mut stream)?;
+        if !on_change() {
+            return Ok(());
+        }
+    }
+}
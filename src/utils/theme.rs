@@ -0,0 +1,122 @@
+use crate::config;
+use gtk::{glib::GString, traits::SettingsExt, Settings};
+use std::{cell::RefCell, env, path::PathBuf};
+
+/// Themes built into GTK, which aren't installed anywhere.
+const BUILTIN_GTK_THEMES: &[&str] = &["Adwaita", "HighContrast", "HighContrastInverse"];
+
+thread_local! {
+    /// The GTK and icon theme from before `apply` first changed them, to go back to once they're
+    /// removed from the config.
+    static SYSTEM_THEMES: RefCell<Option<(Option<GString>, Option<GString>)>> =
+        const { RefCell::new(None) };
+}
+
+/// Gets the directories themes of `kind`, `themes` or `icons`, are looked up in. The user's own
+/// come first, like GTK does: `$XDG_DATA_HOME` (or `~/.local/share`), `~/.<kind>` and then
+/// `$XDG_DATA_DIRS` (or `/usr/local/share:/usr/share`).
+fn get_search_dirs(kind: &str) -> Vec<PathBuf> {
+    let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+    let home = var("HOME").map(PathBuf::from);
+    let mut dirs = Vec::new();
+    if let Some(data_home) = var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".local/share")))
+    {
+        dirs.push(data_home.join(kind));
+    }
+
+    if let Some(home) = home {
+        dirs.push(home.join(format!(".{kind}")));
+    }
+
+    let data_dirs =
+        var("XDG_DATA_DIRS").unwrap_or_else(|| String::from("/usr/local/share:/usr/share"));
+    dirs.extend(
+        data_dirs
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| PathBuf::from(dir).join(kind)),
+    );
+    dirs
+}
+
+/// Whether a theme called `name` is in any of `dirs`, so it has a `marker` inside, like
+/// `index.theme` for icon themes.
+pub fn is_installed(name: &str, dirs: &[PathBuf], marker: &str) -> bool {
+    // Names are directories, anything with separators would point elsewhere.
+    !name.is_empty()
+        && !name.contains('/')
+        && dirs.iter().any(|dir| dir.join(name).join(marker).exists())
+}
+
+/// Whether the icon theme called `name` is installed.
+pub fn is_icon_theme_installed(name: &str) -> bool {
+    is_installed(name, &get_search_dirs("icons"), "index.theme")
+}
+
+/// Whether the GTK theme called `name` is built into GTK, or installed with a GTK 3 variant.
+pub fn is_gtk_theme_installed(name: &str) -> bool {
+    BUILTIN_GTK_THEMES.contains(&name) || is_installed(name, &get_search_dirs("themes"), "gtk-3.0")
+}
+
+/// Picks the theme at `hybrid` -> `key` if it's installed, otherwise warns and picks `system`.
+fn pick(key: &str, system: Option<GString>, is_installed: fn(&str) -> bool) -> Option<GString> {
+    let (name, _) = config::try_get("hybrid", key, true, false)?;
+    if is_installed(&name) {
+        return Some(GString::from(name));
+    }
+
+    log::warn!("hybrid:{key} '{name}' isn't installed, using the system theme instead");
+    system
+}
+
+/// Applies `hybrid` -> `gtk_theme` and `hybrid` -> `icon_theme` to the bar, falling back to the
+/// system themes when they're unset or not installed. Icons looked up by name, like those of the
+/// image and tray widgets, come from the icon theme.
+/// Call this again after the config has been reloaded.
+pub fn apply() {
+    let Some(settings) = Settings::default() else {
+        return;
+    };
+
+    let (gtk_system, icon_system) = SYSTEM_THEMES.with(|system| {
+        system
+            .borrow_mut()
+            .get_or_insert_with(|| (settings.gtk_theme_name(), settings.gtk_icon_theme_name()))
+            .clone()
+    });
+
+    let gtk_theme = pick("gtk_theme", gtk_system, is_gtk_theme_installed);
+    if settings.gtk_theme_name() != gtk_theme {
+        settings.set_gtk_theme_name(gtk_theme.as_deref());
+    }
+
+    let icon_theme = pick("icon_theme", icon_system, is_icon_theme_installed);
+    if settings.gtk_icon_theme_name() != icon_theme {
+        settings.set_gtk_icon_theme_name(icon_theme.as_deref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn installed_themes() {
+        let dir = std::env::temp_dir().join(format!("hybrid-theme-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("Papirus")).unwrap();
+        fs::write(dir.join("Papirus").join("index.theme"), "").unwrap();
+        fs::create_dir_all(dir.join("Broken")).unwrap();
+
+        let dirs = [dir.join("missing"), dir.clone()];
+        assert!(is_installed("Papirus", &dirs, "index.theme"));
+        assert!(!is_installed("Broken", &dirs, "index.theme"));
+        assert!(!is_installed("../Papirus", &dirs, "index.theme"));
+        assert!(!is_installed("", &dirs, "index.theme"));
+        assert!(is_gtk_theme_installed("Adwaita"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,94 @@
+use json::JsonValue;
+use toml::Value;
+
+/// Parses a TOML config into the same tree a JSON config would produce, so the rest of Hybrid
+/// doesn't need to know the difference.
+pub fn parse(content: &str) -> Result<JsonValue, toml::de::Error> {
+    Ok(to_json(Value::Table(toml::from_str(content)?)))
+}
+
+/// Converts a TOML value into its JSON equivalent.
+/// Datetimes have no JSON equivalent, so they're kept as strings such as `1979-05-27T07:32:00Z`.
+fn to_json(value: Value) -> JsonValue {
+    match value {
+        Value::String(string) => JsonValue::from(string),
+        Value::Integer(integer) => JsonValue::from(integer),
+        Value::Float(float) => JsonValue::from(float),
+        Value::Boolean(bool) => JsonValue::from(bool),
+        Value::Datetime(datetime) => JsonValue::from(datetime.to_string()),
+        Value::Array(array) => JsonValue::Array(array.into_iter().map(to_json).collect()),
+        Value::Table(table) => {
+            // Keeps the declaration order, as the widgets are laid out in it.
+            let mut object = JsonValue::new_object();
+            for (key, value) in table {
+                object[key] = to_json(value);
+            }
+
+            object
+        }
+    }
+}
+
+/// Checks whether the config at `path` should be parsed as TOML, based on its extension.
+pub fn is_toml(path: &str) -> bool {
+    path.to_lowercase().ends_with(".toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_and_arrays_become_objects_and_arrays() {
+        let config = parse(
+            r##"
+            [hybrid]
+            namespace = "hybrid" # A comment.
+
+            [left-label_a]
+            text = "# not a comment"
+            update_rate = 500
+            ratio = 0.5
+            markup = false
+            transform = [{ round = 1 }, { suffix = "%" },]
+
+            [left-label_a.nested]
+            list = [[1, 2], [3]]
+            created = 1979-05-27T07:32:00Z
+            "##,
+        )
+        .unwrap();
+        assert_eq!(config["hybrid"]["namespace"], "hybrid");
+        let label = &config["left-label_a"];
+        assert_eq!(label["text"], "# not a comment");
+        assert_eq!(label["update_rate"], 500);
+        assert_eq!(label["ratio"], 0.5);
+        assert_eq!(label["markup"], false);
+        assert_eq!(label["transform"].len(), 2);
+        assert_eq!(label["transform"][1]["suffix"], "%");
+        assert_eq!(label["nested"]["list"][0][1], 2);
+        assert_eq!(label["nested"]["created"], "1979-05-27T07:32:00Z");
+    }
+
+    #[test]
+    fn tables_keep_their_order() {
+        let config = parse("[right-label_b]\n[left-label_a]\n[centered-label_c]\n").unwrap();
+        let keys: Vec<&str> = config.entries().map(|(key, _)| key).collect();
+        assert_eq!(keys, ["right-label_b", "left-label_a", "centered-label_c"]);
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let error = parse("[hybrid]\nnamespace = \"hybrid\"\ntext = \n").unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("TOML parse error at line 3, column 8"));
+    }
+
+    #[test]
+    fn toml_paths() {
+        assert!(is_toml("~/.config/HybridBar/config.toml"));
+        assert!(is_toml("config.TOML"));
+        assert!(!is_toml("config.json"));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{color, config, errors};
+use json::JsonValue;
+
+/// A single step of a `transform`, applied to the output of the previous one.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    /// Divides a number, like bytes into MiB with `1048576`.
+    Divide(f64),
+    /// Rounds a number to this many decimals.
+    Round(usize),
+    Prefix(String),
+    Suffix(String),
+    /// Replaces the text with the label it's mapped to, text that isn't mapped is kept as-is.
+    Map(Vec<(String, String)>),
+    /// Mixes a hex color with white by this much, from `0.0` to `1.0`.
+    Lighten(f64),
+    /// Mixes a hex color with black by this much, from `0.0` to `1.0`.
+    Darken(f64),
+}
+
+/// Parses a `transform`, an array of single-key objects like
+/// `[{ "divide": 1024 }, { "round": 1 }, { "suffix": " KiB" }]`, applied in order.
+/// `null` is no transform at all. Errors name the step that failed.
+pub fn parse(transform: &JsonValue) -> Result<Vec<Step>, String> {
+    if transform.is_null() {
+        return Ok(Vec::new());
+    }
+
+    if !transform.is_array() {
+        return Err(String::from("expected an array of steps"));
+    }
+
+    let mut steps = Vec::new();
+    for (index, step) in transform.members().enumerate() {
+        let mut entries = step.entries();
+        let (Some((name, value)), None) = (entries.next(), entries.next()) else {
+            return Err(format!(
+                "step {} should be an object with a single key, like {{ \"round\": 1 }}",
+                index + 1
+            ));
+        };
+
+        let step = match name {
+            "divide" => config::as_f64(value)
+                .filter(|divisor| *divisor != 0.0 && divisor.is_finite())
+                .map(Step::Divide)
+                .ok_or("expected a number other than 0"),
+            "round" => config::as_i32(value)
+                .filter(|decimals| *decimals >= 0)
+                .map(|decimals| Step::Round(decimals as usize))
+                .ok_or("expected the number of decimals"),
+            "prefix" | "suffix" => value
+                .as_str()
+                .map(|text| match name {
+                    "prefix" => Step::Prefix(text.to_string()),
+                    _ => Step::Suffix(text.to_string()),
+                })
+                .ok_or("expected a string"),
+            "map" if value.is_object() => value
+                .entries()
+                .map(|(from, to)| to.as_str().map(|to| (from.to_string(), to.to_string())))
+                .collect::<Option<_>>()
+                .map(Step::Map)
+                .ok_or("expected strings as the labels"),
+            "map" => Err("expected an object of values and their labels"),
+            // 0.3.5: Mostly for `computed`, like a lighter variant of a theme color.
+            "lighten" | "darken" => config::as_f64(value)
+                .filter(|amount| (0.0..=1.0).contains(amount))
+                .map(|amount| match name {
+                    "lighten" => Step::Lighten(amount),
+                    _ => Step::Darken(amount),
+                })
+                .ok_or("expected an amount from 0.0 to 1.0"),
+            _ => {
+                Err("unknown step, expected divide, round, prefix, suffix, map, lighten or darken")
+            }
+        };
+
+        steps.push(step.map_err(|err| format!("step {} '{name}': {err}", index + 1))?);
+    }
+
+    Ok(steps)
+}
+
+/// Applies `steps` to `text` in order. Numeric steps fail on text that isn't a number, surrounding
+/// whitespace aside.
+pub fn apply(steps: &[Step], text: &str) -> Result<String, String> {
+    let mut text = text.to_string();
+    for step in steps {
+        text = match step {
+            Step::Divide(divisor) => (parse_number(&text)? / divisor).to_string(),
+            Step::Round(decimals) => format!("{:.*}", decimals, parse_number(&text)?),
+            Step::Prefix(prefix) => format!("{prefix}{text}"),
+            Step::Suffix(suffix) => format!("{text}{suffix}"),
+            Step::Map(labels) => labels
+                .iter()
+                .find(|(from, _)| from == text.trim())
+                .map_or(text, |(_, to)| to.clone()),
+            Step::Lighten(amount) => color::to_hex(&color::mix(&parse_color(&text)?, 255, *amount)),
+            Step::Darken(amount) => color::to_hex(&color::mix(&parse_color(&text)?, 0, *amount)),
+        };
+    }
+
+    Ok(text)
+}
+
+/// Applies `steps` to the `output` of the widget named `name`, reporting a failure as its error and
+/// showing the raw output instead.
+pub fn apply_or_raw(name: &str, steps: &[Step], output: &str) -> String {
+    match apply(steps, output) {
+        Ok(text) => text,
+        Err(err) => {
+            log::warn!("{name}: Failed transforming the output: {err}");
+            errors::report(name, &format!("Failed transforming '{output}': {err}"));
+            output.to_string()
+        }
+    }
+}
+
+/// Gets the `transform` of the widget at `key`, no steps at all if it's invalid.
+pub fn get(key: &str) -> Vec<Step> {
+    let transform = config::with_config(|config| parse(&config[key]["transform"]));
+    transform.unwrap_or_else(|err| {
+        log::warn!("{key}: Invalid transform, {err}");
+        Vec::new()
+    })
+}
+
+/// Parses `text` as a number for numeric steps.
+fn parse_number(text: &str) -> Result<f64, String> {
+    text.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite())
+        .ok_or_else(|| format!("'{}' isn't a number", text.trim()))
+}
+
+/// Parses `text` as a hex color for color steps.
+fn parse_color(text: &str) -> Result<(u8, u8, u8, u8), String> {
+    color::parse_hex(text.trim()).ok_or_else(|| format!("'{}' isn't a hex color", text.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_steps() {
+        let steps = parse(&json::array![
+            { "divide": 1024 },
+            { "round": 1 },
+            { "suffix": " KiB" }
+        ])
+        .unwrap();
+        assert_eq!(apply(&steps, "1536\n"), Ok(String::from("1.5 KiB")));
+        assert!(apply(&steps, "n/a").unwrap_err().contains("'n/a'"));
+
+        let steps = parse(&json::array![
+            { "map": { "0": "off", "1": "on" } },
+            { "prefix": "wifi: " }
+        ])
+        .unwrap();
+        assert_eq!(apply(&steps, "1"), Ok(String::from("wifi: on")));
+        assert_eq!(apply(&steps, "2"), Ok(String::from("wifi: 2")));
+
+        let steps = parse(&json::array![{ "lighten": 0.5 }, { "darken": 0.5 }]).unwrap();
+        assert_eq!(apply(&steps, "#000000"), Ok(String::from("#404040")));
+        assert!(apply(&steps, "red").unwrap_err().contains("hex color"));
+        assert!(parse(&json::array![{ "lighten": 2 }]).is_err());
+
+        assert_eq!(parse(&JsonValue::Null), Ok(Vec::new()));
+        assert!(parse(&json::array![{ "divide": 0 }]).is_err());
+        assert!(parse(&json::array![{ "round": 1, "suffix": "%" }]).is_err());
+        assert!(parse(&json::array![{ "multiply": 2 }])
+            .unwrap_err()
+            .contains("'multiply'"));
+    }
+}
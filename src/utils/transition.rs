@@ -0,0 +1,90 @@
+use crate::{config, math};
+use glib::{Continue, SourceId};
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// The longest allowed `transition_ms`, anything longer wouldn't keep up with the updates anyway.
+pub const MAX_TRANSITION_MS: i32 = 10000;
+
+/// Moves a widget from its old value to a new one over `duration`, rather than snapping to it.
+/// Only one runs at a time, starting a new one replaces the old one.
+#[derive(Default, Debug)]
+pub struct Transition {
+    duration: Duration,
+    /// The frames of the transition that's currently running, if any.
+    running: Rc<RefCell<Option<SourceId>>>,
+}
+
+impl Transition {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            running: Rc::default(),
+        }
+    }
+
+    /// Whether changes are shown right away.
+    pub fn is_instant(&self) -> bool {
+        self.duration.is_zero()
+    }
+
+    /// Stops the running transition where it is.
+    pub fn stop(&self) {
+        if let Some(running) = self.running.take() {
+            running.remove();
+        }
+    }
+
+    /// Calls `frame` at `hybrid` -> `fps` with how far along the transition is, eased from `0.0`
+    /// up to `1.0` which is always the last call. Pass it to `math::lerp` to move between values.
+    /// Without a duration `frame` is only called once, with `1.0`.
+    pub fn start(&self, mut frame: impl FnMut(f64) + 'static) {
+        self.stop();
+        if self.is_instant() {
+            frame(1.0);
+            return;
+        }
+
+        let start = Instant::now();
+        let duration = self.duration.as_secs_f64();
+        let running = self.running.clone();
+        let frames = glib::timeout_add_local(config::get_frame_interval(), move || {
+            let progress = start.elapsed().as_secs_f64() / duration;
+            frame(math::ease_in_out(progress));
+            if progress < 1.0 {
+                return Continue(true);
+            }
+
+            // Finished on its own, so there's nothing left to remove.
+            running.take();
+            Continue(false)
+        });
+
+        self.running.replace(Some(frames));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn instant_transition() {
+        let transition = Transition::new(Duration::ZERO);
+        assert!(transition.is_instant());
+
+        let frames = Rc::new(Cell::new(Vec::new()));
+        let seen = frames.clone();
+        transition.start(move |progress| {
+            let mut frames = seen.take();
+            frames.push(progress);
+            seen.set(frames);
+        });
+        assert_eq!(frames.take(), vec![1.0]);
+        assert!(transition.running.borrow().is_none());
+    }
+}
@@ -0,0 +1,353 @@
+use std::{
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+};
+use zbus::{
+    blocking::{Connection, MessageIterator, Proxy, ProxyBuilder},
+    dbus_interface,
+    fdo::{RequestNameFlags, RequestNameReply},
+    CacheProperties, MessageHeader, SignalContext,
+};
+
+/// The well-known name of the StatusNotifierWatcher, which keeps track of all tray items.
+const WATCHER_NAME: &str = "org.kde.StatusNotifierWatcher";
+
+/// Where the StatusNotifierWatcher lives.
+const WATCHER_PATH: &str = "/StatusNotifierWatcher";
+
+/// Where items live when they only register their bus name.
+const DEFAULT_ITEM_PATH: &str = "/StatusNotifierItem";
+
+/// The interface implemented by tray items.
+const ITEM_INTERFACE: &str = "org.kde.StatusNotifierItem";
+
+/// Matches items changing their icon, title, status or tooltip.
+const ITEM_CHANGED_RULE: &str = "type='signal',interface='org.kde.StatusNotifierItem'";
+
+/// Matches items being registered and unregistered.
+const WATCHER_CHANGED_RULE: &str = "type='signal',interface='org.kde.StatusNotifierWatcher'";
+
+/// Matches bus names disappearing, for items which exit without unregistering.
+const NAME_OWNER_CHANGED_RULE: &str = "type='signal',sender='org.freedesktop.DBus',\
+                                       interface='org.freedesktop.DBus',member='NameOwnerChanged'";
+
+/// Used to give every host a unique name, as there's one per tray widget.
+static HOST_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A tray icon, as exposed by an application.
+#[derive(Clone, Debug, Default)]
+pub struct TrayItem {
+    /// The bus name of the item.
+    pub destination: String,
+    pub path: String,
+    pub title: String,
+    /// A themed icon name, preferred over `pixmaps` if the icon exists.
+    pub icon_name: String,
+    /// An extra directory to look up `icon_name` in.
+    pub icon_theme_path: String,
+    /// Icons as `(width, height, ARGB32 data in network byte order)`.
+    pub pixmaps: Vec<(i32, i32, Vec<u8>)>,
+}
+
+/// A minimal StatusNotifierWatcher, only served when nothing else provides one.
+struct Watcher {
+    items: Arc<Mutex<Vec<String>>>,
+}
+
+#[dbus_interface(name = "org.kde.StatusNotifierWatcher")]
+impl Watcher {
+    /// Items register with either their bus name or an object path on the calling connection.
+    async fn register_status_notifier_item(
+        &self,
+        service: &str,
+        #[zbus(header)] header: MessageHeader<'_>,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) {
+        let item = if service.starts_with('/') {
+            let sender = header
+                .sender()
+                .ok()
+                .flatten()
+                .map(|sender| sender.to_string())
+                .unwrap_or_default();
+            format!("{sender}{service}")
+        } else {
+            format!("{service}{DEFAULT_ITEM_PATH}")
+        };
+
+        {
+            let mut items = self.items.lock().unwrap_or_else(|err| err.into_inner());
+            if items.contains(&item) {
+                return;
+            }
+
+            items.push(item.clone());
+        }
+
+        let _ = Watcher::status_notifier_item_registered(&ctxt, &item).await;
+    }
+
+    /// Only a single host (the bar) is tracked, so this only announces it.
+    async fn register_status_notifier_host(
+        &self,
+        _service: &str,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) {
+        let _ = Watcher::status_notifier_host_registered(&ctxt).await;
+    }
+
+    #[dbus_interface(property)]
+    fn registered_status_notifier_items(&self) -> Vec<String> {
+        self.items
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    #[dbus_interface(property)]
+    fn is_status_notifier_host_registered(&self) -> bool {
+        true
+    }
+
+    #[dbus_interface(property)]
+    fn protocol_version(&self) -> i32 {
+        0
+    }
+
+    #[dbus_interface(signal)]
+    async fn status_notifier_item_registered(
+        ctxt: &SignalContext<'_>,
+        service: &str,
+    ) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn status_notifier_item_unregistered(
+        ctxt: &SignalContext<'_>,
+        service: &str,
+    ) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn status_notifier_host_registered(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+}
+
+/// Serves a StatusNotifierWatcher on `connection` if nothing else does yet, returning the shared
+/// list of items if it did.
+fn serve_watcher(connection: &Connection) -> zbus::Result<Option<Arc<Mutex<Vec<String>>>>> {
+    let items = Arc::new(Mutex::default());
+    connection.object_server().at(
+        WATCHER_PATH,
+        Watcher {
+            items: items.clone(),
+        },
+    )?;
+
+    // Another bar or the desktop may already be providing one.
+    let reply =
+        connection.request_name_with_flags(WATCHER_NAME, RequestNameFlags::DoNotQueue.into());
+    if !matches!(reply, Ok(RequestNameReply::PrimaryOwner)) {
+        connection
+            .object_server()
+            .remove::<Watcher, _>(WATCHER_PATH)?;
+        return match reply {
+            Ok(_) | Err(zbus::Error::NameTaken) => Ok(None),
+            Err(err) => Err(err),
+        };
+    }
+
+    log::debug!("No StatusNotifierWatcher is running, providing one");
+    Ok(Some(items))
+}
+
+/// Drops the items owned by `name` from our own watcher, after it disappeared from the bus.
+fn forget_items(connection: &Connection, items: &Mutex<Vec<String>>, name: &str) {
+    let mut removed = Vec::new();
+    items
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .retain(|item| {
+            let owned = item.split_once('/').map_or(item.as_str(), |item| item.0) == name;
+            if owned {
+                removed.push(item.clone());
+            }
+
+            !owned
+        });
+
+    for item in removed {
+        let _ = connection.emit_signal(
+            None::<()>,
+            WATCHER_PATH,
+            WATCHER_NAME,
+            "StatusNotifierItemUnregistered",
+            &item,
+        );
+    }
+}
+
+/// Creates a proxy for the item at `destination` and `path`, properties aren't cached as they're
+/// read again on every change.
+fn item_proxy<'a>(
+    connection: &Connection,
+    destination: &'a str,
+    path: &'a str,
+) -> zbus::Result<Proxy<'a>> {
+    ProxyBuilder::new_bare(connection)
+        .destination(destination)?
+        .path(path)?
+        .interface(ITEM_INTERFACE)?
+        .cache_properties(CacheProperties::No)
+        .build()
+}
+
+/// Reads all the visible items from the watcher.
+fn read_items(connection: &Connection) -> zbus::Result<Vec<TrayItem>> {
+    let watcher = ProxyBuilder::<Proxy>::new_bare(connection)
+        .destination(WATCHER_NAME)?
+        .path(WATCHER_PATH)?
+        .interface(WATCHER_NAME)?
+        .cache_properties(CacheProperties::No)
+        .build()?;
+
+    let mut items = Vec::new();
+    for item in watcher.get_property::<Vec<String>>("RegisteredStatusNotifierItems")? {
+        // Formatted as `bus name/object path`.
+        let Some(index) = item.find('/') else {
+            continue;
+        };
+
+        let (destination, path) = item.split_at(index);
+        let Ok(proxy) = item_proxy(connection, destination, path) else {
+            continue;
+        };
+
+        // Passive items don't want to be shown right now, and items may disappear at any time.
+        let status = proxy.get_property::<String>("Status").unwrap_or_default();
+        if status == "Passive" {
+            continue;
+        }
+
+        items.push(TrayItem {
+            destination: destination.to_string(),
+            path: path.to_string(),
+            title: proxy.get_property("Title").unwrap_or_default(),
+            icon_name: proxy.get_property("IconName").unwrap_or_default(),
+            icon_theme_path: proxy.get_property("IconThemePath").unwrap_or_default(),
+            pixmaps: proxy.get_property("IconPixmap").unwrap_or_default(),
+        });
+    }
+
+    Ok(items)
+}
+
+/// Calls `method` (`Activate`, `SecondaryActivate` or `ContextMenu`) on `item`, with the screen
+/// coordinates of the click. Done on a separate thread, as the item may take a while to respond.
+pub fn activate(connection: &Connection, item: &TrayItem, method: &'static str, x: i32, y: i32) {
+    let connection = connection.clone();
+    let item = item.clone();
+    thread::spawn(move || {
+        let result = item_proxy(&connection, &item.destination, &item.path)
+            .and_then(|proxy| proxy.call_method(method, &(x, y)));
+        if let Err(err) = result {
+            log::warn!(
+                "Failed calling {method} on tray item '{}': {err}",
+                item.title
+            );
+        }
+    });
+}
+
+/// Registers as a StatusNotifierHost, then sends the items to `sender` right away and again every
+/// time they change. Provides a watcher too if there isn't one yet.
+/// Only call this once per widget as it's a loop.
+pub fn watch(connection: Connection, sender: glib::Sender<Vec<TrayItem>>) {
+    thread::spawn(move || {
+        let own_items = match serve_watcher(&connection) {
+            Ok(items) => items,
+            Err(err) => {
+                log::warn!("Tray widget cannot set up the StatusNotifierWatcher: {err}");
+                None
+            }
+        };
+
+        let host_name = format!(
+            "org.kde.StatusNotifierHost-{}-{}",
+            process::id(),
+            HOST_COUNT.fetch_add(1, Ordering::SeqCst)
+        );
+        let registered = connection.request_name(host_name.as_str()).and_then(|_| {
+            Proxy::new(&connection, WATCHER_NAME, WATCHER_PATH, WATCHER_NAME)?
+                .call_method("RegisterStatusNotifierHost", &host_name)
+        });
+
+        if let Err(err) = registered {
+            log::warn!("Tray widget cannot register as a StatusNotifierHost: {err}");
+        }
+
+        // All signals only mean "something changed", so they're funneled into one channel and the
+        // whole state is read again.
+        let (changed_sender, changed_receiver) = mpsc::channel();
+        for rule in [
+            ITEM_CHANGED_RULE,
+            WATCHER_CHANGED_RULE,
+            NAME_OWNER_CHANGED_RULE,
+        ] {
+            let iterator = match MessageIterator::for_match_rule(rule, &connection, Some(64)) {
+                Ok(iterator) => iterator,
+                Err(err) => {
+                    log::warn!("Tray widget cannot listen to D-Bus: {err}");
+                    return;
+                }
+            };
+
+            let changed_sender = changed_sender.clone();
+            let connection = connection.clone();
+            let own_items = own_items.clone();
+            thread::spawn(move || {
+                for message in iterator.flatten() {
+                    if message
+                        .member()
+                        .is_some_and(|member| member == "NameOwnerChanged")
+                    {
+                        // Only names disappearing matter, new items announce themselves.
+                        let Ok((name, _, new_owner)) = message.body::<(String, String, String)>()
+                        else {
+                            continue;
+                        };
+
+                        if !new_owner.is_empty() {
+                            continue;
+                        }
+
+                        // Clean up after items which exited without unregistering.
+                        if let Some(items) = &own_items {
+                            forget_items(&connection, items, &name);
+                        }
+                    }
+
+                    if changed_sender.send(()).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        loop {
+            let items = read_items(&connection).unwrap_or_else(|err| {
+                log::warn!("Tray widget failed reading the items: {err}");
+                Vec::new()
+            });
+
+            // Can only fail if the main loop is gone.
+            if sender.send(items).is_err() || changed_receiver.recv().is_err() {
+                break;
+            }
+
+            // Items usually send several signals at once, only read the state once.
+            while changed_receiver.try_recv().is_ok() {}
+        }
+    });
+}
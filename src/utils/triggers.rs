@@ -0,0 +1,279 @@
+use crate::{
+    compositor::{Compositor, Events},
+    config, locks,
+    pause::Timer,
+};
+use json::JsonValue;
+use signal_hook::{
+    consts::{SIGHUP, SIGUSR2},
+    iterator::Signals,
+};
+use std::{
+    cell::RefCell,
+    rc::{Rc, Weak},
+    sync::Mutex,
+    thread,
+    time::Duration,
+};
+use tokio::sync::Notify;
+
+/// Something a widget can be updated on, see `parse`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Trigger {
+    /// Every `update_rate`, like widgets without an `update_on`.
+    Tick,
+    /// A workspace changing, or windows moving between them.
+    Workspace,
+    /// The focused window changing, or its title.
+    Focus,
+    /// Inputs changing, like the keyboard layout. Not reported by i3.
+    Layout,
+    /// The signal with this number being received, like `SIGUSR2`.
+    Signal(i32),
+}
+
+lazy_static! {
+    /// The triggers which fired since the label loop last looked, see `take_fired`.
+    static ref FIRED: Mutex<Vec<Trigger>> = Mutex::new(Vec::new());
+
+    /// Wakes the label loop up whenever a trigger fires.
+    static ref WAKE: Notify = Notify::new();
+
+    /// The triggers whose source has been started, so each one is only started once.
+    static ref STARTED: Mutex<Vec<Trigger>> = Mutex::new(Vec::new());
+}
+
+thread_local! {
+    /// The `update_rate` and `update_on` of the widget that's currently being built, see `set`.
+    static CURRENT: RefCell<Option<(Duration, Vec<Trigger>)>> = const { RefCell::new(None) };
+
+    /// The timers to call when one of their triggers fires, gone once their source is.
+    static LISTENERS: RefCell<Vec<(Vec<Trigger>, Weak<Timer>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Parses a single trigger: `tick`, `workspace`, `focus`, `layout` or `signal:<name>`, where the
+/// name is `USR2`, `HUP` or `RTMIN+<n>`. `USR1` already reloads the config.
+pub fn parse(trigger: &str) -> Result<Trigger, String> {
+    let signal = match trigger.split_once(':') {
+        Some(("signal", signal)) => signal.trim_start_matches("SIG"),
+        _ => {
+            return match trigger {
+                "tick" => Ok(Trigger::Tick),
+                "workspace" => Ok(Trigger::Workspace),
+                "focus" => Ok(Trigger::Focus),
+                "layout" => Ok(Trigger::Layout),
+                _ => Err(format!(
+                    "unknown trigger '{trigger}', expected tick, workspace, focus, layout or \
+                     signal:<name>"
+                )),
+            }
+        }
+    };
+
+    let offset = match signal {
+        "USR2" => return Ok(Trigger::Signal(SIGUSR2)),
+        "HUP" => return Ok(Trigger::Signal(SIGHUP)),
+        "USR1" => return Err(String::from("signal:USR1 is taken, it reloads the config")),
+        "RTMIN" => Some(0),
+        _ => signal
+            .strip_prefix("RTMIN+")
+            .and_then(|offset| offset.parse::<i32>().ok()),
+    };
+
+    offset
+        .map(|offset| libc::SIGRTMIN() + offset)
+        .filter(|signal| *signal <= libc::SIGRTMAX())
+        .map(Trigger::Signal)
+        .ok_or_else(|| {
+            format!("unknown signal '{signal}', expected USR2, HUP or RTMIN+<n> up to RTMAX")
+        })
+}
+
+/// Parses an `update_on`, an array like `["tick", "workspace", "signal:USR2"]`.
+/// `null` is `None`, so the widget is only updated every `update_rate` as usual.
+pub fn parse_list(update_on: &JsonValue) -> Result<Option<Vec<Trigger>>, String> {
+    if update_on.is_null() {
+        return Ok(None);
+    }
+
+    if !update_on.is_array() {
+        return Err(String::from("expected an array of triggers"));
+    }
+
+    update_on
+        .members()
+        .map(|trigger| {
+            trigger
+                .as_str()
+                .ok_or_else(|| String::from("expected the triggers as strings"))
+                .and_then(parse)
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+/// Gets the `update_on` of the widget at `key`, `None` if it's unset or invalid.
+pub fn get(key: &str) -> Option<Vec<Trigger>> {
+    let update_on = config::with_config(|config| parse_list(&config[key]["update_on"]));
+    update_on.unwrap_or_else(|err| {
+        log::warn!("{key}: Invalid update_on, {err}");
+        None
+    })
+}
+
+/// Whether a widget with `update_on` is updated every `update_rate`.
+pub fn ticks(update_on: &Option<Vec<Trigger>>) -> bool {
+    update_on
+        .as_ref()
+        .is_none_or(|update_on| update_on.contains(&Trigger::Tick))
+}
+
+/// Sets the `update_rate` and `update_on` of the widget that's about to be built, `None` once it's
+/// built or if it doesn't have the latter. Starts the sources of its triggers.
+/// Timers it adds at its `update_rate` are then called whenever one of them fires, see `bind`.
+pub fn set(update_on: Option<(u64, Vec<Trigger>)>) {
+    if let Some((_, triggers)) = &update_on {
+        triggers.iter().for_each(|trigger| start(*trigger));
+    }
+
+    let update_on =
+        update_on.map(|(update_rate, triggers)| (Duration::from_millis(update_rate), triggers));
+    CURRENT.with(|current| *current.borrow_mut() = update_on);
+}
+
+/// Calls `timer` whenever a trigger of the widget that's being built fires, if it runs at its
+/// `update_rate`. Returns whether it should still be called on every tick.
+pub fn bind(interval: Duration, timer: &Rc<Timer>) -> bool {
+    CURRENT.with(|current| {
+        let current = current.borrow();
+        let Some((_, triggers)) = current
+            .as_ref()
+            .filter(|(update_rate, _)| *update_rate == interval)
+        else {
+            return true;
+        };
+
+        LISTENERS.with(|listeners| {
+            let mut listeners = listeners.borrow_mut();
+            listeners.retain(|(_, timer)| timer.strong_count() > 0);
+            listeners.push((triggers.clone(), Rc::downgrade(timer)));
+        });
+
+        triggers.contains(&Trigger::Tick)
+    })
+}
+
+/// Calls every timer listening to `trigger`, then lets the label loop know about it.
+fn fire(trigger: Trigger) {
+    let timers: Vec<Rc<Timer>> = LISTENERS.with(|listeners| {
+        listeners
+            .borrow()
+            .iter()
+            .filter(|(triggers, _)| triggers.contains(&trigger))
+            .filter_map(|(_, timer)| timer.upgrade())
+            .collect()
+    });
+
+    for timer in timers {
+        // Already running if this was fired from inside of a timer.
+        if let Ok(mut func) = timer.try_borrow_mut() {
+            func();
+        }
+    }
+
+    let mut fired = locks::lock(&FIRED);
+    if !fired.contains(&trigger) {
+        fired.push(trigger);
+    }
+
+    WAKE.notify_one();
+}
+
+/// Takes the triggers which fired since this was last called.
+pub fn take_fired() -> Vec<Trigger> {
+    std::mem::take(&mut locks::lock(&FIRED))
+}
+
+/// Waits until a trigger fires.
+pub async fn wait() {
+    WAKE.notified().await;
+}
+
+/// Starts watching for `trigger`, unless that's already being done. Ticks need nothing.
+fn start(trigger: Trigger) {
+    let mut started = locks::lock(&STARTED);
+    if trigger == Trigger::Tick || started.contains(&trigger) {
+        return;
+    }
+
+    started.push(trigger);
+    // Triggers touch GTK, so they're forwarded to the main loop.
+    let (sender, receiver) = glib::MainContext::channel::<()>(glib::PRIORITY_DEFAULT);
+    receiver.attach(None, move |_| {
+        fire(trigger);
+        glib::Continue(true)
+    });
+
+    let events = match trigger {
+        Trigger::Tick => return,
+        Trigger::Workspace => Events::Workspaces,
+        Trigger::Focus => Events::Window,
+        Trigger::Layout => Events::Input,
+        Trigger::Signal(signal) => {
+            let mut signals = match Signals::new([signal]) {
+                Ok(signals) => signals,
+                Err(err) => {
+                    log::warn!("Cannot listen to signal {signal}: {err}");
+                    return;
+                }
+            };
+
+            thread::spawn(move || {
+                for _ in signals.forever() {
+                    if sender.send(()).is_err() {
+                        // The main loop is gone.
+                        break;
+                    }
+                }
+            });
+            return;
+        }
+    };
+
+    match Compositor::detect().filter(|compositor| compositor.supports(events)) {
+        Some(compositor) => compositor.watch(events, |_| Ok(()), sender),
+        None => log::warn!("The compositor doesn't report {trigger:?} events, ignoring them"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triggers() {
+        let update_on = parse_list(&json::array![
+            "tick",
+            "focus",
+            "signal:USR2",
+            "signal:RTMIN+2"
+        ]);
+        assert_eq!(
+            update_on,
+            Ok(Some(vec![
+                Trigger::Tick,
+                Trigger::Focus,
+                Trigger::Signal(SIGUSR2),
+                Trigger::Signal(libc::SIGRTMIN() + 2)
+            ]))
+        );
+        assert!(ticks(&None));
+        assert!(!ticks(&Some(vec![Trigger::Workspace])));
+
+        assert_eq!(parse_list(&JsonValue::Null), Ok(None));
+        assert!(parse("signal:USR1").is_err());
+        assert!(parse("signal:RTMIN+1000").is_err());
+        assert!(parse("hover").unwrap_err().contains("'hover'"));
+        assert!(parse_list(&json::array![1]).is_err());
+    }
+}
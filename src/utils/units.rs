@@ -0,0 +1,71 @@
+/// Units for sizes in powers of 1024.
+const BINARY_UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+/// Units for sizes in powers of 1000.
+const DECIMAL_UNITS: [&str; 7] = ["B", "kB", "MB", "GB", "TB", "PB", "EB"];
+
+/// Formats `bytes` in the largest unit where the value is at least `1`, rounded to one decimal.
+/// For example `1.5 GiB`, or `820.0 MB` if `binary` is `false`. Plain bytes have no decimals.
+pub fn format_bytes(bytes: u64, binary: bool) -> String {
+    let (base, units) = if binary {
+        (1024.0, BINARY_UNITS)
+    } else {
+        (1000.0, DECIMAL_UNITS)
+    };
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    // Also moves up when rounding would give `1024.0 KiB` rather than `1.0 MiB`.
+    while unit < units.len() - 1 && (value >= base || (unit > 0 && round(value) >= base)) {
+        value /= base;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} {}", units[unit])
+    } else {
+        format!("{:.1} {}", round(value), units[unit])
+    }
+}
+
+/// Rounds `value` to one decimal.
+fn round(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_bytes_zero() {
+        assert_eq!(format_bytes(0, true), "0 B");
+        assert_eq!(format_bytes(0, false), "0 B");
+    }
+
+    #[test]
+    fn format_bytes_binary_boundary() {
+        assert_eq!(format_bytes(1023, true), "1023 B");
+        assert_eq!(format_bytes(1024, true), "1.0 KiB");
+        assert_eq!(format_bytes(1536, true), "1.5 KiB");
+    }
+
+    #[test]
+    fn format_bytes_decimal_boundary() {
+        assert_eq!(format_bytes(999, false), "999 B");
+        assert_eq!(format_bytes(1000, false), "1.0 kB");
+        assert_eq!(format_bytes(820_000_000, false), "820.0 MB");
+    }
+
+    #[test]
+    fn format_bytes_rounds_up_to_the_next_unit() {
+        assert_eq!(format_bytes(1024 * 1024 - 1, true), "1.0 MiB");
+        assert_eq!(format_bytes(999_999, false), "1.0 MB");
+    }
+
+    #[test]
+    fn format_bytes_very_large() {
+        assert_eq!(format_bytes(u64::MAX, true), "16.0 EiB");
+        assert_eq!(format_bytes(u64::MAX, false), "18.4 EB");
+    }
+}
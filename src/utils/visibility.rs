@@ -0,0 +1,63 @@
+use crate::{
+    command::{self, BashRunner},
+    config, pause, registry,
+};
+use glib::Continue;
+use gtk::{prelude::*, Widget};
+use std::{cell::Cell, rc::Rc, time::Duration};
+
+/// Shows `widget` only while the `visible_command` of the widget at `key` exits with `0`.
+/// The command is run at the widget's `update_rate`, widgets without a `visible_command` are always
+/// visible.
+pub fn bind(widget: &Widget, key: &str) {
+    let command = config::get_or_default(key, "visible_command", true, true).0;
+    if command.is_empty() {
+        return;
+    }
+
+    // Commands are run on worker threads, the result is then sent back to the main thread which
+    // updates the visibility.
+    let (sender, receiver) = glib::MainContext::channel::<bool>(glib::PRIORITY_DEFAULT);
+    let running = Rc::new(Cell::new(false));
+    let widget = widget.clone();
+    let receiver_running = running.clone();
+    registry::attach(receiver, move |visible| {
+        receiver_running.set(false);
+        if widget.is_visible() != visible {
+            widget.set_visible(visible);
+        }
+
+        Continue(true)
+    });
+
+    let cache_ttl = Duration::from_secs(
+        config::get_or_default(key, "cache_ttl", false, false)
+            .1
+            .max(0) as u64,
+    );
+    let timeout = Duration::from_millis(config::get_command_timeout(key));
+    let check = move || {
+        // Don't run it again while a slow command is still running.
+        if running.replace(true) {
+            return;
+        }
+
+        let sender = sender.clone();
+        let command = command.clone();
+        command::spawn_worker(move || {
+            let output = command::execute_cached_with(&BashRunner, &command, cache_ttl, timeout);
+            // Can only fail if the main loop is gone.
+            let _ = sender.send(output.success);
+        });
+    };
+
+    check();
+    // An update-rate of `0` means it's only checked once.
+    let update_rate = config::get_widget_update_rate(key);
+    if update_rate != 0 {
+        pause::timeout_add_local(Duration::from_millis(update_rate), move || {
+            check();
+            Continue(true)
+        });
+    }
+}
@@ -0,0 +1,73 @@
+use crate::config;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    path::Path,
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+/// For how long events are coalesced before reloading, as editors often write several times per
+/// save.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Starts watching the config file, `on_change` is notified whenever it's written to.
+/// Only call this once, every call watches the file on a thread of its own.
+pub fn watch_config(on_change: glib::Sender<()>) {
+    // 0.3.5: Stdin can only be read once, so there's nothing to watch.
+    if config::is_stdin() {
+        log::info!("The config is read from stdin, hot-reloading is disabled");
+        return;
+    }
+
+    thread::spawn(move || {
+        let conf_path = config::get_config_path();
+        let conf_path = Path::new(&conf_path);
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(sender).expect("Cannot create the config file watcher!");
+
+        arm(&mut watcher, conf_path);
+        log::debug!("Watching '{}' for changes", conf_path.display());
+        loop {
+            // Block until something happens to the config file, if all senders are gone then the
+            // watcher has died and there's nothing left to do.
+            match receiver.recv() {
+                Ok(Ok(event)) if is_write(&event) => {}
+                Ok(_) => continue,
+                Err(_) => break,
+            }
+
+            drain(&receiver);
+
+            // Editors doing "atomic saves" replace the file rather than writing to it, which
+            // leaves the old watch on a deleted inode. Re-arm it so the new file is watched.
+            // Unwatching may fail if the old watch is already gone, which is fine.
+            let _ = watcher.unwatch(conf_path);
+            arm(&mut watcher, conf_path);
+
+            // Can only fail if the main loop is gone.
+            if on_change.send(()).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Waits until no more events arrive within `DEBOUNCE`, discarding them.
+fn drain(receiver: &Receiver<notify::Result<Event>>) {
+    while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+}
+
+/// Watches `path`, retrying until it succeeds in case the file is momentarily missing.
+fn arm(watcher: &mut RecommendedWatcher, path: &Path) {
+    while let Err(err) = watcher.watch(path, RecursiveMode::NonRecursive) {
+        log::warn!("Failed watching '{}', retrying: {err}", path.display());
+        thread::sleep(DEBOUNCE);
+    }
+}
+
+/// Checks whether the event may have changed the contents of the file.
+fn is_write(event: &Event) -> bool {
+    !matches!(event.kind, EventKind::Access(_))
+}
@@ -0,0 +1,104 @@
+use crate::{
+    overflow, pause, placeholders, power,
+    ranges::{self, Ranges},
+    structures::Align,
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{fs, path::PathBuf, time::Duration};
+
+/// The format used when none is specified.
+pub const DEFAULT_FORMAT: &str = "{capacity}%";
+
+/// Creates a new battery widget, which reads the battery state from sysfs instead of through
+/// `acpi`.
+pub struct BatteryWidget {
+    pub tooltip: String,
+    pub format: String,
+    pub ranges: Ranges,
+    /// The battery to read, for example `BAT1`. The first battery found is used if empty.
+    pub device: String,
+    /// Shown when there's no battery, the widget is hidden if empty.
+    pub fallback: String,
+    pub update_rate: u64,
+    pub label: Label,
+}
+
+impl BatteryWidget {
+    /// Returns the sysfs directory of the battery, `None` if there isn't one.
+    fn get_device_path(&self) -> Option<PathBuf> {
+        if !self.device.is_empty() {
+            let path = PathBuf::from(power::POWER_SUPPLY_PATH).join(&self.device);
+            return path.exists().then_some(path);
+        }
+
+        power::find_batteries().into_iter().next()
+    }
+
+    /// Formats the current battery state, `None` if there's no battery.
+    fn read(&self) -> Option<String> {
+        let path = self.get_device_path()?;
+        let read = |file: &str| {
+            fs::read_to_string(path.join(file))
+                .map(|content| content.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        let capacity = read("capacity");
+        Some(placeholders::fill(
+            &self.format,
+            &[
+                ("capacity", &capacity),
+                ("status", &read("status")),
+                (ranges::PLACEHOLDER, self.ranges.pick_str(&capacity)),
+            ],
+        ))
+    }
+
+    /// Syncs the label with the battery state, hiding it if there's no battery and no fallback.
+    fn sync(&self) {
+        match self.read() {
+            Some(text) => self.update_label_reg(&text),
+            None if !self.fallback.is_empty() => self.update_label_reg(&self.fallback),
+            None => {
+                self.label.hide();
+                return;
+            }
+        }
+
+        self.label.show();
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for BatteryWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        // Visibility is managed by `sync`, so `show_all` mustn't show it on desktops.
+        self.label.set_no_show_all(true);
+        self.sync();
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        // An update-rate of `0` means the state is only read once.
+        if self.update_rate != 0 {
+            pause::timeout_add_local(Duration::from_millis(self.update_rate), move || {
+                self.sync();
+                Continue(true)
+            });
+        }
+
+        log::debug!("Added a new battery widget");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the state has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
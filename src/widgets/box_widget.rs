@@ -14,6 +14,6 @@ impl HWidget for BoxWidget {
         widget.set_width_request(self.width);
 
         ui::add_and_align(&widget, align, left, centered, right);
-        log!("Added a new box widget");
+        log::debug!("Added a new box widget");
     }
 }
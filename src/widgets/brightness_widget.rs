@@ -0,0 +1,207 @@
+use crate::{
+    command, events, math, overflow, pause, placeholders,
+    ranges::{self, Ranges},
+    structures::Align,
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{fs, path::PathBuf, rc::Rc, time::Duration};
+
+/// Where the kernel exposes backlights.
+const BACKLIGHT_PATH: &str = "/sys/class/backlight";
+
+/// The format used when none is specified.
+pub const DEFAULT_FORMAT: &str = "{percent}%";
+
+/// Run when writing to sysfs isn't permitted, which is the case unless you're in the `video` group
+/// or have a udev rule for it.
+pub const DEFAULT_SET_COMMAND: &str = "brightnessctl --device={device} set {value}";
+
+/// Creates a new brightness widget, which reads the backlight from sysfs and adjusts it when
+/// scrolled on.
+pub struct BrightnessWidget {
+    pub tooltip: String,
+    pub format: String,
+    pub ranges: Ranges,
+    /// The backlight to use, for example `intel_backlight`. The first one found is used if empty.
+    pub device: String,
+    /// How many percent a single scroll step changes the brightness by.
+    pub step: i32,
+    /// Sets the brightness when sysfs can't be written to, supports the `{value}`, `{percent}`
+    /// and `{device}` placeholders. Run without a shell, see `get_set_args`.
+    pub set_command: String,
+    pub update_rate: u64,
+    pub label: Label,
+    /// Receives the scroll events, as labels can't.
+    pub event_box: EventBox,
+}
+
+/// Splits `set_command` into the program and its arguments on whitespace, then fills the
+/// placeholders in each of them with `values`. As it isn't run through a shell, a device name can
+/// never be taken for another command.
+pub fn get_set_args(set_command: &str, values: &[(&str, &str)]) -> Vec<String> {
+    set_command
+        .split_whitespace()
+        .map(|arg| placeholders::fill(arg, values))
+        .collect()
+}
+
+impl BrightnessWidget {
+    /// Returns the sysfs directory of the backlight, `None` if there isn't one.
+    fn get_device_path(&self) -> Option<PathBuf> {
+        if !self.device.is_empty() {
+            let path = PathBuf::from(BACKLIGHT_PATH).join(&self.device);
+            return path.exists().then_some(path);
+        }
+
+        let mut backlights: Vec<PathBuf> = fs::read_dir(BACKLIGHT_PATH)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect();
+
+        // `read_dir` isn't ordered, sort so the pick is the same every time.
+        backlights.sort();
+        backlights.into_iter().next()
+    }
+
+    /// Reads the current and maximum brightness, `None` if there's no backlight.
+    fn read(&self) -> Option<(PathBuf, u64, u64)> {
+        let path = self.get_device_path()?;
+        let read = |file: &str| {
+            fs::read_to_string(path.join(file))
+                .ok()?
+                .trim()
+                .parse::<u64>()
+                .ok()
+        };
+
+        let brightness = read("brightness")?;
+        let max_brightness = read("max_brightness").filter(|max| *max != 0)?;
+        Some((path, brightness, max_brightness))
+    }
+
+    /// Syncs the label with the brightness, hiding it if there's no backlight.
+    fn sync(&self) {
+        let Some((_, brightness, max_brightness)) = self.read() else {
+            self.event_box.hide();
+            return;
+        };
+
+        let percent = (brightness as f64 / max_brightness as f64 * 100.0).round() as u64;
+        self.update_label_reg(&placeholders::fill(
+            &self.format,
+            &[
+                ("percent", &percent.to_string()),
+                (ranges::PLACEHOLDER, self.ranges.pick(percent as f64)),
+            ],
+        ));
+        self.event_box.show();
+    }
+
+    /// Changes the brightness by `steps` times `step` percent, negative steps make it brighter.
+    fn adjust(&self, steps: i32) {
+        let Some((path, brightness, max_brightness)) = self.read() else {
+            return;
+        };
+
+        let current = (brightness as f64 / max_brightness as f64 * 100.0).round() as i32;
+        // Never go all the way down to 0, as some backlights turn off entirely.
+        let percent = math::clamp_i32(current - steps * self.step, 1, 100);
+        let value = (percent as f64 / 100.0 * max_brightness as f64).round() as u64;
+        if fs::write(path.join("brightness"), value.to_string()).is_ok() {
+            self.sync();
+            return;
+        }
+
+        let device = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let args = get_set_args(
+            &self.set_command,
+            &[
+                ("value", &value.to_string()),
+                ("percent", &percent.to_string()),
+                ("device", &device),
+            ],
+        );
+
+        log::debug!(
+            "No permission to write to '{}', running {args:?}",
+            path.display()
+        );
+        // Show the new brightness straight away, rather than once the command is done.
+        self.update_label_reg(&placeholders::fill(
+            &self.format,
+            &[
+                ("percent", &percent.to_string()),
+                (ranges::PLACEHOLDER, self.ranges.pick(percent as f64)),
+            ],
+        ));
+        if let Some((program, args)) = args.split_first() {
+            let (program, args) = (program.clone(), args.to_vec());
+            command::spawn_worker(move || drop(execute_args!(program, args)));
+        }
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for BrightnessWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        self.event_box.add(&self.label);
+        // Visibility is managed by `sync`, so `show_all` mustn't show it without a backlight.
+        self.event_box.set_no_show_all(true);
+        self.label.show();
+        self.sync();
+        ui::add_and_align(&self.event_box, align, left, centered, right);
+
+        let widget = Rc::new(self);
+        let scrolled = widget.clone();
+        events::connect_scroll_steps(&widget.event_box, move |steps| {
+            scrolled.adjust(steps);
+            true
+        });
+
+        // An update-rate of `0` means the brightness is only read on startup and when scrolling.
+        if widget.update_rate != 0 {
+            pause::timeout_add_local(Duration::from_millis(widget.update_rate), move || {
+                widget.sync();
+                Continue(true)
+            });
+        }
+
+        log::debug!("Added a new brightness widget");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the brightness has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_commands_arent_run_through_a_shell() {
+        let args = get_set_args(
+            DEFAULT_SET_COMMAND,
+            &[("value", "120"), ("device", "intel; rm -rf ~")],
+        );
+        assert_eq!(
+            args,
+            ["brightnessctl", "--device=intel; rm -rf ~", "set", "120"]
+        );
+        assert!(get_set_args("  ", &[]).is_empty());
+    }
+}
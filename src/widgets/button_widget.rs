@@ -1,4 +1,4 @@
-use crate::{structures::Align, ui, widget::HWidget};
+use crate::{command, structures::Align, ui, widget::HWidget};
 use gtk::{traits::*, *};
 
 /// Creates a new button widget.
@@ -17,13 +17,16 @@ impl HWidget for ButtonWidget {
 
         // If the command isn't empty, subscribe to click events.
         if !self.command.is_empty() {
+            let command = self.command;
             self.button.connect_clicked(move |_| {
-                log!(format!("Button '{}' -> Clicked", name));
-                execute!(&self.command);
+                log::debug!("Button '{}' -> Clicked", name);
+                // Don't block the UI while the command is running.
+                let command = command.clone();
+                command::spawn_worker(move || drop(execute!(&command)));
             });
         }
 
         ui::add_and_align(&self.button, align, left, centered, right);
-        log!("Added a new button widget");
+        log::debug!("Added a new button widget");
     }
 }
@@ -0,0 +1,323 @@
+use crate::{
+    color,
+    command::{self, BashRunner, CommandRunner, RetryRunner},
+    errors, pause, registry,
+    structures::Align,
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+/// The size in pixels when `width` or `height` isn't specified.
+pub const DEFAULT_SIZE: i32 = 64;
+
+/// A single instruction of the draw protocol, see `parse`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Instruction {
+    /// The RGBA color of everything drawn after it.
+    Color(f64, f64, f64, f64),
+    /// The line width of everything stroked after it.
+    Width(f64),
+    Line(f64, f64, f64, f64),
+    Rect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        fill: bool,
+    },
+    /// The angles are in degrees, clockwise from 3 o'clock.
+    Arc {
+        x: f64,
+        y: f64,
+        radius: f64,
+        start: f64,
+        end: f64,
+        fill: bool,
+    },
+    /// Connected points, like a sparkline.
+    Poly {
+        points: Vec<(f64, f64)>,
+        fill: bool,
+    },
+}
+
+/// Creates a new canvas widget, which draws shapes described by `draw` or by the output of
+/// `command`.
+pub struct CanvasWidget {
+    pub tooltip: String,
+    /// Drawn when `command` is empty.
+    pub draw: String,
+    /// Should output instructions in the same form as `draw`.
+    pub command: String,
+    pub width: i32,
+    pub height: i32,
+    /// The scale of the bar, instructions are in unscaled pixels like the rest of the config.
+    pub scale: f64,
+    pub update_rate: u64,
+    pub command_timeout: u64,
+    pub retry: command::Retry,
+    pub area: DrawingArea,
+}
+
+/// Parses `numbers` into exactly `count` numbers, or at least `count` if `at_least` is set.
+fn parse_numbers(numbers: &[&str], count: usize, at_least: bool) -> Result<Vec<f64>, String> {
+    if numbers.len() < count || (!at_least && numbers.len() > count) {
+        return Err(format!("expected {count} numbers, found {}", numbers.len()));
+    }
+
+    numbers
+        .iter()
+        .map(|number| {
+            number
+                .parse::<f64>()
+                .ok()
+                .filter(|number| number.is_finite())
+                .ok_or_else(|| format!("'{number}' isn't a number"))
+        })
+        .collect()
+}
+
+/// Parses the draw protocol, one instruction per line or separated by `;`:
+/// - `color #rrggbb[aa]`
+/// - `width N`
+/// - `line x1 y1 x2 y2`
+/// - `rect x y width height` and `fill_rect`
+/// - `arc x y radius start end` and `fill_arc`
+/// - `poly x1 y1 x2 y2 ...` and `fill_poly`
+///
+/// Coordinates are in pixels from the top left corner. Empty lines and lines starting with `//`
+/// are skipped. Errors name the instruction that failed.
+pub fn parse(spec: &str) -> Result<Vec<Instruction>, String> {
+    let mut instructions = Vec::new();
+    for line in spec.split(['\n', ';']).map(str::trim) {
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let args: Vec<&str> = words.collect();
+        let instruction = match name {
+            "color" => match args[..] {
+                [hex] => color::parse_hex(hex)
+                    .map(|(r, g, b, a)| {
+                        let channel = |value: u8| f64::from(value) / 255.0;
+                        Instruction::Color(channel(r), channel(g), channel(b), channel(a))
+                    })
+                    .ok_or_else(|| format!("'{hex}' isn't a hex color")),
+                _ => Err(String::from("expected a single hex color")),
+            },
+            "width" => parse_numbers(&args, 1, false).map(|n| Instruction::Width(n[0])),
+            "line" => {
+                parse_numbers(&args, 4, false).map(|n| Instruction::Line(n[0], n[1], n[2], n[3]))
+            }
+            "rect" | "fill_rect" => parse_numbers(&args, 4, false).map(|n| Instruction::Rect {
+                x: n[0],
+                y: n[1],
+                width: n[2],
+                height: n[3],
+                fill: name == "fill_rect",
+            }),
+            "arc" | "fill_arc" => parse_numbers(&args, 5, false).map(|n| Instruction::Arc {
+                x: n[0],
+                y: n[1],
+                radius: n[2],
+                start: n[3],
+                end: n[4],
+                fill: name == "fill_arc",
+            }),
+            "poly" | "fill_poly" if args.len() % 2 != 0 => {
+                Err(String::from("expected pairs of coordinates"))
+            }
+            "poly" | "fill_poly" => parse_numbers(&args, 4, true).map(|n| Instruction::Poly {
+                points: n.chunks(2).map(|point| (point[0], point[1])).collect(),
+                fill: name == "fill_poly",
+            }),
+            _ => Err(String::from("unknown instruction")),
+        };
+
+        instructions.push(instruction.map_err(|err| format!("'{line}': {err}"))?);
+    }
+
+    Ok(instructions)
+}
+
+/// Draws `instructions` onto `context`, starting out in `color` with lines 1 pixel wide.
+fn draw(instructions: &[Instruction], context: &cairo::Context, color: &gdk::RGBA) {
+    context.set_source_rgba(color.red(), color.green(), color.blue(), color.alpha());
+    context.set_line_width(1.0);
+    for instruction in instructions {
+        // Filled shapes and strokes both end the current path.
+        let fill = match instruction {
+            Instruction::Color(r, g, b, a) => {
+                context.set_source_rgba(*r, *g, *b, *a);
+                continue;
+            }
+            Instruction::Width(width) => {
+                context.set_line_width(*width);
+                continue;
+            }
+            Instruction::Line(x1, y1, x2, y2) => {
+                context.move_to(*x1, *y1);
+                context.line_to(*x2, *y2);
+                false
+            }
+            Instruction::Rect {
+                x,
+                y,
+                width,
+                height,
+                fill,
+            } => {
+                context.rectangle(*x, *y, *width, *height);
+                *fill
+            }
+            Instruction::Arc {
+                x,
+                y,
+                radius,
+                start,
+                end,
+                fill,
+            } => {
+                context.new_sub_path();
+                context.arc(*x, *y, *radius, start.to_radians(), end.to_radians());
+                *fill
+            }
+            Instruction::Poly { points, fill } => {
+                for (x, y) in points {
+                    context.line_to(*x, *y);
+                }
+
+                *fill
+            }
+        };
+
+        let result = if fill {
+            context.fill()
+        } else {
+            context.stroke()
+        };
+        if let Err(err) = result {
+            log::warn!("Failed drawing the canvas: {err}");
+            return;
+        }
+    }
+}
+
+impl CanvasWidget {
+    /// Runs the command through `runner` on a separate thread and sends its output to `sender`.
+    fn refresh(&self, sender: &glib::Sender<String>, runner: impl CommandRunner + Send + 'static) {
+        let sender = sender.clone();
+        let command = self.command.clone();
+        let timeout = Duration::from_millis(self.command_timeout);
+        let retry = self.retry;
+        let name = self.area.widget_name().to_string();
+        command::spawn_worker(move || {
+            let runner = RetryRunner {
+                runner: &runner,
+                retry,
+            };
+            let output = runner.execute(&command, timeout);
+            errors::check(&name, &command, &output);
+            // Can only fail if the main loop is gone.
+            let _ = sender.send(output.stdout);
+        });
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for CanvasWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.area.set_widget_name(&name);
+        self.area.set_tooltip_markup(Some(&self.tooltip));
+        self.area.set_size_request(self.width, self.height);
+        ui::add_and_align(&self.area, align, left, centered, right);
+
+        let instructions = Rc::new(RefCell::new(Vec::new()));
+        let drawn = instructions.clone();
+        let scale = self.scale;
+        self.area.connect_draw(move |area, context| {
+            context.scale(scale, scale);
+            // Starts out in the CSS `color` of the widget, so it follows the theme.
+            let color = area.style_context().color(StateFlags::NORMAL);
+            draw(&drawn.borrow(), context, &color);
+            Inhibit(false)
+        });
+
+        if self.command.is_empty() {
+            match parse(&self.draw) {
+                Ok(parsed) => *instructions.borrow_mut() = parsed,
+                Err(err) => log::warn!("{name}: Invalid draw instruction {err}"),
+            }
+
+            log::debug!("Added a new canvas widget");
+            return;
+        }
+
+        // Invalid output keeps the previous drawing, so a single bad line doesn't blank it.
+        let area = self.area.clone();
+        let (sender, receiver) = glib::MainContext::channel::<String>(glib::PRIORITY_DEFAULT);
+        registry::attach(receiver, move |output| {
+            match parse(&output) {
+                Ok(parsed) if *instructions.borrow() != parsed => {
+                    *instructions.borrow_mut() = parsed;
+                    area.queue_draw();
+                }
+                Ok(_) => {}
+                Err(err) => log::warn!("{}: Invalid draw instruction {err}", area.widget_name()),
+            }
+
+            Continue(true)
+        });
+
+        self.refresh(&sender, BashRunner);
+        // An update-rate of `0` means the command is only run once.
+        if self.update_rate != 0 {
+            pause::timeout_add_local(Duration::from_millis(self.update_rate), move || {
+                self.refresh(&sender, BashRunner);
+                Continue(true)
+            });
+        }
+
+        log::debug!("Added a new canvas widget");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_protocol() {
+        let instructions = parse("color #ff0000\n// a comment\nwidth 2; fill_rect 0 1 10 5\n\n");
+        assert_eq!(
+            instructions,
+            Ok(vec![
+                Instruction::Color(1.0, 0.0, 0.0, 1.0),
+                Instruction::Width(2.0),
+                Instruction::Rect {
+                    x: 0.0,
+                    y: 1.0,
+                    width: 10.0,
+                    height: 5.0,
+                    fill: true
+                },
+            ])
+        );
+
+        assert_eq!(
+            parse("poly 0 0 1 2 2 1"),
+            Ok(vec![Instruction::Poly {
+                points: vec![(0.0, 0.0), (1.0, 2.0), (2.0, 1.0)],
+                fill: false
+            }])
+        );
+        assert!(parse("poly 0 0 1").is_err());
+        assert!(parse("line 0 0 1").unwrap_err().contains("'line 0 0 1'"));
+        assert!(parse("circle 1 2 3").is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{structures::Align, ui, widget::HWidget};
+use crate::{overflow, structures::Align, ui, widget::HWidget};
 use gtk::{traits::*, *};
 use std::fmt::Display;
 
@@ -16,17 +16,15 @@ impl HWidget for CavaWidget {
     fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
         self.label.set_widget_name(&name);
         ui::add_and_align(&self.label, align, left, centered, right);
-        ui::CAVA_INSTANCES
-            .lock()
-            .expect("[ERROR] Couldn't access ui::CAVA_INSTANCES!\n")
-            .push(self)
-            .expect("[ERROR] You cannot have more than `8` Cava widgets!\n");
+        if ui::register(&ui::CAVA_INSTANCES, self).is_err() {
+            panic!("You cannot have more than `8` Cava widgets!")
+        }
     }
 
     fn update_label_reg(&self, new_content: &(impl Display + Clone)) {
         let final_content = &new_content.to_string();
         // Only redraw if the text wasn't the exact same as final_content.
-        if !self.label.text().eq(final_content) {
+        if overflow::get_content(&self.label) != *final_content {
             self.label.set_text(final_content)
         }
     }
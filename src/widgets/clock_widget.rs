@@ -0,0 +1,103 @@
+use crate::{config, overflow, pause, placeholders, structures::Align, ui, widget::HWidget};
+use chrono::{
+    format::{Item, StrftimeItems},
+    Local, Utc,
+};
+use chrono_tz::Tz;
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::time::Duration;
+
+/// The format used when none, or an invalid one, is specified.
+pub const DEFAULT_FORMAT: &str = "%H:%M";
+
+/// Creates a new clock widget, which formats the current time natively instead of through `date`.
+pub struct ClockWidget {
+    pub tooltip: String,
+    pub format: String,
+    pub timezone: Option<Tz>,
+    pub update_rate: u64,
+    pub label: Label,
+}
+
+impl ClockWidget {
+    /// Formats the current time in the configured timezone, or the local one if unset.
+    fn now(&self) -> String {
+        format_now(&self.format, self.timezone)
+    }
+}
+
+/// Formats the current time with `format` in `timezone`, or the local one if `None`.
+pub fn format_now(format: &str, timezone: Option<Tz>) -> String {
+    match timezone {
+        Some(timezone) => Utc::now()
+            .with_timezone(&timezone)
+            .format(format)
+            .to_string(),
+        None => Local::now().format(format).to_string(),
+    }
+}
+
+/// Gets the `format` of the clock at `key`, falling back to `DEFAULT_FORMAT` if it's invalid.
+pub fn get_format(key: &str) -> String {
+    let format = config::try_get(key, "format", true, false).map_or_else(
+        || config::with_default_format(DEFAULT_FORMAT),
+        |format| format.0,
+    );
+    if is_valid_format(&format) {
+        return format;
+    }
+
+    log::warn!("{key}: Invalid format '{format}', defaulting to '{DEFAULT_FORMAT}'");
+    String::from(DEFAULT_FORMAT)
+}
+
+/// Gets the `timezone` of the clock at `key`, `None` meaning the local time.
+pub fn get_timezone(key: &str) -> Option<Tz> {
+    config::try_get(key, "timezone", true, false).and_then(|timezone| {
+        timezone.0.parse().map_or_else(
+            |_| {
+                log::warn!(
+                    "{key}: Unknown timezone '{}', using the local time",
+                    timezone.0
+                );
+                None
+            },
+            Some,
+        )
+    })
+}
+
+/// Checks whether `format` is a valid strftime format, as formatting an invalid one panics.
+pub fn is_valid_format(format: &str) -> bool {
+    !StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for ClockWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        self.label.set_text(&self.now());
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        // An update-rate of `0` means the time is only shown once.
+        if self.update_rate != 0 {
+            pause::timeout_add_local(Duration::from_millis(self.update_rate), move || {
+                self.update_label_reg(&self.now());
+                Continue(true)
+            });
+        }
+
+        log::debug!("Added a new clock widget");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the time has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
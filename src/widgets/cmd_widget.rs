@@ -14,14 +14,14 @@ impl HWidget for CmdWidget {
 
         // .clone() because otherwise it starts crying about move.
         widget.clone().connect_key_press_event(move |_, key| {
-            let real_key = key.keycode().expect("[ERROR] No keycode retrieved (???)\n");
+            let real_key = key.keycode().expect("No keycode retrieved (???)");
             // 36 = Enter
             if real_key == 36 {
                 // Could use execute!() but it waits for the process to finish, which we don't
                 // want.
                 let process = &widget.text();
                 if !process.is_empty() && Command::new(process).spawn().is_err() {
-                    log!(format!("[WARN] Failed spawning process '{process}'!"))
+                    log::warn!("Failed spawning process '{process}'!")
                 }
 
                 // Cleanup.
@@ -31,6 +31,6 @@ impl HWidget for CmdWidget {
             Inhibit(false)
         });
 
-        log!("Added a new cmd widget");
+        log::debug!("Added a new cmd widget");
     }
 }
@@ -0,0 +1,107 @@
+use crate::{
+    overflow, pause, placeholders,
+    ranges::{self, Ranges},
+    structures::Align,
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{cell::Cell, fs, time::Duration};
+
+/// The format used when none is specified.
+pub const DEFAULT_FORMAT: &str = "{usage}%";
+
+/// Creates a new CPU widget, which reads the CPU usage from `/proc/stat` instead of through `top`.
+pub struct CpuWidget {
+    pub tooltip: String,
+    pub format: String,
+    pub ranges: Ranges,
+    pub update_rate: u64,
+    pub label: Label,
+    /// The idle and total time from the previous read, as usage is the difference between two.
+    pub previous: Cell<Option<(u64, u64)>>,
+}
+
+/// Reads the idle and total time spent by all CPUs, in ticks.
+fn read_times() -> Option<(u64, u64)> {
+    let stat = fs::read_to_string("/proc/stat").ok()?;
+    // The first line holds the totals: `cpu user nice system idle iowait irq softirq steal ...`
+    let times: Vec<u64> = stat
+        .lines()
+        .next()?
+        .strip_prefix("cpu ")?
+        .split_whitespace()
+        .filter_map(|time| time.parse().ok())
+        .collect();
+
+    // `idle` + `iowait` are both time spent doing nothing.
+    let idle = times.get(3)? + times.get(4).unwrap_or(&0);
+    Some((idle, times.iter().sum()))
+}
+
+impl CpuWidget {
+    /// Returns the usage in percent since the previous read, `None` on the first read.
+    fn get_usage(&self) -> Option<u64> {
+        let (idle, total) = read_times()?;
+        let (previous_idle, previous_total) = self.previous.replace(Some((idle, total)))?;
+        let total_delta = total.saturating_sub(previous_total);
+        if total_delta == 0 {
+            return None;
+        }
+
+        let busy_delta = total_delta.saturating_sub(idle.saturating_sub(previous_idle));
+        Some(
+            ((busy_delta as f64 / total_delta as f64) * 100.0)
+                .round()
+                .min(100.0) as u64,
+        )
+    }
+
+    /// Syncs the label with the current usage.
+    fn sync(&self) {
+        if let Some(usage) = self.get_usage() {
+            self.update_label_reg(&placeholders::fill(
+                &self.format,
+                &[
+                    ("usage", &usage.to_string()),
+                    (ranges::PLACEHOLDER, self.ranges.pick(usage as f64)),
+                ],
+            ));
+        }
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for CpuWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        // Take the first sample, so the next tick can show the usage.
+        self.sync();
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        // Usage is measured between ticks, so there has to be a timer.
+        let update_rate = if self.update_rate == 0 {
+            1000
+        } else {
+            self.update_rate
+        };
+
+        pause::timeout_add_local(Duration::from_millis(update_rate), move || {
+            self.sync();
+            Continue(true)
+        });
+
+        log::debug!("Added a new cpu widget");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the usage has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{
+    overflow, pause, placeholders,
+    ranges::{self, Ranges},
+    structures::Align,
+    ui, units,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{ffi::CString, mem::MaybeUninit, time::Duration};
+
+/// The format used when none is specified.
+pub const DEFAULT_FORMAT: &str = "{percent}%";
+
+/// The path used when none is specified.
+pub const DEFAULT_PATH: &str = "/";
+
+/// The disk usage of a filesystem, in bytes.
+struct Usage {
+    free: u64,
+    used: u64,
+    total: u64,
+}
+
+/// Creates a new disk widget, which reads the usage of a filesystem through `statvfs` instead of
+/// parsing the output of `df`.
+pub struct DiskWidget {
+    pub tooltip: String,
+    pub format: String,
+    pub ranges: Ranges,
+    /// Any path on the filesystem to read, for example `/home`.
+    pub path: String,
+    /// `1024` for binary units like GiB, or `1000` for decimal ones like GB.
+    pub base: u64,
+    /// Shown when `path` can't be read, for example when it isn't mounted.
+    pub fallback: String,
+    pub update_rate: u64,
+    pub label: Label,
+}
+
+/// Reads the usage of the filesystem at `path`, `None` if it can't be read.
+fn read_usage(path: &str) -> Option<Usage> {
+    let path = CString::new(path).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is a valid C string and `stat` is only read if the call succeeded.
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+
+        stat.assume_init()
+    };
+
+    let block_size = stat.f_frsize as u64;
+    let total = stat.f_blocks as u64 * block_size;
+    Some(Usage {
+        // What's available to regular users, like `df` shows.
+        free: stat.f_bavail as u64 * block_size,
+        used: total.saturating_sub(stat.f_bfree as u64 * block_size),
+        total,
+    })
+}
+
+impl DiskWidget {
+    /// Formats the usage of the filesystem, `None` if it can't be read.
+    fn read(&self) -> Option<String> {
+        let usage = read_usage(&self.path)?;
+        let format_bytes = |bytes: u64| units::format_bytes(bytes, self.base != 1000);
+        // Relative to what's usable, which is what `df` does too.
+        let usable = usage.used + usage.free;
+        let percent = if usable == 0 {
+            0
+        } else {
+            ((usage.used as f64 / usable as f64) * 100.0)
+                .round()
+                .min(100.0) as u64
+        };
+
+        Some(placeholders::fill(
+            &self.format,
+            &[
+                ("free", &format_bytes(usage.free)),
+                ("used", &format_bytes(usage.used)),
+                ("total", &format_bytes(usage.total)),
+                ("percent", &percent.to_string()),
+                (ranges::PLACEHOLDER, self.ranges.pick(percent as f64)),
+            ],
+        ))
+    }
+
+    /// Syncs the label with the current usage, or the fallback if it can't be read.
+    fn sync(&self) {
+        match self.read() {
+            Some(text) => self.update_label_reg(&text),
+            None => self.update_label_reg(&self.fallback),
+        }
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for DiskWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        self.sync();
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        // An update-rate of `0` means the usage is only read once.
+        if self.update_rate != 0 {
+            pause::timeout_add_local(Duration::from_millis(self.update_rate), move || {
+                self.sync();
+                Continue(true)
+            });
+        }
+
+        log::debug!("Added a new disk widget");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the usage has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use crate::{
+    command::{self, BashRunner, CommandRunner, RetryRunner},
+    errors, math, pause, placeholders, registry, sources,
+    structures::Align,
+    transition::Transition,
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::Rc,
+    time::Duration,
+};
+
+/// How many samples are kept when `samples` isn't specified.
+pub const DEFAULT_SAMPLES: usize = 30;
+
+/// The width in pixels when none is specified.
+pub const DEFAULT_WIDTH: i32 = 60;
+
+/// Creates a new graph widget, which draws the last `samples` values of a command or of another
+/// widget as a line or as bars.
+pub struct GraphWidget {
+    pub tooltip: String,
+    /// Should output a number, like `42` or `0.5`.
+    pub command: String,
+    /// The name of the widget whose value is sampled when `command` is empty.
+    pub source: String,
+    pub samples: usize,
+    /// Draws a bar for each sample rather than a line through them.
+    pub bars: bool,
+    /// The value at the bottom of the graph.
+    pub min: f64,
+    /// The value at the top of the graph, the highest sample if it's `None`.
+    pub max: Option<f64>,
+    pub width: i32,
+    pub height: i32,
+    pub update_rate: u64,
+    pub command_timeout: u64,
+    pub retry: command::Retry,
+    pub area: DrawingArea,
+    /// The samples, oldest first. Kept across reloads, even if the widget is rebuilt.
+    pub history: RefCell<VecDeque<f64>>,
+    /// Moves the newest sample from the one before it to its value.
+    pub transition: Transition,
+    /// The value the newest sample is moving to, while it's still moving.
+    pub moving: Cell<Option<f64>>,
+}
+
+/// Reads the first number in `text`, so values like `42%` or `cpu: 4.2` can be sampled.
+pub fn parse_sample(text: &str) -> Option<f64> {
+    let digit = text.find(|char: char| char.is_ascii_digit())?;
+    // Keeps the sign, a dash that's separated from the number like in `cpu - 42` isn't one.
+    let start = match text[..digit].ends_with('-') {
+        true => digit - 1,
+        false => digit,
+    };
+    let rest = &text[start..];
+    let length = rest
+        .char_indices()
+        .find(|(index, char)| {
+            !(char.is_ascii_digit() || *char == '.' || (*index == 0 && *char == '-'))
+        })
+        .map_or(rest.len(), |(index, _)| index);
+    rest[..length]
+        .parse()
+        .ok()
+        .filter(|value: &f64| value.is_finite())
+}
+
+/// Maps `history` onto a `width` by `height` area, newest sample at the right edge. Each point is
+/// the position of a sample, as the top of its bar when drawn as bars.
+pub fn layout(
+    history: &VecDeque<f64>,
+    samples: usize,
+    min: f64,
+    max: Option<f64>,
+    width: f64,
+    height: f64,
+) -> Vec<(f64, f64)> {
+    let max = max.unwrap_or_else(|| history.iter().copied().fold(min, f64::max));
+    // All samples at `min` would otherwise divide by zero.
+    let range = if max > min { max - min } else { 1.0 };
+    let step = width / samples.saturating_sub(1).max(1) as f64;
+    let offset = samples.saturating_sub(history.len());
+    history
+        .iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let fraction = ((value - min) / range).clamp(0.0, 1.0);
+            ((offset + index) as f64 * step, height - fraction * height)
+        })
+        .collect()
+}
+
+impl GraphWidget {
+    /// Adds `value` as the newest sample, dropping the oldest one once there are `samples`.
+    /// 0.3.5: With a `transition_ms` the sample moves there from the one before it.
+    fn push(self: &Rc<Self>, value: f64) {
+        self.transition.stop();
+        let mut history = self.history.borrow_mut();
+        // A sample that's still moving is done once the next one comes in.
+        if let (Some(last), Some(moving)) = (history.back_mut(), self.moving.take()) {
+            *last = moving;
+        }
+
+        let from = history.back().copied().unwrap_or(value);
+        history.push_back(from);
+        while history.len() > self.samples {
+            history.pop_front();
+        }
+
+        drop(history);
+        placeholders::publish(&self.area.widget_name(), &value.to_string());
+        self.moving.set(Some(value));
+        let widget = self.clone();
+        self.transition.start(move |progress| {
+            if let Some(last) = widget.history.borrow_mut().back_mut() {
+                *last = math::lerp(from, value, progress);
+            }
+
+            if progress >= 1.0 {
+                widget.moving.set(None);
+            }
+
+            widget.area.queue_draw();
+        });
+    }
+
+    /// Runs the command through `runner` on a separate thread and sends its value to `sender`,
+    /// unless the output isn't a number.
+    fn refresh(&self, sender: &glib::Sender<f64>, runner: impl CommandRunner + Send + 'static) {
+        let sender = sender.clone();
+        let command = self.command.clone();
+        let timeout = Duration::from_millis(self.command_timeout);
+        let retry = self.retry;
+        let name = self.area.widget_name().to_string();
+        command::spawn_worker(move || {
+            let runner = RetryRunner {
+                runner: &runner,
+                retry,
+            };
+            let output = runner.execute(&command, timeout);
+            errors::check(&name, &command, &output);
+            let output = output.stdout;
+            match parse_sample(&output) {
+                // Can only fail if the main loop is gone.
+                Some(value) => {
+                    let _ = sender.send(value);
+                }
+                None => log::warn!("'{command}' didn't output a number, got '{output}'"),
+            }
+        });
+    }
+
+    /// Draws the samples in the CSS `color` of the widget.
+    fn draw(&self, context: &cairo::Context) {
+        let width = f64::from(self.area.allocated_width());
+        let height = f64::from(self.area.allocated_height());
+        let points = layout(
+            &self.history.borrow(),
+            self.samples,
+            self.min,
+            self.max,
+            width,
+            height,
+        );
+
+        let color = self.area.style_context().color(StateFlags::NORMAL);
+        context.set_source_rgba(color.red(), color.green(), color.blue(), color.alpha());
+        let result = if self.bars {
+            let bar_width = width / self.samples as f64;
+            for (x, y) in points {
+                // Spread over the whole width, the last bar ends at the right edge.
+                let x = x / width.max(1.0) * (width - bar_width);
+                context.rectangle(x, y, bar_width.max(1.0), height - y);
+            }
+
+            context.fill()
+        } else {
+            for (x, y) in points {
+                context.line_to(x, y);
+            }
+
+            context.stroke()
+        };
+
+        if let Err(err) = result {
+            log::warn!("Failed drawing the graph: {err}");
+        }
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for GraphWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.area.set_widget_name(&name);
+        self.area.set_tooltip_markup(Some(&self.tooltip));
+        // Without a height it fills the height of the bar.
+        self.area.set_size_request(self.width, self.height);
+        ui::add_and_align(&self.area, align, left, centered, right);
+
+        // 0.3.5: A graph that's rebuilt on a reload, like after changing its color, keeps the
+        //   samples it has taken so far.
+        if let Some(mut history) = registry::take_over::<VecDeque<f64>>(&name) {
+            while history.len() > self.samples {
+                history.pop_front();
+            }
+
+            self.history.replace(history);
+        }
+
+        let widget = Rc::new(self);
+        let carried = Rc::downgrade(&widget);
+        registry::carry_over(&name, move || {
+            let Some(widget) = carried.upgrade() else {
+                return VecDeque::new();
+            };
+
+            let mut history = widget.history.take();
+            // Skips the rest of a transition.
+            if let (Some(last), Some(moving)) = (history.back_mut(), widget.moving.get()) {
+                *last = moving;
+            }
+
+            history
+        });
+
+        let drawing = widget.clone();
+        widget.area.connect_draw(move |_, context| {
+            drawing.draw(context);
+            Inhibit(false)
+        });
+
+        // An update-rate of `0` would only ever take a single sample.
+        let update_rate = match widget.update_rate {
+            0 => 1000,
+            update_rate => update_rate,
+        };
+
+        if widget.command.is_empty() {
+            // Values of other widgets are published on the main thread, so they're read there too.
+            // 0.3.5: Or the field of a source, like `weather.temp`.
+            let sample = move || match placeholders::get_value(&widget.source)
+                .or_else(|| sources::get_field(&widget.source))
+                .as_deref()
+                .and_then(parse_sample)
+            {
+                Some(value) => widget.push(value),
+                None => log::debug!("'{}' has no number to sample yet", widget.source),
+            };
+
+            sample();
+            pause::timeout_add_local(Duration::from_millis(update_rate), move || {
+                sample();
+                Continue(true)
+            });
+
+            log::debug!("Added a new graph widget");
+            return;
+        }
+
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        let receiving_widget = widget.clone();
+        registry::attach(receiver, move |value| {
+            receiving_widget.push(value);
+            Continue(true)
+        });
+
+        widget.refresh(&sender, BashRunner);
+        pause::timeout_add_local(Duration::from_millis(update_rate), move || {
+            widget.refresh(&sender, BashRunner);
+            Continue(true)
+        });
+
+        log::debug!("Added a new graph widget");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_and_layout() {
+        assert_eq!(parse_sample("cpu: 42.5%"), Some(42.5));
+        assert_eq!(parse_sample("-3 °C"), Some(-3.0));
+        assert_eq!(parse_sample("cpu - 42"), Some(42.0));
+        assert_eq!(parse_sample("n/a"), None);
+
+        let history = VecDeque::from([0.0, 50.0, 100.0]);
+        assert_eq!(
+            layout(&history, 5, 0.0, Some(100.0), 40.0, 10.0),
+            vec![(20.0, 10.0), (30.0, 5.0), (40.0, 0.0)]
+        );
+        // The highest sample is the top without a `max`.
+        assert_eq!(layout(&history, 3, 0.0, None, 20.0, 10.0)[1], (10.0, 5.0));
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{config, structures::Align, ui, widget::HWidget};
+use gtk::{traits::*, *};
+use std::{cell::RefCell, collections::HashSet};
+
+thread_local! {
+    /// The keys of the groups that are currently being built, innermost last.
+    static BUILDING: RefCell<std::vec::Vec<String>> = const { RefCell::new(std::vec::Vec::new()) };
+}
+
+/// Creates a new group widget, which lays out other widgets in a box of its own so they can be
+/// styled together, like an island of widgets.
+pub struct GroupWidget {
+    /// The key of the group, like `right-group_system`.
+    pub key: String,
+    /// The keys of the widgets inside of the group, in order.
+    pub widgets: std::vec::Vec<String>,
+    pub spacing: i32,
+}
+
+/// Gets the keys of the widgets inside of the group at `key`, from `widgets`.
+pub fn get_widgets(key: &str) -> std::vec::Vec<String> {
+    config::with_config(|config| {
+        config[key]["widgets"]
+            .members()
+            .filter_map(|widget| widget.as_str())
+            .map(String::from)
+            .collect()
+    })
+}
+
+/// Whether the widget at `key` is a group, like `right-group_system`.
+pub fn is_group(key: &str) -> bool {
+    key.split(ui::SEPARATOR)
+        .next()
+        .and_then(|identifier| identifier.split_once(ui::ALIGNMENT))
+        .is_some_and(|(_, widget_type)| widget_type == "group")
+}
+
+/// Gets the keys of all widgets inside of a group, which are built by their group rather than
+/// being added to a region of their own.
+pub fn get_grouped_keys() -> HashSet<String> {
+    ui::get_widget_keys()
+        .iter()
+        .filter(|key| is_group(key))
+        .flat_map(|key| get_widgets(key))
+        .collect()
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for GroupWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        let orientation = if config::get_position().is_vertical() {
+            Orientation::Vertical
+        } else {
+            Orientation::Horizontal
+        };
+
+        let container = Box::new(orientation, self.spacing);
+        container.set_widget_name(&name);
+        BUILDING.with(|building| building.borrow_mut().push(self.key.clone()));
+        for key in &self.widgets {
+            // Groups containing each other would never stop building.
+            if BUILDING.with(|building| building.borrow().contains(key)) {
+                log::warn!(
+                    "{}: {key} contains this group itself, skipping it",
+                    self.key
+                );
+                continue;
+            }
+
+            let is_widget = key.contains(ui::ALIGNMENT) && key.contains(ui::SEPARATOR);
+            if !is_widget || !config::with_config(|config| config.has_key(key)) {
+                log::warn!("{}: There's no widget named {key}, skipping it", self.key);
+                continue;
+            }
+
+            ui::create_component(key, &container, &container, &container);
+        }
+
+        BUILDING.with(|building| building.borrow_mut().pop());
+        ui::add_and_align(&container, align, left, centered, right);
+        log::debug!(
+            "Added a new group widget named '{name}' with {} widgets",
+            self.widgets.len()
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{
+    command::{self, BashRunner, CommandRunner, RetryRunner},
+    errors, pause, registry,
+    structures::Align,
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{gdk_pixbuf::Pixbuf, traits::*, *};
+use std::{path::Path, rc::Rc, time::Duration};
+
+/// The size of the image in pixels when none is specified.
+pub const DEFAULT_SIZE: i32 = 16;
+
+/// The themed icon shown in place of a missing file or icon.
+const MISSING_ICON: &str = "image-missing";
+
+/// Creates a new image widget, which shows either an image file or a themed icon.
+pub struct ImageWidget {
+    pub tooltip: String,
+    /// A file path or a themed icon name, shown when `command` is empty.
+    pub source: String,
+    /// Should output a file path or a themed icon name.
+    pub command: String,
+    pub size: i32,
+    /// Hides the widget instead of showing `MISSING_ICON` when the image can't be found.
+    pub hide_missing: bool,
+    pub update_rate: u64,
+    pub command_timeout: u64,
+    pub retry: command::Retry,
+    pub image: Image,
+}
+
+impl ImageWidget {
+    /// Shows `source`, which is treated as a file if it contains a `/` and as an icon name
+    /// otherwise.
+    fn show_source(&self, source: &str) {
+        let source = source.trim();
+        let shown = if source.contains('/') {
+            self.show_file(source)
+        } else {
+            self.show_icon(source)
+        };
+
+        if shown {
+            self.image.show();
+            return;
+        }
+
+        log::debug!("Couldn't find the image '{source}'");
+        if self.hide_missing {
+            self.image.hide();
+        } else {
+            self.image
+                .set_from_icon_name(Some(MISSING_ICON), IconSize::Button);
+            self.image.set_pixel_size(self.size);
+            self.image.show();
+        }
+    }
+
+    /// Shows the image at `path` scaled to `size`, returns `false` if it can't be loaded.
+    fn show_file(&self, path: &str) -> bool {
+        if !Path::new(path).is_file() {
+            return false;
+        }
+
+        match Pixbuf::from_file_at_scale(path, self.size, self.size, true) {
+            Ok(pixbuf) => {
+                self.image.set_from_pixbuf(Some(&pixbuf));
+                true
+            }
+            Err(error) => {
+                log::warn!("Couldn't load the image '{path}': {error}");
+                false
+            }
+        }
+    }
+
+    /// Shows the themed icon `name` at `size`, returns `false` if the theme doesn't have it.
+    fn show_icon(&self, name: &str) -> bool {
+        let has_icon =
+            !name.is_empty() && IconTheme::default().is_some_and(|theme| theme.has_icon(name));
+        if has_icon {
+            self.image.set_from_icon_name(Some(name), IconSize::Button);
+            self.image.set_pixel_size(self.size);
+        }
+
+        has_icon
+    }
+
+    /// Runs the command through `runner` on a separate thread and sends its output to `sender`.
+    fn refresh(&self, sender: &glib::Sender<String>, runner: impl CommandRunner + Send + 'static) {
+        let sender = sender.clone();
+        let command = self.command.clone();
+        let timeout = Duration::from_millis(self.command_timeout);
+        let retry = self.retry;
+        let name = self.image.widget_name().to_string();
+        command::spawn_worker(move || {
+            let runner = RetryRunner {
+                runner: &runner,
+                retry,
+            };
+            let output = runner.execute(&command, timeout);
+            errors::check(&name, &command, &output);
+            // Can only fail if the main loop is gone.
+            let _ = sender.send(output.stdout);
+        });
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for ImageWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.image.set_widget_name(&name);
+        self.image.set_tooltip_markup(Some(&self.tooltip));
+        // Visibility is managed by `show_source`, so `show_all` mustn't show a missing image.
+        self.image.set_no_show_all(true);
+        ui::add_and_align(&self.image, align, left, centered, right);
+
+        if self.command.is_empty() {
+            self.show_source(&self.source);
+            log::debug!("Added a new image widget");
+            return;
+        }
+
+        // The image is swapped on the main thread, as soon as the command has finished.
+        let widget = Rc::new(self);
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        let receiving_widget = widget.clone();
+        registry::attach(receiver, move |source: String| {
+            receiving_widget.show_source(&source);
+            Continue(true)
+        });
+
+        widget.refresh(&sender, BashRunner);
+        // An update-rate of `0` means the command is only run once.
+        if widget.update_rate != 0 {
+            pause::timeout_add_local(Duration::from_millis(widget.update_rate), move || {
+                widget.refresh(&sender, BashRunner);
+                Continue(true)
+            });
+        }
+
+        log::debug!("Added a new image widget");
+    }
+}
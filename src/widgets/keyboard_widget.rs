@@ -0,0 +1,75 @@
+use crate::{
+    compositor::{Compositor, Events},
+    overflow, placeholders, registry,
+    structures::Align,
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::collections::HashMap;
+
+/// The format used when none is specified.
+pub const DEFAULT_FORMAT: &str = "{layout}";
+
+/// Creates a new keyboard widget, which shows the active keyboard layout.
+pub struct KeyboardWidget {
+    pub tooltip: String,
+    pub format: String,
+    /// Maps layout names to what should be shown instead, for example `English (US)` to `en`.
+    pub names: HashMap<String, String>,
+    pub label: Label,
+}
+
+impl KeyboardWidget {
+    /// Syncs the label with `layout`.
+    fn sync(&self, layout: &str) {
+        let layout = self.names.get(layout).map_or(layout, String::as_str);
+        self.update_label_reg(&placeholders::fill(&self.format, &[("layout", layout)]));
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for KeyboardWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        // Hidden until there's a layout to show, also keeps it hidden on other compositors.
+        self.label.set_no_show_all(true);
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        let compositor =
+            Compositor::detect().filter(|compositor| compositor.supports(Events::Input));
+        let Some(compositor) = compositor else {
+            log::warn!("Neither Sway nor Hyprland is running, hiding the keyboard widget");
+            return;
+        };
+
+        // 0.3.5: Updated through IPC events, so it changes as soon as the layout is switched.
+        let (sender, receiver) =
+            glib::MainContext::channel::<Option<String>>(glib::PRIORITY_DEFAULT);
+        compositor.watch(Events::Input, Compositor::get_keyboard_layout, sender);
+        registry::attach(receiver, move |layout| {
+            match layout {
+                Some(layout) => {
+                    self.sync(&layout);
+                    self.label.show();
+                }
+                None => self.label.hide(),
+            }
+
+            Continue(true)
+        });
+
+        log::debug!("Added a new keyboard widget for {compositor:?}");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the layout has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
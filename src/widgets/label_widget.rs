@@ -1,11 +1,22 @@
 use crate::{
-    config,
+    command::{self, CommandRunner, EnvRunner, RetryRunner},
+    config, errors, locks, overflow, placeholders,
+    r#loop::Schedule,
+    registry, sources,
     structures::Align,
+    transform::{self, Step},
+    transition::Transition,
+    triggers::Trigger,
     ui::{self, VEC},
     widget::HWidget,
 };
 use gtk::{traits::*, *};
-use std::{fmt::Display, process::Stdio, sync::RwLock, time::Duration};
+use std::{
+    fmt::Display,
+    process::Stdio,
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
 use tokio::{
     io::{AsyncBufReadExt, BufReader},
     process::Command,
@@ -17,114 +28,388 @@ lazy_static! {
     static ref BUFFER: RwLock<String> = RwLock::new(String::default());
 }
 
+/// Everything needed to run the command of a label on a worker thread.
+/// 0.3.5: Shared with the label loop through an `Arc`, so queuing an update doesn't copy it.
+#[derive(Clone, Debug, Default)]
+pub struct LabelJob {
+    /// The widget name, as GTK widgets can't be read outside of the main thread.
+    pub name: String,
+    pub text: String,
+    pub command: String,
+    pub cache_ttl: u64,
+    pub command_timeout: u64,
+    pub retry: command::Retry,
+    pub show_errors: bool,
+    /// Shown instead when `command` fails or its output is empty, see `needs_fallback`.
+    pub fallback: String,
+    /// Whether trailing new lines are removed from the output of `command`.
+    pub trim: bool,
+    /// Added to the environment of `command`, see `config::get_variable_env`.
+    pub env: Vec<(String, String)>,
+    /// Applied to the command output before it's shown, see `transform::parse`.
+    pub transform: Vec<Step>,
+    /// The field of a source shown instead of the command output, like `weather.temp`.
+    pub source: String,
+    pub last_output: LastOutput,
+}
+
+/// The last output of a command and what was shown for it, so it isn't transformed again while
+/// the command keeps on printing the same. A clone of a job starts over, as it may be changed.
+#[derive(Debug, Default)]
+pub struct LastOutput(Mutex<Option<(String, String)>>);
+
+impl Clone for LastOutput {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl LastOutput {
+    /// Gets what was shown for `output`, if that's what the command printed last time.
+    fn get(&self, output: &str) -> Option<String> {
+        match &*locks::lock(&self.0) {
+            Some((last, shown)) if last == output => Some(shown.clone()),
+            _ => None,
+        }
+    }
+
+    /// Remembers that `shown` is shown for `output`.
+    fn set(&self, output: &str, shown: &str) {
+        *locks::lock(&self.0) = Some((output.to_string(), shown.to_string()));
+    }
+}
+
+impl LabelJob {
+    /// Executes the command through `runner` and returns what the label should show.
+    /// Blocks until the command is done, so only call this on a worker thread.
+    pub fn run(&self, runner: &impl CommandRunner) -> String {
+        // 0.3.5: The source runs the command, the label only shows one of its fields.
+        if !self.source.is_empty() {
+            return match sources::get_field(&self.source) {
+                Some(value) if !needs_fallback(&value, true, &self.fallback) => format!(
+                    "{}{}",
+                    self.text,
+                    transform::apply_or_raw(&self.name, &self.transform, &value)
+                ),
+                _ => self.fallback.clone(),
+            };
+        }
+
+        let runner = RetryRunner {
+            runner: &EnvRunner {
+                runner,
+                env: &self.env,
+            },
+            retry: self.retry,
+        };
+        let output = command::execute_cached_with(
+            &runner,
+            &self.command,
+            Duration::from_secs(self.cache_ttl),
+            Duration::from_millis(self.command_timeout),
+        );
+        errors::check(&self.name, &self.command, &output);
+        let stdout = match self.trim {
+            true => &output.stdout,
+            false => &output.untrimmed_stdout,
+        };
+
+        if self.show_errors && !output.success {
+            format!("{}{}", self.text, output.stderr)
+        } else if needs_fallback(stdout, output.success, &self.fallback) {
+            self.fallback.clone()
+        } else if let Some(shown) = self.last_output.get(stdout) {
+            shown
+        } else {
+            // 0.3.5: Only the output is transformed, errors are shown as-is. Those are reported
+            //   again on every update, so only successfully transformed output is remembered.
+            match transform::apply(&self.transform, stdout) {
+                Ok(transformed) => {
+                    let shown = format!("{}{transformed}", self.text);
+                    self.last_output.set(stdout, &shown);
+                    shown
+                }
+                Err(_) => format!(
+                    "{}{}",
+                    self.text,
+                    transform::apply_or_raw(&self.name, &self.transform, stdout)
+                ),
+            }
+        }
+    }
+}
+
 /// Creates a new label widget.
 #[derive(Debug)]
 pub struct LabelWidget {
     pub tooltip: String,
-    pub text: String,
-    pub command: String,
     pub label: Label,
     pub listen: bool,
+    pub update_rate: u64,
+    /// Used instead of `update_rate` while running on battery, if it's set.
+    pub update_rate_on_battery: Option<u64>,
+    /// Also updated on these, and only every `update_rate` with `Trigger::Tick`. `None` without
+    /// an `update_on`.
+    pub update_on: Option<Vec<Trigger>>,
+    /// Whether the text and the command output are parsed as Pango markup, see `uses_markup`.
+    pub markup: bool,
+    /// Fades new content in.
+    pub transition: Transition,
+    pub job: Arc<LabelJob>,
 }
 
 // For VEC to work.
 unsafe impl Send for LabelWidget {}
 unsafe impl Sync for LabelWidget {}
 
+/// 0.3.5: Whether the label at `key` is parsed as Pango markup. Defaults to `true` for static
+///   labels, as before, and to `false` for labels with a `command`, so that their output is never
+///   mistaken for markup. Variables are expanded before the markup is parsed.
+pub fn uses_markup(key: &str, command: &str) -> bool {
+    config::try_get_bool(key, "markup").unwrap_or(command.is_empty())
+}
+
+/// 0.3.5: Whether `fallback` is shown instead of `text` and the `output` of the command, because
+///   the command failed or printed nothing but whitespace. Never without a `fallback`, so the
+///   label shows whatever there is, and `show_errors` takes precedence as the error is wanted then.
+pub fn needs_fallback(output: &str, success: bool, fallback: &str) -> bool {
+    !fallback.is_empty() && (!success || output.trim().is_empty())
+}
+
 /// 0.3.2: If `listen` is `true`, call this function and then externally set the label text-value
 ///   to that of `BUFFER`.
-fn begin_listen(cmd: String) {
-    task::spawn(async move {
-        let mut child = Command::new("bash")
-            .args(["-c", &cmd])
-            .stdout(Stdio::piped())
-            .kill_on_drop(true)
-            .spawn()
-            .unwrap_or_else(|_| panic!("[ERROR] Cannot start '{cmd}'\n"));
-
-        let out = child
-            .stdout
-            .take()
-            .expect("[ERROR] Cannot take stdout from child!\n");
+/// 0.3.5: Returns the task reading the output along with the pid of the command, so both can be
+///   stopped again.
+fn begin_listen(cmd: String, update_rate: u64) -> (task::JoinHandle<()>, Option<u32>) {
+    let mut child = Command::new("bash")
+        .args(["-c", &cmd])
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        // 0.3.5: Put it in its own process group, so that everything it spawns is stopped with
+        //   the bar.
+        .process_group(0)
+        .spawn()
+        .unwrap_or_else(|_| panic!("Cannot start '{cmd}'"));
 
-        let mut reader = BufReader::new(out).lines();
-        let update_rate = config::get_update_rate();
-        loop {
-            *BUFFER.write().unwrap() = reader
-                .next_line()
-                .await
-                .expect("[ERROR] There are no more lines available!\n")
-                .expect("[ERROR] The string value is None!\n");
+    let pid = child.id();
+    if let Some(pid) = pid {
+        command::register_child(pid);
+    }
 
+    let out = child.stdout.take().expect("Cannot take stdout from child!");
+    let reader = task::spawn(async move {
+        let mut reader = BufReader::new(out).lines();
+        while let Ok(Some(line)) = reader.next_line().await {
+            *BUFFER.write().unwrap() = line;
             tokio::time::sleep(Duration::from_millis(update_rate)).await;
         }
+
+        // 0.3.5: The label keeps showing the last line once the command is done.
+        match child.wait().await {
+            Ok(status) => log::warn!("'{cmd}' exited with {status}"),
+            Err(err) => log::warn!("Failed waiting for '{cmd}': {err}"),
+        }
+
+        if let Some(pid) = pid {
+            command::unregister_child(pid);
+        }
     });
+
+    (reader, pid)
+}
+
+impl LabelWidget {
+    /// Gets how the label loop schedules the label.
+    pub fn schedule(&self) -> Schedule<'_> {
+        Schedule {
+            update_rate: self.update_rate,
+            update_rate_on_battery: self.update_rate_on_battery,
+            update_on: &self.update_on,
+            listen: self.listen,
+        }
+    }
+
+    /// Sets the content of the label.
+    /// 0.3.5: With `markup` it's parsed as Pango markup, so command output has to be valid
+    ///   markup itself. Otherwise it's shown exactly as it is, so there's nothing to escape.
+    fn set_content(&self, content: &str) {
+        if self.markup {
+            self.label.set_markup(content);
+        } else {
+            self.label.set_text(content);
+        }
+    }
+
+    /// Replaces the content of the label, which the new content fades in with a `transition_ms`.
+    fn change_content(&self, content: &str) {
+        self.set_content(content);
+        if self.transition.is_instant() {
+            return;
+        }
+
+        // Hidden until the first frame, so the new content doesn't flash up.
+        self.label.set_opacity(0.0);
+        let label = self.label.clone();
+        self.transition
+            .start(move |progress| label.set_opacity(progress));
+    }
 }
 
 // Implements HWidget for the widget so that we can actually use it.
 impl HWidget for LabelWidget {
     fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
-        let is_static = self.command.is_empty();
+        let is_static = self.job.command.is_empty() && self.job.source.is_empty();
         self.label.set_widget_name(&name);
         // 0.2.7: Support for tooltips
         self.label.set_tooltip_markup(Some(&self.tooltip));
         ui::add_and_align(&self.label, align, left, centered, right);
 
+        // 0.3.5: Shows the most recent widget error instead of its own text.
+        if errors::is_error_widget(&name) {
+            errors::bind(&self.label);
+            log::debug!("Added a new label widget named '{name}', showing widget errors");
+            return;
+        }
+
         if self.listen {
-            begin_listen(self.command.clone());
+            let (reader, pid) = begin_listen(self.job.command.clone(), self.update_rate);
+            // 0.3.5: The command stops along with the label, like when its bar is destroyed.
+            registry::on_teardown(move || {
+                if let Some(pid) = pid {
+                    command::terminate_child(pid);
+                }
+
+                reader.abort();
+            });
         }
 
         // 0.3.2: Don't add widgets that don't have a command set to the vector, as it won't be
         //   updated due to being static.
         if !is_static {
-            VEC.lock()
-                .expect("[ERROR] Cannot access ui::VEC!\n")
-                .push(self)
-                .expect("[ERROR] You cannot have more than `1024` Labels!\n");
+            if ui::register(&VEC, self).is_err() {
+                panic!("You cannot have more than `1024` Labels!")
+            }
         } else {
-            self.label.set_markup(&self.text);
+            self.set_content(&self.job.text);
         }
 
-        log!(format!(
+        log::debug!(
             "Added a new label widget named '{name}', is static: {}",
             is_static
-        ));
+        );
     }
 
     fn update_label_reg(&self, new_content: &(impl Display + Clone)) {
         let ts = new_content.to_string();
-        if self.label.text().eq(&ts) {
+        placeholders::publish(&self.label.widget_name(), &ts);
+        if overflow::get_content(&self.label) == ts {
             // Exact same content, return and don't cause a redraw.
             return;
         }
 
-        log!(format!(
+        log::debug!(
             "[{}] -> Label update received (from => \"{}\", to => \"{}\")",
             self.label.widget_name(),
-            self.label.text(),
+            self.label.label(),
             ts
-        ));
+        );
 
-        // 0.2.7: Support for markup as long as the command is empty.
-        // It doesn't support markup with commands because some strings may cause GTK to mistreat
-        // it, which I may fix in the future.
-        if self.command.is_empty() {
-            self.label.set_markup(&ts);
-        } else {
-            self.label.set_text(&ts);
-        }
+        self.change_content(&ts);
     }
 
     fn update_label_internal(&self) {
-        let new_content = BUFFER
+        let line = BUFFER
             .read()
-            .expect("[ERROR] Failed retrieving content from BUFFER!\n");
-        let old_content = self.label.text();
+            .expect("Failed retrieving content from BUFFER!")
+            .clone();
+        let job = &self.job;
+        let new_content = match needs_fallback(&line, true, &job.fallback) {
+            true => job.fallback.clone(),
+            false => transform::apply_or_raw(&job.name, &job.transform, &line),
+        };
+        let old_content = overflow::get_content(&self.label);
         // eq-check the new content for old_content. Doing the opposite requires a .to_string()
         // call.
+        placeholders::publish(&self.label.widget_name(), &new_content);
         if !new_content.eq(&old_content) {
             // Not the same; set content and redraw.
-            self.label.set_text(&new_content);
+            self.change_content(&new_content);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{allocations, command::MockRunner};
+
+    #[test]
+    fn jobs_show_the_transformed_output() {
+        let job = LabelJob {
+            name: String::from("tests-label_load"),
+            text: String::from("Load: "),
+            command: String::from("load.sh"),
+            env: vec![(String::from("HYBRID_HOST"), String::from("box"))],
+            transform: vec![Step::Round(1), Step::Suffix(String::from("%"))],
+            ..Default::default()
+        };
+
+        let runner = MockRunner::default().with("load.sh", "42.26\n", true);
+        assert_eq!(job.run(&runner), "Load: 42.3%");
+        assert_eq!(*runner.last_env.borrow(), job.env);
+    }
+
+    #[test]
+    fn fallbacks_replace_missing_output() {
+        assert!(needs_fallback("", true, "n/a"));
+        assert!(needs_fallback(" \n\t", true, "n/a"));
+        assert!(needs_fallback("42", false, "n/a"));
+        assert!(!needs_fallback("42", true, "n/a"));
+        // Without a fallback the label shows whatever there is.
+        assert!(!needs_fallback("", false, ""));
+
+        let job = LabelJob {
+            name: String::from("tests-label_fallback"),
+            text: String::from("Temp: "),
+            command: String::from("temp.sh"),
+            fallback: String::from("n/a"),
+            ..Default::default()
+        };
+
+        let empty = MockRunner::default().with("temp.sh", "  \n", true);
+        assert_eq!(job.run(&empty), "n/a");
+        let failed = MockRunner::default().with_stderr("temp.sh", "21", "no sensor", false);
+        assert_eq!(job.run(&failed), "n/a");
+
+        // The error is wanted with `show_errors`, so it's shown instead of the fallback.
+        let job = LabelJob {
+            show_errors: true,
+            ..job
+        };
+        assert_eq!(job.run(&failed), "Temp: no sensor");
+        assert_eq!(job.run(&empty), "n/a");
+        errors::clear(&job.name);
+    }
+
+    #[test]
+    fn queuing_updates_doesnt_copy_labels() {
+        let job = Arc::new(LabelJob {
+            name: String::from("right-label_disk"),
+            text: String::from("Used: "),
+            command: String::from("df --output=used -B1 / | tail -n 1"),
+            fallback: String::from("n/a"),
+            env: (0..8)
+                .map(|index| (format!("HYBRID_VAR_{index}"), index.to_string()))
+                .collect(),
+            transform: vec![Step::Round(1), Step::Suffix(String::from(" GiB"))],
+            ..Default::default()
+        });
+        let jobs = vec![job; 500];
+
+        // What every due label used to cost the label loop on every tick.
+        let copied = allocations::count(|| jobs.iter().for_each(|job| drop(LabelJob::clone(job))));
+        let shared = allocations::count(|| jobs.iter().for_each(|job| drop(Arc::clone(job))));
+        assert!(copied >= jobs.len() * 20, "{copied} allocations");
+        assert_eq!(shared, 0);
+    }
+}
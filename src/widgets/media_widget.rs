@@ -0,0 +1,168 @@
+use crate::{overflow, placeholders, registry, structures::Align, ui, widget::HWidget};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{collections::HashMap, sync::mpsc, thread};
+use zbus::{
+    blocking::{fdo::DBusProxy, Connection, MessageIterator, Proxy},
+    zvariant::{OwnedValue, Value},
+};
+
+/// The format used when none is specified.
+pub const DEFAULT_FORMAT: &str = "{artist} - {title}";
+
+/// Every MPRIS player owns a bus name with this prefix.
+const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+/// Matches players changing their state, for example when the track changes.
+const PROPERTIES_CHANGED_RULE: &str = "type='signal',interface='org.freedesktop.DBus.Properties',\
+                                       member='PropertiesChanged',path='/org/mpris/MediaPlayer2'";
+
+/// Matches players appearing and disappearing.
+const NAME_OWNER_CHANGED_RULE: &str = "type='signal',sender='org.freedesktop.DBus',\
+                                       interface='org.freedesktop.DBus',member='NameOwnerChanged'";
+
+/// Creates a new media widget, which shows the current track of an MPRIS player.
+pub struct MediaWidget {
+    pub tooltip: String,
+    pub format: String,
+    /// Only players whose name contains this are used, all players are if empty.
+    pub player: String,
+    pub label: Label,
+}
+
+/// Returns the value of `key` in `metadata` as a string, joining arrays (like `xesam:artist`) with
+/// `, `.
+fn get_metadata(metadata: &HashMap<String, OwnedValue>, key: &str) -> String {
+    match metadata.get(key).map(|value| &**value) {
+        Some(Value::Str(value)) => value.to_string(),
+        Some(Value::Array(values)) => values
+            .get()
+            .iter()
+            .filter_map(|value| value.downcast_ref::<str>())
+            .collect::<Vec<_>>()
+            .join(", "),
+        _ => String::default(),
+    }
+}
+
+/// Formats the track of the active player, which is the first one playing, otherwise the first
+/// one paused. Returns an empty string if nothing is playing.
+fn read_track(connection: &Connection, format: &str, player: &str) -> zbus::Result<String> {
+    let player = player.to_lowercase();
+    let mut candidates = Vec::new();
+    for name in DBusProxy::new(connection)?.list_names()? {
+        if !name.starts_with(MPRIS_PREFIX) || !name.to_lowercase().contains(&player) {
+            continue;
+        }
+
+        let proxy = Proxy::new(
+            connection,
+            name.to_string(),
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+        )?;
+
+        // Players may disappear at any time, skip those.
+        if let Ok(status) = proxy.get_property::<String>("PlaybackStatus") {
+            candidates.push((status, proxy));
+        }
+    }
+
+    let active = candidates
+        .iter()
+        .find(|(status, _)| status == "Playing")
+        .or_else(|| candidates.iter().find(|(status, _)| status == "Paused"));
+
+    let Some((status, proxy)) = active else {
+        return Ok(String::default());
+    };
+
+    let metadata: HashMap<String, OwnedValue> = proxy.get_property("Metadata")?;
+    Ok(placeholders::fill(
+        format,
+        &[
+            ("artist", &get_metadata(&metadata, "xesam:artist")),
+            ("title", &get_metadata(&metadata, "xesam:title")),
+            ("status", status),
+        ],
+    ))
+}
+
+/// Sends the current track to `sender`, then again every time a player changes.
+/// Only call this once per widget as it's a loop.
+fn watch(format: String, player: String, sender: glib::Sender<String>) {
+    thread::spawn(move || {
+        let connection = match Connection::session() {
+            Ok(connection) => connection,
+            Err(err) => {
+                log::warn!("Media widget cannot connect to D-Bus: {err}");
+                return;
+            }
+        };
+
+        // Both kinds of signals only mean "something changed", so they're funneled into one
+        // channel and the whole state is read again.
+        let (changed_sender, changed_receiver) = mpsc::channel();
+        for rule in [PROPERTIES_CHANGED_RULE, NAME_OWNER_CHANGED_RULE] {
+            let iterator = match MessageIterator::for_match_rule(rule, &connection, Some(64)) {
+                Ok(iterator) => iterator,
+                Err(err) => {
+                    log::warn!("Media widget cannot listen to D-Bus: {err}");
+                    return;
+                }
+            };
+
+            let changed_sender = changed_sender.clone();
+            thread::spawn(move || {
+                for _ in iterator {
+                    if changed_sender.send(()).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+
+        loop {
+            let track = read_track(&connection, &format, &player).unwrap_or_else(|err| {
+                log::warn!("Media widget failed reading the player: {err}");
+                String::default()
+            });
+
+            // Can only fail if the main loop is gone.
+            if sender.send(track).is_err() || changed_receiver.recv().is_err() {
+                break;
+            }
+
+            // A track change sends several signals at once, only read the state once.
+            while changed_receiver.try_recv().is_ok() {}
+        }
+    });
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for MediaWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        // 0.3.5: Updated through D-Bus signals rather than on a timer.
+        let (sender, receiver) = glib::MainContext::channel::<String>(glib::PRIORITY_DEFAULT);
+        watch(self.format.clone(), self.player.clone(), sender);
+        registry::attach(receiver, move |track| {
+            self.update_label_reg(&track);
+            Continue(true)
+        });
+
+        log::debug!("Added a new media widget");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the track has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{
+    overflow, pause, placeholders,
+    ranges::{self, Ranges},
+    structures::Align,
+    ui, units,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{fs, time::Duration};
+
+/// The format used when none is specified.
+pub const DEFAULT_FORMAT: &str = "{percent}%";
+
+/// Creates a new memory widget, which reads the memory usage from `/proc/meminfo` instead of
+/// through `free`.
+pub struct MemoryWidget {
+    pub tooltip: String,
+    pub format: String,
+    pub ranges: Ranges,
+    pub update_rate: u64,
+    pub label: Label,
+}
+
+/// Reads the used and total memory, in KiB.
+fn read_memory() -> Option<(u64, u64)> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let get = |key: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))?
+            .trim()
+            .trim_end_matches("kB")
+            .trim()
+            .parse::<u64>()
+            .ok()
+    };
+
+    let total = get("MemTotal")?;
+    // `MemAvailable` accounts for caches that can be freed, which is what `free` shows as used.
+    Some((total.saturating_sub(get("MemAvailable")?), total))
+}
+
+/// Fills `format` in with the current usage, picking `{icon}` from `ranges` by its percent.
+pub fn read(format: &str, ranges: &Ranges) -> Option<String> {
+    let (used, total) = read_memory()?;
+    let format_kib = |kib: u64| units::format_bytes(kib.saturating_mul(1024), true);
+    let percent = if total == 0 {
+        0
+    } else {
+        ((used as f64 / total as f64) * 100.0).round().min(100.0) as u64
+    };
+
+    Some(placeholders::fill(
+        format,
+        &[
+            ("used", &format_kib(used)),
+            ("total", &format_kib(total)),
+            ("percent", &percent.to_string()),
+            (ranges::PLACEHOLDER, ranges.pick(percent as f64)),
+        ],
+    ))
+}
+
+impl MemoryWidget {
+    /// Syncs the label with the current usage.
+    fn sync(&self) {
+        if let Some(text) = read(&self.format, &self.ranges) {
+            self.update_label_reg(&text);
+        }
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for MemoryWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        self.sync();
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        // An update-rate of `0` means the usage is only read once.
+        if self.update_rate != 0 {
+            pause::timeout_add_local(Duration::from_millis(self.update_rate), move || {
+                self.sync();
+                Continue(true)
+            });
+        }
+
+        log::debug!("Added a new memory widget");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the usage has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
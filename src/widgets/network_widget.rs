@@ -0,0 +1,146 @@
+use crate::{overflow, pause, placeholders, structures::Align, ui, units, widget::HWidget};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{
+    cell::Cell,
+    fs,
+    time::{Duration, Instant},
+};
+
+/// The format used when none is specified.
+pub const DEFAULT_FORMAT: &str = "↓{rx} ↑{tx}";
+
+/// Picks the interface of the default route.
+pub const AUTO_INTERFACE: &str = "auto";
+
+/// Creates a new network widget, which reads the throughput of an interface from `/proc/net/dev`
+/// instead of through external tools.
+pub struct NetworkWidget {
+    pub tooltip: String,
+    pub format: String,
+    /// The interface to read, for example `wlan0`, or `auto` for the one of the default route.
+    pub interface: String,
+    pub update_rate: u64,
+    pub label: Label,
+    /// The received and transmitted bytes from the previous read, as rates are the difference
+    /// between two.
+    pub previous: Cell<Option<(u64, u64, Instant)>>,
+}
+
+/// Returns the interface of the default route from `/proc/net/route`.
+fn get_default_interface() -> Option<String> {
+    let routes = fs::read_to_string("/proc/net/route").ok()?;
+    // Formatted as `Iface Destination Gateway ...`, the default route has `00000000` as destination.
+    routes.lines().skip(1).find_map(|line| {
+        let mut columns = line.split_whitespace();
+        let interface = columns.next()?;
+        (columns.next()? == "00000000").then(|| interface.to_string())
+    })
+}
+
+/// Reads the total received and transmitted bytes of `interface`.
+fn read_bytes(interface: &str) -> Option<(u64, u64)> {
+    let dev = fs::read_to_string("/proc/net/dev").ok()?;
+    // Formatted as `iface: rx_bytes rx_packets ... (8 columns) tx_bytes ...`.
+    let columns: Vec<u64> = dev.lines().skip(2).find_map(|line| {
+        let (name, columns) = line.split_once(':')?;
+        (name.trim() == interface).then(|| {
+            columns
+                .split_whitespace()
+                .filter_map(|column| column.parse().ok())
+                .collect()
+        })
+    })?;
+
+    Some((*columns.first()?, *columns.get(8)?))
+}
+
+/// Formats a rate in bytes per second, for example `1.5 MiB/s`.
+fn format_rate(bytes_per_second: f64) -> String {
+    format!(
+        "{}/s",
+        units::format_bytes(bytes_per_second.round() as u64, true)
+    )
+}
+
+impl NetworkWidget {
+    /// Returns the configured interface, resolving `auto`.
+    fn get_interface(&self) -> Option<String> {
+        if self.interface == AUTO_INTERFACE {
+            get_default_interface()
+        } else {
+            Some(self.interface.clone())
+        }
+    }
+
+    /// Syncs the label with the rates since the previous read, nothing is shown on the first read.
+    fn sync(&self) {
+        let Some(interface) = self.get_interface() else {
+            return;
+        };
+
+        let Some((rx, tx)) = read_bytes(&interface) else {
+            self.previous.set(None);
+            return;
+        };
+
+        let now = Instant::now();
+        let Some((previous_rx, previous_tx, previous_time)) =
+            self.previous.replace(Some((rx, tx, now)))
+        else {
+            return;
+        };
+
+        let elapsed = now.duration_since(previous_time).as_secs_f64();
+        if elapsed == 0.0 {
+            return;
+        }
+
+        // Counters reset when the interface restarts, which would show as a huge spike otherwise.
+        let rate = |bytes: u64, previous: u64| {
+            format_rate(bytes.saturating_sub(previous) as f64 / elapsed)
+        };
+        self.update_label_reg(&placeholders::fill(
+            &self.format,
+            &[
+                ("rx", &rate(rx, previous_rx)),
+                ("tx", &rate(tx, previous_tx)),
+                ("interface", &interface),
+            ],
+        ));
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for NetworkWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        // Take the first sample, so the next tick can show the rates.
+        self.sync();
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        // Rates are measured between ticks, so there has to be a timer.
+        let update_rate = if self.update_rate == 0 {
+            1000
+        } else {
+            self.update_rate
+        };
+
+        pause::timeout_add_local(Duration::from_millis(update_rate), move || {
+            self.sync();
+            Continue(true)
+        });
+
+        log::debug!("Added a new network widget");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the rates have actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{
+    command::{self, BashRunner, CommandRunner, RetryRunner},
+    config, errors, math, pause, registry,
+    structures::Align,
+    transition::Transition,
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{rc::Rc, time::Duration};
+
+/// How long the bar takes to move to a new value when `transition_ms` isn't specified.
+pub const DEFAULT_TRANSITION: Duration = Duration::from_millis(200);
+
+/// The value shown as an empty bar when none is specified.
+pub const DEFAULT_MIN: f64 = 0.0;
+
+/// The value shown as a full bar when none is specified.
+pub const DEFAULT_MAX: f64 = 100.0;
+
+/// Creates a new progress widget, a bar which fills up proportionally to a value from either the
+/// config or a command.
+pub struct ProgressWidget {
+    pub tooltip: String,
+    /// Shown when `command` is empty.
+    pub value: f64,
+    /// Should output a single number, like `42` or `0.5`.
+    pub command: String,
+    pub min: f64,
+    pub max: f64,
+    pub update_rate: u64,
+    pub command_timeout: u64,
+    pub retry: command::Retry,
+    pub progress_bar: ProgressBar,
+    /// Moves the bar to new values.
+    pub transition: Transition,
+}
+
+impl ProgressWidget {
+    /// Maps `value` from `min..=max` onto the `0.0..=1.0` fraction of the bar.
+    fn to_fraction(&self, value: f64) -> f64 {
+        (math::clamp_f64(value, self.min, self.max) - self.min) / (self.max - self.min)
+    }
+
+    /// Animates the bar from where it currently is to `value`, replacing any running animation.
+    fn animate_to(&self, value: f64) {
+        self.transition.stop();
+        let from = self.progress_bar.fraction();
+        let to = self.to_fraction(value);
+        // Only redraw if the value has actually changed.
+        if from == to {
+            return;
+        }
+
+        // 0.3.5: Redrawn at `hybrid` -> `fps`, regardless of the update-rate.
+        let progress_bar = self.progress_bar.clone();
+        self.transition.start(move |progress| {
+            progress_bar.set_fraction(math::lerp(from, to, progress));
+        });
+    }
+
+    /// Runs the command through `runner` on a separate thread and sends its value to `sender`,
+    /// unless the output isn't a number.
+    fn refresh(&self, sender: &glib::Sender<f64>, runner: impl CommandRunner + Send + 'static) {
+        let sender = sender.clone();
+        let command = self.command.clone();
+        let timeout = Duration::from_millis(self.command_timeout);
+        let retry = self.retry;
+        let name = self.progress_bar.widget_name().to_string();
+        command::spawn_worker(move || {
+            let runner = RetryRunner {
+                runner: &runner,
+                retry,
+            };
+            let output = runner.execute(&command, timeout);
+            errors::check(&name, &command, &output);
+            let output = output.stdout;
+            match output.trim().parse() {
+                // Can only fail if the main loop is gone.
+                Ok(value) => {
+                    let _ = sender.send(value);
+                }
+                Err(_) => log::warn!("'{command}' didn't output a number, got '{output}'"),
+            }
+        });
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for ProgressWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.progress_bar.set_widget_name(&name);
+        self.progress_bar.set_tooltip_markup(Some(&self.tooltip));
+        // Follows the bar, vertical bars fill up from the bottom.
+        if config::get_position().is_vertical() {
+            self.progress_bar.set_orientation(Orientation::Vertical);
+            self.progress_bar.set_inverted(true);
+        }
+
+        ui::add_and_align(&self.progress_bar, align, left, centered, right);
+
+        if self.command.is_empty() {
+            self.progress_bar.set_fraction(self.to_fraction(self.value));
+            log::debug!("Added a new progress widget");
+            return;
+        }
+
+        // The value is animated towards on the main thread, as soon as the command has finished.
+        let widget = Rc::new(self);
+        let (sender, receiver) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+        let receiving_widget = widget.clone();
+        registry::attach(receiver, move |value| {
+            receiving_widget.animate_to(value);
+            Continue(true)
+        });
+
+        widget.refresh(&sender, BashRunner);
+        // An update-rate of `0` means the command is only run once.
+        if widget.update_rate != 0 {
+            pause::timeout_add_local(Duration::from_millis(widget.update_rate), move || {
+                widget.refresh(&sender, BashRunner);
+                Continue(true)
+            });
+        }
+
+        log::debug!("Added a new progress widget");
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{
+    command, errors, overflow, placeholders, registry,
+    structures::Align,
+    transform::{self, Step},
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{
+    process::Stdio,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+    task,
+};
+
+/// How long to wait before restarting a script that has exited for the first time.
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The longest wait between restarts. Scripts that ran for at least this long before exiting are
+/// restarted after `MIN_BACKOFF` again.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Creates a new script widget, which keeps `command` running and shows every line it prints.
+pub struct ScriptWidget {
+    pub tooltip: String,
+    /// Shown in front of the output.
+    pub text: String,
+    pub command: String,
+    /// Applied to each line before it's shown, see `transform::parse`.
+    pub transform: Vec<Step>,
+    pub label: Label,
+}
+
+/// Runs `command` of the widget named `name` until it exits, sending each line it prints to
+/// `sender`. Returns `false` if the main loop is gone, so there's no point in restarting it.
+async fn run(name: &str, command: &str, sender: &glib::Sender<String>) -> bool {
+    let mut child = match Command::new("bash")
+        .args(["-c", command])
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        // Put it in its own process group, so that everything it spawns is stopped with the bar.
+        .process_group(0)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(err) => {
+            log::warn!("Failed spawning '{command}': {err}");
+            errors::report(name, &format!("Failed spawning '{command}': {err}"));
+            return true;
+        }
+    };
+
+    let pid = child.id();
+    if let Some(pid) = pid {
+        command::register_child(pid);
+    }
+
+    let stdout = child.stdout.take().expect("Cannot take stdout from child!");
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        errors::clear(name);
+        if sender.send(line).is_err() {
+            return false;
+        }
+    }
+
+    let error = match child.wait().await {
+        Ok(status) => format!("'{command}' exited with {status}"),
+        Err(err) => format!("Failed waiting for '{command}': {err}"),
+    };
+
+    log::warn!("{error}");
+    errors::report(name, &error);
+
+    if let Some(pid) = pid {
+        command::unregister_child(pid);
+    }
+
+    true
+}
+
+/// Keeps `command` running on the Tokio runtime, restarting it with an increasing delay whenever
+/// it exits.
+fn supervise(name: String, command: String, sender: glib::Sender<String>) {
+    task::spawn(async move {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            let started = Instant::now();
+            if !run(&name, &command, &sender).await {
+                return;
+            }
+
+            // Only back off for scripts that keep exiting soon after being started.
+            if started.elapsed() >= MAX_BACKOFF {
+                backoff = MIN_BACKOFF;
+            }
+
+            log::info!("Restarting '{command}' in {}s", backoff.as_secs());
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    });
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for ScriptWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        self.label.set_text(&self.text);
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        // Lines are read asynchronously, then sent back to the main thread which updates the label.
+        let (sender, receiver) = glib::MainContext::channel::<String>(glib::PRIORITY_DEFAULT);
+        supervise(name.clone(), self.command.clone(), sender);
+        registry::attach(receiver, move |line| {
+            let line = transform::apply_or_raw(&name, &self.transform, &line);
+            self.update_label_reg(&format!("{}{line}", self.text));
+            Continue(true)
+        });
+
+        log::debug!("Added a new script widget");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the line has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use crate::{config, structures::Align, ui, widget::HWidget};
+use gtk::{traits::*, *};
+
+/// Creates a new separator widget, a thin line dividing groups of widgets.
+pub struct SeparatorWidget {}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for SeparatorWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        // The line runs across the bar, so it's vertical on horizontal bars and vice versa.
+        let orientation = if config::get_position().is_vertical() {
+            Orientation::Horizontal
+        } else {
+            Orientation::Vertical
+        };
+
+        let widget = Separator::new(orientation);
+        widget.set_widget_name(&name);
+
+        ui::add_and_align(&widget, align, left, centered, right);
+        log::debug!("Added a new separator widget");
+    }
+}
@@ -17,6 +17,6 @@ impl HWidget for SpacingWidget {
         widget.set_margin_end(self.spacing_end);
 
         ui::add_and_align(&widget, align, left, centered, right);
-        log!("Added a new spacing widget");
+        log::debug!("Added a new spacing widget");
     }
 }
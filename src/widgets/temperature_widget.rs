@@ -0,0 +1,174 @@
+use crate::{
+    overflow, pause, placeholders,
+    ranges::{self, Ranges},
+    structures::Align,
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Where the kernel exposes hardware monitors.
+const HWMON_PATH: &str = "/sys/class/hwmon";
+
+/// The format used when none is specified.
+pub const DEFAULT_FORMAT: &str = "{temp}°C";
+
+/// The CSS class added while the temperature is above `warning_threshold`.
+const WARNING_CLASS: &str = "warning";
+
+/// A temperature input, like `coretemp/Package id 0`.
+pub struct Sensor {
+    /// `hwmon name/label`, or `hwmon name/tempN` if the input has no label.
+    pub name: String,
+    /// The `tempN_input` file, holding the temperature in millidegrees.
+    pub input: PathBuf,
+}
+
+/// Finds all temperature sensors, sorted by name.
+pub fn discover_sensors() -> Vec<Sensor> {
+    let Ok(monitors) = fs::read_dir(HWMON_PATH) else {
+        return Vec::new();
+    };
+
+    let mut sensors = Vec::new();
+    for monitor in monitors
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        let read = |file: &str| {
+            fs::read_to_string(monitor.join(file))
+                .map(|content| content.trim().to_string())
+                .ok()
+        };
+
+        let monitor_name = read("name").unwrap_or_default();
+        let Ok(files) = fs::read_dir(&monitor) else {
+            continue;
+        };
+
+        for file in files.filter_map(|entry| entry.ok()) {
+            let file_name = file.file_name().to_string_lossy().to_string();
+            let Some(input) = file_name
+                .strip_suffix("_input")
+                .filter(|input| input.starts_with("temp"))
+            else {
+                continue;
+            };
+
+            let label = read(&format!("{input}_label")).unwrap_or_else(|| input.to_string());
+            sensors.push(Sensor {
+                name: format!("{monitor_name}/{label}"),
+                input: file.path(),
+            });
+        }
+    }
+
+    sensors.sort_by(|a, b| a.name.cmp(&b.name));
+    sensors
+}
+
+/// Creates a new temperature widget, which reads a sensor from hwmon instead of through
+/// `sensors`.
+pub struct TemperatureWidget {
+    pub tooltip: String,
+    pub format: String,
+    pub ranges: Ranges,
+    /// The sensor to read, for example `coretemp/Package id 0`. The first sensor is used if empty.
+    pub sensor: String,
+    /// Above how many degrees the `warning` class is added.
+    pub warning_threshold: Option<i32>,
+    pub update_rate: u64,
+    pub label: Label,
+}
+
+impl TemperatureWidget {
+    /// Picks the configured sensor from `sensors`, `None` if it doesn't exist.
+    fn find_input(&self, sensors: Vec<Sensor>) -> Option<PathBuf> {
+        if self.sensor.is_empty() {
+            return sensors.into_iter().next().map(|sensor| sensor.input);
+        }
+
+        sensors
+            .into_iter()
+            .find(|sensor| sensor.name == self.sensor)
+            .map(|sensor| sensor.input)
+    }
+
+    /// Syncs the label with the temperature at `input`.
+    fn sync(&self, input: &Path) {
+        let Some(temperature) = fs::read_to_string(input)
+            .ok()
+            .and_then(|content| content.trim().parse::<i64>().ok())
+        else {
+            return;
+        };
+
+        // Reported in millidegrees.
+        let temperature = (temperature as f64 / 1000.0).round() as i64;
+        self.update_label_reg(&placeholders::fill(
+            &self.format,
+            &[
+                ("temp", &temperature.to_string()),
+                (ranges::PLACEHOLDER, self.ranges.pick(temperature as f64)),
+            ],
+        ));
+
+        let style_context = self.label.style_context();
+        match self.warning_threshold {
+            Some(threshold) if temperature > threshold as i64 => {
+                style_context.add_class(WARNING_CLASS)
+            }
+            _ => style_context.remove_class(WARNING_CLASS),
+        }
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for TemperatureWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        // Log the sensors, so users can find the one they want for `sensor`.
+        let sensors = discover_sensors();
+        let names: Vec<&str> = sensors.iter().map(|sensor| sensor.name.as_str()).collect();
+        log::info!("Available temperature sensors: {}", names.join(", "));
+
+        let Some(input) = self.find_input(sensors) else {
+            log::warn!(
+                "Temperature sensor '{}' wasn't found, hiding the widget",
+                self.sensor
+            );
+            self.label.set_no_show_all(true);
+            return;
+        };
+
+        self.sync(&input);
+
+        // An update-rate of `0` means the temperature is only read once.
+        if self.update_rate != 0 {
+            pause::timeout_add_local(Duration::from_millis(self.update_rate), move || {
+                self.sync(&input);
+                Continue(true)
+            });
+        }
+
+        log::debug!("Added a new temperature widget");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the temperature has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{
+    command::{self, BashRunner, CommandRunner},
+    errors, overflow, pause, placeholders,
+    structures::Align,
+    ui,
+    widget::HWidget,
+};
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveDateTime, NaiveTime, TimeZone};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{cell::Cell, time::Duration};
+
+/// The format used when none is specified.
+pub const DEFAULT_FORMAT: &str = "{hours:02}:{minutes:02}:{seconds:02}";
+
+/// Creates a new timer widget, which counts down to `target` and then up from it.
+pub struct TimerWidget {
+    pub tooltip: String,
+    /// Shows the time left until `target`.
+    pub format: String,
+    /// Shows the time passed since `target`.
+    pub elapsed_format: String,
+    pub target: DateTime<Local>,
+    /// Run once when `target` passes while the bar is running.
+    pub on_complete: String,
+    pub command_timeout: u64,
+    pub update_rate: u64,
+    pub label: Label,
+    /// Whether `target` has passed, so `on_complete` isn't run again.
+    pub completed: Cell<bool>,
+}
+
+/// Parses `target` relative to `now`, either as an offset like `+1h30m`, `+90s` or `+2d`, or as
+/// an absolute time like `15:30`, `15:30:45`, `2026-12-24 18:00` or an RFC 3339 timestamp.
+/// Times without a date are today's.
+pub fn parse_target(target: &str, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let target = target.trim();
+    if let Some(offset) = target.strip_prefix('+') {
+        return now.checked_add_signed(parse_offset(offset)?);
+    }
+
+    if let Ok(target) = DateTime::parse_from_rfc3339(target) {
+        return Some(target.with_timezone(&Local));
+    }
+
+    let date_time = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(target, format).ok())
+        .or_else(|| {
+            ["%H:%M:%S", "%H:%M"]
+                .iter()
+                .find_map(|format| NaiveTime::parse_from_str(target, format).ok())
+                .map(|time| now.date_naive().and_time(time))
+        })?;
+
+    // Skipped or repeated by a daylight saving change, the earliest one is taken.
+    Local.from_local_datetime(&date_time).earliest()
+}
+
+/// Parses an offset like `1h30m`, made of numbers followed by `d`, `h`, `m` or `s`.
+/// Offsets too large to represent are invalid.
+fn parse_offset(offset: &str) -> Option<ChronoDuration> {
+    let mut total = ChronoDuration::zero();
+    let mut number = String::new();
+    for char in offset.chars() {
+        if char.is_ascii_digit() {
+            number.push(char);
+            continue;
+        }
+
+        let value: i64 = number.parse().ok()?;
+        number.clear();
+        let duration = match char {
+            'd' => ChronoDuration::try_days(value),
+            'h' => ChronoDuration::try_hours(value),
+            'm' => ChronoDuration::try_minutes(value),
+            's' => ChronoDuration::try_seconds(value),
+            _ => return None,
+        };
+        total = total.checked_add(&duration?)?;
+    }
+
+    // A trailing number without a unit, or nothing at all.
+    if !number.is_empty() || offset.is_empty() {
+        return None;
+    }
+
+    Some(total)
+}
+
+/// Fills `format` in with `seconds`. Each unit rolls over into the next larger one, except for
+/// `{total_minutes}` and `{total_seconds}`.
+pub fn format_duration(format: &str, seconds: i64) -> String {
+    let seconds = seconds.max(0);
+    placeholders::fill(
+        format,
+        &[
+            ("days", &(seconds / 86_400).to_string()),
+            ("hours", &(seconds / 3600 % 24).to_string()),
+            ("minutes", &(seconds / 60 % 60).to_string()),
+            ("seconds", &(seconds % 60).to_string()),
+            ("total_minutes", &(seconds / 60).to_string()),
+            ("total_seconds", &seconds.to_string()),
+        ],
+    )
+}
+
+impl TimerWidget {
+    /// Syncs the label with the time left or passed, and runs `on_complete` through `runner` when
+    /// `target` passes.
+    fn sync(&self, runner: impl CommandRunner + Send + 'static) {
+        let remaining = (self.target - Local::now()).num_seconds();
+        if remaining > 0 {
+            self.completed.set(false);
+            self.update_label_reg(&format_duration(&self.format, remaining));
+            return;
+        }
+
+        if !self.completed.replace(true) && !self.on_complete.is_empty() {
+            let command = self.on_complete.clone();
+            let name = self.label.widget_name().to_string();
+            let timeout = Duration::from_millis(self.command_timeout);
+            command::spawn_worker(move || {
+                let output = runner.execute(&command, timeout);
+                errors::check(&name, &command, &output);
+            });
+        }
+
+        self.update_label_reg(&format_duration(&self.elapsed_format, -remaining));
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for TimerWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        // A target that has passed before the bar started doesn't complete.
+        self.completed.set(self.target <= Local::now());
+        self.sync(BashRunner);
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        // An update-rate of `0` means the time is only shown once.
+        if self.update_rate != 0 {
+            pause::timeout_add_local(Duration::from_millis(self.update_rate), move || {
+                self.sync(BashRunner);
+                Continue(true)
+            });
+        }
+
+        log::debug!("Added a new timer widget");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the time has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_and_durations() {
+        let now = Local.with_ymd_and_hms(2026, 10, 14, 12, 0, 0).unwrap();
+        let in_90_minutes = parse_target("+1h30m", now).unwrap();
+        assert_eq!((in_90_minutes - now).num_minutes(), 90);
+        assert_eq!(parse_target("13:30", now), Some(in_90_minutes));
+        assert_eq!(parse_target("2026-10-14 13:30", now), Some(in_90_minutes));
+        assert!(parse_target("+90", now).is_none());
+        assert!(parse_target("soon", now).is_none());
+
+        let seconds = (in_90_minutes - now).num_seconds() + 26 * 3600 + 5;
+        assert_eq!(format_duration(DEFAULT_FORMAT, seconds), "03:30:05");
+        assert_eq!(
+            format_duration("{days}d {total_minutes}m", seconds),
+            "1d 1650m"
+        );
+    }
+
+    #[test]
+    fn huge_offsets_are_invalid() {
+        let now = Local.with_ymd_and_hms(2026, 10, 14, 12, 0, 0).unwrap();
+        assert!(parse_target("+99999999999d", now).is_none());
+        assert!(parse_target("+9223372036854775807s", now).is_none());
+        // Each part fits, but not added up.
+        assert!(parse_target("+2000000000000h2000000000000h", now).is_none());
+        // Fits in a duration, but not in a date.
+        assert!(parse_target("+9000000000000s", now).is_none());
+    }
+}
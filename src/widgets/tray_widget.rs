@@ -0,0 +1,138 @@
+use crate::{
+    registry,
+    structures::Align,
+    tray::{self, TrayItem},
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{gdk_pixbuf::Pixbuf, traits::*, *};
+use std::{cell::RefCell, collections::HashSet};
+use zbus::blocking::Connection;
+
+/// The icon size used when none is specified, in pixels.
+pub const DEFAULT_ICON_SIZE: i32 = 16;
+
+/// Creates a new tray widget, which shows the tray icons of applications through the
+/// StatusNotifierItem protocol.
+pub struct TrayWidget {
+    pub icon_size: i32,
+    pub container: Box,
+}
+
+thread_local! {
+    /// The icon theme paths of tray items which have been added to the default icon theme.
+    static SEARCH_PATHS: RefCell<HashSet<String>> = RefCell::default();
+}
+
+/// Converts the pixmap closest to `size` into a pixbuf, `None` if there are no valid pixmaps.
+fn pixmap_to_pixbuf(pixmaps: &[(i32, i32, Vec<u8>)], size: i32) -> Option<Pixbuf> {
+    let (width, height, data) = pixmaps
+        .iter()
+        .filter(|(width, height, data)| {
+            *width > 0 && *height > 0 && data.len() == (width * height * 4) as usize
+        })
+        .min_by_key(|(width, _, _)| (width - size).abs())?;
+
+    // ARGB in network byte order, while GDK expects RGBA.
+    let mut rgba = Vec::with_capacity(data.len());
+    for pixel in data.chunks_exact(4) {
+        rgba.extend_from_slice(&[pixel[1], pixel[2], pixel[3], pixel[0]]);
+    }
+
+    let pixbuf = Pixbuf::from_bytes(
+        &glib::Bytes::from_owned(rgba),
+        gdk_pixbuf::Colorspace::Rgb,
+        true,
+        8,
+        *width,
+        *height,
+        width * 4,
+    );
+
+    pixbuf.scale_simple(size, size, gdk_pixbuf::InterpType::Bilinear)
+}
+
+/// Creates the icon of `item`, preferring its themed icon over its pixmaps.
+fn create_icon(item: &TrayItem, size: i32) -> Image {
+    let theme = IconTheme::default();
+    if let Some(theme) = &theme {
+        // The theme is shared, so each path is only added once rather than on every rebuild.
+        let is_new =
+            SEARCH_PATHS.with(|paths| paths.borrow_mut().insert(item.icon_theme_path.clone()));
+        if !item.icon_theme_path.is_empty() && is_new {
+            theme.append_search_path(&item.icon_theme_path);
+        }
+    }
+
+    let has_themed_icon =
+        !item.icon_name.is_empty() && theme.is_some_and(|theme| theme.has_icon(&item.icon_name));
+    if !has_themed_icon {
+        if let Some(pixbuf) = pixmap_to_pixbuf(&item.pixmaps, size) {
+            return Image::from_pixbuf(Some(&pixbuf));
+        }
+    }
+
+    let icon = Image::from_icon_name(Some(&item.icon_name), IconSize::Menu);
+    icon.set_pixel_size(size);
+    icon
+}
+
+/// Replaces the icons in `container` with one for each of `items`.
+fn rebuild(connection: &Connection, container: &Box, items: &[TrayItem], icon_size: i32) {
+    for child in container.children() {
+        container.remove(&child);
+    }
+
+    for item in items {
+        let event_box = EventBox::new();
+        let icon = create_icon(item, icon_size);
+        icon.set_tooltip_text(Some(&item.title));
+        event_box.add(&icon);
+
+        let connection = connection.clone();
+        let item = item.clone();
+        event_box.connect_button_press_event(move |_, event| {
+            let method = match event.button() {
+                1 => "Activate",
+                2 => "SecondaryActivate",
+                3 => "ContextMenu",
+                _ => return Inhibit(false),
+            };
+
+            let (x, y) = event.root();
+            tray::activate(&connection, &item, method, x as i32, y as i32);
+            Inhibit(true)
+        });
+
+        container.add(&event_box);
+        event_box.show_all();
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for TrayWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.container.set_widget_name(&name);
+        ui::add_and_align(&self.container, align, left, centered, right);
+
+        let connection = match Connection::session() {
+            Ok(connection) => connection,
+            Err(err) => {
+                log::warn!("Tray widget cannot connect to D-Bus: {err}");
+                return;
+            }
+        };
+
+        // 0.3.5: Updated through D-Bus signals rather than on a timer.
+        let (sender, receiver) =
+            glib::MainContext::channel::<Vec<TrayItem>>(glib::PRIORITY_DEFAULT);
+        tray::watch(connection.clone(), sender);
+        registry::attach(receiver, move |items| {
+            rebuild(&connection, &self.container, &items, self.icon_size);
+            Continue(true)
+        });
+
+        log::debug!("Added a new tray widget");
+    }
+}
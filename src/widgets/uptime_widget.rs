@@ -0,0 +1,93 @@
+use crate::{overflow, pause, placeholders, structures::Align, ui, widget::HWidget};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::{fs, time::Duration};
+
+/// The format used when none is specified.
+pub const DEFAULT_FORMAT: &str = "{days}d {hours}h {minutes}m";
+
+/// Creates a new uptime widget, which reads the uptime from `/proc/uptime` instead of through
+/// `uptime`.
+pub struct UptimeWidget {
+    pub tooltip: String,
+    pub format: String,
+    pub update_rate: u64,
+    pub label: Label,
+}
+
+/// Reads for how many seconds the system has been up.
+fn read_uptime() -> Option<u64> {
+    // The first value is the uptime, the second one how long the CPUs have been idle.
+    let uptime = fs::read_to_string("/proc/uptime").ok()?;
+    let seconds: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+    Some(seconds as u64)
+}
+
+/// Fills `format` in with `seconds` of uptime. Each unit rolls over into the next larger one, so
+/// `{hours}` stays below `24` and `{minutes}` below `60`. They can be zero-padded with `:02`.
+pub fn format_uptime(format: &str, seconds: u64) -> String {
+    let minutes = seconds / 60;
+    placeholders::fill(
+        format,
+        &[
+            ("days", &(minutes / 60 / 24).to_string()),
+            ("hours", &(minutes / 60 % 24).to_string()),
+            ("minutes", &(minutes % 60).to_string()),
+        ],
+    )
+}
+
+/// Fills `format` in with the current uptime.
+pub fn read(format: &str) -> Option<String> {
+    Some(format_uptime(format, read_uptime()?))
+}
+
+impl UptimeWidget {
+    /// Syncs the label with the current uptime.
+    fn sync(&self) {
+        if let Some(text) = read(&self.format) {
+            self.update_label_reg(&text);
+        }
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for UptimeWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        self.sync();
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        // An update-rate of `0` means the uptime is only read once.
+        if self.update_rate != 0 {
+            pause::timeout_add_local(Duration::from_millis(self.update_rate), move || {
+                self.sync();
+                Continue(true)
+            });
+        }
+
+        log::debug!("Added a new uptime widget");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the uptime has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units_roll_over() {
+        let seconds = ((2 * 24 + 23) * 60 + 59) * 60 + 59;
+        assert_eq!(format_uptime(DEFAULT_FORMAT, seconds), "2d 23h 59m");
+        assert_eq!(format_uptime("{hours}:{minutes:02}", 3600 + 5 * 60), "1:05");
+    }
+}
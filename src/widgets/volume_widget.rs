@@ -0,0 +1,145 @@
+use crate::{
+    overflow, placeholders,
+    ranges::{self, Ranges},
+    registry,
+    structures::Align,
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+use libpulse_binding::{
+    callbacks::ListResult,
+    context::{introspect::SinkInfo, subscribe::InterestMaskSet, Context, FlagSet, State},
+    mainloop::standard::{IterateResult, Mainloop},
+    volume::Volume,
+};
+use std::{cell::Cell, rc::Rc, thread, time::Duration};
+
+/// The format used when none is specified.
+pub const DEFAULT_FORMAT: &str = "{volume}%";
+
+/// How long to wait before reconnecting after the sound server went away.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Creates a new volume widget, which shows the volume of the default sink through PulseAudio
+/// (or PipeWire's PulseAudio server) instead of through `pactl`.
+pub struct VolumeWidget {
+    pub tooltip: String,
+    pub format: String,
+    pub ranges: Ranges,
+    pub label: Label,
+}
+
+/// Formats the volume and mute state of `sink`.
+fn format_sink(format: &str, ranges: &Ranges, sink: &SinkInfo) -> String {
+    let volume = (sink.volume.avg().0 as f64 / Volume::NORMAL.0 as f64 * 100.0).round();
+    placeholders::fill(
+        format,
+        &[
+            ("volume", &(volume as u64).to_string()),
+            (ranges::PLACEHOLDER, ranges.pick(volume)),
+            ("muted", if sink.mute { "muted" } else { "" }),
+        ],
+    )
+}
+
+/// Runs a single iteration of the main loop, blocking until something happens.
+fn iterate(mainloop: &mut Mainloop) -> Result<(), String> {
+    match mainloop.iterate(true) {
+        IterateResult::Success(_) => Ok(()),
+        IterateResult::Quit(_) => Err(String::from("The main loop quit")),
+        IterateResult::Err(err) => Err(format!("{err}")),
+    }
+}
+
+/// Connects to the sound server, then sends the formatted volume to `sender` every time the
+/// default sink changes. Returns once the connection is lost.
+fn run(format: &str, ranges: &Ranges, sender: &glib::Sender<String>) -> Result<(), String> {
+    let mut mainloop =
+        Mainloop::new().ok_or_else(|| String::from("Cannot create the main loop"))?;
+    let mut context = Context::new(&mainloop, "HybridBar")
+        .ok_or_else(|| String::from("Cannot create the context"))?;
+    context
+        .connect(None, FlagSet::NOFLAGS, None)
+        .map_err(|err| format!("{err}"))?;
+
+    loop {
+        iterate(&mut mainloop)?;
+        match context.get_state() {
+            State::Ready => break,
+            State::Failed | State::Terminated => {
+                return Err(String::from("Cannot connect to the sound server"))
+            }
+            _ => {}
+        }
+    }
+
+    // Callbacks can't use the context, so they only flag that the sink has to be read again.
+    let changed = Rc::new(Cell::new(true));
+    let subscribe_changed = changed.clone();
+    context.set_subscribe_callback(Some(std::boxed::Box::new(move |_, _, _| {
+        subscribe_changed.set(true)
+    })));
+    // The server is included, as changing the default sink is a server event.
+    context.subscribe(InterestMaskSet::SINK | InterestMaskSet::SERVER, |_| {});
+
+    loop {
+        if changed.replace(false) {
+            let format = format.to_string();
+            let ranges = ranges.clone();
+            let sender = sender.clone();
+            context
+                .introspect()
+                .get_sink_info_by_name("@DEFAULT_SINK@", move |result| {
+                    if let ListResult::Item(sink) = result {
+                        // Can only fail if the main loop is gone.
+                        let _ = sender.send(format_sink(&format, &ranges, sink));
+                    }
+                });
+        }
+
+        iterate(&mut mainloop)?;
+        if matches!(context.get_state(), State::Failed | State::Terminated) {
+            return Err(String::from("Lost the connection to the sound server"));
+        }
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for VolumeWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        // 0.3.5: Updated through sound server events rather than on a timer.
+        let (sender, receiver) = glib::MainContext::channel::<String>(glib::PRIORITY_DEFAULT);
+        let format = self.format.clone();
+        let ranges = self.ranges.clone();
+        thread::spawn(move || loop {
+            // Reconnect if the sound server restarts.
+            if let Err(err) = run(&format, &ranges, &sender) {
+                log::warn!("Volume widget: {err}, reconnecting");
+            }
+
+            thread::sleep(RECONNECT_DELAY);
+        });
+
+        registry::attach(receiver, move |volume| {
+            self.update_label_reg(&volume);
+            Continue(true)
+        });
+
+        log::debug!("Added a new volume widget");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the volume has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
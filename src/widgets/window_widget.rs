@@ -0,0 +1,79 @@
+use crate::{
+    compositor::{Compositor, Events},
+    overflow, placeholders, registry,
+    structures::{Align, Window},
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+
+/// The format used when none is specified.
+pub const DEFAULT_FORMAT: &str = "{title}";
+
+/// Creates a new window widget, which shows the focused window.
+pub struct WindowWidget {
+    pub tooltip: String,
+    pub format: String,
+    /// Shown when no window is focused, the widget is hidden instead if it's empty.
+    pub fallback: String,
+    pub label: Label,
+}
+
+/// Fills `format` in with the title and app id of `window`.
+pub fn format_window(format: &str, window: &Window) -> String {
+    placeholders::fill(
+        format,
+        &[("title", &window.title), ("app_id", &window.app_id)],
+    )
+}
+
+impl WindowWidget {
+    /// Syncs the label with `window`, showing `fallback` if there's none.
+    fn sync(&self, window: Option<Window>) {
+        let text = match window {
+            Some(window) => format_window(&self.format, &window),
+            None => self.fallback.clone(),
+        };
+
+        self.label.set_visible(!text.is_empty());
+        self.update_label_reg(&text);
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for WindowWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.label.set_widget_name(&name);
+        self.label.set_tooltip_markup(Some(&self.tooltip));
+        // Hidden until there's a window to show, also keeps it hidden on other compositors.
+        self.label.set_no_show_all(true);
+        ui::add_and_align(&self.label, align, left, centered, right);
+
+        let Some(compositor) = Compositor::detect() else {
+            log::warn!("Neither Sway nor Hyprland is running, hiding the window widget");
+            return;
+        };
+
+        // 0.3.5: Updated through IPC events rather than `update_rate`, so it follows the focus and
+        //   title of the window right away.
+        let (sender, receiver) =
+            glib::MainContext::channel::<Option<Window>>(glib::PRIORITY_DEFAULT);
+        compositor.watch(Events::Window, Compositor::get_focused_window, sender);
+        registry::attach(receiver, move |window| {
+            self.sync(window);
+            Continue(true)
+        });
+
+        log::debug!("Added a new window widget for {compositor:?}");
+    }
+
+    fn update_label_reg(&self, new_content: &(impl std::fmt::Display + Clone)) {
+        let new_content = new_content.to_string();
+        placeholders::publish(&self.label.widget_name(), &new_content);
+        // Only redraw if the window has actually changed.
+        if overflow::get_content(&self.label) != new_content {
+            self.label.set_text(&new_content)
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::{
+    compositor::{Compositor, Events},
+    registry,
+    structures::{Align, Workspace},
+    ui,
+    widget::HWidget,
+};
+use glib::Continue;
+use gtk::{traits::*, *};
+use std::thread;
+
+/// Creates a new workspaces widget, which shows a button per Sway, i3 or Hyprland workspace.
+pub struct WorkspacesWidget {
+    pub container: Box,
+}
+
+/// Replaces the buttons in `container` with one for each of `workspaces`.
+fn rebuild(compositor: Compositor, container: &Box, workspaces: &[Workspace]) {
+    for child in container.children() {
+        container.remove(&child);
+    }
+
+    for workspace in workspaces {
+        let button = Button::with_label(&workspace.name);
+        let style_context = button.style_context();
+        for (class, enabled) in [
+            ("focused", workspace.focused),
+            ("visible", workspace.visible),
+            ("urgent", workspace.urgent),
+            ("occupied", workspace.occupied),
+        ] {
+            if enabled {
+                style_context.add_class(class);
+            }
+        }
+
+        let workspace = workspace.clone();
+        button.connect_clicked(move |_| {
+            let workspace = workspace.clone();
+            // Don't block the UI while the compositor is switching.
+            thread::spawn(move || {
+                if let Err(err) = compositor.focus_workspace(&workspace) {
+                    log::warn!("Failed switching to workspace '{}': {err}", workspace.name);
+                }
+            });
+        });
+
+        container.add(&button);
+        button.show();
+    }
+}
+
+// Implements HWidget for the widget so that we can actually use it.
+impl HWidget for WorkspacesWidget {
+    fn add(self, name: String, align: Align, left: &Box, centered: &Box, right: &Box) {
+        self.container.set_widget_name(&name);
+        // Hidden until there are workspaces to show, also keeps it hidden on other compositors.
+        self.container.set_no_show_all(true);
+        ui::add_and_align(&self.container, align, left, centered, right);
+
+        let Some(compositor) = Compositor::detect() else {
+            log::warn!("Neither Sway, i3 nor Hyprland is running, hiding the workspaces widget");
+            return;
+        };
+
+        // 0.3.5: Updated through IPC events rather than on a timer.
+        let (sender, receiver) =
+            glib::MainContext::channel::<Vec<Workspace>>(glib::PRIORITY_DEFAULT);
+        compositor.watch(Events::Workspaces, Compositor::get_workspaces, sender);
+        registry::attach(receiver, move |workspaces| {
+            rebuild(compositor, &self.container, &workspaces);
+            self.container.show();
+            Continue(true)
+        });
+
+        log::debug!("Added a new workspaces widget for {compositor:?}");
+    }
+}